@@ -0,0 +1,96 @@
+//! Measures the latency of the main steps of a load, as paid at startup by applications.
+//!
+//! Run with `cargo bench`.
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Write as _;
+use tempfile::NamedTempFile;
+
+#[allow(dead_code)]
+#[derive(Debug, Default, Deserialize)]
+struct Small {
+    name: String,
+    port: u16,
+    verbose: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Default, Deserialize)]
+struct Large {
+    services: HashMap<String, Service>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Default, Deserialize)]
+struct Service {
+    host: String,
+    port: u16,
+    tags: Vec<String>,
+}
+
+fn config(source: &str) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    write!(file, "{source}").unwrap();
+    file
+}
+
+fn large_source(services: usize) -> String {
+    let mut source =
+        String::from("let port_of = fun index => 8000 + index in\n{\n  services = {\n");
+    for index in 0..services {
+        source.push_str(&format!(
+            "    service_{index} = {{ host = \"host-{index}.internal\", port = port_of {index}, \
+             tags = [\"a\", \"b\", std.string.from_number {index}] }},\n"
+        ));
+    }
+    source.push_str("  }\n}\n");
+    source
+}
+
+fn discovery(c: &mut Criterion) {
+    let loader = nickelodeon::Loader::new("nickelodeon_bench_without_configuration");
+    c.bench_function("discovery without configuration", |b| {
+        b.iter(|| loader.load::<Small>().unwrap());
+    });
+}
+
+fn small(c: &mut Criterion) {
+    let small = config(r#"{ name = "bench", port = 8000 + 80, verbose = true }"#);
+    let loader = nickelodeon::Loader::new("nickelodeon_bench")
+        .config_path_from_flag(Some(small.path().to_path_buf()))
+        .diagnostics(std::io::sink());
+    c.bench_function("small configuration", |b| {
+        b.iter(|| loader.load::<Small>().unwrap());
+    });
+}
+
+fn large(c: &mut Criterion) {
+    let large = config(&large_source(2000));
+    let loader = nickelodeon::Loader::new("nickelodeon_bench")
+        .config_path_from_flag(Some(large.path().to_path_buf()))
+        .diagnostics(std::io::sink());
+    let mut group = c.benchmark_group("large configuration");
+    group.sample_size(10);
+    group.bench_function("whole", |b| {
+        b.iter(|| loader.load::<Large>().unwrap());
+    });
+    group.bench_function("single field", |b| {
+        b.iter(|| {
+            loader
+                .load_field::<Service>("services.service_1000")
+                .unwrap()
+        });
+    });
+    let mut program = loader.program().unwrap().unwrap();
+    group.bench_function("deserialization", |b| {
+        b.iter(|| program.deserialize::<Large>().unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, discovery, small, large);
+criterion_main!(benches);
@@ -0,0 +1,149 @@
+//! Compile time checks of the Nickel configurations embedded in applications, re-exported
+//! by `nickelodeon` when its `macros` feature is enabled.
+#![deny(clippy::all)]
+#![allow(clippy::blanket_clippy_restriction_lints)]
+#![deny(clippy::pedantic)]
+#![deny(clippy::restriction)]
+#![deny(clippy::nursery)]
+#![deny(clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+#![allow(clippy::implicit_return)]
+#![allow(clippy::missing_inline_in_public_items)]
+#![allow(clippy::std_instead_of_core)]
+#![allow(clippy::missing_docs_in_private_items)]
+#![allow(clippy::question_mark_used)]
+#![allow(clippy::allow_attributes)]
+#![allow(clippy::allow_attributes_without_reason)]
+#![allow(clippy::absolute_paths)]
+#![allow(clippy::arbitrary_source_item_ordering)]
+#![allow(clippy::std_instead_of_alloc)]
+#![allow(clippy::pub_use)]
+#![allow(clippy::pub_with_shorthand)]
+#![allow(clippy::redundant_pub_crate)]
+#![allow(clippy::missing_trait_methods)]
+#![allow(clippy::result_large_err)]
+#![allow(clippy::single_call_fn)]
+#![allow(clippy::pattern_type_mismatch)]
+#![allow(clippy::separated_literal_suffix)]
+#![allow(clippy::default_numeric_fallback)]
+
+use codespan_reporting::term::termcolor::Buffer;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::cache::ErrorTolerance;
+use nickel_lang_core::error::Error;
+use nickel_lang_core::error::IntoDiagnostics as _;
+use nickel_lang_core::eval::cache::CacheImpl;
+use nickel_lang_core::eval::VirtualMachine;
+use proc_macro::TokenStream;
+use quote::quote;
+use std::path::PathBuf;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+use syn::LitStr;
+use syn::Token;
+
+/// Checks at compile time that an embedded Nickel configuration evaluates, and expands to
+/// its source, as a `&'static str`.
+///
+/// The configuration is given either inline, or as a `file` relative to the manifest of the
+/// crate, whose imports are resolved relative to it. Editing the file rebuilds the crate.
+///
+/// ```
+/// const DEFAULTS: &str = nickelodeon_macros::nickel_config!("{ port = 8000 + 80 }");
+/// ```
+///
+/// Configurations that fail to parse, typecheck or evaluate fail the build with the Nickel
+/// error instead, so shipped defaults can never break at runtime:
+///
+/// ```compile_fail
+/// const DEFAULTS: &str = nickelodeon_macros::nickel_config!("{ port = 8080 + \"0\" }");
+/// ```
+#[proc_macro]
+pub fn nickel_config(input: TokenStream) -> TokenStream {
+    let config = syn::parse_macro_input!(input as Config);
+    let mut cache = Cache::new(ErrorTolerance::Strict);
+    let (main_id, expansion) = match &config {
+        Config::Inline(source) => (
+            Ok(cache.add_string("<nickel_config>", source.value())),
+            quote!(#source),
+        ),
+        Config::File(file) => {
+            let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+            let path = PathBuf::from(manifest_dir).join(file.value());
+            let display = path.display().to_string();
+            (
+                cache
+                    .add_file(&path)
+                    .map_err(|err| format!("can't read `{display}`: {err}")),
+                quote!(include_str!(#display)),
+            )
+        }
+    };
+    match main_id.and_then(|id| evaluate(cache, id)) {
+        Ok(()) => expansion.into(),
+        Err(message) => syn::Error::new(config.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// The argument of [`nickel_config!`].
+enum Config {
+    /// An inline source, like `"{ port = 8080 }"`.
+    Inline(LitStr),
+
+    /// A file, like `file = "config/defaults.ncl"`.
+    File(LitStr),
+}
+
+impl Config {
+    fn span(&self) -> proc_macro2::Span {
+        match self {
+            Self::Inline(literal) | Self::File(literal) => literal.span(),
+        }
+    }
+}
+
+impl Parse for Config {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            return input.parse().map(Self::Inline);
+        }
+        let key: syn::Ident = input.parse()?;
+        if key != "file" {
+            return Err(syn::Error::new(
+                key.span(),
+                "expected a Nickel source or `file = \"path\"`",
+            ));
+        }
+        input.parse::<Token![=]>()?;
+        input.parse().map(Self::File)
+    }
+}
+
+/// Fully evaluates `main_id`, returning the rendered Nickel error if it fails.
+fn evaluate(cache: Cache, main_id: codespan::FileId) -> Result<(), String> {
+    let mut vm: VirtualMachine<Cache, CacheImpl> = VirtualMachine::new(cache, std::io::sink());
+    let evaluation = vm.prepare_eval(main_id).and_then(|(term, env)| {
+        vm.reset();
+        vm.eval_full_for_export(term, &env).map_err(Error::from)
+    });
+    let Err(error) = evaluation else {
+        return Ok(());
+    };
+
+    let files = vm.import_resolver_mut();
+    let stdlib_ids = files.get_all_stdlib_modules_file_id();
+    let diagnostics = error.into_diagnostics(files.files_mut(), stdlib_ids.as_ref());
+    let config = codespan_reporting::term::Config::default();
+    let mut buffer = Buffer::no_color();
+    for diagnostic in &diagnostics {
+        // Writing to an in-memory buffer can't fail.
+        let _infallible: Result<(), _> =
+            codespan_reporting::term::emit(&mut buffer, &config, files.files(), diagnostic);
+    }
+    Err(format!(
+        "invalid Nickel configuration\n{}",
+        String::from_utf8_lossy(buffer.as_slice()).trim_end()
+    ))
+}
@@ -0,0 +1,171 @@
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+/// The environment variable holding the age identity, or the path of its file, used when
+/// no [`crate::Loader::age_identity`] is given.
+const AGE_IDENTITY: &str = "AGE_IDENTITY";
+
+/// What the secret keys of age identities start with.
+const SECRET_KEY_PREFIX: &str = "AGE-SECRET-KEY-";
+
+/// Tells whether the configuration file at `path` is encrypted with age, like
+/// `config.ncl.age`.
+pub(crate) fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "age")
+}
+
+/// Returns the configuration file at `path`, encrypted with age, decrypted with the `age`
+/// command line and the `identity` file (or the identity of [`AGE_IDENTITY`]).
+pub(crate) fn decrypted(path: &Path, identity: Option<&Path>) -> io::Result<String> {
+    let chosen = match identity {
+        Some(file) => Identity::File(file.to_path_buf()),
+        None => std::env::var(AGE_IDENTITY)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| Identity::from_variable(&value))
+            .ok_or_else(|| {
+                io::Error::other(format!(
+                    "can't decrypt {} without an age identity: set `{AGE_IDENTITY}`",
+                    path.display()
+                ))
+            })?,
+    };
+    decrypt(Command::new("age"), path, &chosen)
+}
+
+/// An age identity able to decrypt a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Identity {
+    /// The path of an identity file.
+    File(PathBuf),
+
+    /// The identity itself (e.g. `AGE-SECRET-KEY-1...`).
+    Key(String),
+}
+
+impl Identity {
+    /// Reads the value of [`AGE_IDENTITY`], which is either an identity or the path of its
+    /// file.
+    fn from_variable(value: &str) -> Self {
+        if value.trim_start().starts_with(SECRET_KEY_PREFIX) {
+            Self::Key(value.to_owned())
+        } else {
+            Self::File(value.into())
+        }
+    }
+}
+
+/// Decrypts the file at `path` with `age`, the command running the `age` command line, and
+/// `identity`. A [`Identity::Key`] is given through `stdin`, so it isn't seen by other
+/// processes.
+fn decrypt(mut age: Command, path: &Path, identity: &Identity) -> io::Result<String> {
+    age.arg("--decrypt").arg("--identity");
+    match identity {
+        Identity::File(file) => age.arg(file).stdin(Stdio::null()),
+        Identity::Key(_key) => age.arg("-").stdin(Stdio::piped()),
+    };
+    let mut child = age
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| io::Error::other(format!("can't run `age`: {err}")))?;
+    if let (Identity::Key(key), Some(mut stdin)) = (identity, child.stdin.take()) {
+        writeln!(stdin, "{}", key.trim())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!(
+            "can't decrypt {} with `age`: {}",
+            path.display(),
+            reason.trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod identity {
+        use super::super::Identity;
+
+        #[test]
+        fn from_variable() {
+            assert_eq!(
+                Identity::from_variable("AGE-SECRET-KEY-1QQQ"),
+                Identity::Key("AGE-SECRET-KEY-1QQQ".to_owned())
+            );
+            assert_eq!(
+                Identity::from_variable("/home/nick/.age/key.txt"),
+                Identity::File("/home/nick/.age/key.txt".into())
+            );
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod decrypt {
+        use super::super::decrypt;
+        use super::super::Identity;
+        use crate::fake_command::fake_command;
+        use std::path::Path;
+        use std::process::Command;
+
+        /// Returns a fake `age` command line running `script`, which gets the arguments
+        /// after `--decrypt --identity`.
+        fn age(script: &str) -> Command {
+            fake_command("age", &format!("shift 2; {script}"))
+        }
+
+        #[test]
+        fn identity_file() {
+            let identity = Identity::File("key.txt".into());
+
+            let decrypted = decrypt(
+                age(r#"echo "{ identity = \"$1\", file = \"$2\" }""#),
+                Path::new("config.ncl.age"),
+                &identity,
+            );
+
+            assert_eq!(
+                decrypted.unwrap(),
+                "{ identity = \"key.txt\", file = \"config.ncl.age\" }\n"
+            );
+        }
+
+        #[test]
+        fn identity_key() {
+            let identity = Identity::Key("AGE-SECRET-KEY-1QQQ\n".to_owned());
+
+            let decrypted = decrypt(
+                age(r#"test "$1" = - && read key && echo "{ key = \"$key\" }""#),
+                Path::new("config.ncl.age"),
+                &identity,
+            );
+
+            assert_eq!(decrypted.unwrap(), "{ key = \"AGE-SECRET-KEY-1QQQ\" }\n");
+        }
+
+        #[test]
+        fn failed() {
+            let identity = Identity::File("key.txt".into());
+
+            let result = decrypt(
+                age("echo 'age: error: no identity matched any of the recipients' >&2; exit 1"),
+                Path::new("config.ncl.age"),
+                &identity,
+            );
+
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "can't decrypt config.ncl.age with `age`: age: error: no identity matched any of \
+                 the recipients"
+            );
+        }
+    }
+}
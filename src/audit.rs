@@ -0,0 +1,108 @@
+use crate::disk_cache::hash;
+use crate::Failure;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// How many [`ReloadRecord`]s a [`crate::SharedConfig`] keeps.
+pub(crate) const HISTORY: usize = 32;
+
+/// An attempt to load a watched configuration, as listed by
+/// [`crate::SharedConfig::history`].
+///
+/// Serializable, so a running service can show its recent configuration history, e.g. from
+/// a debug endpoint.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReloadRecord {
+    /// When the configuration was loaded.
+    pub at: SystemTime,
+
+    /// The configuration file, or `None` if none was found.
+    pub path: Option<PathBuf>,
+
+    /// The SHA-256 of the contents of [`ReloadRecord::path`], in hexadecimal, to tell the
+    /// versions of the file apart. Stable, so it can be compared with `sha256sum`.
+    pub hash: Option<String>,
+
+    /// Why the configuration failed to load, or `None` if it loaded successfully.
+    pub failure: Option<Failure>,
+}
+
+impl ReloadRecord {
+    /// Records an attempt to load the configuration file at `path`, now, hashing its
+    /// current contents.
+    pub(crate) fn now(path: Option<PathBuf>, failure: Option<Failure>) -> Self {
+        Self {
+            at: SystemTime::now(),
+            hash: path
+                .as_ref()
+                .and_then(|file| std::fs::read(file).ok())
+                .map(|contents| hash(&contents)),
+            path,
+            failure,
+        }
+    }
+
+    /// Tells whether the configuration loaded successfully.
+    #[must_use]
+    pub const fn succeeded(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Adds `record` to the `history`, forgetting the oldest records beyond [`HISTORY`].
+pub(crate) fn remember(history: &mut VecDeque<ReloadRecord>, record: ReloadRecord) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(record);
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod remember {
+        use super::super::remember;
+        use super::super::ReloadRecord;
+        use super::super::HISTORY;
+        use std::collections::VecDeque;
+        use std::path::PathBuf;
+
+        #[test]
+        fn oldest_are_forgotten() {
+            let mut history = VecDeque::new();
+
+            for index in 0..=HISTORY {
+                remember(
+                    &mut history,
+                    ReloadRecord::now(Some(PathBuf::from(index.to_string())), None),
+                );
+            }
+
+            assert_eq!(history.len(), HISTORY);
+            assert_eq!(history.front().unwrap().path, Some(PathBuf::from("1")));
+        }
+    }
+
+    #[cfg(test)]
+    mod now {
+        use super::super::ReloadRecord;
+        use std::fs;
+
+        #[test]
+        fn sha_256() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "hello").unwrap();
+
+            let record = ReloadRecord::now(Some(path), None);
+
+            assert_eq!(
+                record.hash.as_deref(),
+                Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+            );
+        }
+    }
+}
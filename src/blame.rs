@@ -0,0 +1,81 @@
+use codespan::FileId;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::error::Error;
+use nickel_lang_core::error::EvalError;
+use nickel_lang_core::position::RawSpan;
+use nickel_lang_core::position::TermPos;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+
+/// Finds the configuration field holding the value that broke a contract, when `error` is
+/// a broken contract, and returns its dotted path (e.g. `server.port`) together with the
+/// custom message of the contract, if it has any.
+///
+/// The field is found by looking for the value blamed by the contract in the source of the
+/// configuration file `file_id`.
+pub(crate) fn blamed_field(
+    cache: &Cache,
+    file_id: FileId,
+    error: &Error,
+) -> Option<(String, Option<String>)> {
+    let Error::EvalError(EvalError::BlameError {
+        label,
+        evaluated_arg,
+        ..
+    }) = error
+    else {
+        return None;
+    };
+    let (rt, _errors) = cache.parse_nocache(file_id).ok()?;
+    // Contracts applying other contracts (e.g. `nullable`) blame their own argument, so the
+    // evaluated value is looked for when that argument isn't in the configuration.
+    let field = [
+        label.arg_pos,
+        evaluated_arg
+            .as_ref()
+            .map_or_else(Default::default, |arg| arg.pos),
+    ]
+    .into_iter()
+    .filter_map(TermPos::into_opt)
+    .find_map(|target| find(&rt, target, ""))?;
+    let message = label
+        .diagnostics
+        .iter()
+        .rev()
+        .find_map(|diagnostic| diagnostic.message.clone());
+
+    Some((field, message))
+}
+
+/// Returns the path, under `prefix`, of the innermost field of `rt` whose value contains
+/// `target`. Goes through the `let` bindings and annotations wrapping records.
+#[allow(clippy::wildcard_enum_match_arm)]
+fn find(rt: &RichTerm, target: RawSpan, prefix: &str) -> Option<String> {
+    let record = match rt.as_ref() {
+        Term::Record(record) | Term::RecRecord(record, ..) => record,
+        Term::Let(_, _, body, _) | Term::LetPattern(_, _, _, body) | Term::Annotated(_, body) => {
+            return find(body, target, prefix);
+        }
+        _ => return None,
+    };
+
+    record.fields.iter().find_map(|(ident, field)| {
+        let span = field.value.as_ref()?.pos.into_opt()?;
+        let contains =
+            span.src_id == target.src_id && span.start <= target.start && target.end <= span.end;
+        if !contains {
+            return None;
+        }
+
+        let path = if prefix.is_empty() {
+            ident.label().to_owned()
+        } else {
+            format!("{prefix}.{}", ident.label())
+        };
+        let nested = field
+            .value
+            .as_ref()
+            .and_then(|value| find(value, target, &path));
+        Some(nested.unwrap_or(path))
+    })
+}
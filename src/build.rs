@@ -0,0 +1,157 @@
+//! Helpers for build scripts, to check the Nickel files bundled with an application at
+//! build time rather than when users run it.
+
+use crate::Loader;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Evaluates every Nickel file (`.ncl` or `.nickel`) found in `dir` and its
+/// subdirectories, like bundled default configurations and templates, failing the build
+/// with their errors.
+///
+/// Meant to be called from `build.rs`, with `dir` relative to the manifest of the crate.
+/// The build script reruns whenever a file in `dir` changes.
+///
+/// ```no_run
+/// // build.rs
+/// nickelodeon::build::validate_dir("assets/config");
+/// ```
+pub fn validate_dir<P: AsRef<Path>>(dir: P) {
+    let app = std::env::var("CARGO_PKG_NAME").unwrap_or_default();
+    validate_dir_with(dir, &Loader::new(&app));
+}
+
+/// Same as [`validate_dir`], but evaluates the files with the options of `loader`, so they
+/// are also checked against its [`Loader::contract`]s, with its [`Loader::prelude`]s in
+/// scope.
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize)]
+/// # struct Config {}
+/// // build.rs
+/// let loader = nickelodeon::Loader::new("my-app").derived_contract::<Config>();
+/// nickelodeon::build::validate_dir_with("assets/config", &loader);
+/// ```
+pub fn validate_dir_with<P: AsRef<Path>>(dir: P, loader: &Loader) {
+    // There is nothing left to report to if stdout is gone.
+    let _ignored: io::Result<usize> = validate(dir.as_ref(), loader, &mut io::stdout().lock());
+}
+
+/// Evaluates the Nickel files in `dir`, writing the cargo instructions reporting their
+/// errors to `out`, and returns how many of them are invalid.
+fn validate<W: Write>(dir: &Path, loader: &Loader, out: &mut W) -> io::Result<usize> {
+    writeln!(out, "cargo::rerun-if-changed={}", dir.display())?;
+    let mut files = Vec::new();
+    if let Err(err) = nickel_files(dir, &mut files) {
+        writeln!(out, "cargo::error=can't read `{}`: {err}", dir.display())?;
+        return Ok(1);
+    }
+    files.sort();
+
+    let mut invalid: usize = 0;
+    for file in files {
+        let checked = loader
+            .clone()
+            .config_path_from_flag(Some(file))
+            .required(true)
+            .diagnostics(io::sink())
+            .program();
+        if let Err(error) = checked {
+            invalid = invalid.saturating_add(1);
+            for line in error.render().lines() {
+                writeln!(out, "cargo::error={line}")?;
+            }
+        }
+    }
+    Ok(invalid)
+}
+
+/// Collects the Nickel files found in `dir` and its subdirectories into `files`.
+fn nickel_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            nickel_files(&path, files)?;
+            continue;
+        }
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "ncl" || extension == "nickel")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod validate {
+        use super::super::validate;
+        use crate::Loader;
+        use std::fs;
+
+        fn loader() -> Loader {
+            Loader::new("nickelodeon_test")
+        }
+
+        #[test]
+        fn valid_files() {
+            let dir = tempfile::tempdir().unwrap();
+            fs::write(dir.path().join("defaults.ncl"), "{ port = 8080 }").unwrap();
+            fs::create_dir_all(dir.path().join("templates")).unwrap();
+            fs::write(dir.path().join("templates/web.nickel"), "{ port = 80 }").unwrap();
+
+            let mut out = Vec::new();
+            let result = validate(dir.path(), &loader(), &mut out).unwrap();
+
+            assert_eq!(result, 0);
+            let expected = format!("cargo::rerun-if-changed={}\n", dir.path().display());
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+
+        #[test]
+        fn invalid_files() {
+            let dir = tempfile::tempdir().unwrap();
+            fs::write(dir.path().join("defaults.ncl"), "{ port = 8080 }").unwrap();
+            fs::create_dir_all(dir.path().join("templates")).unwrap();
+            fs::write(dir.path().join("templates/web.ncl"), "{ port = 1 + \"\" }").unwrap();
+            fs::write(dir.path().join("README.md"), "{").unwrap();
+
+            let mut out = Vec::new();
+            let result = validate(dir.path(), &loader(), &mut out).unwrap();
+
+            assert_eq!(result, 1);
+            let output = String::from_utf8(out).unwrap();
+            assert!(output.lines().any(|line| line.starts_with("cargo::error=")));
+            assert!(output.contains("web.ncl"));
+        }
+
+        #[test]
+        fn contracts() {
+            let dir = tempfile::tempdir().unwrap();
+            fs::write(dir.path().join("defaults.ncl"), "{ port = -1 }").unwrap();
+            let loader = loader().contract("{ port | std.number.PosNat, .. }");
+
+            let result = validate(dir.path(), &loader, &mut Vec::new()).unwrap();
+
+            assert_eq!(result, 1);
+        }
+
+        #[test]
+        fn missing_dir() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut out = Vec::new();
+            let result = validate(&dir.path().join("missing"), &loader(), &mut out).unwrap();
+
+            assert_eq!(result, 1);
+            assert!(String::from_utf8(out)
+                .unwrap()
+                .contains("cargo::error=can't read"));
+        }
+    }
+}
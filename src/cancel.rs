@@ -0,0 +1,43 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Cancels a load in progress, failing it with [`crate::Error::Cancelled`], e.g. when the
+/// user quits or presses Ctrl-C during a reload.
+///
+/// Clones share the same state, so one clone can be handed to [`crate::Loader::cancellation`]
+/// while another one is kept to cancel the load from a different thread.
+///
+/// ```
+/// let token = nickelodeon::CancellationToken::default();
+/// let loader = nickelodeon::Loader::new("my-app").cancellation(token.clone());
+///
+/// // From a signal handler, or the thread of the user interface:
+/// token.cancel();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Cancels the loads using this token, or any of its clones. Loads that finished are
+    /// not affected, and loads started later fail right away.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Tells whether the token was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    /// Uses `flag` as the cancellation state: the loads are cancelled once it's `true`.
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+}
+
+/// The payload the evaluation unwinds with when its [`CancellationToken`] is cancelled.
+pub(crate) struct Cancelled;
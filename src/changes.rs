@@ -0,0 +1,258 @@
+use crate::secret::exposing;
+use crate::secret::unmarked;
+use crate::secret::Exposure;
+use crate::secret::REDACTED;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// The settings that changed between two versions of a configuration, as delivered by a
+/// [`crate::Watcher`] or given to the [`crate::SharedConfig::on_change`] callbacks.
+///
+/// Its [`fmt::Display`] implementation summarizes it for logs, one setting per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    changes: Vec<Change>,
+}
+
+/// A setting that changed between two versions of a configuration.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// The dotted path of the setting, like `server.port`.
+    pub path: String,
+
+    /// The previous value of the setting, or `None` if it was added.
+    pub before: Option<Value>,
+
+    /// The new value of the setting, or `None` if it was removed.
+    pub after: Option<Value>,
+}
+
+impl ChangeSet {
+    /// Lists the settings that changed between the `old` and `new` versions of a
+    /// configuration, serialized by [`serialized`], redacting the values of the [`Secret`]s
+    /// and of the `secrets` (dotted paths), and of everything nested in them.
+    ///
+    /// [`Secret`]: crate::Secret
+    pub(crate) fn between(old: &Value, new: &Value, secrets: &[String]) -> Self {
+        let mut redacted_paths = secrets.to_vec();
+        let old_values = unmarked(old, "", &mut redacted_paths);
+        let new_values = unmarked(new, "", &mut redacted_paths);
+        let mut changes = Vec::new();
+        differences(Some(&old_values), Some(&new_values), "", &mut changes);
+        changes.sort_by(|left, right| left.path.cmp(&right.path));
+        for change in &mut changes {
+            if redacted_paths
+                .iter()
+                .any(|secret| within(&change.path, secret))
+            {
+                let redacted = || Value::String(REDACTED.to_owned());
+                change.before = change.before.as_ref().map(|_| redacted());
+                change.after = change.after.as_ref().map(|_| redacted());
+            }
+        }
+        Self { changes }
+    }
+
+    /// Returns the settings that changed, were added or were removed, sorted by path.
+    /// Records are compared field by field, while any change in an array counts as a
+    /// change of the whole array.
+    #[must_use]
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns the dotted paths (like `server.port`) of the [`ChangeSet::changes`].
+    #[must_use]
+    pub fn paths(&self) -> Vec<&str> {
+        self.changes
+            .iter()
+            .map(|change| change.path.as_str())
+            .collect()
+    }
+
+    /// Tells whether nothing changed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Tells whether the setting at `path` (a dotted path, like `server` or `server.port`)
+    /// changed, including any setting nested in it.
+    #[must_use]
+    pub fn contains(&self, path: &str) -> bool {
+        self.changes.iter().any(|change| within(&change.path, path))
+    }
+}
+
+impl fmt::Display for ChangeSet {
+    /// Writes one line per change: `+ path: after` for the added settings, `- path: before`
+    /// for the removed ones, and `~ path: before -> after` for the modified ones.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            match (&change.before, &change.after) {
+                (None, Some(after)) => writeln!(f, "+ {}: {after}", change.path)?,
+                (Some(before), None) => writeln!(f, "- {}: {before}", change.path)?,
+                (Some(before), Some(after)) => {
+                    writeln!(f, "~ {}: {before} -> {after}", change.path)?;
+                }
+                (None, None) => writeln!(f, "~ {}", change.path)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes `config` to compare it with [`ChangeSet::between`], the [`Secret`]s marked so
+/// they are redacted.
+///
+/// [`Secret`]: crate::Secret
+pub(crate) fn serialized<T: Serialize>(config: &T) -> Option<Value> {
+    exposing(Exposure::Marked, || serde_json::to_value(config).ok())
+}
+
+/// Tells whether the setting at `path` is the one at `ancestor`, or nested in it.
+fn within(path: &str, ancestor: &str) -> bool {
+    path.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Adds the settings, under `prefix`, that differ between `old` and `new` to `changes`.
+fn differences(old: Option<&Value>, new: Option<&Value>, prefix: &str, changes: &mut Vec<Change>) {
+    let (Some(Value::Object(old_fields)), Some(Value::Object(new_fields))) = (old, new) else {
+        if old != new {
+            changes.push(Change {
+                path: prefix.to_owned(),
+                before: old.cloned(),
+                after: new.cloned(),
+            });
+        }
+        return;
+    };
+    for (name, old_value) in old_fields {
+        differences(
+            Some(old_value),
+            new_fields.get(name),
+            &nested(prefix, name),
+            changes,
+        );
+    }
+    for (name, new_value) in new_fields {
+        if !old_fields.contains_key(name) {
+            differences(None, Some(new_value), &nested(prefix, name), changes);
+        }
+    }
+}
+
+/// Returns the path of the field `name` of the record at `prefix`.
+fn nested(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod between {
+        use super::super::serialized;
+        use super::super::ChangeSet;
+        use crate::Secret;
+        use serde_json::json;
+
+        #[test]
+        fn nothing_changed() {
+            let config = json!({ "server": { "port": 80 }, "hosts": ["a", "b"] });
+
+            let changes = ChangeSet::between(&config, &config, &[]);
+
+            assert!(changes.is_empty());
+        }
+
+        #[test]
+        fn nested_fields() {
+            let old = json!({
+                "server": { "host": "localhost", "port": 80 },
+                "hosts": ["a", "b"],
+                "log": "info",
+                "workers": 4,
+            });
+            let new = json!({
+                "server": { "host": "localhost", "port": 8080 },
+                "hosts": ["a"],
+                "log": "info",
+                "timeout": 30,
+            });
+
+            let changes = ChangeSet::between(&old, &new, &[]);
+
+            assert_eq!(
+                changes.paths(),
+                ["hosts", "server.port", "timeout", "workers"]
+            );
+            assert_eq!(
+                changes.to_string(),
+                "~ hosts: [\"a\",\"b\"] -> [\"a\"]\n\
+                 ~ server.port: 80 -> 8080\n\
+                 + timeout: 30\n\
+                 - workers: 4\n"
+            );
+        }
+
+        #[test]
+        fn contains() {
+            let old = json!({ "server": { "port": 80 }, "server_name": "a" });
+            let new = json!({ "server": { "port": 8080 }, "server_name": "a" });
+
+            let changes = ChangeSet::between(&old, &new, &[]);
+
+            assert!(changes.contains("server"));
+            assert!(changes.contains("server.port"));
+            assert!(!changes.contains("server.host"));
+            assert!(!changes.contains("server_name"));
+            assert!(!changes.contains("serv"));
+        }
+
+        #[test]
+        fn secrets() {
+            let old = json!({ "database": { "password": "hunter2", "port": 5432 } });
+            let new = json!({ "database": { "password": "letmein", "port": 5433 } });
+
+            let changes = ChangeSet::between(&old, &new, &["database.password".to_owned()]);
+
+            assert_eq!(
+                changes.to_string(),
+                "~ database.password: \"<redacted>\" -> \"<redacted>\"\n\
+                 ~ database.port: 5432 -> 5433\n"
+            );
+        }
+
+        #[test]
+        fn secret_values() {
+            #[derive(serde::Serialize)]
+            struct Database {
+                password: Secret<String>,
+                port: u16,
+            }
+
+            let old = serialized(&Database {
+                password: Secret::new("hunter2".to_owned()),
+                port: 5432,
+            });
+            let new = serialized(&Database {
+                password: Secret::new("letmein".to_owned()),
+                port: 5432,
+            });
+
+            let changes = ChangeSet::between(&old.unwrap(), &new.unwrap(), &[]);
+
+            assert_eq!(
+                changes.to_string(),
+                "~ password: \"<redacted>\" -> \"<redacted>\"\n"
+            );
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Helpers for [clap](https://docs.rs/clap) command lines, so their flags can override the
+//! settings of the configuration.
+
+use crate::secret::exposing;
+use crate::secret::Exposure;
+use crate::Error;
+use crate::FieldError;
+use crate::Result;
+use ::clap::parser::ValueSource;
+use ::clap::ArgMatches;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Overrides the settings of `config` with the arguments in `matches` given on the command
+/// line, or through environment variables.
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig {}
+/// # fn main() -> nickelodeon::Result<()> {
+/// let matches = clap::Command::new("my_app")
+///     .arg(clap::Arg::new("server_port").long("server-port"))
+///     .get_matches();
+/// let config: MyConfig = nickelodeon::load_configuration("my_app", None);
+/// let config = nickelodeon::clap::merge_args(config, &matches)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Arguments are matched to settings by name: the id of an argument (by default, the name
+/// of its field in a `clap::Parser` struct) is the dotted path of its setting, with dots and
+/// dashes replaced by underscores, e.g. `server_port` for `server.port`. Arguments without
+/// a setting are left alone.
+///
+/// The precedence is flags, then environment variables, then configuration files and
+/// last the defaults of the configuration. The default values of the arguments are
+/// ignored, as the configuration always has a value of its own.
+///
+/// # Errors
+///
+/// Will return `Err` if `config` can't be serialized, or if an argument isn't a valid
+/// value for its setting. The errors name the flag, as given by [`flag_name`].
+pub fn merge_args<T>(config: T, matches: &ArgMatches) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut settings = exposing(Exposure::Revealed, || serde_json::to_value(config))
+        .map_err(|err| Error::SerializationError(err.to_string()))?;
+    let mut errors = Vec::new();
+    for id in matches.ids() {
+        let given = matches!(
+            matches.value_source(id.as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        if !given {
+            continue;
+        }
+        let Ok(Some(raw)) = matches.try_get_raw(id.as_str()) else {
+            continue;
+        };
+        let Some((path, setting)) = setting(&mut settings, id.as_str(), "") else {
+            continue;
+        };
+        let values: Vec<String> = raw.map(|arg| arg.to_string_lossy().into_owned()).collect();
+        match parsed(setting, &values) {
+            Some(value) => *setting = value,
+            None => errors.push(FieldError {
+                message: format!(
+                    "`{}` is not a valid value for {}",
+                    values.join(" "),
+                    flag_name(&path)
+                ),
+                path,
+            }),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(Error::InvalidFields(errors));
+    }
+    serde_json::from_value(settings).map_err(|err| {
+        Error::InvalidFields(vec![FieldError {
+            path: String::new(),
+            message: err.to_string(),
+        }])
+    })
+}
+
+/// Returns the long flag of the setting at `path` (a dotted path, like `server.port`), as
+/// `clap` derives it from the id [`merge_args`] matches: `--server-port`.
+#[must_use]
+pub fn flag_name(path: &str) -> String {
+    format!("--{}", path.replace(['.', '_'], "-"))
+}
+
+/// Finds the setting, under `value` at `prefix`, whose path matches the argument `id`.
+/// Returns its path and value.
+fn setting<'value>(
+    value: &'value mut Value,
+    id: &str,
+    prefix: &str,
+) -> Option<(String, &'value mut Value)> {
+    let Value::Object(fields) = value else {
+        return None;
+    };
+    let wanted = normalized(id);
+    fields.iter_mut().find_map(|(name, field)| {
+        let path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        let candidate = normalized(&path);
+        if candidate == wanted {
+            Some((path, field))
+        } else if wanted.starts_with(&format!("{candidate}_")) {
+            setting(field, id, &path)
+        } else {
+            None
+        }
+    })
+}
+
+/// Turns a dotted path, or an argument id, into the name they are compared by.
+fn normalized(name: &str) -> String {
+    name.replace(['.', '-'], "_")
+}
+
+/// Parses the raw `values` of an argument as the new value of the setting whose value is
+/// `current`, or `None` if they aren't valid for its type.
+fn parsed(current: &Value, values: &[String]) -> Option<Value> {
+    match current {
+        Value::Array(elements) => values
+            .iter()
+            .map(|raw| {
+                elements.first().map_or_else(
+                    || Some(guessed(raw)),
+                    |element| parsed(element, std::slice::from_ref(raw)),
+                )
+            })
+            .collect(),
+        Value::String(_) => values.last().cloned().map(Value::String),
+        Value::Bool(_) => values.last()?.parse().ok().map(Value::Bool),
+        Value::Number(_) => serde_json::from_str(values.last()?).ok().map(Value::Number),
+        Value::Null | Value::Object(_) => Some(guessed(values.last()?)),
+    }
+}
+
+/// Parses `raw` as a boolean or a number if it looks like one, as a string otherwise.
+fn guessed(raw: &str) -> Value {
+    match serde_json::from_str(raw) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_))) => value,
+        _ => Value::String(raw.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod merge_args {
+        use super::super::merge_args;
+        use crate::Error;
+        use clap::Arg;
+        use clap::ArgAction;
+        use clap::Command;
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+        struct Config {
+            name: String,
+            verbose: bool,
+            tags: Vec<String>,
+            server: Server,
+        }
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        fn config() -> Config {
+            Config {
+                name: "from the file".to_owned(),
+                verbose: false,
+                tags: Vec::new(),
+                server: Server { port: 80 },
+            }
+        }
+
+        fn command() -> Command {
+            Command::new("app")
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .env("NICKELODEON_TEST_CLAP_NAME")
+                        .default_value("from the default"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(Arg::new("tags").long("tag").action(ArgAction::Append))
+                .arg(Arg::new("server_port").long("server-port"))
+                .arg(Arg::new("unrelated").long("unrelated"))
+        }
+
+        #[test]
+        fn flags_override_the_configuration() {
+            let matches = command().get_matches_from([
+                "app",
+                "--verbose",
+                "--tag",
+                "a",
+                "--tag",
+                "b",
+                "--server-port",
+                "8080",
+                "--unrelated",
+                "x",
+            ]);
+
+            let merged = merge_args(config(), &matches).unwrap();
+
+            assert_eq!(
+                merged,
+                Config {
+                    name: "from the file".to_owned(),
+                    verbose: true,
+                    tags: vec!["a".to_owned(), "b".to_owned()],
+                    server: Server { port: 8080 },
+                }
+            );
+        }
+
+        #[test]
+        fn environment_variables_override_the_configuration() {
+            std::env::set_var("NICKELODEON_TEST_CLAP_NAME", "from the environment");
+            let from_env = command().get_matches_from(["app"]);
+            let from_flag = command().get_matches_from(["app", "--name", "from the flag"]);
+            std::env::remove_var("NICKELODEON_TEST_CLAP_NAME");
+
+            let merged = merge_args(config(), &from_env).unwrap();
+            let flagged = merge_args(config(), &from_flag).unwrap();
+
+            assert_eq!(merged.name, "from the environment");
+            assert_eq!(flagged.name, "from the flag");
+        }
+
+        #[test]
+        fn defaults_of_the_arguments_are_ignored() {
+            let matches = command().get_matches_from(["app"]);
+
+            let merged = merge_args(config(), &matches).unwrap();
+
+            assert_eq!(merged, config());
+        }
+
+        #[test]
+        fn invalid_values() {
+            let matches = command().get_matches_from(["app", "--server-port", "http"]);
+
+            let result = merge_args(config(), &matches);
+
+            let Err(Error::InvalidFields(errors)) = result else {
+                panic!("expected invalid fields, got {result:?}");
+            };
+            assert_eq!(
+                errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                ["`server.port`: `http` is not a valid value for --server-port"]
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod flag_name {
+        use super::super::flag_name;
+
+        #[test]
+        fn kebab_case() {
+            assert_eq!(
+                flag_name("server.max_connections"),
+                "--server-max-connections"
+            );
+        }
+    }
+}
@@ -0,0 +1,707 @@
+use crate::prelude::quoted;
+use crate::syntax::Annotations;
+use crate::syntax::Nickel;
+use serde::de;
+use serde::de::value::Error;
+use serde::de::DeserializeOwned;
+use serde::de::IntoDeserializer as _;
+use serde::de::Visitor;
+use serde::forward_to_deserialize_any;
+use std::cell::Cell;
+use std::fmt::Write as _;
+
+/// How deep options, sequences and maps are followed, so recursive types can be described.
+const MAX_DEPTH: usize = 16;
+
+/// The words that can't be used as bare field names in Nickel.
+const KEYWORDS: &[&str] = &[
+    "Array",
+    "Bool",
+    "Dyn",
+    "Number",
+    "String",
+    "default",
+    "doc",
+    "else",
+    "false",
+    "forall",
+    "force",
+    "fun",
+    "if",
+    "import",
+    "in",
+    "let",
+    "match",
+    "not_exported",
+    "null",
+    "optional",
+    "priority",
+    "rec",
+    "then",
+    "true",
+];
+
+/// Returns a Nickel contract checking that a configuration can be deserialized into a `T`.
+///
+/// It can be passed to [`crate::Loader::contract`] (see [`crate::Loader::derived_contract`]),
+/// or written next to the configuration so its authors can import it for editor support.
+///
+/// The contract is derived from the `Deserialize` implementation of `T`, so it works with
+/// any `#[derive(Deserialize)]` type: fields become record fields (`optional` when they
+/// have a default or are an `Option`), integers become `std.number.Nat` or
+/// `std.number.Integer`, sequences become arrays and maps become dictionaries. Unknown
+/// fields are allowed, like serde does by default, and what can't be described (enums,
+/// untagged values, values parsed from strings...) is left unchecked.
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Server {
+///     host: String,
+///     #[serde(default)]
+///     port: u16,
+/// }
+///
+/// let contract = nickelodeon::contract_for::<Server>();
+/// assert!(contract.contains("port | optional | std.number.Nat"));
+/// ```
+#[must_use]
+pub fn contract_for<T: DeserializeOwned>() -> String {
+    let (mut shape, _complete) = probe::<T>(None);
+    shape.for_each_field(&mut |field| {
+        let (_shape, result) = probe::<T>(Some(field.index));
+        field.required =
+            result.is_err_and(|err| err.to_string() == format!("missing field `{}`", field.name));
+    });
+
+    let mut contract = String::from(
+        "let nullable = fun contract label value =>\n  \
+         if value == null then value else std.contract.apply contract label value\nin\n",
+    );
+    shape.render(&mut contract, 0);
+    contract.push('\n');
+    contract
+}
+
+/// Returns the contracts of the fields of `value`, a value of `T`, whose type is a simple
+/// one (a number, a string, a boolean or an array of those), e.g. ` | std.number.Nat`, to
+/// annotate its source with.
+pub(crate) fn field_contracts<T: DeserializeOwned>(value: &Nickel) -> Annotations {
+    let (shape, _complete) = probe::<T>(None);
+    let mut annotations = Annotations::new();
+    shape.annotate(value, &mut Vec::new(), &mut annotations);
+    annotations
+}
+
+/// Feeds `T` with placeholder values, returning the shape it asked for and whether it
+/// could be built. The field numbered `omitted`, if any, is left out of its struct.
+fn probe<T: DeserializeOwned>(omitted: Option<usize>) -> (Shape, Result<T, Error>) {
+    let state = State {
+        fields: Cell::new(0),
+        omitted,
+    };
+    let mut shape = Shape::Dyn;
+    let result = T::deserialize(Probe {
+        state: &state,
+        depth: 0,
+        out: &mut shape,
+    });
+    (shape, result)
+}
+
+/// What a `Deserialize` implementation asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Shape {
+    Dyn,
+    Bool,
+    Nat,
+    Integer,
+    Number,
+    String,
+    Nullable(Box<Self>),
+    Array(Box<Self>),
+    Dictionary(Box<Self>),
+    Record(Vec<Field>),
+}
+
+/// A field of a struct, numbered in the order the probe met it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    name: &'static str,
+    index: usize,
+    required: bool,
+    shape: Shape,
+}
+
+impl Shape {
+    fn for_each_field(&mut self, action: &mut impl FnMut(&mut Field)) {
+        match self {
+            Self::Nullable(inner) | Self::Array(inner) | Self::Dictionary(inner) => {
+                inner.for_each_field(action);
+            }
+            Self::Record(fields) => {
+                for field in fields {
+                    action(field);
+                    field.shape.for_each_field(action);
+                }
+            }
+            Self::Dyn | Self::Bool | Self::Nat | Self::Integer | Self::Number | Self::String => {}
+        }
+    }
+
+    /// Adds the contracts of the fields of `value`, the value at `path` of this shape, to
+    /// `annotations`.
+    fn annotate(&self, value: &Nickel, path: &mut Vec<String>, annotations: &mut Annotations) {
+        let (Self::Record(fields), Nickel::Record(values)) = (self, value) else {
+            return;
+        };
+        for (name, field_value) in values {
+            let Some(field) = fields.iter().find(|field| field.name == name) else {
+                continue;
+            };
+            path.push(name.clone());
+            if field.shape.is_simple() {
+                let mut contract = String::from(" | ");
+                field.shape.render(&mut contract, 0);
+                annotations.insert(path.clone(), contract);
+            }
+            field.shape.annotate(field_value, path, annotations);
+            path.pop();
+        }
+    }
+
+    /// Tells whether this is the shape of a number, a string, a boolean or an array of
+    /// those.
+    fn is_simple(&self) -> bool {
+        match self {
+            Self::Bool | Self::Nat | Self::Integer | Self::Number | Self::String => true,
+            Self::Array(inner) => inner.is_simple(),
+            Self::Dyn | Self::Nullable(_) | Self::Dictionary(_) | Self::Record(_) => false,
+        }
+    }
+
+    /// Writes the shape as a Nickel contract, with its nested lines indented by `indent`.
+    fn render(&self, out: &mut String, indent: usize) {
+        match self {
+            Self::Dyn => out.push_str("Dyn"),
+            Self::Bool => out.push_str("Bool"),
+            Self::Nat => out.push_str("std.number.Nat"),
+            Self::Integer => out.push_str("std.number.Integer"),
+            Self::Number => out.push_str("Number"),
+            Self::String => out.push_str("String"),
+            Self::Nullable(inner) => {
+                out.push_str("nullable (");
+                inner.render(out, indent);
+                out.push(')');
+            }
+            Self::Array(inner) => {
+                out.push_str("Array (");
+                inner.render(out, indent);
+                out.push(')');
+            }
+            Self::Dictionary(inner) => {
+                out.push_str("{ _ : ");
+                inner.render(out, indent);
+                out.push_str(" }");
+            }
+            Self::Record(fields) => {
+                let padding = "  ".repeat(indent.saturating_add(1));
+                out.push_str("{\n");
+                for field in fields {
+                    let _infallible = write!(out, "{padding}{} | ", field_name(field.name));
+                    if !field.required {
+                        out.push_str("optional | ");
+                    }
+                    field.shape.render(out, indent.saturating_add(1));
+                    out.push_str(",\n");
+                }
+                let _infallible = write!(out, "{padding}..\n{}}}", "  ".repeat(indent));
+            }
+        }
+    }
+}
+
+/// Returns `name` as a Nickel field name, quoted when it isn't a valid identifier.
+fn field_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|next| next.is_ascii_alphanumeric() || next == '_')
+        && !KEYWORDS.contains(&name);
+    if identifier {
+        name.to_owned()
+    } else {
+        quoted(name)
+    }
+}
+
+struct State {
+    /// How many struct fields the probe met so far.
+    fields: Cell<usize>,
+    omitted: Option<usize>,
+}
+
+struct Probe<'probe> {
+    state: &'probe State,
+    depth: usize,
+    out: &'probe mut Shape,
+}
+
+impl<'probe> Probe<'probe> {
+    const fn nested<'nested>(&self, out: &'nested mut Shape) -> Probe<'nested>
+    where
+        'probe: 'nested,
+    {
+        Probe {
+            state: self.state,
+            depth: self.depth.saturating_add(1),
+            out,
+        }
+    }
+
+    const fn exhausted(&self) -> bool {
+        self.depth >= MAX_DEPTH
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Probe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::Dyn;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::Bool;
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::Integer;
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::Nat;
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::Number;
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::String;
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = Shape::String;
+        visitor.visit_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut inner = Shape::Dyn;
+        let result = if self.exhausted() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self.nested(&mut inner))
+        };
+        *self.out = Shape::Nullable(Box::new(inner));
+        result
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut element = Shape::Dyn;
+        let elements = usize::from(!self.exhausted());
+        let result = visitor.visit_seq(SeqProbe {
+            probe: self.nested(&mut element),
+            remaining: elements,
+        });
+        *self.out = Shape::Array(Box::new(element));
+        result
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut elements = Shape::Dyn;
+        let result = visitor.visit_seq(SeqProbe {
+            probe: self.nested(&mut elements),
+            remaining: len,
+        });
+        *self.out = Shape::Array(Box::new(Shape::Dyn));
+        result
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut value = Shape::Dyn;
+        let entries = usize::from(!self.exhausted());
+        let result = visitor.visit_map(MapProbe {
+            probe: self.nested(&mut value),
+            remaining: entries,
+        });
+        *self.out = Shape::Dictionary(Box::new(value));
+        result
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut access = StructProbe {
+            state: self.state,
+            depth: self.depth,
+            names: fields.iter(),
+            fields: Vec::new(),
+        };
+        let result = visitor.visit_map(&mut access);
+        *self.out = Shape::Record(access.fields);
+        result
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        *self.out = Shape::Dyn;
+        let Some(variant) = variants.first() else {
+            return Err(de::Error::custom("enum without variants"));
+        };
+        visitor.visit_enum(VariantProbe {
+            state: self.state,
+            depth: self.depth,
+            variant,
+        })
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct
+    }
+}
+
+/// Gives `remaining` probed elements.
+struct SeqProbe<'probe> {
+    probe: Probe<'probe>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqProbe<'_> {
+    type Error = Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let Some(remaining) = self.remaining.checked_sub(1) else {
+            return Ok(None);
+        };
+        self.remaining = remaining;
+        seed.deserialize(Probe {
+            state: self.probe.state,
+            depth: self.probe.depth,
+            out: &mut *self.probe.out,
+        })
+        .map(Some)
+    }
+}
+
+/// Gives `remaining` entries with an empty key and a probed value.
+struct MapProbe<'probe> {
+    probe: Probe<'probe>,
+    remaining: usize,
+}
+
+impl<'de> de::MapAccess<'de> for MapProbe<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let Some(remaining) = self.remaining.checked_sub(1) else {
+            return Ok(None);
+        };
+        self.remaining = remaining;
+        seed.deserialize("".into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(Probe {
+            state: self.probe.state,
+            depth: self.probe.depth,
+            out: &mut *self.probe.out,
+        })
+    }
+}
+
+/// Gives every field of a struct, but the omitted one, with a probed value.
+struct StructProbe<'probe> {
+    state: &'probe State,
+    depth: usize,
+    names: std::slice::Iter<'static, &'static str>,
+    fields: Vec<Field>,
+}
+
+impl<'de> de::MapAccess<'de> for StructProbe<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        for name in self.names.by_ref() {
+            let index = self.state.fields.get();
+            self.state.fields.set(index.saturating_add(1));
+            if self.state.omitted == Some(index) {
+                continue;
+            }
+            self.fields.push(Field {
+                name,
+                index,
+                required: false,
+                shape: Shape::Dyn,
+            });
+            return seed.deserialize(name.into_deserializer()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let Some(field) = self.fields.last_mut() else {
+            return Err(de::Error::custom("value without a field"));
+        };
+        seed.deserialize(Probe {
+            state: self.state,
+            depth: self.depth,
+            out: &mut field.shape,
+        })
+    }
+}
+
+/// Picks the first variant of an enum, with probed values.
+struct VariantProbe<'probe> {
+    state: &'probe State,
+    depth: usize,
+    variant: &'static str,
+}
+
+impl VariantProbe<'_> {
+    const fn probe<'scratch>(&'scratch self, scratch: &'scratch mut Shape) -> Probe<'scratch> {
+        Probe {
+            state: self.state,
+            depth: self.depth.saturating_add(1),
+            out: scratch,
+        }
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for VariantProbe<'_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self), Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantProbe<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let mut scratch = Shape::Dyn;
+        seed.deserialize(self.probe(&mut scratch))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut scratch = Shape::Dyn;
+        de::Deserializer::deserialize_tuple(self.probe(&mut scratch), len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut scratch = Shape::Dyn;
+        de::Deserializer::deserialize_struct(self.probe(&mut scratch), "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Server {
+        host: String,
+        #[serde(default)]
+        port: u16,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct TestConfiguration {
+        name: String,
+        server: Option<Server>,
+        mirrors: Vec<Server>,
+        retries: i32,
+        ratio: f64,
+        verbose: bool,
+        labels: HashMap<String, String>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Names {
+        max_connections: u32,
+        default: bool,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Tree {
+        children: Vec<Self>,
+    }
+
+    #[cfg(test)]
+    mod contract_for {
+        use super::super::contract_for;
+        use super::Names;
+        use super::TestConfiguration;
+        use super::Tree;
+
+        #[test]
+        fn describes_the_fields() {
+            let result = contract_for::<TestConfiguration>();
+
+            let expected = "let nullable = fun contract label value =>
+  if value == null then value else std.contract.apply contract label value
+in
+{
+  name | String,
+  server | optional | nullable ({
+    host | String,
+    port | optional | std.number.Nat,
+    ..
+  }),
+  mirrors | Array ({
+    host | String,
+    port | optional | std.number.Nat,
+    ..
+  }),
+  retries | std.number.Integer,
+  ratio | Number,
+  verbose | Bool,
+  labels | { _ : String },
+  ..
+}
+";
+            assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn recursive_types() {
+            let result = contract_for::<Tree>();
+
+            assert!(result.contains("children | Array ({"));
+        }
+
+        #[test]
+        fn quoted_field_names() {
+            let result = contract_for::<Names>();
+
+            assert!(result.contains("\"max-connections\" | std.number.Nat"));
+            assert!(result.contains("\"default\" | Bool"));
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::ChangeSet;
+use crate::Loader;
+use crate::Result;
+use crate::SharedConfig;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::Write as _;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Duration;
+
+/// Sets up the configuration of a daemon with the codename `app`, then hands it to `daemon`
+/// and returns the future it builds, for the runtime of the application to run it:
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig { workers: usize }
+/// # fn resize(workers: usize) {}
+/// # async fn run() {
+/// nickelodeon::run_with_config::<MyConfig, _, _>("my_app", |mut config| async move {
+///     resize(config.current().workers);
+///     loop {
+///         if config.changed().await.contains("workers") {
+///             resize(config.current().workers);
+///         }
+///     }
+/// })
+/// .await;
+/// # }
+/// ```
+///
+/// The configuration is looked for and loaded right away: Nickel diagnostics are printed to
+/// `stderr` and the process exits if it can't be loaded, like [`crate::load_configuration`]
+/// does. Then it's loaded again every time its files change, checking them every second, or
+/// when the process receives a `SIGHUP` (see [`Loader::reload_on_hangup`]). What changed is
+/// printed to `stderr`, and so are the diagnostics of the configurations that fail to load,
+/// which are skipped to keep the last valid one.
+#[must_use]
+#[allow(clippy::exit)]
+pub fn run_with_config<T, F, D>(app: &str, daemon: F) -> D
+where
+    T: DeserializeOwned + Serialize + Default + Send + Sync + 'static,
+    F: FnOnce(ConfigEvents<T>) -> D,
+    D: Future,
+{
+    let loader = Loader::new(app).reload_on_hangup(true);
+    let events = events(&loader, Duration::from_secs(1))
+        .unwrap_or_else(|err| std::process::exit(err.exit_code()));
+    daemon(events)
+}
+
+/// Watches the configuration loaded by `loader`, checking its files every `interval`,
+/// writing its changes and the diagnostics of its failures to the [`Loader::diagnostics`].
+pub(crate) fn events<T>(loader: &Loader, interval: Duration) -> Result<ConfigEvents<T>>
+where
+    T: DeserializeOwned + Serialize + Default + Send + Sync + 'static,
+{
+    let config = loader.watch_shared(interval)?;
+    let pending = Arc::new(Mutex::new(Pending {
+        changes: VecDeque::new(),
+        waker: None,
+    }));
+    let notified = Arc::clone(&pending);
+    let mut reloads = loader.diagnostic_sink();
+    config.on_change(move |_, changes| {
+        let _ignored = write!(reloads, "configuration reloaded:\n{changes}");
+        let mut state = notified.lock().unwrap_or_else(PoisonError::into_inner);
+        state.changes.push_back(changes.clone());
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    let mut failures = loader.diagnostic_sink();
+    config.on_failure(move |failure| {
+        let _ignored = write!(
+            failures,
+            "invalid configuration, keeping the previous one:\n{failure}"
+        );
+    });
+    Ok(ConfigEvents { config, pending })
+}
+
+/// The configuration of a daemon started with [`run_with_config`], always the last valid
+/// one, with a way to wait for its changes.
+///
+/// The configuration is watched for as long as this (or a clone of
+/// [`ConfigEvents::shared`]) is alive.
+#[derive(Debug)]
+pub struct ConfigEvents<T> {
+    config: SharedConfig<T>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+/// The changes not yet returned by [`ConfigEvents::changed`], and the task waiting for them.
+#[derive(Debug)]
+struct Pending {
+    changes: VecDeque<ChangeSet>,
+    waker: Option<Waker>,
+}
+
+impl<T> ConfigEvents<T> {
+    /// Returns the current version of the configuration.
+    #[must_use]
+    pub fn current(&self) -> Arc<T> {
+        self.config.load()
+    }
+
+    /// Returns the configuration, to share it with other tasks.
+    #[must_use]
+    pub fn shared(&self) -> SharedConfig<T> {
+        self.config.clone()
+    }
+
+    /// Waits for the configuration to change, returning the settings that changed. Each
+    /// change is returned once, in order, even if it happened while not waiting.
+    pub fn changed(&mut self) -> Changed<'_> {
+        Changed {
+            pending: &self.pending,
+        }
+    }
+}
+
+/// The future returned by [`ConfigEvents::changed`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Changed<'events> {
+    pending: &'events Mutex<Pending>,
+}
+
+impl Future for Changed<'_> {
+    type Output = ChangeSet;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ChangeSet> {
+        let mut state = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        let next = state.changes.pop_front();
+        if next.is_none() {
+            state.waker = Some(cx.waker().clone());
+        }
+        drop(state);
+        next.map_or(Poll::Pending, Poll::Ready)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod events {
+        use super::super::events;
+        use crate::loader::tests::SharedBuffer;
+        use crate::offload::tests::block_on;
+        use crate::Loader;
+        use std::fs;
+        use std::time::Duration;
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        fn changed() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+            let captured = SharedBuffer::default();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(captured.clone())
+                .debounce(Duration::from_millis(20));
+
+            let mut config = events::<Server>(&loader, Duration::from_millis(10)).unwrap();
+            let initial = config.current().port;
+            fs::write(&path, r#"{ port = "8080" }"#).unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            let last_good = config.current().port;
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            let changes = block_on(config.changed());
+
+            assert_eq!(initial, 80);
+            assert_eq!(last_good, 80);
+            assert_eq!(changes.paths(), ["port"]);
+            assert_eq!(config.current().port, 8080);
+            assert!(captured
+                .contents()
+                .contains("invalid configuration, keeping the previous one:\nerror: "));
+            assert!(captured.contents().contains("configuration reloaded:\n"));
+        }
+    }
+}
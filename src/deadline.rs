@@ -0,0 +1,105 @@
+use crate::CancellationToken;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Interrupts the evaluation of a configuration once its [`crate::Loader::timeout`]
+/// expires: a thread of its own cancels the [`Deadline::token`] checked by the evaluation
+/// at every step, unless the deadline is dropped first.
+///
+/// Dropping the deadline stops its thread and waits for it, so none is left running.
+pub(crate) struct Deadline {
+    timeout: Duration,
+    token: CancellationToken,
+    stop: Option<Sender<()>>,
+    watchdog: Option<JoinHandle<()>>,
+}
+
+impl Deadline {
+    /// Starts counting down `timeout`.
+    pub(crate) fn start(timeout: Duration) -> Self {
+        let token = CancellationToken::default();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let expired = token.clone();
+        let watchdog = thread::spawn(move || {
+            if stopped.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+                expired.cancel();
+            }
+        });
+        Self {
+            timeout,
+            token,
+            stop: Some(stop),
+            watchdog: Some(watchdog),
+        }
+    }
+
+    /// Returns the token cancelled once the deadline expires.
+    pub(crate) fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Returns the timeout the deadline counts down.
+    pub(crate) const fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(watchdog) = self.watchdog.take() {
+            let _ignored: thread::Result<()> = watchdog.join();
+        }
+    }
+}
+
+/// The payload the evaluation unwinds with when its [`Deadline`] expires, with the
+/// timeout.
+pub(crate) struct Expired(Duration);
+
+impl Expired {
+    /// The deadline expired after `timeout`.
+    pub(crate) const fn after(timeout: Duration) -> Self {
+        Self(timeout)
+    }
+
+    /// Returns the timeout after which the deadline expired.
+    pub(crate) const fn timeout(&self) -> Duration {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod deadline {
+        use super::super::Deadline;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        #[test]
+        fn expires() {
+            let deadline = Deadline::start(Duration::from_millis(10));
+
+            while !deadline.token().is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        #[test]
+        fn stopped_when_dropped() {
+            let started = Instant::now();
+            let deadline = Deadline::start(Duration::from_mins(1));
+            let token = deadline.token();
+
+            drop(deadline);
+
+            assert!(!token.is_cancelled());
+            assert!(started.elapsed() < Duration::from_mins(1));
+        }
+    }
+}
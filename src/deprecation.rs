@@ -0,0 +1,121 @@
+use crate::Message;
+use crate::Messages;
+use codespan::FileId;
+use codespan_reporting::diagnostic::Diagnostic;
+use codespan_reporting::diagnostic::Label;
+use nickel_lang_core::identifier::Ident;
+use nickel_lang_core::term::record::Field;
+use nickel_lang_core::term::record::RecordData;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::SharedTerm;
+use nickel_lang_core::term::Term;
+
+/// A field that was renamed from [`Deprecation::old`] to [`Deprecation::new`]. Both are
+/// dotted paths (e.g. `server.addr`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Deprecation {
+    old: String,
+    new: String,
+}
+
+impl Deprecation {
+    pub(crate) fn new(old: &str, new: &str) -> Self {
+        Self {
+            old: old.to_owned(),
+            new: new.to_owned(),
+        }
+    }
+}
+
+/// Moves every deprecated field still set in `rt` to its new name, returning a warning for
+/// each of them.
+///
+/// When the new name is also set, the new value wins and the deprecated one is dropped.
+pub(crate) fn remap(
+    rt: &mut RichTerm,
+    deprecations: &[Deprecation],
+    messages: &dyn Messages,
+) -> Vec<Diagnostic<FileId>> {
+    let mut warnings = Vec::new();
+
+    for deprecation in deprecations {
+        let old: Vec<&str> = deprecation.old.split('.').collect();
+        let new: Vec<&str> = deprecation.new.split('.').collect();
+        let Some((ident, field)) = take(rt, &old) else {
+            continue;
+        };
+
+        let mut warning = Diagnostic::warning()
+            .with_message(messages.message(&Message::Deprecated {
+                old: &deprecation.old,
+            }))
+            .with_notes(vec![messages.message(&Message::UseInstead {
+                new: &deprecation.new,
+            })]);
+        if let Some(span) = ident.pos.into_opt() {
+            warning = warning.with_labels(vec![Label::primary(
+                span.src_id,
+                span.start.to_usize()..span.end.to_usize(),
+            )
+            .with_message(messages.message(&Message::DeprecatedField))]);
+        }
+        if !insert(rt, &new, field) {
+            warning
+                .notes
+                .push(messages.message(&Message::DeprecatedIgnored {
+                    new: &deprecation.new,
+                }));
+        }
+        warnings.push(warning);
+    }
+
+    warnings
+}
+
+/// Removes the field at `path` from `rt`, if there is any.
+fn take(rt: &mut RichTerm, path: &[&str]) -> Option<(Ident, Field)> {
+    let (first, rest) = path.split_first()?;
+    let Term::Record(record) = SharedTerm::make_mut(&mut rt.term) else {
+        return None;
+    };
+
+    if rest.is_empty() {
+        record.fields.shift_remove_entry(&Ident::from(*first))
+    } else {
+        let child = record
+            .fields
+            .get_mut(&Ident::from(*first))?
+            .value
+            .as_mut()?;
+        take(child, rest)
+    }
+}
+
+/// Sets the field at `path` in `rt`, creating the intermediate records as needed. Returns
+/// `false`, leaving `rt` untouched, when the field is already set.
+fn insert(rt: &mut RichTerm, path: &[&str], field: Field) -> bool {
+    let Some((first, rest)) = path.split_first() else {
+        return false;
+    };
+    let Term::Record(record) = SharedTerm::make_mut(&mut rt.term) else {
+        return false;
+    };
+    let ident = Ident::from(*first);
+
+    if rest.is_empty() {
+        if record.fields.contains_key(&ident) {
+            return false;
+        }
+        record.fields.insert(ident, field);
+        return true;
+    }
+
+    let child = record
+        .fields
+        .entry(ident)
+        .or_insert_with(|| Field::from(RichTerm::from(Term::Record(RecordData::empty()))));
+    child
+        .value
+        .as_mut()
+        .is_some_and(|value| insert(value, rest, field))
+}
@@ -0,0 +1,207 @@
+use codespan::FileId;
+use codespan::Files;
+use codespan_reporting::diagnostic::LabelStyle;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A single problem found while loading a configuration file.
+///
+/// Diagnostics are extracted from the errors reported by Nickel, so applications can feed
+/// them into their own error UIs, IDE plugins or logs instead of parsing the human readable
+/// reports.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// The file the problem was found in, when it can be attributed to one.
+    pub path: Option<PathBuf>,
+
+    /// The region of [`Diagnostic::path`] the problem refers to.
+    pub span: Option<Span>,
+
+    /// The dotted path (e.g. `server.port`) of the configuration field the problem refers
+    /// to, when it is known.
+    pub field: Option<String>,
+
+    /// How bad the problem is.
+    pub severity: Severity,
+
+    /// A one line description of the problem.
+    pub message: String,
+
+    /// Additional details that help understanding or fixing the problem.
+    pub notes: Vec<String>,
+}
+
+/// A region of a source file, from [`Span::start`] (inclusive) to [`Span::end`] (exclusive).
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+/// A position in a source file. Both the line and the column are 1-based.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// How bad a [`Diagnostic`] is.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// A message for the user, usually attached to another diagnostic.
+    Help,
+
+    /// Extra context, usually attached to another diagnostic.
+    Note,
+
+    /// A problem that didn't stop the configuration from loading.
+    Warning,
+
+    /// A problem that stopped the configuration from loading.
+    Error,
+
+    /// A problem caused by a bug in Nickel itself.
+    Bug,
+}
+
+impl Diagnostic {
+    /// Builds a diagnostic that can't be attributed to any region of a file.
+    pub(crate) const fn error(message: String) -> Self {
+        Self {
+            path: None,
+            span: None,
+            field: None,
+            severity: Severity::Error,
+            message,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Converts a diagnostic produced by Nickel, resolving its primary label into a path and
+    /// a [`Span`] using the `files` it was produced from.
+    pub(crate) fn from_codespan(
+        diagnostic: &codespan_reporting::diagnostic::Diagnostic<FileId>,
+        files: &Files<String>,
+    ) -> Self {
+        let primary = diagnostic
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary);
+
+        Self {
+            path: primary.map(|label| PathBuf::from(files.name(label.file_id))),
+            span: primary.and_then(|label| {
+                Some(Span {
+                    start: location(files, label.file_id, label.range.start)?,
+                    end: location(files, label.file_id, label.range.end)?,
+                })
+            }),
+            field: None,
+            severity: diagnostic.severity.into(),
+            message: diagnostic.message.clone(),
+            notes: diagnostic.notes.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Help => "help",
+            Self::Note => "note",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Bug => "bug",
+        })
+    }
+}
+
+impl From<codespan_reporting::diagnostic::Severity> for Severity {
+    fn from(severity: codespan_reporting::diagnostic::Severity) -> Self {
+        match severity {
+            codespan_reporting::diagnostic::Severity::Help => Self::Help,
+            codespan_reporting::diagnostic::Severity::Note => Self::Note,
+            codespan_reporting::diagnostic::Severity::Warning => Self::Warning,
+            codespan_reporting::diagnostic::Severity::Error => Self::Error,
+            codespan_reporting::diagnostic::Severity::Bug => Self::Bug,
+        }
+    }
+}
+
+/// Translates a byte offset into a 1-based line and column.
+pub(crate) fn location(files: &Files<String>, file_id: FileId, offset: usize) -> Option<Location> {
+    let byte_index = u32::try_from(offset).ok()?;
+    let location = files.location(file_id, byte_index).ok()?;
+    Some(Location {
+        line: location.line.number().to_usize(),
+        column: location.column.to_usize().saturating_add(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[cfg(test)]
+    mod from_codespan {
+        use super::super::Diagnostic;
+        use super::super::Location;
+        use super::super::Severity;
+        use super::super::Span;
+        use codespan::Files;
+        use codespan_reporting::diagnostic::Label;
+        use std::path::PathBuf;
+
+        #[test]
+        fn resolves_the_primary_label() {
+            let mut files = Files::new();
+            let file_id = files.add("/etc/app/config.ncl", "{\n  port = \"80\",\n}".to_owned());
+            let diagnostic = codespan_reporting::diagnostic::Diagnostic::error()
+                .with_message("contract broken")
+                .with_labels(vec![
+                    Label::secondary(file_id, 0..1),
+                    Label::primary(file_id, 11..15),
+                ])
+                .with_notes(vec!["expected a number".to_owned()]);
+
+            let result = Diagnostic::from_codespan(&diagnostic, &files);
+
+            let expected = Diagnostic {
+                path: Some(PathBuf::from("/etc/app/config.ncl")),
+                span: Some(Span {
+                    start: Location {
+                        line: 2,
+                        column: 10,
+                    },
+                    end: Location {
+                        line: 2,
+                        column: 14,
+                    },
+                }),
+                field: None,
+                severity: Severity::Error,
+                message: "contract broken".to_owned(),
+                notes: vec!["expected a number".to_owned()],
+            };
+            assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn without_labels() {
+            let files = Files::new();
+            let diagnostic =
+                codespan_reporting::diagnostic::Diagnostic::note().with_message("some context");
+
+            let result = Diagnostic::from_codespan(&diagnostic, &files);
+
+            assert_eq!(result.path, None);
+            assert_eq!(result.span, None);
+            assert_eq!(result.severity, Severity::Note);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Digest as _;
+use sha2::Sha256;
+use std::fmt::Write as _;
+use std::fs;
+use std::fs::File;
+use std::fs::Metadata;
+use std::io;
+use std::io::Write as _;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt as _;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Returns the directory where the evaluated configurations of the application with the
+/// codename `app` are cached: `$XDG_CACHE_HOME/nickelodeon/<app>`, falling back to
+/// `~/.cache/nickelodeon/<app>`.
+pub(crate) fn default_dir(app: &str) -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("nickelodeon").join(app))
+}
+
+/// A configuration evaluated by a previous run, together with the files it depends on.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    version: String,
+    files: Vec<Fingerprint>,
+    value: Value,
+}
+
+/// Identifies the content of a file.
+///
+/// The modification time and size of the file are kept too, so files whose metadata didn't
+/// change don't need to be hashed again.
+#[derive(Serialize, Deserialize)]
+struct Fingerprint {
+    path: PathBuf,
+    hash: String,
+    modified: Option<SystemTime>,
+    size: u64,
+}
+
+/// Whether a file changed since it was fingerprinted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Freshness {
+    /// Same metadata, assumed to be the same content.
+    Unchanged,
+
+    /// Different metadata, but the same content.
+    Touched,
+
+    /// Different content (or the file is gone).
+    Changed,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Option<Self> {
+        let mut fingerprint = Self {
+            path: path.to_path_buf(),
+            hash: hash(&fs::read(path).ok()?),
+            modified: None,
+            size: 0,
+        };
+        fingerprint.record_metadata(&fs::metadata(path).ok()?);
+        Some(fingerprint)
+    }
+
+    /// Keeps the modification time and size of the file, unless it was modified too
+    /// recently: a file written again within the resolution of the file system clock would
+    /// keep the same modification time, so the metadata of such files isn't trusted.
+    fn record_metadata(&mut self, metadata: &Metadata) {
+        let modified = metadata.modified().ok();
+        let settled = modified
+            .and_then(|time| time.elapsed().ok())
+            .is_some_and(|age| age >= RACY_WINDOW);
+        self.modified = modified.filter(|_| settled);
+        self.size = metadata.len();
+    }
+
+    /// Tells whether the file changed, only hashing it when its metadata did.
+    fn refresh(&mut self) -> Freshness {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Freshness::Changed;
+        };
+        if self.modified.is_some()
+            && self.modified == metadata.modified().ok()
+            && self.size == metadata.len()
+        {
+            return Freshness::Unchanged;
+        }
+
+        if fs::read(&self.path).is_ok_and(|content| hash(&content) == self.hash) {
+            self.record_metadata(&metadata);
+            Freshness::Touched
+        } else {
+            Freshness::Changed
+        }
+    }
+}
+
+/// How long after being modified the modification time of a file is trusted.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Returns the evaluated configuration `config` cached in `dir` under `key` (see [`key`]) as
+/// a `T`, together with the files it imports, unless any of the files it depends on changed
+/// since (or it isn't a valid `T`).
+pub(crate) fn lookup<T: DeserializeOwned>(
+    dir: &Path,
+    key: &str,
+    config: &Path,
+) -> Option<(T, Vec<PathBuf>)> {
+    let mut entry: Entry = serde_json::from_slice(&fs::read(entry_path(dir, key)).ok()?).ok()?;
+    if entry.version != env!("CARGO_PKG_VERSION") {
+        return None;
+    }
+    let mut touched = false;
+    for fingerprint in &mut entry.files {
+        match fingerprint.refresh() {
+            Freshness::Unchanged => {}
+            Freshness::Touched => touched = true,
+            Freshness::Changed => return None,
+        }
+    }
+    if touched {
+        write(dir, key, &entry);
+    }
+
+    let value = serde_json::from_value(entry.value).ok()?;
+    let imports = entry
+        .files
+        .into_iter()
+        .map(|fingerprint| fingerprint.path)
+        .filter(|path| path != config)
+        .collect();
+    Some((value, imports))
+}
+
+/// Caches in `dir`, under `key` (see [`key`]), an evaluated configuration, which depends on
+/// `files`.
+///
+/// Caching is best effort: failing to write the cache only means the configuration is
+/// evaluated again next time.
+pub(crate) fn store(dir: &Path, key: &str, files: &[PathBuf], value: Value) {
+    let Some(fingerprints) = files.iter().map(|path| Fingerprint::of(path)).collect() else {
+        return;
+    };
+    let entry = Entry {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        files: fingerprints,
+        value,
+    };
+    write(dir, key, &entry);
+}
+
+/// Writes `entry`, cached under `key`, in `dir`, ignoring failures.
+///
+/// The entries hold the whole configuration, so they are only readable by the user, like
+/// the directories created for them.
+fn write(dir: &Path, key: &str, entry: &Entry) {
+    let path = entry_path(dir, key);
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    let _ignored: io::Result<()> = create_private_dir(dir)
+        .and_then(|()| {
+            let _stale: io::Result<()> = fs::remove_file(&temporary);
+            create_private_file(&temporary)?.write_all(&serde_json::to_vec(entry)?)
+        })
+        .and_then(|()| fs::rename(&temporary, &path));
+}
+
+/// Creates `dir` and its missing parents, only accessible by the user.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(dir)
+}
+
+/// Creates the file `path`, which must not exist yet, only readable by the user.
+fn create_private_file(path: &Path) -> io::Result<File> {
+    let mut options = File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)
+}
+
+/// Returns where the evaluated configuration cached under `key` is kept in `dir`.
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(key).with_extension("json")
+}
+
+/// Returns the key an evaluated configuration is cached under: the hash of the path of the
+/// configuration file `config` and of the `options` the evaluation depends on.
+pub(crate) fn key(config: &Path, options: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in std::iter::once(config.as_os_str().as_encoded_bytes())
+        .chain(options.iter().map(|option| option.as_bytes()))
+    {
+        // Each part is prefixed with its length, so parts can't run into each other.
+        hasher.update(format!("{}:", part.len()));
+        hasher.update(part);
+    }
+    hex(&hasher.finalize())
+}
+
+/// Hashes `bytes` with SHA-256.
+pub(crate) fn hash(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// Returns `bytes` in hexadecimal.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _infallible = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod key {
+        use super::super::key;
+        use std::path::Path;
+
+        #[test]
+        fn depends_on_the_options() {
+            let config = Path::new("/etc/app/config.ncl");
+
+            assert_eq!(key(config, &["a"]), key(config, &["a"]));
+            assert_ne!(key(config, &["a"]), key(config, &["b"]));
+            assert_ne!(key(config, &["ab"]), key(config, &["a", "b"]));
+            assert_ne!(key(config, &[]), key(Path::new("/etc/app/other.ncl"), &[]));
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod store {
+        use super::super::entry_path;
+        use super::super::key;
+        use super::super::store;
+        use std::os::unix::fs::PermissionsExt as _;
+
+        #[test]
+        fn private() {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = dir.path().join("nickelodeon").join("app");
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ password = "hunter2" }"#).unwrap();
+
+            store(
+                &cache,
+                &key(&config, &[]),
+                std::slice::from_ref(&config),
+                serde_json::json!({ "password": "hunter2" }),
+            );
+
+            let mode = |path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(entry_path(&cache, &key(&config, &[]))), 0o600);
+            assert_eq!(mode(cache), 0o700);
+            assert_eq!(mode(dir.path().join("nickelodeon")), 0o700);
+        }
+    }
+
+    #[cfg(test)]
+    mod lookup {
+        use super::super::entry_path;
+        use super::super::key;
+        use super::super::lookup;
+        use super::super::store;
+        use std::fs::File;
+        use std::path::Path;
+        use std::time::Duration;
+        use std::time::SystemTime;
+
+        fn age(path: &Path) {
+            let past = SystemTime::now()
+                .checked_sub(Duration::from_mins(1))
+                .unwrap();
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+
+        fn tamper_with_hashes(dir: &Path, config: &Path) {
+            let path = entry_path(dir, &key(config, &[]));
+            let mut entry: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            let files = entry.get_mut("files").unwrap().as_array_mut().unwrap();
+            for file in files {
+                *file.get_mut("hash").unwrap() = "tampered".into();
+            }
+            std::fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        }
+
+        #[test]
+        fn unchanged_metadata_skips_hashing() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, "1").unwrap();
+            age(&config);
+            store(
+                dir.path(),
+                &key(&config, &[]),
+                std::slice::from_ref(&config),
+                1.into(),
+            );
+            tamper_with_hashes(dir.path(), &config);
+
+            let result = lookup::<u8>(dir.path(), &key(&config, &[]), &config);
+
+            assert_eq!(result, Some((1, vec![])));
+        }
+
+        #[test]
+        fn changed_metadata_hashes_again() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, "1").unwrap();
+            age(&config);
+            store(
+                dir.path(),
+                &key(&config, &[]),
+                std::slice::from_ref(&config),
+                1.into(),
+            );
+            std::fs::write(&config, "2").unwrap();
+
+            let result = lookup::<u8>(dir.path(), &key(&config, &[]), &config);
+
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn recently_modified_files_are_hashed() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, "1").unwrap();
+            store(
+                dir.path(),
+                &key(&config, &[]),
+                std::slice::from_ref(&config),
+                1.into(),
+            );
+            tamper_with_hashes(dir.path(), &config);
+
+            let result = lookup::<u8>(dir.path(), &key(&config, &[]), &config);
+
+            assert_eq!(result, None);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+
+/// Reads the variables set by the `.env` file at `path`, in order. A missing or unreadable
+/// file sets none.
+pub(crate) fn read(path: &Path) -> Vec<(String, String)> {
+    fs::read_to_string(path)
+        .map(|source| parse(&source))
+        .unwrap_or_default()
+}
+
+/// Parses the `NAME=value` lines of a `.env` file, optionally starting with `export`.
+///
+/// Values can be single quoted (taken literally), double quoted (with `\n`, `\t`, `\"` and
+/// `\\` escapes) or unquoted, in which case a ` #` starts a comment. Blank lines, comments
+/// and lines without a `=` are skipped.
+pub(crate) fn parse(source: &str) -> Vec<(String, String)> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let assignment = line.strip_prefix("export ").unwrap_or(line);
+            let (left, value) = assignment.split_once('=')?;
+            let name = left.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return None;
+            }
+            Some((name.to_owned(), unquoted(value.trim())))
+        })
+        .collect()
+}
+
+/// Returns the text of the `value` of a variable, without its quotes or trailing comment.
+fn unquoted(value: &str) -> String {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        return quoted.split('\'').next().unwrap_or_default().to_owned();
+    }
+    let Some(quoted) = value.strip_prefix('"') else {
+        let end = value.find(" #").unwrap_or(value.len());
+        return value.get(..end).unwrap_or(value).trim_end().to_owned();
+    };
+
+    let mut text = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(next) = chars.next() {
+        match next {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(other) => text.push(other),
+                None => text.push('\\'),
+            },
+            other => text.push(other),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod parse {
+        use super::super::parse;
+
+        fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect()
+        }
+
+        #[test]
+        fn assignments() {
+            let source = r#"
+                # Local development.
+                DATABASE_URL=postgres://localhost/dev
+                export LOG_LEVEL = debug # for now
+                GREETING="hello \"world\"\n"
+                RAW='no $expansion \n here'
+                EMPTY=
+                not an assignment
+            "#;
+
+            assert_eq!(
+                parse(source),
+                vars(&[
+                    ("DATABASE_URL", "postgres://localhost/dev"),
+                    ("LOG_LEVEL", "debug"),
+                    ("GREETING", "hello \"world\"\n"),
+                    ("RAW", "no $expansion \\n here"),
+                    ("EMPTY", ""),
+                ])
+            );
+        }
+    }
+}
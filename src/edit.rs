@@ -0,0 +1,290 @@
+use crate::loader::report;
+use crate::loader::DiagnosticSink;
+use crate::syntax::field_name;
+use crate::syntax::Comments;
+use crate::syntax::Nickel;
+use crate::Error;
+use crate::Message;
+use crate::Result;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::cache::ErrorTolerance;
+use nickel_lang_core::cache::InputFormat;
+use nickel_lang_core::position::RawSpan;
+use nickel_lang_core::term::record::RecordData;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use std::io;
+
+/// Returns `source`, the source of a Nickel configuration, with the field at `path` set to
+/// `value`.
+///
+/// Only the text of the value is replaced, so the comments, order and formatting of the
+/// rest of the file are kept. A missing field is added at the end of the innermost record
+/// of `path` that exists.
+pub(crate) fn with_field(source: &str, path: &[String], value: Nickel) -> Result<String> {
+    let mut cache = Cache::new(ErrorTolerance::Strict);
+    let id = cache.add_string("config.ncl", source.to_owned());
+    if let Err(errors) = cache.parse_multi(id, InputFormat::Nickel) {
+        let error = nickel_lang_core::error::Error::from(errors);
+        let mut sink = DiagnosticSink::new(io::sink());
+        let diagnostics = report(&mut cache, error.clone(), &mut sink);
+        return Err(Error::NickelEvaluationError(error, diagnostics));
+    }
+
+    let term = cache.get_owned(id).unwrap_or_else(|| Term::Null.into());
+    edit(source, &term, path, value).ok_or_else(|| {
+        let dotted = path.join(".");
+        Error::ConfigFileWritingError(Message::NotEditable(&dotted).to_string())
+    })
+}
+
+/// Sets the field at `path`, under `rt`, to `value`. Returns `None` when `rt`, or the
+/// value of one of the fields of `path`, isn't written as a record.
+fn edit(source: &str, rt: &RichTerm, path: &[String], value: Nickel) -> Option<String> {
+    let (record, span) = record(rt)?;
+    let (name, rest) = path.split_first()?;
+    let Some((ident, field)) = record
+        .fields
+        .iter()
+        .find(|(ident, _)| ident.label() == name)
+    else {
+        return insert(source, record, span?, name, &nested(rest, value));
+    };
+
+    let current = field.value.as_ref()?;
+    if !rest.is_empty() {
+        return edit(source, current, rest, value);
+    }
+    let target = current.pos.into_opt()?;
+    let line = ident
+        .pos
+        .into_opt()
+        .map_or(target.start, |written| written.start);
+    let start = target.start.to_usize();
+    Some(format!(
+        "{}{}{}",
+        source.get(..start)?,
+        render(&value, indent(source, line.to_usize())?),
+        source.get(target.end.to_usize()..)?
+    ))
+}
+
+/// Wraps `value` into the records of the fields of `path`, e.g. `{ server = { port = 80 } }`
+/// for `server.port`.
+pub(crate) fn nested(path: &[String], value: Nickel) -> Nickel {
+    path.iter().rev().fold(value, |inner, name| {
+        Nickel::Record(vec![(name.clone(), inner)])
+    })
+}
+
+/// Returns the record written by `rt`, with its span, going through the `let` bindings and
+/// annotations wrapping it.
+#[allow(clippy::wildcard_enum_match_arm)]
+fn record(rt: &RichTerm) -> Option<(&RecordData, Option<RawSpan>)> {
+    match rt.as_ref() {
+        Term::Record(record) | Term::RecRecord(record, ..) => Some((record, rt.pos.into_opt())),
+        Term::Let(_, _, body, _) | Term::LetPattern(_, _, _, body) | Term::Annotated(_, body) => {
+            record(body)
+        }
+        _ => None,
+    }
+}
+
+/// Adds the field `name`, set to `value`, at the end of `record`, written at `span`.
+fn insert(
+    source: &str,
+    record: &RecordData,
+    span: RawSpan,
+    name: &str,
+    value: &Nickel,
+) -> Option<String> {
+    let open = span.start.to_usize();
+    let close = span.end.to_usize().checked_sub(1)?;
+    if source.get(open..=open)? != "{" || source.get(close..=close)? != "}" {
+        return None;
+    }
+    let inside = source.get(open.checked_add(1)?..close)?;
+    let before = source.get(..open)?;
+    let after = source.get(close..)?;
+
+    if !inside.contains('\n') {
+        let fields = inside.trim_end();
+        let field = format!("{} = {}", field_name(name), render(value, ""));
+        return Some(if fields.trim_start().is_empty() {
+            format!("{before}{{ {field} }}{}", after.get(1..)?)
+        } else if fields.ends_with(',') {
+            format!("{before}{{{fields} {field} }}{}", after.get(1..)?)
+        } else {
+            format!("{before}{{{fields}, {field} }}{}", after.get(1..)?)
+        });
+    }
+
+    let last = record
+        .fields
+        .iter()
+        .filter_map(|(ident, field)| {
+            let start = ident.pos.into_opt()?.start.to_usize();
+            let end = field
+                .value
+                .as_ref()
+                .and_then(|current| current.pos.into_opt())
+                .map_or(start, |current| current.end.to_usize());
+            Some((start, end))
+        })
+        .max_by_key(|&(_, end)| end);
+    let (margin, anchor, comma) = match last {
+        Some((start, end)) => {
+            let rest = source.get(end..close)?;
+            let comma = rest
+                .trim_start()
+                .strip_prefix(',')
+                .map(|after_comma| close.saturating_sub(after_comma.len()));
+            (
+                indent(source, start)?.to_owned(),
+                comma.unwrap_or(end),
+                comma.is_none().then_some(end),
+            )
+        }
+        None => (
+            format!("{}  ", indent(source, close)?),
+            open.saturating_add(1),
+            None,
+        ),
+    };
+
+    let line_end = source
+        .get(anchor..close)?
+        .find('\n')
+        .map_or(close, |offset| anchor.saturating_add(offset));
+    let field = format!(
+        "\n{margin}{} = {},",
+        field_name(name),
+        render(value, &margin)
+    );
+    let closing = if line_end == close {
+        format!("\n{}", margin.get(2..).unwrap_or_default())
+    } else {
+        String::new()
+    };
+    let mut edited = source.to_owned();
+    edited.insert_str(line_end, &format!("{field}{closing}"));
+    if let Some(end) = comma {
+        edited.insert(end, ',');
+    }
+    Some(edited)
+}
+
+/// Writes `value` as the source of a field on a line indented with `indent`.
+fn render(value: &Nickel, indent: &str) -> String {
+    value
+        .to_source(&Comments::new())
+        .trim_end()
+        .replace('\n', &format!("\n{indent}"))
+}
+
+/// Returns the whitespace indenting the line of `source` holding the byte at `position`.
+fn indent(source: &str, position: usize) -> Option<&str> {
+    let line = source
+        .get(..position)?
+        .rfind('\n')
+        .map_or(0, |newline| newline.saturating_add(1));
+    let text = source.get(line..)?;
+    let width = text
+        .len()
+        .saturating_sub(text.trim_start_matches([' ', '\t']).len());
+    text.get(..width)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod with_field {
+        use super::super::with_field;
+        use crate::syntax::Nickel;
+        use crate::Error;
+
+        fn set(source: &str, dotted: &str, value: &str) -> String {
+            let path: Vec<String> = dotted.split('.').map(str::to_owned).collect();
+            with_field(source, &path, Nickel::Literal(value.to_owned())).unwrap()
+        }
+
+        #[test]
+        fn replaces_only_the_value() {
+            let source = r#"# Preferences
+{
+  # The colors.
+  theme | String = "light", # or "dark"
+  server = {
+    port = 8080,
+    host = "localhost",
+  },
+}
+"#;
+
+            let edited = set(source, "server.port", "9090");
+
+            assert_eq!(edited, source.replace("8080", "9090"));
+        }
+
+        #[test]
+        fn indents_nested_values() {
+            let source = "{\n  server = {\n    port = 8080,\n  },\n}\n";
+            let path = vec!["server".to_owned(), "port".to_owned()];
+            let value = Nickel::Array(vec![Nickel::Record(vec![(
+                "number".to_owned(),
+                Nickel::Literal("1".to_owned()),
+            )])]);
+
+            let edited = with_field(source, &path, value).unwrap();
+
+            assert_eq!(
+                edited,
+                "{\n  server = {\n    port = [\n      {\n        number = 1,\n      },\n    ],\n  },\n}\n"
+            );
+        }
+
+        #[test]
+        fn adds_missing_fields() {
+            let source = "{\n  # The colors.\n  theme = \"light\" # or \"dark\"\n}\n";
+
+            let edited = set(source, "server.port", "9090");
+
+            assert_eq!(
+                edited,
+                "{\n  # The colors.\n  theme = \"light\", # or \"dark\"\n  server = {\n    port = 9090,\n  },\n}\n"
+            );
+        }
+
+        #[test]
+        fn adds_fields_to_single_line_records() {
+            assert_eq!(set("{ a = 1 }", "b", "2"), "{ a = 1, b = 2 }");
+            assert_eq!(set("{}", "b", "2"), "{ b = 2 }");
+            assert_eq!(
+                set("let x = 1 in {\n}", "b", "2"),
+                "let x = 1 in {\n  b = 2,\n}"
+            );
+        }
+
+        #[test]
+        fn not_a_record() {
+            let path = vec!["theme".to_owned(), "name".to_owned()];
+
+            let result = with_field(
+                "{ theme = \"light\" }",
+                &path,
+                Nickel::Literal("1".to_owned()),
+            );
+
+            assert!(matches!(result, Err(Error::ConfigFileWritingError(_))));
+        }
+
+        #[test]
+        fn invalid() {
+            let path = vec!["theme".to_owned()];
+
+            let result = with_field("{ theme = }", &path, Nickel::Literal("1".to_owned()));
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+    }
+}
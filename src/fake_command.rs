@@ -0,0 +1,9 @@
+use std::process::Command;
+
+/// Returns a command line standing in for `program` in tests, running the shell `script`
+/// with the arguments given to `program` as `$1`, `$2`...
+pub(crate) fn fake_command(program: &str, script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.args(["-c", script, program]);
+    command
+}
@@ -0,0 +1,483 @@
+use crate::schema::fields_of;
+use crate::schema::PathSegment;
+use crate::Message;
+use crate::Messages;
+use serde::de::DeserializeOwned;
+use serde_json::Map;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+
+/// A field of the configuration that doesn't match the shape expected by the Rust type it
+/// is deserialized into.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The dotted path of the field (e.g. `server.ports[1]`). Empty for the root value.
+    pub path: String,
+
+    /// What is wrong with the field, including the expected type.
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "`{}`: {}", self.path, self.message)
+        }
+    }
+}
+
+/// A step of the path leading to a value nested in records and arrays.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Upper bound on the deserialization attempts, to guarantee termination on recursive types.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// Values tried, in order, in place of a field that failed to deserialize, until one of them
+/// has the shape the target type expects.
+fn probe(attempt: usize) -> Option<Value> {
+    match attempt {
+        0 => Some(Value::Null),
+        1 => Some(Value::Bool(false)),
+        2 => Some(Value::from(0_u8)),
+        3 => Some(Value::String(String::new())),
+        4 => Some(Value::Array(Vec::new())),
+        5 => Some(Value::Object(Map::new())),
+        _ => None,
+    }
+}
+
+/// Deserializes `value` into a `T`, reporting every mismatched field instead of only the
+/// first one.
+///
+/// Every time a field fails to deserialize, the error is recorded and the field is replaced
+/// by a [`probe`] value so the next attempt gets past it and reaches the following mismatch.
+/// Errors caused by the probes themselves are not reported.
+pub(crate) fn deserialize_collecting_errors<T: DeserializeOwned>(
+    mut value: Value,
+    messages: &dyn Messages,
+) -> Result<T, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut probes: HashMap<Vec<Step>, usize> = HashMap::new();
+
+    for _ in 0..MAX_ATTEMPTS {
+        let err = match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(result) if errors.is_empty() => return Ok(result),
+            Ok(_) => return Err(errors),
+            Err(err) => err,
+        };
+
+        let message = err.inner().to_string();
+        let Some(mut steps) = steps_of(err.path()) else {
+            errors.push(FieldError {
+                path: String::new(),
+                message,
+            });
+            return Err(errors);
+        };
+
+        if let Some(name) = missing_field(&message) {
+            steps.push(Step::Key(name.to_owned()));
+        }
+
+        let is_probe = probes.keys().any(|probed| steps.starts_with(probed));
+        if !is_probe {
+            errors.push(field_error::<T>(&steps, &message, messages));
+        }
+
+        let attempt = probes
+            .get(&steps)
+            .map_or(0, |previous| previous.saturating_add(1));
+        let Some(replacement) = probe(attempt) else {
+            return Err(errors);
+        };
+        if !replace(&mut value, &steps, replacement) {
+            return Err(errors);
+        }
+        probes.insert(steps, attempt);
+    }
+
+    Err(errors)
+}
+
+/// Deserializes `value` into a `T`, replacing every mismatched field by its value in
+/// `defaults` (the serialized `T::default()`), and returns the result together with every
+/// replaced field.
+///
+/// When a field doesn't exist in `defaults` (e.g. an element of an array), its closest
+/// parent that does is replaced instead. Fails, reporting what was found so far, when even
+/// the defaults don't deserialize.
+pub(crate) fn deserialize_with_defaults<T: DeserializeOwned>(
+    mut value: Value,
+    defaults: &Value,
+    messages: &dyn Messages,
+) -> Result<(T, Vec<FieldError>), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut replaced: Vec<Vec<Step>> = Vec::new();
+
+    for _ in 0..MAX_ATTEMPTS {
+        let err = match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(result) => return Ok((result, errors)),
+            Err(err) => err,
+        };
+
+        let message = err.inner().to_string();
+        let mut steps = steps_of(err.path()).unwrap_or_default();
+        if let Some(name) = missing_field(&message) {
+            steps.push(Step::Key(name.to_owned()));
+        }
+
+        if !replaced.iter().any(|parent| steps.starts_with(parent)) {
+            errors.push(field_error::<T>(&steps, &message, messages));
+        }
+
+        let Some((parent, default)) = (0..=steps.len()).rev().find_map(|len| {
+            let parent = steps.get(..len)?;
+            Some((parent, lookup(defaults, parent)?))
+        }) else {
+            return Err(errors);
+        };
+        if replaced.iter().any(|previous| previous == parent)
+            || !replace(&mut value, parent, default.clone())
+        {
+            return Err(errors);
+        }
+        replaced.push(parent.to_vec());
+    }
+
+    Err(errors)
+}
+
+/// Builds the error for the field at `steps`, shortening serde's "missing field" errors
+/// since the field name is already part of the path, and listing the fields `T` expects
+/// next to the missing one.
+fn field_error<T: DeserializeOwned>(
+    steps: &[Step],
+    message: &str,
+    messages: &dyn Messages,
+) -> FieldError {
+    let text = if missing_field(message).is_some() {
+        let missing = messages.message(&Message::MissingField);
+        let parent = steps.split_last().map_or(steps, |(_, parent)| parent);
+        match fields_of::<T>(&segments(parent)) {
+            Some(fields) => format!(
+                "{missing}; {}",
+                messages.message(&Message::AvailableFields(fields))
+            ),
+            None => missing,
+        }
+    } else {
+        message.to_owned()
+    };
+
+    FieldError {
+        path: format_path(steps),
+        message: text,
+    }
+}
+
+/// Returns the fields expected next to the missing one when `value` fails to deserialize
+/// into a `T` because of a missing field.
+pub(crate) fn available_fields<T: DeserializeOwned>(
+    value: &Value,
+) -> Option<&'static [&'static str]> {
+    let err = serde_path_to_error::deserialize::<_, T>(value).err()?;
+    missing_field(&err.inner().to_string())?;
+    let steps = steps_of(err.path())?;
+    fields_of::<T>(&segments(&steps))
+}
+
+/// Converts a path into the segments understood by [`fields_of`].
+fn segments(steps: &[Step]) -> Vec<PathSegment<'_>> {
+    steps
+        .iter()
+        .map(|step| match step {
+            Step::Key(key) => PathSegment::Key(key),
+            Step::Index(_) => PathSegment::Element,
+        })
+        .collect()
+}
+
+/// Converts the path reported by `serde_path_to_error`, if it only goes through records and
+/// arrays.
+fn steps_of(path: &serde_path_to_error::Path) -> Option<Vec<Step>> {
+    path.iter()
+        .map(|segment| match segment {
+            Segment::Map { key } => Some(Step::Key(key.clone())),
+            Segment::Seq { index } => Some(Step::Index(*index)),
+            Segment::Enum { .. } | Segment::Unknown => None,
+        })
+        .collect()
+}
+
+/// Extracts the field name out of serde's "missing field" errors.
+fn missing_field(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("missing field `")?;
+    rest.strip_suffix('`')
+}
+
+/// Renders a path as `a.b[1].c`.
+fn format_path(steps: &[Step]) -> String {
+    let mut path = String::new();
+    for step in steps {
+        match step {
+            Step::Key(key) if path.is_empty() => path.push_str(key),
+            Step::Key(key) => {
+                path.push('.');
+                path.push_str(key);
+            }
+            Step::Index(index) => {
+                let _infallible: fmt::Result = write!(path, "[{index}]");
+            }
+        }
+    }
+    path
+}
+
+/// Returns the value at `steps`, if there is any.
+fn lookup<'value>(value: &'value Value, steps: &[Step]) -> Option<&'value Value> {
+    steps
+        .iter()
+        .try_fold(value, |current, step| match (step, current) {
+            (Step::Key(key), Value::Object(record)) => record.get(key),
+            (Step::Index(index), Value::Array(array)) => array.get(*index),
+            _ => None,
+        })
+}
+
+/// Replaces (or inserts, for missing record fields) the value at `steps`. Returns `false`
+/// when the path doesn't lead anywhere in `value`.
+fn replace(value: &mut Value, steps: &[Step], replacement: Value) -> bool {
+    let Some((last, parents)) = steps.split_last() else {
+        *value = replacement;
+        return true;
+    };
+
+    let mut current = value;
+    for step in parents {
+        let child = match (step, current) {
+            (Step::Key(key), Value::Object(record)) => record.get_mut(key),
+            (Step::Index(index), Value::Array(array)) => array.get_mut(*index),
+            _ => None,
+        };
+        let Some(next) = child else {
+            return false;
+        };
+        current = next;
+    }
+
+    match (last, current) {
+        (Step::Key(key), Value::Object(record)) => {
+            record.insert(key.clone(), replacement);
+            true
+        }
+        (Step::Index(index), Value::Array(array)) => array.get_mut(*index).is_some_and(|slot| {
+            *slot = replacement;
+            true
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    #[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+    struct TestConfiguration {
+        name: String,
+        verbose: bool,
+        server: Server,
+        tags: Vec<String>,
+    }
+
+    #[cfg(test)]
+    mod deserialize_collecting_errors {
+        use super::super::deserialize_collecting_errors;
+        use super::super::FieldError;
+        use super::Server;
+        use super::TestConfiguration;
+        use crate::English;
+        use serde_json::json;
+
+        fn error(path: &str, message: &str) -> FieldError {
+            FieldError {
+                path: path.to_owned(),
+                message: message.to_owned(),
+            }
+        }
+
+        #[test]
+        fn happy() {
+            let value = json!({
+                "name": "nick",
+                "verbose": true,
+                "server": { "host": "localhost", "port": 80 },
+                "tags": ["a"],
+            });
+
+            let result: Result<TestConfiguration, _> =
+                deserialize_collecting_errors(value, &English);
+
+            let expected = TestConfiguration {
+                name: "nick".to_owned(),
+                verbose: true,
+                server: Server {
+                    host: "localhost".to_owned(),
+                    port: 80,
+                },
+                tags: vec!["a".to_owned()],
+            };
+            assert_eq!(result, Ok(expected));
+        }
+
+        #[test]
+        fn reports_every_mismatch() {
+            let value = json!({
+                "name": 1,
+                "verbose": "yes",
+                "server": { "host": "localhost", "port": "80" },
+                "tags": ["a", false],
+            });
+
+            let result: Result<TestConfiguration, _> =
+                deserialize_collecting_errors(value, &English);
+
+            let expected = vec![
+                error("name", "invalid type: integer `1`, expected a string"),
+                error("server.port", "invalid type: string \"80\", expected u16"),
+                error(
+                    "tags[1]",
+                    "invalid type: boolean `false`, expected a string",
+                ),
+                error(
+                    "verbose",
+                    "invalid type: string \"yes\", expected a boolean",
+                ),
+            ];
+            assert_eq!(result, Err(expected));
+        }
+
+        #[test]
+        fn reports_missing_fields_once() {
+            let value = json!({
+                "verbose": true,
+                "tags": [],
+            });
+
+            let result: Result<TestConfiguration, _> =
+                deserialize_collecting_errors(value, &English);
+
+            let available = "available fields are `name`, `verbose`, `server`, `tags`";
+            let expected = vec![
+                error("name", &format!("missing field; {available}")),
+                error("server", &format!("missing field; {available}")),
+            ];
+            assert_eq!(result, Err(expected));
+        }
+
+        #[test]
+        fn wrong_type_for_a_nested_record() {
+            let value = json!({
+                "name": "nick",
+                "verbose": true,
+                "server": "localhost:80",
+                "tags": [],
+            });
+
+            let result: Result<TestConfiguration, _> =
+                deserialize_collecting_errors(value, &English);
+
+            let expected = vec![error(
+                "server",
+                "invalid type: string \"localhost:80\", expected struct Server",
+            )];
+            assert_eq!(result, Err(expected));
+        }
+    }
+
+    #[cfg(test)]
+    mod deserialize_with_defaults {
+        use super::super::deserialize_with_defaults;
+        use super::super::FieldError;
+        use super::Server;
+        use super::TestConfiguration;
+        use crate::English;
+        use serde_json::json;
+
+        fn defaults() -> serde_json::Value {
+            serde_json::to_value(TestConfiguration {
+                name: "default".to_owned(),
+                verbose: false,
+                server: Server {
+                    host: "localhost".to_owned(),
+                    port: 8080,
+                },
+                tags: vec!["default".to_owned()],
+            })
+            .unwrap()
+        }
+
+        #[test]
+        fn replaces_the_mismatched_fields() {
+            let value = json!({
+                "name": "nick",
+                "verbose": "yes",
+                "server": { "host": "example.com", "port": "80" },
+                "tags": ["a", false],
+            });
+
+            let (result, errors): (TestConfiguration, _) =
+                deserialize_with_defaults(value, &defaults(), &English).unwrap();
+
+            let expected = TestConfiguration {
+                name: "nick".to_owned(),
+                verbose: false,
+                server: Server {
+                    host: "example.com".to_owned(),
+                    port: 8080,
+                },
+                tags: vec!["default".to_owned()],
+            };
+            assert_eq!(result, expected);
+            let paths: Vec<&str> = errors.iter().map(|error| error.path.as_str()).collect();
+            assert_eq!(paths, vec!["server.port", "tags[1]", "verbose"]);
+        }
+
+        #[test]
+        fn fills_missing_fields() {
+            let value = json!({ "verbose": true, "tags": [] });
+
+            let (result, errors): (TestConfiguration, _) =
+                deserialize_with_defaults(value, &defaults(), &English).unwrap();
+
+            assert_eq!(result.name, "default");
+            assert_eq!(result.server.port, 8080);
+            assert_eq!(
+                errors.first(),
+                Some(&FieldError {
+                    path: "name".to_owned(),
+                    message:
+                        "missing field; available fields are `name`, `verbose`, `server`, `tags`"
+                            .to_owned(),
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! A [figment](https://docs.rs/figment) provider, so projects already using figment (e.g.
+//! Rocket apps) can add Nickel configurations to their existing merge and profile stack.
+
+use crate::first_existing_config;
+use crate::Loader;
+use ::figment::providers::Serialized;
+use ::figment::value::Dict;
+use ::figment::value::Map;
+use ::figment::Error;
+use ::figment::Metadata;
+use ::figment::Profile;
+use ::figment::Provider;
+use std::path::PathBuf;
+
+/// A figment [`Provider`] reading the configuration of an application from its Nickel
+/// file, evaluated by a [`Loader`]:
+///
+/// ```no_run
+/// use figment::providers::Serialized;
+/// use figment::Figment;
+/// use nickelodeon::figment::NickelProvider;
+///
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig {}
+/// let config: MyConfig = Figment::from(Serialized::defaults(MyConfig::default()))
+///     .merge(NickelProvider::new("my_app"))
+///     .extract()
+///     .expect("invalid configuration");
+/// ```
+///
+/// Like figment's own file providers, the whole configuration goes into the default
+/// profile, unless another one is selected with [`NickelProvider::profile`], or its
+/// top-level fields are used as profiles with [`NickelProvider::nested`]. A missing file
+/// provides no values.
+#[derive(Clone)]
+pub struct NickelProvider {
+    loader: Loader,
+    profile: Option<Profile>,
+}
+
+impl NickelProvider {
+    /// Reads the configuration of the application with the codename `app`, from the file
+    /// a [`Loader::new`] would find.
+    #[must_use]
+    pub fn new(app: &str) -> Self {
+        Self::from_loader(Loader::new(app))
+    }
+
+    /// Reads the Nickel file at `path`.
+    #[must_use]
+    pub fn file(path: PathBuf) -> Self {
+        Self::from_loader(Loader::new("").config_path_from_flag(Some(path)))
+    }
+
+    /// Reads the configuration `loader` finds, with its options: preludes, contracts,
+    /// defaults and so on.
+    #[must_use]
+    pub const fn from_loader(loader: Loader) -> Self {
+        Self {
+            loader,
+            profile: Some(Profile::Default),
+        }
+    }
+
+    /// Provides the configuration to `profile` instead of the default one.
+    #[must_use]
+    #[allow(clippy::same_name_method)]
+    pub fn profile<P: Into<Profile>>(mut self, profile: P) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Provides each top-level field of the configuration, which must be a record, to the
+    /// profile of the same name, e.g. `{ debug = { .. }, release = { .. } }`.
+    #[must_use]
+    pub fn nested(mut self) -> Self {
+        self.profile = None;
+        self
+    }
+}
+
+impl Provider for NickelProvider {
+    fn metadata(&self) -> Metadata {
+        let metadata = Metadata::named("Nickel file");
+        match first_existing_config(&self.loader.locations()) {
+            Some(path) => metadata.source(path.as_path()),
+            None => metadata,
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let exported = self
+            .loader
+            .export_json()
+            .map_err(|err| Error::from(err.render_colored(false)))?;
+        let Some(profile) = &self.profile else {
+            let profiles = Serialized::defaults(exported)
+                .data()?
+                .remove(&Profile::Default)
+                .unwrap_or_default();
+            return profiles
+                .into_iter()
+                .map(|(name, value)| {
+                    let dict = value.into_dict().ok_or_else(|| {
+                        Error::from(format!("the `{name}` profile is not a record"))
+                    })?;
+                    Ok((Profile::from(name.as_str()), dict))
+                })
+                .collect();
+        };
+        Serialized::from(exported, profile.clone()).data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod nickel_provider {
+        use super::super::NickelProvider;
+        use figment::providers::Serialized;
+        use figment::Figment;
+        use figment::Provider as _;
+        use std::path::PathBuf;
+        use tempfile::TempDir;
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq)]
+        struct Config {
+            name: String,
+            port: u16,
+        }
+
+        fn file(contents: &str) -> (TempDir, PathBuf) {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            std::fs::write(&path, contents).unwrap();
+            (dir, path)
+        }
+
+        fn provider(path: PathBuf) -> NickelProvider {
+            NickelProvider::from_loader(
+                crate::Loader::new("nickelodeon_test")
+                    .config_path_from_flag(Some(path))
+                    .diagnostics(std::io::sink()),
+            )
+        }
+
+        #[test]
+        fn merges_over_other_providers() {
+            let (_dir, path) = file("{ port = 40 + 2 }");
+            let defaults = Config {
+                name: "app".to_owned(),
+                port: 80,
+            };
+
+            let config: Config = Figment::from(Serialized::defaults(defaults))
+                .merge(provider(path.clone()))
+                .extract()
+                .unwrap();
+
+            assert_eq!(
+                config,
+                Config {
+                    name: "app".to_owned(),
+                    port: 42,
+                }
+            );
+            let metadata = provider(path.clone()).metadata();
+            let source = metadata
+                .source
+                .as_ref()
+                .and_then(|source| source.file_path());
+            assert_eq!(source, Some(path.as_path()));
+        }
+
+        #[test]
+        fn nested_profiles() {
+            let (_dir, path) =
+                file(r#"{ "default" = { name = "app", port = 80 }, debug = { port = 8080 } }"#);
+
+            let config: Config = Figment::new()
+                .merge(provider(path).nested())
+                .select("debug")
+                .extract()
+                .unwrap();
+
+            assert_eq!(config.port, 8080);
+        }
+
+        #[test]
+        fn evaluation_errors() {
+            let (_dir, path) = file(r#"{ port = "80" + 1 }"#);
+
+            let result = Figment::new().merge(provider(path)).extract::<Config>();
+
+            assert!(matches!(result, Err(error) if error.metadata.is_some()));
+        }
+    }
+}
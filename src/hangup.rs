@@ -0,0 +1,37 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// How many `SIGHUP`s the process received since [`install`] was first called.
+static HANGUPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the `SIGHUP`s received by the process from now on, replacing their default
+/// handling, which terminates it. Only effective on Unix.
+#[cfg(unix)]
+pub(crate) fn install() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+
+    extern "C" fn on_hangup(_signal: libc::c_int) {
+        HANGUPS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // `signal` takes the address of the handler.
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::fn_to_numeric_cast_any)]
+    let handler = on_hangup as *const () as libc::sighandler_t;
+    INSTALLED.call_once(|| {
+        // SAFETY: the handler only touches an atomic, which is async-signal-safe.
+        unsafe {
+            libc::signal(libc::SIGHUP, handler);
+        }
+    });
+}
+
+/// Counts the `SIGHUP`s received by the process from now on, replacing their default
+/// handling, which terminates it. Only effective on Unix.
+#[cfg(not(unix))]
+pub(crate) fn install() {}
+
+/// Returns how many `SIGHUP`s the process received since [`install`] was first called.
+pub(crate) fn hangups() -> usize {
+    HANGUPS.load(Ordering::Relaxed)
+}
@@ -0,0 +1,120 @@
+use crate::dotenv;
+use crate::prelude::quoted;
+use crate::prelude::Prelude;
+use std::fmt::Write as _;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The facts about the host exposed to the configuration, as a `host` record, by
+/// [`crate::Loader::host_facts`]:
+///
+/// - `hostname`: the name of the host, empty when unknown.
+/// - `os`: the operating system, like `linux`, `macos` or `windows`.
+/// - `family`: the family of the operating system, `unix` or `windows`.
+/// - `arch`: the architecture of the CPU, like `x86_64` or `aarch64`.
+/// - `cpus`: how many CPUs the application can use.
+/// - `env`: the allowed environment variables ([`HostFacts::env_var`]) that are set, and
+///   the variables of the [`HostFacts::dotenv`] file.
+///
+/// ```nickel
+/// {
+///   workers = host.cpus * 2,
+///   log_dir = if host.os == "linux" then "/var/log/my-app" else "logs",
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostFacts {
+    env: Vec<String>,
+    dotenv: Option<PathBuf>,
+}
+
+impl HostFacts {
+    /// Exposes the environment variable `name` in `host.env`, when it's set. No
+    /// environment variable is exposed by default, so secrets stay out of reach of the
+    /// configuration.
+    #[must_use]
+    pub fn env_var(mut self, name: &str) -> Self {
+        self.env.push(name.to_owned());
+        self
+    }
+
+    /// Reads the `.env` file at `path` (usually `.env`, in the current directory) and
+    /// exposes its variables in `host.env`, so 12-factor style applications can keep their
+    /// local settings in it:
+    ///
+    /// ```
+    /// let facts = nickelodeon::HostFacts::default().dotenv(std::path::Path::new(".env"));
+    /// ```
+    ///
+    /// The variables set in the environment of the process win over the ones of the file,
+    /// as usual. A missing file is ignored, so it can be left out of production
+    /// deployments.
+    #[must_use]
+    pub fn dotenv(mut self, path: &Path) -> Self {
+        self.dotenv = Some(path.to_owned());
+        self
+    }
+
+    /// Returns the `host` record as a prelude.
+    pub(crate) fn prelude(&self) -> Prelude {
+        let cpus = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let mut source = format!(
+            "{{ hostname = {}, os = {}, family = {}, arch = {}, cpus = {cpus}, env = {{",
+            quoted(&hostname().unwrap_or_default()),
+            quoted(std::env::consts::OS),
+            quoted(std::env::consts::FAMILY),
+            quoted(std::env::consts::ARCH),
+        );
+        for (name, value) in self.env_vars() {
+            let _infallible = write!(source, " {} = {},", quoted(&name), quoted(&value));
+        }
+        source.push_str(" } }");
+        Prelude::new("host", &source)
+    }
+
+    /// Returns the exposed environment variables that are set, with their values: the
+    /// allowed ones first, then the ones of the `.env` file.
+    fn env_vars(&self) -> Vec<(String, String)> {
+        let file = self.dotenv.as_deref().map(dotenv::read).unwrap_or_default();
+        let mut vars: Vec<(String, String)> = Vec::new();
+        for name in self.env.iter().chain(file.iter().map(|(name, _)| name)) {
+            if vars.iter().any(|(exposed, _)| exposed == name) {
+                continue;
+            }
+            let set = std::env::var(name).ok().or_else(|| {
+                file.iter()
+                    .rev()
+                    .find(|(set, _)| set == name)
+                    .map(|(_, value)| value.clone())
+            });
+            if let Some(value) = set {
+                vars.push((name.clone(), value));
+            }
+        }
+        vars
+    }
+}
+
+/// Returns the name of the host.
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buffer = [0_u8; 256];
+    // SAFETY: the buffer is valid for writes of its whole length, and `gethostname`
+    // truncates longer names.
+    let status = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if status != 0 {
+        return None;
+    }
+    let end = buffer
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(buffer.len());
+    String::from_utf8(buffer.get(..end)?.to_vec()).ok()
+}
+
+/// Returns the name of the host.
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
@@ -0,0 +1,238 @@
+use crate::prelude::quoted;
+use codespan::FileId;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::cache::ErrorTolerance;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use nickel_lang_core::term::Traverse as _;
+use nickel_lang_core::term::TraverseOrder;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Which files a configuration file is allowed to `import`.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ImportPolicy {
+    /// Any file can be imported.
+    #[default]
+    Allow,
+
+    /// Imports are rejected with [`crate::Error::ForbiddenImport`]. Meant for configuration
+    /// files coming from untrusted users.
+    Deny,
+
+    /// Only the files inside the directory of the configuration file (or its
+    /// subdirectories) can be imported, so a configuration can be split across files.
+    ConfigDirectory,
+
+    /// Only the files inside one of the given directories (or their subdirectories) can be
+    /// imported.
+    Within(Vec<PathBuf>),
+}
+
+/// Returns the first import that `policy` rejects, together with the file importing it,
+/// looking at the configuration file `path` (with id `file_id`) and, transitively, at the
+/// files it is allowed to import. Imports are returned as they are written in the file.
+///
+/// The files in the `search_paths` are allowed too, unless imports are denied.
+///
+/// Paths are compared once symbolic links and `..` components are resolved, so they can't
+/// be used to escape the allowed directories. Files that don't parse are let through, so
+/// their parse errors are reported by the evaluation.
+pub(crate) fn forbidden_import(
+    cache: &mut Cache,
+    path: &Path,
+    file_id: FileId,
+    policy: &ImportPolicy,
+    search_paths: &[PathBuf],
+) -> Option<(PathBuf, PathBuf)> {
+    let mut roots: Vec<PathBuf> = match policy {
+        ImportPolicy::Allow => return None,
+        ImportPolicy::Deny => Vec::new(),
+        ImportPolicy::ConfigDirectory => canonical(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .into_iter()
+            .collect(),
+        ImportPolicy::Within(roots) => roots.iter().map(|root| canonical(root)).collect(),
+    };
+    if *policy != ImportPolicy::Deny {
+        roots.extend(search_paths.iter().map(|dir| canonical(dir)));
+    }
+
+    let mut pending = vec![(path.to_path_buf(), file_id)];
+    let mut visited = HashSet::new();
+    while let Some((file, id)) = pending.pop() {
+        let directory = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        // The keys of a mounted directory are imported by the configuration itself.
+        let mounted = file.is_dir();
+        for import in imports_of(cache, id, &file) {
+            let imported = canonical(&directory.join(&import));
+            if !mounted && !roots.iter().any(|root| imported.starts_with(root)) {
+                return Some((file, import));
+            }
+            if visited.insert(imported.clone()) {
+                // Files already in the cache may have had their imports rewritten.
+                let imported_id = cache
+                    .id_of(&imported)
+                    .map_or_else(|| cache.add_file(imported.clone()).ok(), Some);
+                if let Some(next_id) = imported_id {
+                    pending.push((imported, next_id));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Returns every file imported, directly or transitively, by the evaluated configuration
+/// file `path`, sorted.
+///
+/// Imports are all resolved before the evaluation starts, so these are the files, other than
+/// the configuration file, the standard library and the sources that don't come from a file
+/// (like preludes), the evaluation parsed.
+pub(crate) fn import_closure(cache: &Cache, path: &Path) -> Vec<PathBuf> {
+    let main_id = cache.id_of(path);
+    let closure: BTreeSet<PathBuf> = cache
+        .terms()
+        .keys()
+        .filter(|id| Some(**id) != main_id && !cache.is_stdlib_module(**id))
+        .map(|id| PathBuf::from(cache.name(*id)))
+        .filter(|imported| imported.is_file())
+        .collect();
+    closure.into_iter().collect()
+}
+
+/// Looks for the imports of the configuration file `main_id` of `cache` (transitively) that
+/// aren't found next to the importing file in the `search_paths`: the importing files are
+/// replaced in `cache` with those imports rewritten to the files found, since Nickel only
+/// resolves imports relatively to the importing file.
+pub(crate) fn search_imports(cache: &mut Cache, main_id: FileId, search_paths: &[PathBuf]) {
+    if search_paths.is_empty() {
+        return;
+    }
+
+    // Files are parsed in a scratch cache, so only the rewritten ones end up in `cache`.
+    let mut scratch = Cache::new(ErrorTolerance::Strict);
+    let mut pending = vec![PathBuf::from(cache.name(main_id))];
+    let mut visited = HashSet::new();
+    while let Some(file) = pending.pop() {
+        if !visited.insert(file.clone()) || is_data(&file) {
+            continue;
+        }
+        // The configuration file is read from `cache`, where it may have been decrypted.
+        let read = if file.as_os_str() == cache.name(main_id) {
+            Ok(cache.source(main_id).to_owned())
+        } else {
+            std::fs::read_to_string(&file)
+        };
+        let Ok(source) = read else {
+            continue;
+        };
+        let scratch_id = scratch.add_string(file.clone(), source.clone());
+        let Ok((rt, _errors)) = scratch.parse_nocache(scratch_id) else {
+            continue;
+        };
+
+        let directory = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut rewrites = Vec::new();
+        for (import, position) in spanned_imports(rt) {
+            let next_to = directory.join(&import);
+            let searched = (!next_to.exists())
+                .then(|| {
+                    search_paths
+                        .iter()
+                        .map(|dir| dir.join(&import))
+                        .find(|candidate| candidate.is_file())
+                })
+                .flatten();
+            match (searched, position) {
+                (Some(found), Some(span)) => {
+                    pending.push(found.clone());
+                    rewrites.push((span, found));
+                }
+                (Some(_) | None, _) => pending.push(next_to),
+            }
+        }
+
+        if !rewrites.is_empty() {
+            cache.replace_string(file.as_os_str(), rewrite(source, rewrites));
+        }
+    }
+}
+
+/// Replaces the paths of the imports at the given spans of `source` with the given paths.
+fn rewrite(mut source: String, mut rewrites: Vec<(Range<usize>, PathBuf)>) -> String {
+    rewrites.sort_by_key(|(span, _path)| span.start);
+    for (span, path) in rewrites.into_iter().rev() {
+        if let Some(literal) = literal(&source, span) {
+            source.replace_range(literal, &quoted(&path.to_string_lossy()));
+        }
+    }
+    source
+}
+
+/// Returns where the string literal holding the path of the `import` expression at `span`
+/// of `source` is. The span of the expression may include enclosing parentheses.
+fn literal(source: &str, span: Range<usize>) -> Option<Range<usize>> {
+    let expression = source.get(span.clone())?;
+    let keyword = expression.find("import")?;
+    let start = keyword.checked_add(expression.get(keyword..)?.find('"')?)?;
+    let end = expression.rfind('"')?;
+    (end > start)
+        .then(|| span.start.saturating_add(start)..span.start.saturating_add(end).saturating_add(1))
+}
+
+/// Returns the paths imported by the file `file_id`, located at `path`, in the order they
+/// appear. Only Nickel files can import other files.
+fn imports_of(cache: &Cache, file_id: FileId, path: &Path) -> Vec<PathBuf> {
+    if is_data(path) {
+        return Vec::new();
+    }
+    let Ok((rt, _errors)) = cache.parse_nocache(file_id) else {
+        return Vec::new();
+    };
+    spanned_imports(rt)
+        .into_iter()
+        .map(|(import, _span)| import)
+        .collect()
+}
+
+/// Tells whether `path` is a data file (JSON, YAML or TOML), which can't import other files.
+fn is_data(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        ["json", "yaml", "yml", "toml"]
+            .iter()
+            .any(|format| extension == *format)
+    })
+}
+
+/// Returns the paths imported by the parsed file `rt`, in the order they appear, together
+/// with the span of the `import` expressions in the file, when known.
+fn spanned_imports(rt: RichTerm) -> Vec<(PathBuf, Option<Range<usize>>)> {
+    let mut imports = Vec::new();
+    let _traversed: Result<RichTerm, Infallible> = rt.traverse(
+        &|term: RichTerm, found: &mut Vec<(PathBuf, Option<Range<usize>>)>| {
+            if let Term::Import(import) = term.as_ref() {
+                let span = term
+                    .pos
+                    .as_opt_ref()
+                    .map(|raw| raw.start.to_usize()..raw.end.to_usize());
+                found.push((PathBuf::from(import), span));
+            }
+            Ok(term)
+        },
+        &mut imports,
+        TraverseOrder::TopDown,
+    );
+    imports
+}
+
+/// Resolves the symbolic links and `..` components of `path`, when it exists.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
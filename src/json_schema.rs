@@ -0,0 +1,546 @@
+use crate::syntax::field_name;
+use crate::syntax::string;
+use serde_json::Map;
+use serde_json::Value;
+use std::fmt::Write as _;
+
+/// The helpers the converted contracts are written with.
+const HELPERS: &str = "let nullable = fun contract label value =>
+  if value == null then value else std.contract.apply contract label value
+in
+let all_of = fun contracts label value =>
+  std.array.fold_left (fun checked contract => std.contract.apply contract label checked) value contracts
+in
+let satisfying = fun message predicate label value =>
+  if predicate value then value else std.contract.blame_with_message message label
+in
+";
+
+/// Returns a Nickel contract checking that a configuration is valid according to the JSON
+/// `schema`.
+///
+/// The schemas many tools already publish for their configuration can so be reused instead
+/// of rewriting their rules by hand.
+/// It can be passed to [`crate::Loader::contract`] (see
+/// [`crate::Loader::json_schema_contract`]), or written next to the configuration so its
+/// authors can import it.
+///
+/// `type`, `properties`, `required`, `additionalProperties`, `items`, `enum`, `const`,
+/// `allOf`, the bounds of numbers, strings and arrays, `pattern` and the local `$ref`s
+/// (e.g. `#/$defs/server`) are checked. Enum tags are compared by their names, so `'debug`
+/// is one of `["debug", "info"]`. What can't be described (`anyOf` and `oneOf`, other than
+/// to make a value nullable, or remote `$ref`s) is left unchecked.
+///
+/// ```
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": { "port": { "type": "integer", "minimum": 1 } },
+///     "required": ["port"],
+/// });
+///
+/// let contract = nickelodeon::contract_from_json_schema(&schema);
+/// assert!(contract.contains("port | std.number.Integer | satisfying "));
+/// ```
+#[must_use]
+pub fn contract_from_json_schema(schema: &Value) -> String {
+    let mut converter = Converter {
+        root: schema,
+        definitions: Vec::new(),
+    };
+    let root = converter.contract(schema, 1);
+    let mut definitions = Vec::new();
+    while let Some(pointer) = converter.definitions.get(definitions.len()).cloned() {
+        let definition = schema
+            .pointer(&pointer)
+            .map_or_else(|| "Dyn".to_owned(), |found| converter.contract(found, 1));
+        definitions.push(definition);
+    }
+
+    let mut contract = String::from(HELPERS);
+    if definitions.is_empty() {
+        contract.push_str(&converter.contract(schema, 0));
+    } else {
+        let _infallible = write!(contract, "let schema = {{\n  root = {root},\n");
+        for (index, definition) in definitions.iter().enumerate() {
+            let _also_infallible = writeln!(contract, "  definition_{index} = {definition},");
+        }
+        contract.push_str("}\nin\nschema.root");
+    }
+    contract.push('\n');
+    contract
+}
+
+/// Returns the JSON Schema of `T`, the configuration of an application, so editors can
+/// complete and validate its configuration files and web UIs can render forms for it.
+///
+/// ```
+/// #[derive(serde::Deserialize, schemars::JsonSchema)]
+/// struct Config {
+///     /// The port to listen on.
+///     port: u16,
+/// }
+///
+/// let schema = nickelodeon::json_schema_for::<Config>();
+/// assert_eq!(schema["properties"]["port"]["description"], "The port to listen on.");
+/// ```
+///
+/// The schema describes the deserialized shape of `T` (its doc comments becoming
+/// descriptions), so it also checks the configurations written in JSON, and can be turned
+/// into a Nickel contract by [`contract_from_json_schema`].
+#[cfg(feature = "schemars")]
+#[must_use]
+pub fn json_schema_for<T: schemars::JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
+
+struct Converter<'schema> {
+    root: &'schema Value,
+
+    /// The JSON pointers to the schemas referenced with `$ref`, named by their index.
+    definitions: Vec<String>,
+}
+
+impl Converter<'_> {
+    /// Writes `schema` as a Nickel contract, with its nested lines indented by `indent`.
+    fn contract(&mut self, schema: &Value, indent: usize) -> String {
+        let (contracts, nullable) = self.contracts(schema, indent);
+        let contract = match contracts.as_slice() {
+            [] => "Dyn".to_owned(),
+            [single] => single.clone(),
+            several => format!("all_of [{}]", several.join(", ")),
+        };
+        if nullable {
+            format!("nullable ({contract})")
+        } else {
+            contract
+        }
+    }
+
+    /// Writes the contracts `schema` is made of, and tells whether `null` is allowed too.
+    fn contracts(&mut self, schema: &Value, indent: usize) -> (Vec<String>, bool) {
+        let keywords = match schema {
+            Value::Bool(false) => {
+                let never = "satisfying \"no value is allowed\" (fun _value => false)";
+                return (vec![never.to_owned()], false);
+            }
+            Value::Object(keywords) => keywords,
+            Value::Null
+            | Value::Bool(true)
+            | Value::Number(_)
+            | Value::String(_)
+            | Value::Array(_) => return (Vec::new(), false),
+        };
+
+        let mut contracts = Vec::new();
+        if let Some(reference) = keywords.get("$ref").and_then(Value::as_str) {
+            contracts.push(self.reference(reference));
+        }
+        let named: Vec<&str> = match keywords.get("type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let nullable = named.len() > 1 && named.contains(&"null");
+        let types: Vec<&str> = named
+            .into_iter()
+            .filter(|name| !nullable || *name != "null")
+            .collect();
+        match types.as_slice() {
+            [] if keywords.contains_key("properties") => {
+                contracts.push(self.record(keywords, indent));
+            }
+            [] if keywords.contains_key("items") => contracts.push(self.array(keywords, indent)),
+            [] => {}
+            [name] => contracts.push(self.typed(name, keywords, indent)),
+            several => contracts.push(one_of_types(several)),
+        }
+
+        for subschema in keywords
+            .get("allOf")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            contracts.push(self.contract(subschema, indent));
+        }
+        let alternatives = keywords
+            .get("anyOf")
+            .or_else(|| keywords.get("oneOf"))
+            .and_then(Value::as_array);
+        let mut nullable_alternative = false;
+        if let Some([first, second]) = alternatives.map(Vec::as_slice) {
+            let is_null = |alternative: &Value| alternative.get("type") == Some(&"null".into());
+            if let Some(other) = match (is_null(first), is_null(second)) {
+                (true, false) => Some(second),
+                (false, true) => Some(first),
+                (true, true) | (false, false) => None,
+            } {
+                nullable_alternative = true;
+                contracts.push(self.contract(other, indent));
+            }
+        }
+        contracts.extend(constraints(keywords));
+        (contracts, nullable || nullable_alternative)
+    }
+
+    /// Writes the contract of the values of the JSON type `name`.
+    fn typed(&mut self, name: &str, keywords: &Map<String, Value>, indent: usize) -> String {
+        match name {
+            "string" => "String".to_owned(),
+            "number" => "Number".to_owned(),
+            "integer" => "std.number.Integer".to_owned(),
+            "boolean" => "Bool".to_owned(),
+            "null" => "satisfying \"expected null\" (fun value => value == null)".to_owned(),
+            "array" => self.array(keywords, indent),
+            "object" => self.record(keywords, indent),
+            _ => "Dyn".to_owned(),
+        }
+    }
+
+    /// Writes the contract of an array, from its `items`.
+    fn array(&mut self, keywords: &Map<String, Value>, indent: usize) -> String {
+        match keywords.get("items") {
+            Some(items @ (Value::Object(_) | Value::Bool(_))) => {
+                format!("Array ({})", self.contract(items, indent))
+            }
+            _ => "Array Dyn".to_owned(),
+        }
+    }
+
+    /// Writes the contract of an object, from its `properties`, `required` and
+    /// `additionalProperties`.
+    fn record(&mut self, keywords: &Map<String, Value>, indent: usize) -> String {
+        let defined = keywords.get("properties").and_then(Value::as_object);
+        let additional = keywords.get("additionalProperties");
+        let Some(properties) = defined.filter(|found| !found.is_empty()) else {
+            return match additional {
+                Some(Value::Bool(false)) => "{}".to_owned(),
+                Some(schema @ Value::Object(_)) => {
+                    format!("{{ _ : {} }}", self.contract(schema, indent))
+                }
+                _ => "{ .. }".to_owned(),
+            };
+        };
+        let required: Vec<&str> = keywords
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+
+        let padding = "  ".repeat(indent.saturating_add(1));
+        let mut record = String::from("{\n");
+        for (name, property) in properties {
+            let _infallible = write!(record, "{padding}{} | ", field_name(name));
+            if !required.contains(&name.as_str()) {
+                record.push_str("optional | ");
+            }
+            let nested = indent.saturating_add(1);
+            match self.contracts(property, nested) {
+                (contracts, false) if !contracts.is_empty() => {
+                    record.push_str(&contracts.join(" | "));
+                }
+                _ => record.push_str(&self.contract(property, nested)),
+            }
+            record.push_str(",\n");
+        }
+        if additional != Some(&Value::Bool(false)) {
+            let _infallible = writeln!(record, "{padding}..");
+        }
+        record.push_str(&"  ".repeat(indent));
+        record.push('}');
+        record
+    }
+
+    /// Returns the name of the definition `reference` points to, or `Dyn` if it isn't a
+    /// pointer into this schema.
+    fn reference(&mut self, reference: &str) -> String {
+        let Some(pointer) = reference.strip_prefix('#') else {
+            return "Dyn".to_owned();
+        };
+        if self.root.pointer(pointer).is_none() {
+            return "Dyn".to_owned();
+        }
+        let index = self
+            .definitions
+            .iter()
+            .position(|known| known == pointer)
+            .unwrap_or_else(|| {
+                self.definitions.push(pointer.to_owned());
+                self.definitions.len().saturating_sub(1)
+            });
+        format!("definition_{index}")
+    }
+}
+
+/// Writes the contract of the values of any of the JSON types `names`.
+fn one_of_types(names: &[&str]) -> String {
+    let tags: Vec<&str> = names
+        .iter()
+        .filter_map(|name| match *name {
+            "string" => Some("'String"),
+            "number" | "integer" => Some("'Number"),
+            "boolean" => Some("'Bool"),
+            "array" => Some("'Array"),
+            "object" => Some("'Record"),
+            _ => None,
+        })
+        .collect();
+    format!(
+        "satisfying {} (fun value => std.array.elem (std.typeof value) [{}])",
+        string(&format!("expected a value of type {}", names.join(" or "))),
+        tags.join(", ")
+    )
+}
+
+/// Writes the contracts checking the `enum`, `const`, bounds and `pattern` keywords.
+fn constraints(keywords: &Map<String, Value>) -> Vec<String> {
+    let mut constraints = Vec::new();
+    let mut check = |message: String, predicate: String| {
+        constraints.push(format!(
+            "satisfying {} (fun value => {predicate})",
+            string(&message)
+        ));
+    };
+
+    let allowed = match (keywords.get("enum"), keywords.get("const")) {
+        (Some(Value::Array(values)), _) => Some(values.as_slice()),
+        (_, Some(value)) => Some(std::slice::from_ref(value)),
+        _ => None,
+    };
+    if let Some(values) = allowed {
+        let literals: Vec<String> = values.iter().map(literal).collect();
+        check(
+            format!("expected one of {}", literals.join(", ")),
+            format!(
+                "std.array.elem (if std.is_enum value then std.to_string value else value) [{}]",
+                literals.join(", ")
+            ),
+        );
+    }
+
+    let bounds = [
+        ("minimum", "std.is_number", "value", ">=", "at least"),
+        ("maximum", "std.is_number", "value", "<=", "at most"),
+        (
+            "exclusiveMinimum",
+            "std.is_number",
+            "value",
+            ">",
+            "more than",
+        ),
+        (
+            "exclusiveMaximum",
+            "std.is_number",
+            "value",
+            "<",
+            "less than",
+        ),
+        (
+            "minLength",
+            "std.is_string",
+            "std.string.length value",
+            ">=",
+            "a length of at least",
+        ),
+        (
+            "maxLength",
+            "std.is_string",
+            "std.string.length value",
+            "<=",
+            "a length of at most",
+        ),
+        (
+            "minItems",
+            "std.is_array",
+            "std.array.length value",
+            ">=",
+            "at least",
+        ),
+        (
+            "maxItems",
+            "std.is_array",
+            "std.array.length value",
+            "<=",
+            "at most",
+        ),
+    ];
+    for (keyword, applies, measure, comparison, description) in bounds {
+        let Some(Value::Number(bound)) = keywords.get(keyword) else {
+            continue;
+        };
+        let items = if keyword.ends_with("Items") {
+            " items"
+        } else {
+            ""
+        };
+        check(
+            format!("expected {description} {bound}{items}"),
+            format!("!({applies} value) || {measure} {comparison} {bound}"),
+        );
+    }
+    if let Some(Value::Number(divisor)) = keywords.get("multipleOf") {
+        check(
+            format!("expected a multiple of {divisor}"),
+            format!("!(std.is_number value) || std.number.is_integer (value / {divisor})"),
+        );
+    }
+    if let Some(pattern) = keywords.get("pattern").and_then(Value::as_str) {
+        check(
+            format!("expected to match {pattern}"),
+            format!(
+                "!(std.is_string value) || std.string.is_match {} value",
+                string(pattern)
+            ),
+        );
+    }
+    constraints
+}
+
+/// Writes the JSON `value` as a Nickel value.
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(boolean) => boolean.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => string(text),
+        Value::Array(elements) => {
+            let literals: Vec<String> = elements.iter().map(literal).collect();
+            format!("[{}]", literals.join(", "))
+        }
+        Value::Object(fields) => {
+            let written: Vec<String> = fields
+                .iter()
+                .map(|(name, field)| format!("{} = {}", field_name(name), literal(field)))
+                .collect();
+            format!("{{ {} }}", written.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod contract_from_json_schema {
+        use super::super::contract_from_json_schema;
+        use serde_json::json;
+
+        #[test]
+        fn describes_the_properties() {
+            let schema = json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 },
+                    "server": {
+                        "type": ["object", "null"],
+                        "properties": { "port": { "type": "integer" } },
+                        "additionalProperties": false,
+                    },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "level": { "enum": ["debug", "info"] },
+                },
+                "required": ["name"],
+            });
+
+            let result = contract_from_json_schema(&schema);
+
+            let expected = r#"{
+  level | optional | satisfying "expected one of \"debug\", \"info\"" (fun value => std.array.elem (if std.is_enum value then std.to_string value else value) ["debug", "info"]),
+  name | String | satisfying "expected a length of at least 1" (fun value => !(std.is_string value) || std.string.length value >= 1),
+  server | optional | nullable ({
+    port | optional | std.number.Integer,
+  }),
+  tags | optional | Array (String),
+  ..
+}
+"#;
+            assert!(result.ends_with(expected), "{result}");
+        }
+
+        #[test]
+        fn references() {
+            let schema = json!({
+                "$ref": "#/$defs/tree",
+                "$defs": {
+                    "tree": {
+                        "type": "object",
+                        "properties": {
+                            "children": { "type": "array", "items": { "$ref": "#/$defs/tree" } },
+                        },
+                    },
+                },
+            });
+
+            let result = contract_from_json_schema(&schema);
+
+            assert!(result.contains("root = definition_0,"), "{result}");
+            assert!(result.contains("children | optional | Array (definition_0),"));
+            assert!(result.ends_with("schema.root\n"));
+        }
+
+        #[test]
+        fn unchecked() {
+            assert!(contract_from_json_schema(&json!(true)).ends_with("\nDyn\n"));
+            assert!(contract_from_json_schema(
+                &json!({ "$ref": "https://example.com/schema.json" })
+            )
+            .ends_with("\nDyn\n"));
+        }
+    }
+    #[cfg(test)]
+    #[cfg(feature = "schemars")]
+    mod json_schema_for {
+        use super::super::contract_from_json_schema;
+        use super::super::json_schema_for;
+        use crate::Loader;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+        struct Config {
+            /// The name of the service.
+            name: String,
+            server: Option<Server>,
+        }
+
+        #[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        #[allow(clippy::indexing_slicing)]
+        fn describes_the_type() {
+            let schema = json_schema_for::<Config>();
+
+            assert_eq!(schema["title"], "Config");
+            assert_eq!(schema["required"], serde_json::json!(["name"]));
+            assert_eq!(
+                schema["properties"]["name"]["description"],
+                "The name of the service."
+            );
+        }
+
+        #[test]
+        fn checks_configurations() {
+            let contract = contract_from_json_schema(&json_schema_for::<Config>());
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{{ name = \"app\", server = {{ port = -1 }} }}").unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .contract(&contract)
+                .load::<Config>();
+
+            let Err(crate::Error::NickelEvaluationError(_, diagnostics)) = result else {
+                panic!("expected a contract violation, got {result:?}");
+            };
+            assert_eq!(
+                diagnostics.first().unwrap().field.as_deref(),
+                Some("server.port")
+            );
+        }
+    }
+}
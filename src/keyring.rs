@@ -0,0 +1,154 @@
+use std::process::Command;
+
+/// Resolves a `keyring://` reference, `<service>/<account>`, into the password stored for
+/// that account of that service in the keyring of the platform.
+pub(crate) fn resolve(reference: &str) -> Result<String, String> {
+    let (service, account) = parse(reference)?;
+    let lookup = lookup(service, account)
+        .ok_or_else(|| "the keyring of this platform isn't supported".to_owned())?;
+    read(lookup)
+}
+
+/// Splits a `keyring://` reference into its service and its account.
+fn parse(reference: &str) -> Result<(&str, &str), String> {
+    reference
+        .split_once('/')
+        .filter(|(service, account)| !service.is_empty() && !account.is_empty())
+        .ok_or_else(|| "expected `keyring://<service>/<account>`".to_owned())
+}
+
+/// Returns the command line printing the password of `account` of `service` stored in the
+/// Secret Service (GNOME Keyring, `KWallet`...), with the attributes used by most tools.
+#[cfg(all(unix, not(target_os = "macos")))]
+#[allow(clippy::unnecessary_wraps)]
+fn lookup(service: &str, account: &str) -> Option<Command> {
+    let mut command = Command::new("secret-tool");
+    command.args(["lookup", "service", service, "username", account]);
+    Some(command)
+}
+
+/// Returns the command line printing the password of `account` of `service` stored in the
+/// login Keychain.
+#[cfg(target_os = "macos")]
+#[allow(clippy::unnecessary_wraps)]
+fn lookup(service: &str, account: &str) -> Option<Command> {
+    let mut command = Command::new("security");
+    command.args(["find-generic-password", "-s", service, "-a", account, "-w"]);
+    Some(command)
+}
+
+/// The Windows Credential Manager has no command line able to read passwords back.
+#[cfg(not(unix))]
+const fn lookup(_service: &str, _account: &str) -> Option<Command> {
+    None
+}
+
+/// Runs `lookup`, returning the password it prints.
+fn read(mut lookup: Command) -> Result<String, String> {
+    let program = lookup.get_program().to_string_lossy().into_owned();
+    let output = lookup
+        .output()
+        .map_err(|err| format!("can't run `{program}`: {err}"))?;
+    let printed = String::from_utf8_lossy(&output.stdout);
+    let password = printed.strip_suffix('\n').unwrap_or(&printed);
+    if !output.status.success() || password.is_empty() {
+        let reason = String::from_utf8_lossy(&output.stderr);
+        return Err(if reason.trim().is_empty() {
+            "no such password in the keyring".to_owned()
+        } else {
+            reason.trim().to_owned()
+        });
+    }
+    Ok(password.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod parse {
+        use super::super::parse;
+
+        #[test]
+        fn service_and_account() {
+            assert_eq!(parse("myapp/api_token"), Ok(("myapp", "api_token")));
+            assert_eq!(parse("myapp/team/token"), Ok(("myapp", "team/token")));
+        }
+
+        #[test]
+        fn malformed() {
+            let expected = Err("expected `keyring://<service>/<account>`".to_owned());
+
+            assert_eq!(parse("myapp"), expected);
+            assert_eq!(parse("/api_token"), expected);
+            assert_eq!(parse("myapp/"), expected);
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod keyring_references {
+        use crate::Error;
+        use crate::Loader;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Config {
+            #[allow(dead_code)]
+            password: String,
+        }
+
+        #[test]
+        fn only_memoized_unresolved() {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            let reference = "keyring://nickelodeon-test/missing";
+            std::fs::write(&config, format!(r#"{{ password = "{reference}" }}"#)).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .memoize(true)
+                .cache_dir(cache.path().to_path_buf())
+                .keyring_references();
+
+            for _attempt in 0..2 {
+                let result = loader.load::<Config>();
+
+                assert!(matches!(
+                    result,
+                    Err(Error::UnresolvedReference(path, unresolved, _reason))
+                        if path == "password" && unresolved == reference
+                ));
+            }
+            // Whether secrets would be deserialized from it isn't known yet.
+            assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 0);
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod read {
+        use super::super::read;
+        use crate::fake_command::fake_command;
+        use std::process::Command;
+
+        /// Returns a fake lookup command line running `script`.
+        fn lookup(script: &str) -> Command {
+            fake_command("secret-tool", script)
+        }
+
+        #[test]
+        fn found() {
+            assert_eq!(read(lookup("echo ' hunter2 '")), Ok(" hunter2 ".to_owned()));
+        }
+
+        #[test]
+        fn missing() {
+            assert_eq!(
+                read(lookup("exit 1")),
+                Err("no such password in the keyring".to_owned())
+            );
+            assert_eq!(
+                read(lookup("echo 'The keychain is locked.' >&2; exit 36")),
+                Err("The keychain is locked.".to_owned())
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::Loader;
+use crate::Result;
+use serde::de::DeserializeOwned;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
+
+/// A configuration loaded on first access, meant to be kept in a `static`, so small
+/// applications can read it from anywhere without passing it around.
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, Default)]
+/// # struct MyConfig { verbose: bool }
+/// static CONFIG: nickelodeon::LazyConfig<MyConfig> = nickelodeon::LazyConfig::new("my_app");
+///
+/// fn log(message: &str) {
+///     if CONFIG.get().verbose {
+///         eprintln!("{message}");
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LazyConfig<T> {
+    setup: Setup,
+    config: OnceLock<T>,
+    loading: Mutex<()>,
+}
+
+/// How a [`LazyConfig`] builds its [`Loader`].
+#[derive(Debug, Clone, Copy)]
+enum Setup {
+    App(&'static str),
+    Loader(fn() -> Loader),
+}
+
+impl<T> LazyConfig<T> {
+    /// Creates a configuration loaded like [`crate::load_configuration`] does for the
+    /// application with the codename `app`.
+    #[must_use]
+    pub const fn new(app: &'static str) -> Self {
+        Self::with_setup(Setup::App(app))
+    }
+
+    /// Creates a configuration loaded by the [`Loader`] built by `loader`, to tweak how it
+    /// is loaded.
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, Default)]
+    /// # struct MyConfig {}
+    /// static CONFIG: nickelodeon::LazyConfig<MyConfig> = nickelodeon::LazyConfig::with_loader(|| {
+    ///     nickelodeon::Loader::new("my_app").required(true)
+    /// });
+    /// ```
+    #[must_use]
+    pub const fn with_loader(loader: fn() -> Loader) -> Self {
+        Self::with_setup(Setup::Loader(loader))
+    }
+
+    const fn with_setup(setup: Setup) -> Self {
+        Self {
+            setup,
+            config: OnceLock::new(),
+            loading: Mutex::new(()),
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Default> LazyConfig<T> {
+    /// Returns the configuration, loading it on the first call.
+    ///
+    /// Nickel diagnostics are printed to `stderr` and the process exits if the
+    /// configuration can't be loaded, like [`crate::load_configuration`] does. Use
+    /// [`LazyConfig::try_get`] to handle the errors yourself.
+    #[must_use]
+    #[allow(clippy::exit)]
+    pub fn get(&self) -> &T {
+        self.try_get()
+            .unwrap_or_else(|err| std::process::exit(err.exit_code()))
+    }
+
+    /// Returns the configuration, loading it on the first call.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the configuration can't be loaded, like [`Loader::load`]. The
+    /// configuration is then loaded again by the next call.
+    pub fn try_get(&self) -> Result<&T> {
+        if let Some(config) = self.config.get() {
+            return Ok(config);
+        }
+        let _loading = self.loading.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(config) = self.config.get() {
+            return Ok(config);
+        }
+        let loader = match self.setup {
+            Setup::App(app) => Loader::new(app),
+            Setup::Loader(loader) => loader(),
+        };
+        let config = loader.load()?;
+        Ok(self.config.get_or_init(|| config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod try_get {
+        use super::super::LazyConfig;
+        use crate::Error;
+        use crate::Loader;
+        use std::fs;
+        use std::path::PathBuf;
+
+        #[derive(serde::Deserialize, Debug, Default, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        static MISSING: LazyConfig<Server> = LazyConfig::new("this_app_does_not_exist");
+
+        fn path() -> PathBuf {
+            std::env::temp_dir().join(format!("nickelodeon_lazy_{}.ncl", std::process::id()))
+        }
+
+        #[test]
+        fn no_configuration() {
+            assert_eq!(MISSING.try_get().unwrap(), &Server::default());
+        }
+
+        #[test]
+        fn retried_after_errors() {
+            let config: LazyConfig<Server> = LazyConfig::with_loader(|| {
+                Loader::new("nickelodeon_test")
+                    .config_path_from_flag(Some(path()))
+                    .diagnostics(std::io::sink())
+            });
+            fs::write(path(), r#"{ port = "80" }"#).unwrap();
+
+            let invalid = config.try_get().map(|server| server.port);
+            fs::write(path(), "{ port = 80 }").unwrap();
+            let valid = config.try_get().map(|server| server.port);
+            fs::write(path(), "{ port = 8080 }").unwrap();
+            let loaded_once = config.try_get().map(|server| server.port);
+            fs::remove_file(path()).unwrap();
+
+            assert!(matches!(invalid, Err(Error::RustDeserializationError(..))));
+            assert_eq!(valid, Ok(80));
+            assert_eq!(loaded_once, Ok(80));
+        }
+    }
+}
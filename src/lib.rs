@@ -10,54 +10,535 @@
 #![allow(clippy::std_instead_of_core)]
 #![allow(clippy::missing_docs_in_private_items)]
 #![allow(clippy::question_mark_used)]
+#![allow(clippy::allow_attributes)]
+#![allow(clippy::allow_attributes_without_reason)]
+#![allow(clippy::absolute_paths)]
+#![allow(clippy::arbitrary_source_item_ordering)]
+#![allow(clippy::std_instead_of_alloc)]
+#![allow(clippy::pub_use)]
+#![allow(clippy::pub_with_shorthand)]
+#![allow(clippy::redundant_pub_crate)]
+#![allow(clippy::missing_trait_methods)]
+#![allow(clippy::result_large_err)]
+#![allow(clippy::single_call_fn)]
+#![allow(clippy::pattern_type_mismatch)]
+#![allow(clippy::separated_literal_suffix)]
+#![allow(clippy::default_numeric_fallback)]
+#![allow(clippy::self_named_module_files)]
+
+#[cfg(feature = "age")]
+mod age;
+mod audit;
+mod blame;
+pub mod build;
+mod cancel;
+mod changes;
+#[cfg(feature = "clap")]
+pub mod clap;
+mod contract;
+mod daemon;
+mod deadline;
+mod deprecation;
+mod diagnostic;
+mod disk_cache;
+mod dotenv;
+mod edit;
+#[cfg(all(
+    test,
+    unix,
+    any(
+        feature = "age",
+        feature = "keyring",
+        feature = "remote",
+        feature = "sops",
+        feature = "vault"
+    )
+))]
+mod fake_command;
+mod field_error;
+#[cfg(feature = "figment")]
+pub mod figment;
+mod hangup;
+mod host;
+mod imports;
+mod json_schema;
+#[cfg(feature = "keyring")]
+mod keyring;
+mod lazy;
+mod limits;
+mod loader;
+mod memo;
+mod messages;
+mod metrics;
+#[cfg(feature = "tower")]
+mod middleware;
+mod migrate;
+mod mount;
+mod offload;
+pub mod parse;
+mod path;
+mod permissions;
+mod prelude;
+mod program;
+mod provenance;
+mod references;
+#[cfg(feature = "remote")]
+mod remote;
+mod render;
+mod report;
+#[cfg(feature = "schemars")]
+mod sample;
+mod save;
+mod schema;
+mod secret;
+mod sections;
+mod serializer;
+mod shared;
+#[cfg(feature = "sops")]
+mod sops;
+mod subscription;
+mod syntax;
+mod trace;
+mod value;
+#[cfg(feature = "vault")]
+mod vault;
+mod watch;
+#[cfg(feature = "web")]
+mod web;
+
+pub use audit::ReloadRecord;
+pub use cancel::CancellationToken;
+pub use changes::Change;
+pub use changes::ChangeSet;
+pub use contract::contract_for;
+pub use daemon::run_with_config;
+pub use daemon::Changed;
+pub use daemon::ConfigEvents;
+pub use diagnostic::Diagnostic;
+pub use diagnostic::Location;
+pub use diagnostic::Severity;
+pub use diagnostic::Span;
+pub use field_error::FieldError;
+pub use host::HostFacts;
+pub use imports::ImportPolicy;
+pub use json_schema::contract_from_json_schema;
+#[cfg(feature = "schemars")]
+pub use json_schema::json_schema_for;
+pub use lazy::LazyConfig;
+pub use limits::Limit;
+pub use limits::Limits;
+pub use loader::Loader;
+pub use memo::forget_memoized;
+pub use messages::English;
+pub use messages::Message;
+pub use messages::Messages;
+pub use metrics::ReloadCounters;
+pub use metrics::ReloadMetrics;
+#[cfg(feature = "tower")]
+pub use middleware::SnapshotLayer;
+#[cfg(feature = "tower")]
+pub use middleware::SnapshotService;
+pub use migrate::migrate_to_nickel;
+#[cfg(feature = "macros")]
+pub use nickelodeon_macros::nickel_config;
+pub use offload::Loading;
+pub use offload::Offload;
+pub use offload::SpawnThread;
+pub use path::ExpandedPathBuf;
+pub use path::ResolvedPathBuf;
+pub use permissions::PermissionCheck;
+pub use program::ProgramHandle;
+pub use provenance::Origin;
+pub use provenance::Provenance;
+pub use provenance::Source;
+pub use report::LoadReport;
+#[cfg(feature = "schemars")]
+pub use sample::sample_config;
+pub use save::save_configuration;
+pub use save::set_field;
+pub use save::Saver;
+pub use secret::Secret;
+pub use sections::Sections;
+pub use serializer::default_config_source;
+pub use serializer::to_nickel_string;
+pub use shared::SharedConfig;
+pub use shared::Snapshot;
+pub use subscription::Subscription;
+pub use value::Value;
+pub use watch::Event;
+pub use watch::Failure;
+pub use watch::Update;
+pub use watch::Watcher;
+#[cfg(feature = "web")]
+pub use web::app_state;
 
 use config_finder::ConfigDirs;
-use nickel_lang_core::error::EvalError;
-use nickel_lang_core::eval::cache::CacheImpl;
-use nickel_lang_core::program::Program;
-use nickel_lang_core::term::RichTerm;
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
-/// # Errors
+/// Locates, evaluates and deserializes the configuration of the application with the
+/// codename `app`, returning `T::default()` when no configuration file is found.
 ///
-/// Will return `Err` if the found config file can't be read, evaluated or if it
-/// doesn't match the deserialization contract for `T`
-pub fn load_configuration<'de, T: Deserialize<'de> + Default>(
+/// Nickel diagnostics are printed to `stderr` and the process exits if the found config
+/// file can't be read, evaluated or if it doesn't match the deserialization contract for
+/// `T`. Use a [`Loader`] to handle those errors yourself.
+#[must_use]
+#[allow(clippy::exit)]
+pub fn load_configuration<T: DeserializeOwned + Default>(
     app: &str,
     config_path_from_flag: Option<PathBuf>,
 ) -> T {
-    config_path_from_flag
-        .or_else(|| first_existing_config(app))
-        .map_or_else(|| T::default(), |path| load(path))
+    Loader::new(app)
+        .config_path_from_flag(config_path_from_flag)
+        .load()
+        .unwrap_or_else(|err| std::process::exit(err.exit_code()))
+}
+
+/// Loads the configuration of the application with the codename `app`, and loads it again
+/// every time it changes, checking its files every second. See [`Loader::watch`].
+///
+/// Nickel diagnostics, including the ones of the configurations that fail to load and are
+/// skipped, are printed to `stderr`.
+#[must_use]
+pub fn watch_configuration<T>(app: &str) -> Watcher<T>
+where
+    T: DeserializeOwned + Serialize + Default + Send + 'static,
+{
+    Loader::new(app).watch(std::time::Duration::from_secs(1))
+}
+
+/// Loads only the field at `path` (a dotted path, like `server.tls.cert_path`) of the
+/// configuration of the application with the codename `app`.
+///
+/// Returns `None` when no configuration file is found. See [`Loader::load_field`].
+///
+/// # Errors
+///
+/// Will return `Err` if the found config file can't be read or evaluated, if it has no
+/// field at `path`, or if the field doesn't match the deserialization contract for `T`.
+pub fn load_field<T: DeserializeOwned>(app: &str, path: &str) -> Result<Option<T>> {
+    Loader::new(app).load_field(path)
+}
+
+/// Loads the configuration of the application with the codename `app` without knowing its
+/// shape, as a JSON value. See [`Loader::load_dynamic`].
+///
+/// # Errors
+///
+/// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+/// values JSON can't represent.
+pub fn load_dynamic(app: &str) -> Result<serde_json::Value> {
+    Loader::new(app).load_dynamic()
+}
+
+/// Returns the whole configuration of the application with the codename `app` as JSON, or
+/// an empty object when no configuration file is found. See [`Loader::export_json`].
+///
+/// ```no_run
+/// # fn main() -> nickelodeon::Result<()> {
+/// println!("{:#}", nickelodeon::export_json("my_app")?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if the found config file can't be read or evaluated.
+pub fn export_json(app: &str) -> Result<serde_json::Value> {
+    Loader::new(app).export_json()
+}
+
+/// Returns the whole configuration of the application with the codename `app` as a YAML
+/// document. See [`Loader::export_yaml`].
+///
+/// # Errors
+///
+/// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+/// values YAML can't represent.
+#[cfg(feature = "yaml")]
+pub fn export_yaml(app: &str) -> Result<String> {
+    Loader::new(app).export_yaml()
+}
+
+/// Returns the whole configuration of the application with the codename `app` as a TOML
+/// document. See [`Loader::export_toml`].
+///
+/// # Errors
+///
+/// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+/// values TOML can't represent.
+#[cfg(feature = "toml")]
+pub fn export_toml(app: &str) -> Result<String> {
+    Loader::new(app).export_toml()
 }
 
 /// A specialized [`Result`] type for nickelodeon operations.
 ///
 /// This type is used in [`nickelodeon`] for reporting the location,
 /// loading, evaluation and deserialization of configuration files
-/// written in Nickel
+/// written in Nickel.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Describes everything that can go wrong loading [`ConfigFileReadingError`],
 /// evaluating [`NickelEvaluationError`] or deserializing [`RustDeserializationError`]
-/// Nickel configuration files
+/// Nickel configuration files.
 #[allow(clippy::exhaustive_enums)]
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
-    /// Something went wrong reading the file
+    /// Something went wrong reading the file.
     ConfigFileReadingError(String),
 
-    /// Something went wrong evaluating the nickel program (i.e. running the nickel code)
-    NickelEvaluationError(nickel_lang_core::error::Error),
+    /// The configuration file at the given path is larger than the given
+    /// [`Loader::max_file_size`], so it wasn't read.
+    ConfigTooLarge(PathBuf, u64),
+
+    /// No configuration file was found in any of the listed locations. Only returned by
+    /// [`Loader::required`] loaders.
+    ConfigNotFound(Vec<PathBuf>),
+
+    /// The configuration file at the given path could have been tampered with by another
+    /// user, for the given reason. Only returned when [`Loader::permission_check`] is set
+    /// to [`PermissionCheck::Deny`].
+    InsecurePermissions(PathBuf, String),
+
+    /// Both a `config.ncl` and a `config.nickel` exist in the same directory. Only returned
+    /// by [`Loader::strict_ambiguity`] loaders.
+    AmbiguousConfig(PathBuf, PathBuf),
+
+    /// The configuration file at the first path imports the second one (as written in the
+    /// file), which the [`Loader::imports`] policy doesn't allow.
+    ForbiddenImport(PathBuf, PathBuf),
+
+    /// Something went wrong evaluating the nickel program (i.e. running the nickel code).
+    /// Carries the [`Diagnostic`]s describing what went wrong.
+    NickelEvaluationError(nickel_lang_core::error::Error, Vec<Diagnostic>),
+
+    /// Nickel panicked while evaluating the file at the given path, with the given panic
+    /// message. Always a bug in Nickel, caught to keep the application running.
+    EvaluationPanicked(PathBuf, String),
+
+    /// The evaluation of the configuration file at the given path didn't finish within the
+    /// given [`Loader::timeout`].
+    EvaluationTimeout(PathBuf, std::time::Duration),
+
+    /// The evaluation of the configuration file at the given path reached one of the
+    /// [`Loader::limits`].
+    LimitExceeded(PathBuf, Limit),
 
-    /// Something went wrong converting the resulting nickel data into the requested shape
-    RustDeserializationError(nickel_lang_core::deserialize::RustDeserializationError),
+    /// The load was cancelled through its [`Loader::cancellation`] token.
+    Cancelled,
+
+    /// Something went wrong converting the resulting nickel data into the requested shape.
+    /// Carries the [`Diagnostic`]s describing what went wrong.
+    RustDeserializationError(
+        nickel_lang_core::deserialize::RustDeserializationError,
+        Vec<Diagnostic>,
+    ),
+
+    /// Some fields of the configuration don't match the shape of the requested type. Only
+    /// returned when [`Loader::collect_all_errors`] is enabled, in which case every
+    /// mismatched field is listed.
+    InvalidFields(Vec<FieldError>),
+
+    /// A value couldn't be written as Nickel source, for the given reason.
+    SerializationError(String),
+
+    /// Something went wrong writing the configuration file.
+    ConfigFileWritingError(String),
+
+    /// The field at the given dotted path holds the given reference (e.g.
+    /// `vault://secret/data/app#password`), which couldn't be resolved for the given reason.
+    /// Only returned for the schemes registered with [`Loader::resolve_references`].
+    UnresolvedReference(String, String, String),
 }
 
-/// Given a base path, returns the two possible names the configuration file might have
+impl Error {
+    /// Returns the structured [`Diagnostic`]s describing this error, ready to be shown in
+    /// an application specific error UI.
+    #[must_use]
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics_in(&English)
+    }
+
+    /// Same as [`Error::diagnostics`], but the messages produced by nickelodeon itself are
+    /// built by `messages`.
+    #[must_use]
+    pub fn diagnostics_in(&self, messages: &dyn Messages) -> Vec<Diagnostic> {
+        match self {
+            Self::ConfigFileReadingError(message) | Self::ConfigFileWritingError(message) => {
+                vec![Diagnostic::error(message.clone())]
+            }
+            Self::ConfigTooLarge(path, max) => vec![Diagnostic {
+                path: Some(path.clone()),
+                ..Diagnostic::error(messages.message(&Message::TooLarge(*max)))
+            }],
+            Self::ConfigNotFound(searched) => vec![Diagnostic {
+                notes: searched
+                    .iter()
+                    .map(|path| messages.message(&Message::Searched(path)))
+                    .collect(),
+                ..Diagnostic::error(messages.message(&Message::ConfigNotFound))
+            }],
+            Self::InsecurePermissions(path, reason) => vec![Diagnostic {
+                path: Some(path.clone()),
+                ..Diagnostic::error(reason.clone())
+            }],
+            Self::AmbiguousConfig(used, other) => vec![Diagnostic {
+                path: Some(used.clone()),
+                ..Diagnostic::error(messages.message(&Message::Ambiguous { used, other }))
+            }],
+            Self::ForbiddenImport(file, import) => vec![Diagnostic {
+                path: Some(file.clone()),
+                ..Diagnostic::error(messages.message(&Message::ForbiddenImport { file, import }))
+            }],
+            Self::EvaluationPanicked(path, reason) => vec![Diagnostic {
+                path: Some(path.clone()),
+                severity: Severity::Bug,
+                ..Diagnostic::error(messages.message(&Message::Panicked(reason)))
+            }],
+            Self::EvaluationTimeout(path, timeout) => vec![Diagnostic {
+                path: Some(path.clone()),
+                ..Diagnostic::error(messages.message(&Message::TimedOut(*timeout)))
+            }],
+            Self::LimitExceeded(path, limit) => vec![Diagnostic {
+                path: Some(path.clone()),
+                ..Diagnostic::error(messages.message(&Message::LimitExceeded(*limit)))
+            }],
+            Self::Cancelled => vec![Diagnostic::error(messages.message(&Message::Cancelled))],
+            Self::NickelEvaluationError(_, diagnostics)
+            | Self::RustDeserializationError(_, diagnostics) => diagnostics.clone(),
+            Self::InvalidFields(errors) => errors
+                .iter()
+                .map(|error| Diagnostic::error(error.to_string()))
+                .collect(),
+            Self::SerializationError(reason) => vec![Diagnostic::error(reason.clone())],
+            Self::UnresolvedReference(field, reference, reason) => vec![Diagnostic {
+                field: Some(field.clone()),
+                ..Diagnostic::error(
+                    messages.message(&Message::UnresolvedReference { reference, reason }),
+                )
+            }],
+        }
+    }
+
+    /// Returns a stable, `snake_case`, identifier of the kind of error (e.g.
+    /// `config_not_found`), meant for machines.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::ConfigFileReadingError(_) => "config_file_reading_error",
+            Self::ConfigFileWritingError(_) => "config_file_writing_error",
+            Self::ConfigTooLarge(..) => "config_too_large",
+            Self::ConfigNotFound(_) => "config_not_found",
+            Self::InsecurePermissions(..) => "insecure_permissions",
+            Self::AmbiguousConfig(..) => "ambiguous_config",
+            Self::ForbiddenImport(..) => "forbidden_import",
+            Self::NickelEvaluationError(..) => "nickel_evaluation_error",
+            Self::EvaluationPanicked(..) => "evaluation_panicked",
+            Self::EvaluationTimeout(..) => "evaluation_timeout",
+            Self::LimitExceeded(..) => "limit_exceeded",
+            Self::Cancelled => "cancelled",
+            Self::RustDeserializationError(..) => "rust_deserialization_error",
+            Self::InvalidFields(_) => "invalid_fields",
+            Self::SerializationError(_) => "serialization_error",
+            Self::UnresolvedReference(..) => "unresolved_reference",
+        }
+    }
+
+    /// Returns a machine readable version of this error, for GUI frontends and CI systems,
+    /// with the shape:
+    ///
+    /// ```json
+    /// {
+    ///   "kind": "nickel_evaluation_error",
+    ///   "diagnostics": [
+    ///     {
+    ///       "path": "/etc/app/config.ncl",
+    ///       "span": { "start": { "line": 3, "column": 5 }, "end": { "line": 3, "column": 9 } },
+    ///       "field": "server.port",
+    ///       "severity": "error",
+    ///       "message": "contract broken by a value",
+    ///       "notes": []
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// `path`, `span` and `field` are `null` when unknown.
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "kind": self.kind(),
+            "diagnostics": self.diagnostics(),
+        })
+    }
+
+    /// Returns the exit code a CLI application should use when failing because of this
+    /// error:
+    ///
+    /// - `1` when the configuration file can't be read or written, is too large, is insecure
+    ///   or imports a forbidden file, or when a reference it holds can't be resolved.
+    /// - `2` when the Nickel program fails to evaluate (e.g. parse errors or broken
+    ///   contracts), Nickel panics or the evaluation times out or reaches a limit.
+    /// - `3` when the configuration doesn't match the requested type, or a value can't be
+    ///   written as Nickel.
+    /// - `4` when no configuration file, or an ambiguous one, is found.
+    /// - `130` when the load is cancelled, like a process interrupted by Ctrl-C.
+    #[must_use]
+    pub const fn exit_code(&self) -> i32 {
+        match self {
+            Self::ConfigFileReadingError(_)
+            | Self::ConfigFileWritingError(_)
+            | Self::ConfigTooLarge(..)
+            | Self::InsecurePermissions(..)
+            | Self::ForbiddenImport(..)
+            | Self::UnresolvedReference(..) => 1,
+            Self::NickelEvaluationError(..)
+            | Self::EvaluationPanicked(..)
+            | Self::EvaluationTimeout(..)
+            | Self::LimitExceeded(..) => 2,
+            Self::RustDeserializationError(..)
+            | Self::InvalidFields(_)
+            | Self::SerializationError(_) => 3,
+            Self::ConfigNotFound(_) | Self::AmbiguousConfig(..) => 4,
+            Self::Cancelled => 130,
+        }
+    }
+
+    /// Prints the [`Error::render`]ed error to `stderr` and exits the process with its
+    /// [`Error::exit_code`].
+    #[allow(clippy::exit)]
+    #[allow(clippy::print_stderr)]
+    pub fn report_and_exit(&self) -> ! {
+        eprint!("{}", self.render());
+        std::process::exit(self.exit_code())
+    }
+
+    /// Renders the [`Error::diagnostics`] of this error for a terminal, ready to be printed
+    /// to `stderr`.
+    ///
+    /// ANSI colors are used when `stderr` is a terminal, unless the `NO_COLOR` environment
+    /// variable is set.
+    #[must_use]
+    pub fn render(&self) -> String {
+        self.render_colored(render::use_color())
+    }
+
+    /// Same as [`Error::render`], but colors are used if, and only if, `color` is set.
+    #[must_use]
+    pub fn render_colored(&self, color: bool) -> String {
+        render::render(&self.diagnostics(), color)
+    }
+}
+
+/// Given a base path, returns the two possible names the configuration file might have.
 fn expand_names(mut pb1: PathBuf) -> Vec<PathBuf> {
     let mut pb2 = pb1.clone();
     pb1.push("config.ncl");
@@ -66,88 +547,206 @@ fn expand_names(mut pb1: PathBuf) -> Vec<PathBuf> {
 }
 
 /// Given a base path, and an application codename, returns the two possible locations (e.g. `app/config.ncl` and
-/// `app/config.nickel`) where the configuration file might be located
+/// `app/config.nickel`) where the configuration file might be located.
 fn expand_path_and_names(app: &str, pb0: &Path) -> Vec<PathBuf> {
-    let mut pb1 = pb0.to_path_buf();
-    pb1.push(app);
-    expand_names(pb1)
+    expand_names(pb0.join(app))
+}
+
+/// The names of the configuration files in other formats, enabled by the feature of the
+/// same name, looked for after the Nickel ones: apps moving their users to Nickel can read
+/// both for a while. An age-encrypted Nickel file comes first, right after the plain ones.
+/// JSON comes last, as it's meant for the files generated by other tools (e.g.
+/// orchestrators), which the ones written by hand should override.
+const FALLBACK_NAMES: &[&str] = &[
+    #[cfg(feature = "age")]
+    "config.ncl.age",
+    #[cfg(feature = "toml")]
+    "config.toml",
+    #[cfg(feature = "yaml")]
+    "config.yaml",
+    #[cfg(feature = "yaml")]
+    "config.yml",
+    #[cfg(feature = "json")]
+    "config.json",
+];
+
+/// Adds the [`FALLBACK_NAMES`], next to the configuration files in `names`, as the last
+/// ones to look for.
+fn with_fallbacks(mut names: Vec<PathBuf>) -> Vec<PathBuf> {
+    if let Some(first) = names.first().cloned() {
+        names.extend(FALLBACK_NAMES.iter().map(|name| first.with_file_name(name)));
+    }
+    names
+}
+
+/// Tells whether the configuration file at `path` is in YAML, whose support is deprecated.
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml")
+}
+
+/// The environment variable systemd sets to the directory holding the credentials of the
+/// service (`LoadCredential=`, `SetCredential=`...).
+const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+/// Returns the directory holding the systemd credentials of the service, if any.
+fn credentials_directory() -> Option<PathBuf> {
+    std::env::var_os(CREDENTIALS_DIRECTORY)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
 }
 
 fn all_location_candidates(app: &str) -> Vec<PathBuf> {
-    all_location_candidates_impl(std::env::current_dir, app)
+    all_location_candidates_impl(std::env::current_dir, credentials_directory(), app)
 }
 
-fn all_location_candidates_impl<F>(pwd: F, app: &str) -> Vec<PathBuf>
+/// The configuration files given as systemd credentials (e.g.
+/// `LoadCredential=config.ncl:/etc/secrets/app.ncl`) come first, as they are handed to the
+/// service specifically.
+fn all_location_candidates_impl<F>(pwd: F, credentials: Option<PathBuf>, app: &str) -> Vec<PathBuf>
 where
     F: Fn() -> io::Result<PathBuf>,
 {
-    let mut buffer: Vec<PathBuf> = pwd().map_or_else(
-        |_| Vec::new(),
-        |mut pwd_base| {
-            pwd_base.push(format!(".{app}"));
-            expand_names(pwd_base)
-        },
-    );
+    let mut buffer: Vec<PathBuf> = credentials
+        .map(|dir| with_fallbacks(expand_names(dir)))
+        .unwrap_or_default();
+
+    if let Ok(mut pwd_base) = pwd() {
+        pwd_base.push(format!(".{app}"));
+        buffer.extend(with_fallbacks(expand_names(pwd_base)));
+    }
 
-    buffer.append(
-        &mut ConfigDirs::empty()
+    buffer.extend(
+        ConfigDirs::empty()
             .add_platform_config_dir()
             .add_root_etc()
             .paths()
             .iter()
-            .flat_map(|pb0| expand_path_and_names(app, pb0))
-            .collect(),
+            .flat_map(|pb0| with_fallbacks(expand_path_and_names(app, pb0))),
     );
 
     buffer
 }
 
-/// Goes through all the locations that the configuration file for an app
-/// with the codename [`app`] could be located and return the full path of
-/// the first one that actually exist and is a file
-fn first_existing_config(app: &str) -> Option<PathBuf> {
-    first_existing_config_impl(|pb| pb.is_file(), all_location_candidates(app))
+/// Tells which kind of [`Source`] a configuration file of the app with the codename
+/// [`app`], found by [`first_existing_config`], is.
+fn source_of(app: &str, path: &Path) -> Source {
+    if credentials_directory().is_some_and(|dir| path.starts_with(dir)) {
+        return Source::System;
+    }
+    source_of_impl(std::env::current_dir, app, path)
+}
+
+fn source_of_impl<F>(pwd: F, app: &str, path: &Path) -> Source
+where
+    F: Fn() -> io::Result<PathBuf>,
+{
+    if pwd().is_ok_and(|pwd_base| path.starts_with(pwd_base.join(format!(".{app}")))) {
+        Source::Project
+    } else if path.starts_with("/etc") {
+        Source::System
+    } else {
+        Source::User
+    }
+}
+
+/// Goes through the `candidates` locations (see [`all_location_candidates`]) where the
+/// configuration file of an app could be located and return the full path of the first
+/// one that actually exist and is a file.
+fn first_existing_config(candidates: &[PathBuf]) -> Option<PathBuf> {
+    first_existing_config_impl(|pb| pb.is_file(), candidates)
 }
 
-/// Goes through all the locations that the configuration file for an app
-/// with the codename [`app`] could be located and return the full path of
-/// the first one that actually exist and is a file.
+/// How many candidates [`first_existing_config`] probes at once, at most.
+const PROBES: usize = 4;
+
+/// Goes through the `candidates` locations where the configuration file of an app could be
+/// located and return the full path of the first one that actually exist and is a file.
 ///
 /// This implementation uses the `P` predicate to decide if a path exists.
 /// This approach is used to facilitate testing.
-fn first_existing_config_impl<P>(is_file: P, candidates: Vec<PathBuf>) -> Option<PathBuf>
+///
+/// Candidates are probed concurrently by up to [`PROBES`] threads, as each probe can take a
+/// while on network home directories, but the first one in order still wins. Once one is
+/// found, the candidates after it are no longer probed.
+fn first_existing_config_impl<P>(is_file: P, candidates: &[PathBuf]) -> Option<PathBuf>
+where
+    P: Fn(&PathBuf) -> bool + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let first_found = AtomicUsize::new(usize::MAX);
+    std::thread::scope(|scope| {
+        for _probe in 0..PROBES.min(candidates.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(candidate) = candidates.get(index) else {
+                    break;
+                };
+                if index > first_found.load(Ordering::Relaxed) {
+                    break;
+                }
+                if is_file(candidate) {
+                    first_found.fetch_min(index, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    candidates.get(first_found.into_inner()).cloned()
+}
+
+/// Returns the `config.nickel` living next to the `config.ncl` at `path`, if there is one.
+/// Both being in the same directory is ambiguous, as it's easy to edit the wrong one.
+fn ambiguous_sibling(path: &Path) -> Option<PathBuf> {
+    (path.file_name()? == "config.ncl")
+        .then(|| path.with_file_name("config.nickel"))
+        .filter(|sibling| sibling.is_file())
+}
+
+/// Goes through all the `candidates` locations (see [`all_location_candidates`]) where the
+/// configuration file of an app could be located and returns the ones that actually exist
+/// and are files, in order: the first one is used, and the others are shadowed by it (see
+/// [`shadowed_configs`]).
+fn existing_configs(candidates: &[PathBuf]) -> Vec<PathBuf> {
+    existing_configs_impl(|pb| pb.is_file(), candidates)
+}
+
+/// Same as [`existing_configs`], using the `P` predicate to decide if a path exists.
+///
+/// Like [`first_existing_config_impl`], candidates are probed concurrently by up to
+/// [`PROBES`] threads, each of them once.
+fn existing_configs_impl<P>(is_file: P, candidates: &[PathBuf]) -> Vec<PathBuf>
 where
-    P: FnMut(&PathBuf) -> bool,
+    P: Fn(&PathBuf) -> bool + Sync,
 {
-    candidates.into_iter().find(is_file)
+    let next = AtomicUsize::new(0);
+    let found: Vec<AtomicBool> = candidates.iter().map(|_| AtomicBool::new(false)).collect();
+    std::thread::scope(|scope| {
+        for _probe in 0..PROBES.min(candidates.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let (Some(candidate), Some(exists)) = (candidates.get(index), found.get(index))
+                else {
+                    break;
+                };
+                exists.store(is_file(candidate), Ordering::Relaxed);
+            });
+        }
+    });
+    candidates
+        .iter()
+        .zip(found)
+        .filter_map(|(candidate, exists)| exists.into_inner().then(|| candidate.clone()))
+        .collect()
 }
 
-/// Loads, evaluates and deserializes the data in the file located at [`path`].
-fn load<'de, T: Deserialize<'de>>(path: PathBuf) -> T {
-    let mut program: Program<CacheImpl> = Program::new_from_file(path, std::io::stderr())
-        .unwrap_or_else(|err| {
-            eprintln!("Error when reading input: {err}");
-            std::process::exit(1)
-        });
-
-    let rt: RichTerm = program
-        .eval_full_for_export()
-        .map(RichTerm::from)
-        .unwrap_or_else(|err| {
-            program.report(err);
-            std::process::exit(2)
-        });
-
-    let pos = rt.pos.clone();
-
-    T::deserialize(rt).unwrap_or_else(|err| {
-        program.report(EvalError::DeserializationError(
-            String::from("nickel"),
-            format!("{err}"),
-            pos,
-        ));
-        std::process::exit(3)
-    })
+/// Returns the configuration files among the `existing` ones (see [`existing_configs`]),
+/// other than `used`, which are therefore ignored.
+fn shadowed_configs(existing: &[PathBuf], used: &Path) -> Vec<PathBuf> {
+    existing
+        .iter()
+        .filter(|candidate| *candidate != used)
+        .cloned()
+        .collect()
 }
 
 #[cfg(test)]
@@ -226,37 +825,39 @@ mod tests {
 
     #[cfg(test)]
     mod first_existing_config {
+        use super::super::all_location_candidates;
         use super::super::first_existing_config;
         use super::super::first_existing_config_impl;
+        use super::super::PROBES;
         use std::path::PathBuf;
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
 
         #[test]
         fn nothing_found() {
-            let result = first_existing_config("this_app_does_not_exist");
+            let candidates = all_location_candidates("this_app_does_not_exist");
+            let result = first_existing_config(&candidates);
             assert_eq!(result, None);
         }
 
         #[test]
         fn one_file_exists() {
-            fn is_file(path: &PathBuf) -> bool {
-                path.as_os_str().to_string_lossy().ends_with("_file")
-            }
+            let is_file = |path: &PathBuf| path.as_os_str().to_string_lossy().ends_with("_file");
 
             let candidates = vec![
                 PathBuf::from("file_is_not"),
                 PathBuf::from("the_actual_file"),
             ];
 
-            let result = first_existing_config_impl(is_file, candidates);
+            let result = first_existing_config_impl(is_file, &candidates);
 
             assert_eq!(result, Some(PathBuf::from("the_actual_file")));
         }
 
         #[test]
         fn first_file_found() {
-            fn is_file(path: &PathBuf) -> bool {
-                path.as_os_str().to_string_lossy().ends_with("_file")
-            }
+            let is_file = |path: &PathBuf| path.as_os_str().to_string_lossy().ends_with("_file");
 
             let candidates = vec![
                 PathBuf::from("file_is_not"),
@@ -264,9 +865,153 @@ mod tests {
                 PathBuf::from("not_the_first_file"),
             ];
 
-            let result = first_existing_config_impl(is_file, candidates);
+            let result = first_existing_config_impl(is_file, &candidates);
+
+            assert_eq!(result, Some(PathBuf::from("the_actual_file")));
+        }
+
+        #[test]
+        fn slow_first_file_still_wins() {
+            let is_file = |path: &PathBuf| {
+                if path == &PathBuf::from("slow_file") {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                path.as_os_str().to_string_lossy().ends_with("_file")
+            };
+
+            let candidates = vec![PathBuf::from("slow_file"), PathBuf::from("fast_file")];
+
+            let result = first_existing_config_impl(is_file, &candidates);
+
+            assert_eq!(result, Some(PathBuf::from("slow_file")));
+        }
+
+        #[test]
+        fn stops_once_found() {
+            let probed = AtomicUsize::new(0);
+            let is_file = |path: &PathBuf| {
+                probed.fetch_add(1, Ordering::Relaxed);
+                path.as_os_str().to_string_lossy().ends_with("_file")
+            };
+
+            let mut candidates = vec![PathBuf::from("the_actual_file")];
+            candidates.extend((0..100).map(|index| PathBuf::from(format!("not_probed_{index}"))));
+
+            let result = first_existing_config_impl(is_file, &candidates);
 
             assert_eq!(result, Some(PathBuf::from("the_actual_file")));
+            assert!(probed.into_inner() <= PROBES);
+        }
+    }
+
+    #[cfg(test)]
+    mod ambiguous_sibling {
+        use super::super::ambiguous_sibling;
+        use std::fs::File;
+        use tempfile::tempdir;
+
+        #[test]
+        fn both_extensions() {
+            let dir = tempdir().unwrap();
+            let ncl = dir.path().join("config.ncl");
+            let nickel = dir.path().join("config.nickel");
+            File::create(&ncl).unwrap();
+            File::create(&nickel).unwrap();
+
+            assert_eq!(ambiguous_sibling(&ncl), Some(nickel));
+        }
+
+        #[test]
+        fn single_file() {
+            let dir = tempdir().unwrap();
+            let ncl = dir.path().join("config.ncl");
+            File::create(&ncl).unwrap();
+
+            assert_eq!(ambiguous_sibling(&ncl), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod existing_configs {
+        use super::super::existing_configs_impl;
+        use std::path::PathBuf;
+        use std::sync::Mutex;
+
+        #[test]
+        fn each_probed_once() {
+            let probed = Mutex::new(Vec::new());
+            let is_file = |path: &PathBuf| {
+                probed.lock().unwrap().push(path.clone());
+                path.as_os_str().to_string_lossy().ends_with("_file")
+            };
+
+            let mut candidates: Vec<_> = (0..100)
+                .map(|index| PathBuf::from(format!("missing_{index}")))
+                .collect();
+            candidates.insert(50, PathBuf::from("shadowed_file"));
+            candidates.insert(10, PathBuf::from("the_actual_file"));
+
+            let result = existing_configs_impl(is_file, &candidates);
+            let mut probed_paths = probed.into_inner().unwrap();
+            probed_paths.sort();
+            candidates.sort();
+
+            assert_eq!(
+                result,
+                vec![
+                    PathBuf::from("the_actual_file"),
+                    PathBuf::from("shadowed_file")
+                ]
+            );
+            assert_eq!(probed_paths, candidates);
+        }
+    }
+
+    #[cfg(test)]
+    mod shadowed_configs {
+        use super::super::shadowed_configs;
+        use std::path::Path;
+        use std::path::PathBuf;
+
+        #[test]
+        fn lists_the_other_existing_files() {
+            let existing = vec![
+                PathBuf::from("the_actual_file"),
+                PathBuf::from("shadowed_file"),
+            ];
+
+            let result = shadowed_configs(&existing, Path::new("the_actual_file"));
+
+            assert_eq!(result, vec![PathBuf::from("shadowed_file")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod with_fallbacks {
+        use super::super::expand_names;
+        use super::super::with_fallbacks;
+        use std::path::PathBuf;
+
+        #[test]
+        fn nickel_comes_first() {
+            let result = with_fallbacks(expand_names(PathBuf::from("/tmp")));
+            let expected: Vec<PathBuf> = [
+                "/tmp/config.ncl",
+                "/tmp/config.nickel",
+                "/tmp/config.ncl.age",
+                "/tmp/config.toml",
+                "/tmp/config.yaml",
+                "/tmp/config.yml",
+                "/tmp/config.json",
+            ]
+            .into_iter()
+            .filter(|path| cfg!(feature = "age") || !path.ends_with("config.ncl.age"))
+            .filter(|path| cfg!(feature = "toml") || !path.ends_with("config.toml"))
+            .filter(|path| cfg!(feature = "yaml") || !path.contains("config.y"))
+            .filter(|path| cfg!(feature = "json") || !path.ends_with("config.json"))
+            .map(PathBuf::from)
+            .collect();
+            assert_eq!(result, expected);
         }
     }
 
@@ -278,6 +1023,7 @@ mod tests {
         use std::path::PathBuf;
 
         #[test]
+        #[allow(clippy::manual_assert)]
         fn works() {
             if cfg!(windows) {
                 // The logic that depends on the underlaying platform is implemented by
@@ -288,37 +1034,218 @@ mod tests {
             }
             std::env::set_var("HOME", "/home/testuser");
             std::env::remove_var("XDG_CONFIG_HOME");
-            fn pwd_mock() -> io::Result<PathBuf> {
-                Ok(PathBuf::from("/projects/project_folder"))
-            }
-            let result = all_location_candidates_impl(pwd_mock, "some_app");
-            let expected = vec![
-                PathBuf::from("/projects/project_folder/.some_app/config.ncl"),
-                PathBuf::from("/projects/project_folder/.some_app/config.nickel"),
-                PathBuf::from("/home/testuser/.config/some_app/config.ncl"),
-                PathBuf::from("/home/testuser/.config/some_app/config.nickel"),
-                PathBuf::from("/etc/some_app/config.ncl"),
-                PathBuf::from("/etc/some_app/config.nickel"),
-            ];
+            let pwd_mock =
+                || -> io::Result<PathBuf> { Ok(PathBuf::from("/projects/project_folder")) };
+            let result = all_location_candidates_impl(pwd_mock, None, "some_app");
+            let expected: Vec<PathBuf> = [
+                "/projects/project_folder/.some_app/config.ncl",
+                "/projects/project_folder/.some_app/config.nickel",
+                "/projects/project_folder/.some_app/config.ncl.age",
+                "/projects/project_folder/.some_app/config.toml",
+                "/projects/project_folder/.some_app/config.yaml",
+                "/projects/project_folder/.some_app/config.yml",
+                "/projects/project_folder/.some_app/config.json",
+                "/home/testuser/.config/some_app/config.ncl",
+                "/home/testuser/.config/some_app/config.nickel",
+                "/home/testuser/.config/some_app/config.ncl.age",
+                "/home/testuser/.config/some_app/config.toml",
+                "/home/testuser/.config/some_app/config.yaml",
+                "/home/testuser/.config/some_app/config.yml",
+                "/home/testuser/.config/some_app/config.json",
+                "/etc/some_app/config.ncl",
+                "/etc/some_app/config.nickel",
+                "/etc/some_app/config.ncl.age",
+                "/etc/some_app/config.toml",
+                "/etc/some_app/config.yaml",
+                "/etc/some_app/config.yml",
+                "/etc/some_app/config.json",
+            ]
+            .into_iter()
+            .filter(|path| cfg!(feature = "age") || !path.ends_with("config.ncl.age"))
+            .filter(|path| cfg!(feature = "toml") || !path.ends_with("config.toml"))
+            .filter(|path| cfg!(feature = "yaml") || !path.contains("config.y"))
+            .filter(|path| cfg!(feature = "json") || !path.ends_with("config.json"))
+            .map(PathBuf::from)
+            .collect();
             assert_eq!(result, expected);
         }
 
+        #[test]
+        fn credentials_come_first() {
+            let pwd_mock =
+                || -> io::Result<PathBuf> { Ok(PathBuf::from("/projects/project_folder")) };
+            let credentials = PathBuf::from("/run/credentials/some_app.service");
+            let result = all_location_candidates_impl(pwd_mock, Some(credentials), "some_app");
+
+            assert_eq!(
+                result.get(..2),
+                Some(
+                    &[
+                        PathBuf::from("/run/credentials/some_app.service/config.ncl"),
+                        PathBuf::from("/run/credentials/some_app.service/config.nickel"),
+                    ][..]
+                )
+            );
+            assert_eq!(
+                result.get(2 + super::super::FALLBACK_NAMES.len()),
+                Some(&PathBuf::from(
+                    "/projects/project_folder/.some_app/config.ncl"
+                ))
+            );
+        }
+
         #[test]
         fn wired_correctly() {
             std::env::set_var("HOME", "/home/testuser");
             std::env::remove_var("XDG_CONFIG_HOME");
+            std::env::remove_var("CREDENTIALS_DIRECTORY");
             let result = all_location_candidates("some_app");
-            let expected = 6;
+            let expected = 3 * (2 + super::super::FALLBACK_NAMES.len());
             assert_eq!(result.len(), expected);
         }
     }
 
+    #[cfg(test)]
+    mod exit_code {
+        use crate::Error;
+        use crate::FieldError;
+        use std::path::PathBuf;
+
+        #[test]
+        fn distinguishes_the_kind_of_failure() {
+            let reading = Error::ConfigFileReadingError("denied".to_owned());
+            let not_found = Error::ConfigNotFound(vec![PathBuf::from("/etc/app/config.ncl")]);
+            let invalid = Error::InvalidFields(vec![FieldError {
+                path: "port".to_owned(),
+                message: "missing field".to_owned(),
+            }]);
+
+            assert_eq!(reading.exit_code(), 1);
+            assert_eq!(invalid.exit_code(), 3);
+            assert_eq!(not_found.exit_code(), 4);
+        }
+    }
+
+    #[cfg(test)]
+    mod diagnostics_in {
+        use crate::Error;
+        use crate::Message;
+        use crate::Messages;
+        use std::path::PathBuf;
+
+        struct Catalan;
+
+        impl Messages for Catalan {
+            #[allow(clippy::wildcard_enum_match_arm)]
+            fn message(&self, message: &Message<'_>) -> String {
+                match message {
+                    Message::ConfigNotFound => "no s'ha trobat la configuraci\u{f3}".to_owned(),
+                    Message::Searched(path) => format!("s'ha cercat {}", path.display()),
+                    other => other.to_string(),
+                }
+            }
+        }
+
+        #[test]
+        fn translates_the_messages() {
+            let error = Error::ConfigNotFound(vec![PathBuf::from("/etc/app/config.ncl")]);
+
+            let result = error.diagnostics_in(&Catalan);
+
+            let diagnostic = result.first().unwrap();
+            assert_eq!(diagnostic.message, "no s'ha trobat la configuraci\u{f3}");
+            assert_eq!(diagnostic.notes, vec!["s'ha cercat /etc/app/config.ncl"]);
+        }
+
+        #[test]
+        fn defaults_to_english() {
+            let error = Error::ConfigNotFound(Vec::new());
+
+            let result = error.diagnostics();
+
+            assert_eq!(
+                result.first().unwrap().message,
+                "no configuration file found"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod to_json {
+        use crate::Error;
+        use serde_json::json;
+        use std::path::PathBuf;
+
+        #[test]
+        fn stable_shape() {
+            let error = Error::ConfigNotFound(vec![PathBuf::from("/etc/app/config.ncl")]);
+
+            let result = error.to_json();
+
+            let expected = json!({
+                "kind": "config_not_found",
+                "diagnostics": [{
+                    "path": null,
+                    "span": null,
+                    "field": null,
+                    "severity": "error",
+                    "message": "no configuration file found",
+                    "notes": ["searched /etc/app/config.ncl"],
+                }],
+            });
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[cfg(test)]
+    mod source_of {
+        use super::super::source_of_impl;
+        use crate::Source;
+        use std::io;
+        use std::path::Path;
+        use std::path::PathBuf;
+
+        #[test]
+        fn project() {
+            let path = Path::new("/home/testuser/.some_app/config.ncl");
+            let result = source_of_impl(
+                || -> io::Result<PathBuf> { Ok(PathBuf::from("/home/testuser")) },
+                "some_app",
+                path,
+            );
+            assert_eq!(result, Source::Project);
+        }
+
+        #[test]
+        fn user() {
+            let path = Path::new("/home/testuser/.config/some_app/config.ncl");
+            let result = source_of_impl(
+                || -> io::Result<PathBuf> { Ok(PathBuf::from("/home/testuser")) },
+                "some_app",
+                path,
+            );
+            assert_eq!(result, Source::User);
+        }
+
+        #[test]
+        fn system() {
+            let path = Path::new("/etc/some_app/config.ncl");
+            let result = source_of_impl(
+                || -> io::Result<PathBuf> { Ok(PathBuf::from("/home/testuser")) },
+                "some_app",
+                path,
+            );
+            assert_eq!(result, Source::System);
+        }
+    }
+
     #[cfg(test)]
     mod load {
         use crate::tests::TestConfiguration;
 
-        use super::super::load;
-        use std::io::Write;
+        use crate::loader::load;
+        use crate::loader::DiagnosticSink;
+        use std::io::Write as _;
         use tempfile::NamedTempFile;
 
         #[test]
@@ -334,9 +1261,10 @@ mod tests {
             ))
             .unwrap();
 
-            let result: TestConfiguration = load(ntf.path().to_path_buf());
+            let result: TestConfiguration =
+                load(ntf.path(), DiagnosticSink::new(std::io::sink())).unwrap();
             let expected = TestConfiguration {
-                test_value: "nick".to_string(),
+                test_value: "nick".to_owned(),
             };
 
             assert_eq!(result, expected);
@@ -349,10 +1277,11 @@ mod tests {
 
         use super::super::load_configuration;
         use std::fs::{create_dir_all, File};
-        use std::io::Write;
+        use std::io::Write as _;
         use tempfile::tempdir;
 
         #[test]
+        #[allow(clippy::manual_assert)]
         fn happy() {
             if cfg!(windows) {
                 // The logic that depends on the underlaying platform is implemented by
@@ -371,18 +1300,18 @@ mod tests {
             conf_file
                 .write_fmt(format_args!(
                     "{}",
-                    r##"
+                    r#"
                         {
                           test_value = "nick",
                         }
-                    "##
+                    "#
                 ))
                 .unwrap();
             std::env::set_var("XDG_CONFIG_HOME", home_config_path.to_str().unwrap());
 
             let result: TestConfiguration = load_configuration("some_app", None);
             let expected = TestConfiguration {
-                test_value: "nick".to_string(),
+                test_value: "nick".to_owned(),
             };
 
             assert_eq!(result, expected);
@@ -0,0 +1,267 @@
+use crate::cancel::Cancelled;
+use crate::deadline::Deadline;
+use crate::deadline::Expired;
+use crate::CancellationToken;
+use nickel_lang_core::eval::cache::BlackholedError;
+use nickel_lang_core::eval::cache::Cache;
+use nickel_lang_core::eval::cache::CacheImpl;
+use nickel_lang_core::eval::cache::CacheIndex;
+use nickel_lang_core::eval::Closure;
+use nickel_lang_core::eval::Environment;
+use nickel_lang_core::eval::IdentKind;
+use nickel_lang_core::identifier::Ident;
+use nickel_lang_core::term::record::FieldDeps;
+use nickel_lang_core::term::BindingType;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Traverse as _;
+use nickel_lang_core::term::TraverseOrder;
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::fmt;
+use std::panic;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Bounds on the resources the evaluation of a configuration can use. Unlimited by default.
+///
+/// They keep a broken or malicious configuration from exhausting the memory of a long
+/// running application, e.g. during a reload.
+///
+/// ```
+/// let limits = nickelodeon::Limits::default()
+///     .max_steps(10_000_000)
+///     .max_allocations(1_000_000);
+/// ```
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    steps: Option<u64>,
+    allocations: Option<u64>,
+    size: Option<u64>,
+}
+
+impl Limits {
+    /// Caps the number of values the evaluation can look up, which bounds how long (and
+    /// how deep) it can recurse.
+    #[must_use]
+    pub const fn max_steps(mut self, steps: u64) -> Self {
+        self.steps = Some(steps);
+        self
+    }
+
+    /// Caps the number of values the evaluation can allocate, roughly bounding the memory
+    /// it uses.
+    #[must_use]
+    pub const fn max_allocations(mut self, allocations: u64) -> Self {
+        self.allocations = Some(allocations);
+        self
+    }
+
+    /// Caps the number of nodes (records, fields, arrays, strings, numbers...) of the
+    /// evaluated configuration.
+    #[must_use]
+    pub const fn max_size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+/// A [`Limits`] bound reached by the evaluation of a configuration, with its value.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// [`Limits::max_steps`].
+    Steps(u64),
+
+    /// [`Limits::max_allocations`].
+    Allocations(u64),
+
+    /// [`Limits::max_size`].
+    Size(u64),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Steps(steps) => write!(f, "{steps} steps"),
+            Self::Allocations(allocations) => write!(f, "{allocations} allocations"),
+            Self::Size(size) => write!(f, "{size} nodes"),
+        }
+    }
+}
+
+/// Returns the [`Limit`] that the evaluated term `rt` exceeds, if any.
+pub(crate) fn oversized(rt: &RichTerm, limits: Limits) -> Option<Limit> {
+    let max = limits.size?;
+    let mut size: u64 = 0;
+    let _traversed: Result<RichTerm, Infallible> = rt.clone().traverse(
+        &|term: RichTerm, count: &mut u64| {
+            *count = count.saturating_add(1);
+            Ok(term)
+        },
+        &mut size,
+        TraverseOrder::TopDown,
+    );
+    (size > max).then_some(Limit::Size(max))
+}
+
+/// The Nickel evaluation cache, counting the values looked up and allocated by the
+/// evaluation to enforce the [`Limits`].
+///
+/// Nickel has no way to stop an evaluation, so reaching a limit unwinds the evaluation with
+/// the [`Limit`] as payload (without running the panic hook), to be caught by the loader.
+/// Cancelling its [`CancellationToken`] unwinds it the same way, with [`Cancelled`], and so
+/// does its [`Deadline`] expiring, with [`Expired`].
+#[derive(Clone)]
+pub(crate) struct LimitedCache {
+    inner: CacheImpl,
+    limits: Limits,
+    cancellation: CancellationToken,
+    deadline: Option<(CancellationToken, Duration)>,
+    steps: Rc<Cell<u64>>,
+    allocations: Rc<Cell<u64>>,
+}
+
+impl LimitedCache {
+    pub(crate) fn with_limits(limits: Limits, cancellation: CancellationToken) -> Self {
+        Self {
+            limits,
+            cancellation,
+            ..Self::new()
+        }
+    }
+
+    /// Interrupts the evaluation once `deadline` expires.
+    pub(crate) fn until(mut self, deadline: &Deadline) -> Self {
+        self.deadline = Some((deadline.token(), deadline.timeout()));
+        self
+    }
+
+    /// Counts from zero again, so preparing the standard library doesn't count against the
+    /// limits of the configuration.
+    pub(crate) fn restart(&self) {
+        self.steps.set(0);
+        self.allocations.set(0);
+    }
+
+    /// Returns the number of values allocated by the evaluation so far.
+    pub(crate) fn allocations(&self) -> u64 {
+        self.allocations.get()
+    }
+
+    fn step(&self) {
+        if self.cancellation.is_cancelled() {
+            panic::resume_unwind(Box::new(Cancelled));
+        }
+        if let Some((expired, timeout)) = &self.deadline {
+            if expired.is_cancelled() {
+                panic::resume_unwind(Box::new(Expired::after(*timeout)));
+            }
+        }
+        count(&self.steps, self.limits.steps, Limit::Steps);
+    }
+}
+
+/// Increments `counter`, unwinding with `limit` once it goes over `max`.
+fn count(counter: &Cell<u64>, max: Option<u64>, limit: fn(u64) -> Limit) {
+    let value = counter.get().saturating_add(1);
+    counter.set(value);
+    if let Some(reached) = max.filter(|bound| value > *bound) {
+        panic::resume_unwind(Box::new(limit(reached)));
+    }
+}
+
+impl Cache for LimitedCache {
+    type UpdateIndex = <CacheImpl as Cache>::UpdateIndex;
+
+    fn get(&self, idx: CacheIndex) -> Closure {
+        self.step();
+        self.inner.get(idx)
+    }
+
+    fn get_update_index(
+        &mut self,
+        idx: &mut CacheIndex,
+    ) -> Result<Option<Self::UpdateIndex>, BlackholedError> {
+        self.step();
+        self.inner.get_update_index(idx)
+    }
+
+    fn add(&mut self, clos: Closure, kind: IdentKind, bty: BindingType) -> CacheIndex {
+        count(
+            &self.allocations,
+            self.limits.allocations,
+            Limit::Allocations,
+        );
+        self.inner.add(clos, kind, bty)
+    }
+
+    fn patch<F: Fn(&mut Closure)>(&mut self, idx: CacheIndex, f: F) {
+        self.inner.patch(idx, f);
+    }
+
+    fn get_then<T, F: FnOnce(&Closure) -> T>(&self, idx: CacheIndex, f: F) -> T {
+        self.inner.get_then(idx, f)
+    }
+
+    fn update(&mut self, clos: Closure, idx: Self::UpdateIndex) {
+        self.inner.update(clos, idx);
+    }
+
+    fn new() -> Self {
+        Self {
+            inner: CacheImpl::new(),
+            limits: Limits::default(),
+            cancellation: CancellationToken::default(),
+            deadline: None,
+            steps: Rc::default(),
+            allocations: Rc::default(),
+        }
+    }
+
+    fn reset_index_state(&mut self, idx: &mut Self::UpdateIndex) {
+        self.inner.reset_index_state(idx);
+    }
+
+    fn map_at_index<F: FnMut(&mut Self, &Closure) -> Closure>(
+        &mut self,
+        idx: &CacheIndex,
+        mut f: F,
+    ) -> CacheIndex {
+        let mut outer = self.clone();
+        self.inner
+            .map_at_index(idx, |_inner, closure| f(&mut outer, closure))
+    }
+
+    fn build_cached(&mut self, idx: &mut CacheIndex, rec_env: &[(Ident, CacheIndex)]) {
+        self.inner.build_cached(idx, rec_env);
+    }
+
+    fn ident_kind(&self, idx: &CacheIndex) -> IdentKind {
+        self.inner.ident_kind(idx)
+    }
+
+    fn saturate<'ident, I: DoubleEndedIterator<Item = &'ident Ident> + Clone>(
+        &mut self,
+        idx: CacheIndex,
+        env: &mut Environment,
+        fields: I,
+    ) -> RichTerm {
+        self.inner.saturate(idx, env, fields)
+    }
+
+    fn revert(&mut self, idx: &CacheIndex) -> CacheIndex {
+        self.inner.revert(idx)
+    }
+
+    fn deps(&self, idx: &CacheIndex) -> Option<FieldDeps> {
+        self.inner.deps(idx)
+    }
+
+    fn make_update_index(
+        &mut self,
+        idx: &mut CacheIndex,
+    ) -> Result<Self::UpdateIndex, BlackholedError> {
+        self.inner.make_update_index(idx)
+    }
+}
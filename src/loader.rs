@@ -0,0 +1,4850 @@
+use crate::all_location_candidates;
+use crate::ambiguous_sibling;
+use crate::blame::blamed_field;
+use crate::cancel::Cancelled;
+use crate::changes::serialized;
+use crate::credentials_directory;
+use crate::deadline::Deadline;
+use crate::deadline::Expired;
+use crate::deprecation::remap;
+use crate::deprecation::Deprecation;
+use crate::disk_cache;
+use crate::existing_configs;
+use crate::field_error::available_fields;
+use crate::field_error::deserialize_collecting_errors;
+use crate::field_error::deserialize_with_defaults;
+use crate::first_existing_config;
+use crate::hangup;
+use crate::imports::forbidden_import;
+use crate::imports::import_closure;
+use crate::imports::search_imports;
+use crate::is_yaml;
+use crate::limits::oversized;
+use crate::limits::LimitedCache;
+use crate::memo;
+use crate::mount::merged_keys;
+use crate::offload::offloaded;
+use crate::offload::Loading;
+use crate::offload::SpawnThread;
+use crate::path::resolving_against;
+use crate::permissions::insecure;
+use crate::prelude::new_cache;
+use crate::prelude::prepare_eval;
+use crate::prelude::Prelude;
+use crate::references::files_in;
+use crate::references::resolve_exported_references;
+use crate::references::resolve_references;
+use crate::references::Resolver;
+use crate::render::render;
+use crate::secret::detecting_secrets;
+use crate::secret::exposing;
+use crate::secret::Exposure;
+use crate::shadowed_configs;
+use crate::source_of;
+use crate::trace::traced;
+use crate::trace::Stage;
+use crate::watch::spawn;
+use crate::watch::watch;
+use crate::watch::Polling;
+use crate::CancellationToken;
+use crate::ChangeSet;
+use crate::Diagnostic;
+use crate::English;
+use crate::Error;
+use crate::Event;
+use crate::Failure;
+use crate::FieldError;
+use crate::HostFacts;
+use crate::ImportPolicy;
+use crate::Limit;
+use crate::Limits;
+use crate::LoadReport;
+use crate::Message;
+use crate::Messages;
+use crate::Offload;
+use crate::PermissionCheck;
+use crate::ProgramHandle;
+use crate::Provenance;
+use crate::ReloadMetrics;
+use crate::ReloadRecord;
+use crate::Result;
+use crate::Sections;
+use crate::Severity;
+use crate::SharedConfig;
+use crate::Source;
+use crate::Update;
+use crate::Watcher;
+use codespan::FileId;
+use codespan_reporting::term::termcolor::NoColor;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::deserialize::RustDeserializationError;
+use nickel_lang_core::error::EvalError;
+use nickel_lang_core::error::IntoDiagnostics;
+use nickel_lang_core::eval::VirtualMachine;
+use nickel_lang_core::identifier::Ident;
+#[cfg(any(feature = "yaml", feature = "toml"))]
+use nickel_lang_core::serialize;
+#[cfg(any(feature = "yaml", feature = "toml"))]
+use nickel_lang_core::serialize::ExportFormat;
+#[cfg(any(feature = "yaml", feature = "toml"))]
+use nickel_lang_core::term::record::RecordData;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use nickel_lang_core::term::UnaryOp;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::io;
+use std::io::Write;
+use std::mem;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Locates, evaluates and deserializes the Nickel configuration of an application.
+///
+/// [`crate::load_configuration`] is a shortcut for the most common setup. Build a
+/// [`Loader`] when you need to tweak how the configuration is loaded, for example to
+/// redirect the Nickel diagnostics away from `stderr`:
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, Default)]
+/// # struct MyConfig {}
+/// let config: nickelodeon::Result<MyConfig> = nickelodeon::Loader::new("my_app")
+///     .diagnostics(std::io::sink())
+///     .load();
+/// ```
+///
+/// With the `sops` feature, a configuration file encrypted by [SOPS](https://getsops.io)
+/// (a Nickel file encrypted as binary data, or a JSON or YAML one) is decrypted with the
+/// `sops` command line before being evaluated, so the usual SOPS keys and settings apply.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone)]
+pub struct Loader {
+    app: String,
+    config_path_from_flag: Option<PathBuf>,
+    diagnostics: DiagnosticSink,
+    collect_all_errors: bool,
+    required: bool,
+    deprecations: Vec<Deprecation>,
+    messages: Arc<dyn Messages>,
+    strict_ambiguity: bool,
+    permission_check: PermissionCheck,
+    imports: ImportPolicy,
+    timeout: Option<Duration>,
+    limits: Limits,
+    disk_cache: CacheLocation,
+    memoize: bool,
+    max_file_size: u64,
+    cancellation: CancellationToken,
+    pure: bool,
+    search_paths: Vec<PathBuf>,
+    preludes: Vec<Prelude>,
+    host_facts: Option<HostFacts>,
+    contracts: Vec<String>,
+    defaults: Option<String>,
+    offload: Arc<dyn Offload>,
+    debounce: Duration,
+    reload_on_hangup: bool,
+    compare_contents: bool,
+    secrets: Vec<String>,
+    mount: Option<PathBuf>,
+    resolvers: Vec<(String, Resolver)>,
+    metrics: Option<Arc<dyn ReloadMetrics>>,
+    on_yaml_config: Option<PathCallback>,
+    #[cfg(feature = "age")]
+    age_identity: Option<PathBuf>,
+    /// The [`Loader::remote_config`]s, with their own timeout if they have one.
+    #[cfg(feature = "remote")]
+    remotes: Vec<(String, Option<Duration>)>,
+    #[cfg(feature = "remote")]
+    remote_timeout: Duration,
+
+    /// The outcomes of fetching each [`Loader::remote_config`] already, by an async load.
+    #[cfg(feature = "remote")]
+    fetched_remotes: Option<Vec<std::result::Result<PathBuf, String>>>,
+}
+
+/// A callback registered with [`Loader::on_yaml_config`].
+type PathCallback = Arc<dyn Fn(&Path) + Send + Sync>;
+
+/// The imports allowed by a [`Loader::pure`] loader.
+static PURE_IMPORTS: ImportPolicy = ImportPolicy::ConfigDirectory;
+
+/// The name given to the [`Loader::embedded_defaults`] when they are used alone.
+const DEFAULTS: &str = "<defaults>";
+
+/// The default [`Loader::max_file_size`]: 10 MiB.
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Where Docker mounts the secrets of a container, read by [`Loader::docker_secrets`].
+const DOCKER_SECRETS: &str = "/run/secrets";
+
+/// The default [`Loader::debounce`] window.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Where the [`Loader::disk_cache`] lives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CacheLocation {
+    Off,
+    Default,
+    In(PathBuf),
+}
+
+impl Loader {
+    /// Creates a loader for the application with the codename `app`. By default, Nickel
+    /// diagnostics are written to `stderr`.
+    #[must_use]
+    pub fn new(app: &str) -> Self {
+        Self {
+            app: app.to_owned(),
+            config_path_from_flag: None,
+            diagnostics: DiagnosticSink::new(io::stderr()),
+            collect_all_errors: false,
+            required: false,
+            deprecations: Vec::new(),
+            messages: Arc::new(English),
+            strict_ambiguity: false,
+            permission_check: PermissionCheck::Off,
+            imports: ImportPolicy::Allow,
+            timeout: None,
+            limits: Limits::default(),
+            disk_cache: CacheLocation::Off,
+            memoize: false,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            cancellation: CancellationToken::default(),
+            pure: false,
+            search_paths: Vec::new(),
+            preludes: Vec::new(),
+            host_facts: None,
+            contracts: Vec::new(),
+            defaults: None,
+            offload: Arc::new(SpawnThread),
+            debounce: DEFAULT_DEBOUNCE,
+            reload_on_hangup: false,
+            compare_contents: false,
+            secrets: Vec::new(),
+            mount: None,
+            resolvers: Vec::new(),
+            metrics: None,
+            on_yaml_config: None,
+            #[cfg(feature = "age")]
+            age_identity: None,
+            #[cfg(feature = "remote")]
+            remotes: Vec::new(),
+            #[cfg(feature = "remote")]
+            remote_timeout: crate::remote::DEFAULT_TIMEOUT,
+            #[cfg(feature = "remote")]
+            fetched_remotes: None,
+        }
+    }
+
+    /// Uses the given path (usually coming from a `--config` flag) instead of looking for
+    /// the configuration file in the standard locations.
+    #[must_use]
+    pub fn config_path_from_flag(mut self, path: Option<PathBuf>) -> Self {
+        self.config_path_from_flag = path;
+        self
+    }
+
+    /// Sends the human readable Nickel diagnostics (error reports and the output of
+    /// `std.trace`) to `sink` instead of `stderr`.
+    ///
+    /// Use [`std::io::sink`] to silence them, or a `Vec<u8>` behind a shared handle to
+    /// capture them.
+    #[must_use]
+    pub fn diagnostics<W>(mut self, sink: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.diagnostics = DiagnosticSink::new(sink);
+        self
+    }
+
+    /// When enabled, a configuration that doesn't match the shape of the requested type
+    /// fails with [`Error::InvalidFields`], listing every mismatched field and the type it
+    /// was expected to have, instead of stopping at the first one.
+    #[must_use]
+    pub const fn collect_all_errors(mut self, collect: bool) -> Self {
+        self.collect_all_errors = collect;
+        self
+    }
+
+    /// When enabled, failing to find a configuration file is an error
+    /// ([`Error::ConfigNotFound`], listing every location searched) instead of falling back
+    /// to `T::default()`.
+    #[must_use]
+    pub const fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// When enabled, finding both a `config.ncl` and a `config.nickel` in the same
+    /// directory is an error ([`Error::AmbiguousConfig`]) instead of silently using the
+    /// `config.ncl` one (and warning about the other one in the [`LoadReport`]).
+    #[must_use]
+    pub const fn strict_ambiguity(mut self, strict: bool) -> Self {
+        self.strict_ambiguity = strict;
+        self
+    }
+
+    /// Checks that the configuration file can't have been tampered with by other users:
+    /// that it isn't world-writable and is owned by the current user (or `root`). Only
+    /// effective on Unix. Off by default.
+    #[must_use]
+    pub const fn permission_check(mut self, check: PermissionCheck) -> Self {
+        self.permission_check = check;
+        self
+    }
+
+    /// Sets which files the configuration file is allowed to `import`. Any file by default.
+    #[must_use]
+    pub fn imports(mut self, policy: ImportPolicy) -> Self {
+        self.imports = policy;
+        self
+    }
+
+    /// Adds `dir` to the directories where imports are looked for when they aren't found next
+    /// to the importing file, so configurations can `import "my-app-lib.ncl"` to use the
+    /// helpers shipped by the application (e.g. in `/usr/share/my-app/nickel`). Directories
+    /// are searched in the order they are added.
+    ///
+    /// Unless imports are denied altogether, or the loader is [`Loader::pure`], the files in
+    /// these directories can always be imported.
+    #[must_use]
+    pub fn import_search_path(mut self, dir: PathBuf) -> Self {
+        self.search_paths.push(dir);
+        self
+    }
+
+    /// Binds `name` to the Nickel library `source` (usually embedded with `include_str!`) in
+    /// the environment of the configuration, so configurations can use the helpers and
+    /// constants shipped by the application (e.g. `my_app.helpers.port_of "http"`) without
+    /// importing them.
+    ///
+    /// Each prelude can use the ones registered before it.
+    ///
+    /// ```
+    /// let loader = nickelodeon::Loader::new("my-app")
+    ///     .prelude("my_app", "{ default_port = 8080 }");
+    /// ```
+    #[must_use]
+    pub fn prelude(mut self, name: &str, source: &str) -> Self {
+        self.preludes.push(Prelude::new(name, source));
+        self
+    }
+
+    /// Checks the configuration against the Nickel `contract` (usually embedded with
+    /// `include_str!`) during its evaluation, so mistakes are reported as precise Nickel
+    /// contract violations, pointing at the offending values, rather than as serde errors.
+    ///
+    /// The contract can use the [`Loader::prelude`]s. When several contracts are given, they
+    /// are all applied, in order.
+    ///
+    /// ```
+    /// let loader = nickelodeon::Loader::new("my-app")
+    ///     .contract("{ port | std.number.PosNat | default = 8080, .. }");
+    /// ```
+    #[must_use]
+    pub fn contract(mut self, contract: &str) -> Self {
+        self.contracts.push(contract.to_owned());
+        self
+    }
+
+    /// Checks the configuration against a contract derived from `T` (see
+    /// [`crate::contract_for`]), so fields of the wrong type or missing are reported as
+    /// Nickel contract violations, pointing at the offending values.
+    ///
+    /// ```
+    /// #[derive(serde::Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    /// }
+    ///
+    /// let loader = nickelodeon::Loader::new("my-app").derived_contract::<Config>();
+    /// ```
+    #[must_use]
+    pub fn derived_contract<T: DeserializeOwned>(self) -> Self {
+        let contract = crate::contract_for::<T>();
+        self.contract(&contract)
+    }
+
+    /// Checks the configuration against a contract converted from the JSON `schema` (see
+    /// [`crate::contract_from_json_schema`]), so the schema published for a configuration
+    /// validates it without rewriting its rules in Nickel.
+    ///
+    /// ```
+    /// let schema = serde_json::json!({
+    ///     "type": "object",
+    ///     "properties": { "port": { "type": "integer", "minimum": 1 } },
+    /// });
+    ///
+    /// let loader = nickelodeon::Loader::new("my-app").json_schema_contract(&schema);
+    /// ```
+    #[must_use]
+    pub fn json_schema_contract(self, schema: &serde_json::Value) -> Self {
+        let contract = crate::contract_from_json_schema(schema);
+        self.contract(&contract)
+    }
+
+    /// Merges the configuration over the Nickel `defaults` shipped with the application
+    /// (usually embedded with `include_str!`), so they can be complete and documented while
+    /// the configuration files only hold overrides. The defaults are used alone when no
+    /// configuration file is found (unless the loader is [`Loader::required`]).
+    ///
+    /// They are merged with the usual Nickel merge semantics, so the fields meant to be
+    /// overridden must have the `default` priority.
+    ///
+    /// ```
+    /// let loader = nickelodeon::Loader::new("my-app").embedded_defaults(
+    ///     "{
+    ///       # The port to listen on.
+    ///       port | Number | default = 8080,
+    ///     }",
+    /// );
+    /// ```
+    #[must_use]
+    pub fn embedded_defaults(mut self, defaults: &str) -> Self {
+        self.defaults = Some(defaults.to_owned());
+        self
+    }
+
+    /// Returns the default configuration as the source of a Nickel file, so applications can
+    /// print it (e.g. for a `--print-default-config` flag): the
+    /// [`Loader::embedded_defaults`] as they were given, or `T::default()` written by
+    /// [`crate::default_config_source`] when there are none.
+    ///
+    /// ```
+    /// #[derive(Default, serde::Deserialize, serde::Serialize)]
+    /// struct Config {
+    ///     port: u16,
+    /// }
+    ///
+    /// let source = nickelodeon::Loader::new("my-app")
+    ///     .embedded_defaults("{ port | Number | default = 8080 }")
+    ///     .default_config_source::<Config>()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(source, "{ port | Number | default = 8080 }");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there are no embedded defaults and `T::default()` can't be
+    /// written in Nickel.
+    pub fn default_config_source<T>(&self) -> Result<String>
+    where
+        T: Serialize + DeserializeOwned + Default,
+    {
+        self.defaults
+            .clone()
+            .map_or_else(crate::default_config_source::<T>, Ok)
+    }
+
+    /// Exposes facts about the host (its name, operating system, CPUs...) to the
+    /// configuration as a `host` record, so it can branch on them:
+    /// `if host.os == "linux" then … else …`. See [`HostFacts`] for the available facts.
+    ///
+    /// Not exposed by default, nor by [`Loader::pure`] loaders, since they make the
+    /// configuration depend on the host.
+    #[must_use]
+    pub fn host_facts(mut self, facts: HostFacts) -> Self {
+        self.host_facts = Some(facts);
+        self
+    }
+
+    /// When enabled, the evaluated configuration depends only on the content of the files in
+    /// the directory of the configuration file, so it can be fingerprinted or reproduced in
+    /// a build: only those files can be imported (unless imports are denied altogether), not
+    /// even the ones of the [`Loader::import_search_path`]s. Off by default.
+    ///
+    /// Nickel itself can neither read the environment nor the clock, and the fields of the
+    /// records are always serialized sorted. Nothing else is read either: the references
+    /// (see [`Loader::resolve_references`]) fail with [`Error::UnresolvedReference`], and
+    /// the [`Loader::remote_config`]s aren't fetched, the configuration files being looked
+    /// for instead.
+    #[must_use]
+    pub const fn pure(mut self, pure: bool) -> Self {
+        self.pure = pure;
+        self
+    }
+
+    /// Gives up evaluating the configuration after `timeout`, failing with
+    /// [`Error::EvaluationTimeout`], so an accidental infinite recursion doesn't hang the
+    /// application. No timeout by default.
+    ///
+    /// The evaluation is interrupted where it is when the timeout expires, like when its
+    /// [`Loader::cancellation`] is cancelled. The memory of a very deep interrupted
+    /// evaluation isn't reclaimed, as it couldn't be freed within the timeout: use
+    /// [`Loader::limits`] as well to bound it.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Bounds the resources the evaluation of the configuration can use, failing with
+    /// [`Error::LimitExceeded`] when one of the `limits` is reached. Unlimited by default.
+    #[must_use]
+    pub const fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Aborts the load, failing with [`Error::Cancelled`], once `token` is cancelled, even
+    /// in the middle of the evaluation.
+    #[must_use]
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Refuses to read configuration files larger than `bytes`, failing with
+    /// [`Error::ConfigTooLarge`], so pointing the application at a huge binary file by
+    /// mistake doesn't exhaust its memory. 10 MiB by default.
+    #[must_use]
+    pub const fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// When enabled, the evaluated configuration is cached on disk, and reused by later loads
+    /// as long as neither the configuration file nor any of the files it imports change,
+    /// skipping the evaluation. Off by default.
+    ///
+    /// The cache lives in `$XDG_CACHE_HOME/nickelodeon/<app>`, unless a
+    /// [`Loader::cache_dir`] is given.
+    /// It is not used when imports are restricted or [`Loader::limits`] are set, since
+    /// they are only enforced by the evaluation, nor with [`Loader::host_facts`], read at
+    /// each load. Configuration files encrypted with age or SOPS aren't cached either, so
+    /// they are never written decrypted, nor the ones holding secrets: with
+    /// [`Loader::secret_field`]s, or deserialized into [`crate::Secret`]s. The entries are
+    /// only readable by the user. Loaders with different deprecations, defaults,
+    /// preludes or contracts keep separate entries. The [`LoadReport`] of a cached load
+    /// counts a [`LoadReport::cache_hits`], but has no provenance nor warnings.
+    #[must_use]
+    pub fn disk_cache(mut self, enabled: bool) -> Self {
+        self.disk_cache = match (enabled, self.disk_cache) {
+            (false, _) => CacheLocation::Off,
+            (true, CacheLocation::Off) => CacheLocation::Default,
+            (true, location) => location,
+        };
+        self
+    }
+
+    /// When enabled, the evaluated configuration is kept in memory and reused by the later
+    /// loads of the same configuration file in the process (by any memoizing loader with the
+    /// same deprecations, defaults, preludes and contracts, e.g. libraries loading their own
+    /// section of the configuration), skipping the evaluation. Off by default.
+    ///
+    /// Memoized configurations are kept until [`crate::forget_memoized`] is called, or until
+    /// a [`Loader::watch`] of the loader notices a change. Like
+    /// the [`Loader::disk_cache`], memoization is not used when imports are restricted or
+    /// [`Loader::limits`] are set.
+    #[must_use]
+    pub const fn memoize(mut self, enabled: bool) -> Self {
+        self.memoize = enabled;
+        self
+    }
+
+    /// Enables the [`Loader::disk_cache`], keeping it in `dir` instead of the default
+    /// location.
+    #[must_use]
+    pub fn cache_dir(mut self, dir: PathBuf) -> Self {
+        self.disk_cache = CacheLocation::In(dir);
+        self
+    }
+
+    /// Registers a field renamed from `old` to `new` (both dotted paths, like
+    /// `server.addr`). Configurations still setting `old` keep working: its value is moved to
+    /// `new` and a warning is added to the [`LoadReport`].
+    ///
+    /// When both are set, `new` wins and the value of `old` is ignored.
+    #[must_use]
+    pub fn deprecated_field(mut self, old: &str, new: &str) -> Self {
+        self.deprecations.push(Deprecation::new(old, new));
+        self
+    }
+
+    /// Calls `callback` with the path of the configuration file whenever it's a YAML one
+    /// (only looked for with the `yaml` feature), e.g. to nudge users into moving it to
+    /// Nickel. A warning is added to the [`LoadReport`] either way.
+    #[must_use]
+    pub fn on_yaml_config<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Path) + Send + Sync + 'static,
+    {
+        self.on_yaml_config = Some(Arc::new(callback));
+        self
+    }
+
+    /// Builds the messages produced by nickelodeon itself (as opposed to the ones coming
+    /// from Nickel) with `messages`, to translate them. Defaults to [`English`].
+    #[must_use]
+    pub fn messages<M>(mut self, messages: M) -> Self
+    where
+        M: Messages + 'static,
+    {
+        self.messages = Arc::new(messages);
+        self
+    }
+
+    /// Runs the blocking work of the async loads, like [`Loader::load_async`], through
+    /// `offload`, e.g. the blocking pool of the async runtime of the application. Defaults to
+    /// [`SpawnThread`].
+    #[must_use]
+    pub fn offload<O>(mut self, offload: O) -> Self
+    where
+        O: Offload + 'static,
+    {
+        self.offload = Arc::new(offload);
+        self
+    }
+
+    /// Locates, evaluates and deserializes the configuration. If no configuration file is
+    /// found, `T::default()` is returned (unless the loader is [`Loader::required`]).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read, evaluated or if it
+    /// doesn't match the deserialization contract for `T`.
+    pub fn load<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        self.load_with_report().map(|(value, _report)| value)
+    }
+
+    /// Same as [`Loader::load`], but also returns a [`LoadReport`] describing how the
+    /// configuration was loaded, so it can be logged at startup.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read, evaluated or if it
+    /// doesn't match the deserialization contract for `T`.
+    pub fn load_with_report<T: DeserializeOwned + Default>(&self) -> Result<(T, LoadReport)> {
+        let recall = |path: &Path| self.recall(path);
+        self.load_with(&[], recall, |rt, mut vm, sink| {
+            if self.collect_all_errors {
+                deserialize_collecting_all_errors(&rt, sink, self.messages.as_ref())
+            } else {
+                deserialize(&rt, &mut vm, sink, self.messages.as_ref())
+            }
+        })
+    }
+
+    /// Lenient version of [`Loader::load`], meant for tooling like linters and editors: the
+    /// fields that don't match `T` are replaced by their value in `T::default()` and
+    /// returned, together with the best-effort configuration, instead of failing the whole
+    /// load.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated.
+    pub fn load_partial<T>(&self) -> Result<(T, Vec<FieldError>)>
+    where
+        T: DeserializeOwned + Serialize + Default,
+    {
+        let mut problems = Vec::new();
+        let recall = |path: &Path| self.recall(path);
+        let (value, _report) = self.load_with(&[], recall, |rt, _vm, sink| {
+            let (value, errors) =
+                deserialize_replacing_with_defaults(&rt, sink, self.messages.as_ref())?;
+            problems = errors;
+            Ok(value)
+        })?;
+        Ok((value, problems))
+    }
+
+    /// Locates the configuration and evaluates and deserializes only its field at `path` (a
+    /// dotted path, like `server.tls.cert_path`), thanks to the laziness of Nickel: tools
+    /// needing a single setting don't pay for evaluating a whole large configuration.
+    ///
+    /// Returns `None` if no configuration file is found (unless the loader is
+    /// [`Loader::required`]). Deprecated fields, caches and memoization are not used.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated, if it has no
+    /// field at `path`, or if the field doesn't match the deserialization contract for `T`.
+    pub fn load_field<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        let field: Vec<String> = path.split('.').map(str::to_owned).collect();
+        let (value, _report) = self.load_with(
+            &field,
+            |_| None,
+            |rt, mut vm, sink| deserialize(&rt, &mut vm, sink, self.messages.as_ref()).map(Some),
+        )?;
+        Ok(value)
+    }
+
+    /// Locates, evaluates and returns the configuration as a JSON [`Value`], for applications
+    /// that can't know its shape at compile time, like plugin hosts or generic tools.
+    ///
+    /// Works like [`Loader::load`]: deprecated fields are renamed and the configuration
+    /// can be reused from the caches. Integers stay integers. An empty object is returned
+    /// if no configuration file is found (unless the loader is [`Loader::required`]).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+    /// values JSON can't represent, like functions.
+    pub fn load_dynamic(&self) -> Result<Value> {
+        let recall = |path: &Path| self.recall(path);
+        let (loaded, _report) =
+            self.load_with(&[], recall, |rt, mut vm, sink| export(&rt, &mut vm, sink))?;
+        if loaded.is_null() {
+            return Ok(Value::Object(serde_json::Map::new()));
+        }
+        Ok(loaded)
+    }
+
+    /// Locates, evaluates and returns the whole configuration as JSON, as the application
+    /// sees it: with the [`Loader::embedded_defaults`], preludes and contracts applied. Meant
+    /// for `config dump --json` like commands, and for debugging layered setups.
+    ///
+    /// An empty object is returned if no configuration file is found (unless the loader is
+    /// [`Loader::required`]).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+    /// values JSON can't represent, like functions.
+    pub fn export_json(&self) -> Result<Value> {
+        let (exported, _report) =
+            self.load_with(&[], |_| None, |rt, mut vm, sink| export(&rt, &mut vm, sink))?;
+        if exported.is_null() {
+            return Ok(Value::Object(serde_json::Map::new()));
+        }
+        Ok(exported)
+    }
+
+    /// Same as [`Loader::export_json`], but returns the configuration as a YAML document.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+    /// values YAML can't represent, like functions.
+    #[cfg(feature = "yaml")]
+    pub fn export_yaml(&self) -> Result<String> {
+        self.export_as(ExportFormat::Yaml)
+    }
+
+    /// Same as [`Loader::export_json`], but returns the configuration as a TOML document.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated, or if it holds
+    /// values TOML can't represent, like `null` or functions.
+    #[cfg(feature = "toml")]
+    pub fn export_toml(&self) -> Result<String> {
+        self.export_as(ExportFormat::Toml)
+    }
+
+    /// Returns the whole configuration serialized into `format`, like `nickel export` does.
+    #[cfg(any(feature = "yaml", feature = "toml"))]
+    fn export_as(&self, format: ExportFormat) -> Result<String> {
+        let (exported, _report) = self.load_with(
+            &[],
+            |_| None,
+            |rt, mut vm, sink| {
+                serialize::validate(format, &rt)
+                    .and_then(|()| serialize::to_string(format, &rt))
+                    .map(Some)
+                    .map_err(|err| {
+                        let error = EvalError::SerializationError(err);
+                        let diagnostics = report(vm.import_resolver_mut(), error.clone(), sink);
+                        Error::NickelEvaluationError(error.into(), diagnostics)
+                    })
+            },
+        )?;
+        Ok(exported.unwrap_or_else(|| {
+            let empty = RichTerm::from(Term::Record(RecordData::empty()));
+            serialize::to_string(format, &empty).unwrap_or_default()
+        }))
+    }
+
+    /// Async version of [`Loader::load`], for any async runtime: the configuration is
+    /// loaded through the [`Loader::offload`], without blocking the awaiting task.
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, Default)]
+    /// # struct MyConfig {}
+    /// # async fn run() -> nickelodeon::Result<()> {
+    /// let config: MyConfig = nickelodeon::Loader::new("my_app").load_async().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to `Err` if the found config file can't be read, evaluated or if
+    /// it doesn't match the deserialization contract for `T`.
+    pub fn load_async<T>(&self) -> Loading<T>
+    where
+        T: DeserializeOwned + Default + Send + 'static,
+    {
+        self.offloaded(Self::load)
+    }
+
+    /// Async version of [`Loader::load_field`], loading the field through the
+    /// [`Loader::offload`].
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to `Err` if the found config file can't be read or evaluated, if
+    /// it has no field at `path`, or if the field doesn't match the deserialization
+    /// contract for `T`.
+    pub fn load_field_async<T>(&self, path: &str) -> Loading<Option<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let field = path.to_owned();
+        self.offloaded(move |loader| loader.load_field(&field))
+    }
+
+    /// Runs `work` with this loader through the [`Loader::offload`], for the async loads.
+    ///
+    /// With the `tokio` feature, when called on a tokio runtime, the [`Loader::remote_config`]s
+    /// are all fetched at once on the runtime first, so waiting for the servers doesn't hold
+    /// an offloaded thread, and `curl` is killed if it doesn't answer within the timeout of
+    /// its source.
+    fn offloaded<T, F>(&self, work: F) -> Loading<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self) -> Result<T> + Send + 'static,
+    {
+        #[cfg(all(feature = "remote", feature = "tokio"))]
+        if let (false, Ok(runtime)) = (
+            self.remotes().is_empty(),
+            tokio::runtime::Handle::try_current(),
+        ) {
+            let fetching: Vec<_> = self
+                .remotes()
+                .into_iter()
+                .map(|remote| {
+                    let dir = self.remote_dir();
+                    runtime.spawn(async move { remote.fetch_async(&dir).await })
+                })
+                .collect();
+            let mut loader = self.clone();
+            return crate::offload::offloaded_after(
+                &runtime,
+                Arc::clone(&self.offload),
+                async move {
+                    let mut fetched = Vec::new();
+                    for source in fetching {
+                        fetched.push(source.await.unwrap_or_else(|err| Err(err.to_string())));
+                    }
+                    fetched
+                },
+                move |fetched| {
+                    loader.fetched_remotes = Some(fetched);
+                    work(&loader)
+                },
+            );
+        }
+        let loader = self.clone();
+        offloaded(self.offload.as_ref(), move || work(&loader))
+    }
+
+    /// Registers the setting at `path` (a dotted path, like `database.password`) as a secret:
+    /// its values, and the ones of every setting nested in it, are redacted from the
+    /// [`crate::ChangeSet`]s of the watches, so they don't end up in logs.
+    #[must_use]
+    pub fn secret_field(mut self, path: &str) -> Self {
+        self.secrets.push(path.to_owned());
+        self
+    }
+
+    /// Resolves the strings of the configuration written as references of the `scheme`
+    /// (like `scheme://what`) into the value `resolve` returns for them (given `what`), so
+    /// secrets can be fetched from where they are kept instead of living in the
+    /// configuration file:
+    ///
+    /// ```no_run
+    /// let loader = nickelodeon::Loader::new("my-app").resolve_references("env", |name| {
+    ///     std::env::var(name).map_err(|err| err.to_string())
+    /// });
+    /// ```
+    ///
+    /// The references are resolved once the configuration is evaluated, before
+    /// deserializing it, so contracts see them as written. The loads fail with
+    /// [`Error::UnresolvedReference`] when `resolve` returns an error.
+    #[must_use]
+    pub fn resolve_references<F>(mut self, scheme: &str, resolve: F) -> Self
+    where
+        F: Fn(&str) -> std::result::Result<String, String> + Send + Sync + 'static,
+    {
+        self.resolvers.push((scheme.to_owned(), Arc::new(resolve)));
+        self
+    }
+
+    /// Reads the configuration from the directory `/etc/<app>`, where Kubernetes mounts the
+    /// keys of a `ConfigMap` or `Secret` as files, instead of looking for a configuration file.
+    /// See [`Loader::kubernetes_mount_at`].
+    #[must_use]
+    pub fn kubernetes_mount(self) -> Self {
+        let dir = Path::new("/etc").join(&self.app);
+        self.kubernetes_mount_at(dir)
+    }
+
+    /// Reads the configuration from the directory `dir`, where Kubernetes mounts the keys of
+    /// a `ConfigMap` or `Secret` (or of a projected volume) as files, instead of looking for a
+    /// configuration file:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, Default)]
+    /// # struct MyConfig {}
+    /// let config: nickelodeon::Result<MyConfig> = nickelodeon::Loader::new("my_app")
+    ///     .kubernetes_mount_at("/config".into())
+    ///     .load();
+    /// ```
+    ///
+    /// The configuration is the merge (`&`) of the keys in Nickel, JSON, YAML or TOML (e.g.
+    /// `server.ncl` and `database.json`), so each key can hold a part of it. The watches
+    /// reload it when Kubernetes updates the mounted keys, which swaps the `..data`
+    /// symbolic link they point through. A configuration file given with
+    /// [`Loader::config_path_from_flag`] is still read instead.
+    #[must_use]
+    pub fn kubernetes_mount_at(mut self, dir: PathBuf) -> Self {
+        self.mount = Some(dir);
+        self
+    }
+
+    /// Resolves the strings written as `docker-secret://<name>` (e.g.
+    /// `docker-secret://db_password`) into the content of the secret `name` Docker Swarm or
+    /// Compose mounted in the container, the file `/run/secrets/<name>`, without its final
+    /// newline. See [`Loader::resolve_references`].
+    #[must_use]
+    pub fn docker_secrets(self) -> Self {
+        self.docker_secrets_in(PathBuf::from(DOCKER_SECRETS))
+    }
+
+    /// Same as [`Loader::docker_secrets`], but with the secrets mounted in `dir` instead
+    /// of `/run/secrets` (e.g. a custom `target` of Compose, or a test directory).
+    #[must_use]
+    pub fn docker_secrets_in(mut self, dir: PathBuf) -> Self {
+        self.resolvers
+            .push(("docker-secret".to_owned(), files_in(dir)));
+        self
+    }
+
+    /// Resolves the strings written as `credential://<name>` (e.g. `credential://db_password`)
+    /// into the content of the systemd credential `name` of the service (see
+    /// `LoadCredential=` and `SetCredential=`), the file `$CREDENTIALS_DIRECTORY/<name>`,
+    /// without its final newline. See [`Loader::resolve_references`].
+    ///
+    /// The configuration file itself can be a credential too: `config.ncl` (or any other
+    /// configuration file name) in `$CREDENTIALS_DIRECTORY` is looked for before the usual
+    /// locations.
+    #[must_use]
+    pub fn systemd_credentials(self) -> Self {
+        self.credentials_in(credentials_directory())
+    }
+
+    /// Same as [`Loader::systemd_credentials`], with the credentials in `dir`, if the
+    /// service has any.
+    fn credentials_in(mut self, dir: Option<PathBuf>) -> Self {
+        let resolver = dir.map_or_else(
+            || -> Resolver {
+                Arc::new(|_name| {
+                    Err(
+                        "`$CREDENTIALS_DIRECTORY` isn't set: the service has no credentials"
+                            .to_owned(),
+                    )
+                })
+            },
+            files_in,
+        );
+        self.resolvers.push(("credential".to_owned(), resolver));
+        self
+    }
+
+    /// Resolves the strings written as `vault://<path>#<field>` (e.g.
+    /// `vault://secret/data/app#password`) into the value of the `field` of the secret
+    /// stored in `HashiCorp` Vault at `path`, so it doesn't have to live in the
+    /// configuration file. See [`Loader::resolve_references`].
+    ///
+    /// The secrets are read with the `vault` command line (`vault read`), which must be
+    /// installed and is configured as usual: `VAULT_ADDR`, `VAULT_TOKEN` or the token of the
+    /// last `vault login`... Both versions of the key/value secrets engine are supported. A
+    /// `vault` that hasn't answered within 10 seconds is killed, failing the reference.
+    #[cfg(feature = "vault")]
+    #[must_use]
+    pub fn vault_references(self) -> Self {
+        self.resolve_references("vault", crate::vault::resolve)
+    }
+
+    /// Resolves the strings written as `keyring://<service>/<account>` (e.g.
+    /// `keyring://my-app/api_token`) into the password stored for `account` of `service` in
+    /// the keyring of the platform, so it doesn't have to live in the configuration file.
+    /// See [`Loader::resolve_references`].
+    ///
+    /// The passwords are read from the Secret Service (GNOME Keyring, `KWallet`...) with
+    /// `secret-tool`, looking up the `service` and `username` attributes, and from the
+    /// macOS Keychain with `security`. The Windows Credential Manager has no command line
+    /// able to read them, so the references fail to resolve there.
+    #[cfg(feature = "keyring")]
+    #[must_use]
+    pub fn keyring_references(self) -> Self {
+        self.resolve_references("keyring", crate::keyring::resolve)
+    }
+
+    /// Decrypts the configuration files encrypted with [age](https://age-encryption.org)
+    /// (`config.ncl.age`, looked for right after `config.ncl` and `config.nickel`) with the
+    /// identity file at `path`, instead of the identity of `AGE_IDENTITY`.
+    ///
+    /// `AGE_IDENTITY` holds either an identity (`AGE-SECRET-KEY-1...`) or the path of its
+    /// file. The files are decrypted with the `age` command line (or `rage`, installed as
+    /// `age`), so an encrypted dotfiles repository can carry the configuration.
+    #[cfg(feature = "age")]
+    #[must_use]
+    pub fn age_identity(mut self, path: PathBuf) -> Self {
+        self.age_identity = Some(path);
+        self
+    }
+
+    /// Fetches the configuration from the HTTPS `url` (e.g.
+    /// `https://config.example.com/my-app/config.ncl`), so a fleet of machines can share a
+    /// centralized configuration, instead of looking for a configuration file.
+    ///
+    /// The configuration is fetched with the `curl` command line on each load, within the
+    /// [`Loader::remote_timeout`]. A copy is kept in the `remote` directory of the
+    /// [`Loader::cache_dir`] (`$XDG_CACHE_HOME/nickelodeon/<app>/remote` by default) and only
+    /// downloaded again when the server says it changed (`ETag` and `If-Modified-Since`).
+    /// When it can't be fetched, a warning is added to the [`LoadReport`] and that copy is
+    /// used, or the configuration files are looked for as usual if there is none yet.
+    ///
+    /// Calling it again adds another source, used when the ones added before can't be
+    /// fetched, before falling back to their copies. With the `tokio` feature, async loads
+    /// on a tokio runtime, like [`Loader::load_async`], fetch all of them at once on the
+    /// runtime instead of blocking a thread on each in turn.
+    ///
+    /// Its format is told by the extension of the URL, Nickel by default. A configuration
+    /// file given with [`Loader::config_path_from_flag`] is still read instead. Watches
+    /// only fetch it again when reloading for another reason, like a
+    /// [`Loader::reload_on_hangup`].
+    #[cfg(feature = "remote")]
+    #[must_use]
+    pub fn remote_config(mut self, url: &str) -> Self {
+        self.remotes.push((url.to_owned(), None));
+        self
+    }
+
+    /// Same as [`Loader::remote_config`], giving up fetching this source after `timeout`
+    /// instead of the [`Loader::remote_timeout`], e.g. so a mirror far away can take longer.
+    #[cfg(feature = "remote")]
+    #[must_use]
+    pub fn remote_config_with_timeout(mut self, url: &str, timeout: Duration) -> Self {
+        self.remotes.push((url.to_owned(), Some(timeout)));
+        self
+    }
+
+    /// Gives up fetching each [`Loader::remote_config`] after `timeout`, 5 seconds by
+    /// default.
+    #[cfg(feature = "remote")]
+    #[must_use]
+    pub const fn remote_timeout(mut self, timeout: Duration) -> Self {
+        self.remote_timeout = timeout;
+        self
+    }
+
+    /// Returns where the diagnostics of this loader are written (see [`Loader::diagnostics`]).
+    pub(crate) fn diagnostic_sink(&self) -> DiagnosticSink {
+        self.diagnostics.clone()
+    }
+
+    /// Returns the diagnostics of `error`, in the language of the [`Loader::messages`].
+    pub(crate) fn diagnostics_of(&self, error: &Error) -> Vec<Diagnostic> {
+        error.diagnostics_in(self.messages.as_ref())
+    }
+
+    /// Returns the [`Loader::metrics`], if any.
+    pub(crate) fn reload_metrics(&self) -> Option<&dyn ReloadMetrics> {
+        self.metrics.as_deref()
+    }
+
+    /// Returns the [`Loader::secret_field`]s.
+    pub(crate) fn secret_fields(&self) -> &[String] {
+        &self.secrets
+    }
+
+    /// Waits for the files of the configuration to stay unchanged for `window` before a
+    /// [`Loader::watch`] reloads it, so the bursts of writes of editors saving a file
+    /// (truncating, writing, renaming) trigger a single reload, once the file is complete.
+    /// 100 ms by default.
+    #[must_use]
+    pub const fn debounce(mut self, window: Duration) -> Self {
+        self.debounce = window;
+        self
+    }
+
+    /// When enabled, a [`Loader::watch`] also reloads the configuration when the process
+    /// receives a `SIGHUP`, like daemons traditionally do, delivering it through the same
+    /// [`Watcher`]. Only effective on Unix. Off by default.
+    ///
+    /// The `SIGHUP` handler is installed by the first such watch, and then stays for the
+    /// life of the process, replacing any other one.
+    #[must_use]
+    pub const fn reload_on_hangup(mut self, enabled: bool) -> Self {
+        self.reload_on_hangup = enabled;
+        self
+    }
+
+    /// When enabled, the watches of this loader, like [`Loader::watch`], tell that the files
+    /// changed by hashing their contents every interval instead of checking their
+    /// modification times and sizes. Off by default.
+    ///
+    /// Meant for files on network file systems, or synced from remote sources, whose
+    /// metadata can't be trusted: the configuration is reloaded when, and only when, the
+    /// contents of its files changed, at the cost of reading them all every interval.
+    #[must_use]
+    pub const fn compare_contents(mut self, enabled: bool) -> Self {
+        self.compare_contents = enabled;
+        self
+    }
+
+    /// Reports the counters of the watches of this loader, like [`Loader::watch`], to
+    /// `metrics` after each load. Each watch counts on its own.
+    #[must_use]
+    pub fn metrics<M>(mut self, metrics: M) -> Self
+    where
+        M: ReloadMetrics + 'static,
+    {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Loads the configuration, and loads it again every time it changes: the returned
+    /// [`Watcher`] delivers each new valid configuration, for long-running applications to
+    /// apply it without restarting, and the diagnostics of the invalid ones, for them to
+    /// keep the last valid one.
+    ///
+    /// Besides the files the configuration depends on (see [`LoadReport::files`]), every
+    /// location where a configuration file could be found is watched, so creating one is
+    /// noticed too. The configurations [`Loader::memoize`]d by the loader are forgotten on
+    /// changes.
+    ///
+    /// Changes are notified by the operating system. The files are checked every
+    /// `interval` instead where notifications aren't available, and when
+    /// [`Loader::compare_contents`] is enabled, since they don't work on network file
+    /// systems.
+    #[must_use]
+    pub fn watch<T>(&self, interval: Duration) -> Watcher<T>
+    where
+        T: DeserializeOwned + Serialize + Default + Send + 'static,
+    {
+        watch(self.clone(), self.polling(interval))
+    }
+
+    /// Loads the configuration into a [`SharedConfig`], then keeps it up to date like a
+    /// [`Loader::watch`] does, with the same `interval`, until every clone of the
+    /// [`SharedConfig`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the configuration can't be loaded at first, like
+    /// [`Loader::load`]. The later versions that fail to load are reported to the
+    /// [`SharedConfig::on_failure`] callbacks and skipped, keeping the last valid one.
+    pub fn watch_shared<T>(&self, interval: Duration) -> Result<SharedConfig<T>>
+    where
+        T: DeserializeOwned + Serialize + Default + Send + Sync + 'static,
+    {
+        let stop = CancellationToken::default();
+        let (loaded, report): (T, _) = self.load_with_report()?;
+        let initial = serialized(&loaded);
+        let shared = SharedConfig::watched(loaded, stop.clone());
+        let updater = shared.updater();
+        updater.remember(ReloadRecord::now(report.path, None));
+        spawn(
+            self.clone(),
+            self.polling(interval),
+            stop,
+            initial,
+            move |event, record| updater.deliver(event, record),
+        );
+        Ok(shared)
+    }
+
+    /// Loads the sections of the configuration at `paths` (dotted paths, like `logging` or
+    /// `server.tls`) into a [`SharedConfig`] of the tuple `S` of their structs, then keeps
+    /// them up to date like [`Loader::watch_shared`] does, with the same `interval`.
+    ///
+    /// Meant for applications made of subsystems configured by structs of their own, e.g.
+    /// from different crates: each new version is validated as a whole, every section
+    /// matching its struct, before all of them replace the current ones at once. The
+    /// subsystems never observe a mix of versions, and none of them is updated when the
+    /// section of any of them is invalid:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize)]
+    /// # struct LoggingConfig { level: String }
+    /// # #[derive(serde::Deserialize)]
+    /// # struct ServerConfig { port: u16 }
+    /// # fn main() -> nickelodeon::Result<()> {
+    /// let config = nickelodeon::Loader::new("my_app")
+    ///     .watch_sections::<(LoggingConfig, ServerConfig)>(
+    ///         ["logging", "server"],
+    ///         std::time::Duration::from_secs(1),
+    ///     )?;
+    ///
+    /// let current = config.load();
+    /// let (logging, server) = &*current;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the configuration can't be loaded at first, like
+    /// [`Loader::load`], or if any of its sections doesn't match its struct, with
+    /// [`Error::InvalidFields`] listing the mismatched fields of every section.
+    pub fn watch_sections<S>(&self, paths: S::Paths, interval: Duration) -> Result<SharedConfig<S>>
+    where
+        S: Sections + Send + Sync + 'static,
+        S::Paths: Send + 'static,
+    {
+        let stop = CancellationToken::default();
+        let (whole, report): (Value, _) = self.load_with_report()?;
+        let loaded =
+            S::decode(&whole, &paths, self.messages.as_ref()).map_err(Error::InvalidFields)?;
+        let shared = SharedConfig::watched(loaded, stop.clone());
+        let updater = shared.updater();
+        updater.remember(ReloadRecord::now(report.path, None));
+        let decoding = self.clone();
+        spawn(
+            self.clone(),
+            self.polling(interval),
+            stop,
+            Some(whole),
+            move |event: Event<Value>, mut record| {
+                let decoded = match event {
+                    Event::Updated(update) => {
+                        match S::decode(&update.config, &paths, decoding.messages.as_ref()) {
+                            Ok(config) => Event::Updated(Update {
+                                config,
+                                changes: update.changes,
+                            }),
+                            Err(errors) => {
+                                let failure = Failure {
+                                    diagnostics: decoding
+                                        .diagnostics_of(&Error::InvalidFields(errors)),
+                                };
+                                record.failure = Some(failure.clone());
+                                Event::Failed(failure)
+                            }
+                        }
+                    }
+                    Event::Failed(failure) => Event::Failed(failure),
+                };
+                updater.deliver(decoded, record)
+            },
+        );
+        Ok(shared)
+    }
+
+    /// Returns a [`SharedConfig`] holding `T::default()` right away, for applications that
+    /// can't wait for the configuration to start, like GUIs that must show a window
+    /// instantly. The configuration is loaded in the background, through the
+    /// [`Loader::offload`], then replaces the default one, calling the
+    /// [`SharedConfig::on_change`] callbacks with the settings that differ from the defaults.
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct MyConfig { theme: String }
+    /// # fn apply_theme(theme: &str) {}
+    /// let config = nickelodeon::Loader::new("my_app").load_in_background::<MyConfig>();
+    /// config.on_change(|config, _| apply_theme(&config.theme));
+    /// apply_theme(&config.load().theme);
+    /// ```
+    ///
+    /// A configuration that fails to load is reported to the [`SharedConfig::on_failure`]
+    /// callbacks, keeping the default one. Either way, the attempt is recorded in the
+    /// [`SharedConfig::history`].
+    #[must_use]
+    pub fn load_in_background<T>(&self) -> SharedConfig<T>
+    where
+        T: DeserializeOwned + Serialize + Default + Send + Sync + 'static,
+    {
+        let shared = SharedConfig::new(T::default());
+        let updater = shared.updater();
+        let loader = self.clone();
+        self.offload.offload(Box::new(move || {
+            let (event, record) = match loader.load_with_report::<T>() {
+                Ok((config, report)) => {
+                    let changes = match (serialized(&T::default()), serialized(&config)) {
+                        (Some(old), Some(new)) => {
+                            ChangeSet::between(&old, &new, loader.secret_fields())
+                        }
+                        _ => ChangeSet::default(),
+                    };
+                    let record = ReloadRecord::now(report.path, None);
+                    (Event::Updated(Update { config, changes }), record)
+                }
+                Err(error) => {
+                    let failure = Failure {
+                        diagnostics: loader.diagnostics_of(&error),
+                    };
+                    let path = first_existing_config(&loader.locations());
+                    let record = ReloadRecord::now(path, Some(failure.clone()));
+                    (Event::Failed(failure), record)
+                }
+            };
+            updater.deliver(event, record);
+        }));
+        shared
+    }
+
+    /// Returns how the watches of this loader check the configuration every `interval`,
+    /// installing the `SIGHUP` handler if needed.
+    fn polling(&self, interval: Duration) -> Polling {
+        if self.reload_on_hangup {
+            hangup::install();
+        }
+        Polling::new(
+            interval,
+            self.debounce,
+            self.reload_on_hangup,
+            self.compare_contents,
+        )
+    }
+
+    /// Returns every location where the configuration file is looked for: the one given
+    /// by [`Loader::config_path_from_flag`], or the standard ones.
+    pub(crate) fn locations(&self) -> Vec<PathBuf> {
+        match (&self.config_path_from_flag, &self.mount) {
+            (Some(path), _) => vec![path.clone()],
+            // The link swapped on updates changes even if no key is added or removed.
+            (None, Some(dir)) => vec![dir.clone(), dir.join(crate::mount::DATA)],
+            (None, None) => all_location_candidates(&self.app),
+        }
+    }
+
+    /// Locates and evaluates the configuration, returning a [`ProgramHandle`] to deserialize
+    /// it into several types, or query several of its fields, from this single evaluation.
+    ///
+    /// Returns `None` if no configuration file is found (unless the loader is
+    /// [`Loader::required`]). Caches and memoization are not used.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated.
+    pub fn program(&self) -> Result<Option<ProgramHandle>> {
+        self.program_with(&[])
+    }
+
+    /// Same as [`Loader::program`], but only evaluates the field of the configuration at
+    /// `path` (a dotted path, like `server`), usually a section: tools needing a single
+    /// block of a large configuration don't pay for evaluating and exporting the rest. The
+    /// paths queried through the [`ProgramHandle`] are relative to that field.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the found config file can't be read or evaluated, or if it has
+    /// no field at `path`.
+    pub fn program_field(&self, path: &str) -> Result<Option<ProgramHandle>> {
+        let field: Vec<String> = path.split('.').map(str::to_owned).collect();
+        self.program_with(&field)
+    }
+
+    /// Evaluates the configuration (or only its nested `field`, when not empty) into a
+    /// [`ProgramHandle`].
+    fn program_with(&self, field: &[String]) -> Result<Option<ProgramHandle>> {
+        let (program, report) = self.load_with(
+            field,
+            |_| None,
+            |rt, vm, sink| {
+                Ok(Some(ProgramHandle::new(
+                    rt,
+                    vm,
+                    sink.clone(),
+                    Arc::clone(&self.messages),
+                )))
+            },
+        )?;
+        Ok(program.map(|mut handle| {
+            handle.set_report(report);
+            handle
+        }))
+    }
+
+    /// Returns the [`Loader::imports`] policy, restricted to the directory of the
+    /// configuration file for [`Loader::pure`] loaders.
+    fn import_policy(&self) -> &ImportPolicy {
+        if self.pure && self.imports != ImportPolicy::Deny {
+            &PURE_IMPORTS
+        } else {
+            &self.imports
+        }
+    }
+
+    /// Returns the [`Loader::import_search_path`]s whose files can always be imported: none
+    /// for [`Loader::pure`] loaders.
+    fn importable_search_paths(&self) -> &[PathBuf] {
+        if self.pure {
+            &[]
+        } else {
+            &self.search_paths
+        }
+    }
+
+    /// Returns the resolvers of the references. Those of [`Loader::pure`] loaders refuse to
+    /// resolve them, since they look outside of the configuration directory.
+    fn resolvers(&self) -> Vec<(String, Resolver)> {
+        if !self.pure {
+            return self.resolvers.clone();
+        }
+        self.resolvers
+            .iter()
+            .map(|(scheme, _resolver)| {
+                let refused: Resolver = Arc::new(|_reference: &str| {
+                    Err("references aren't resolved by pure loaders".to_owned())
+                });
+                (scheme.clone(), refused)
+            })
+            .collect()
+    }
+
+    /// Returns the [`Loader::prelude`]s, followed by the [`Loader::host_facts`] (unless the
+    /// loader is [`Loader::pure`]).
+    fn preludes(&self) -> Vec<Prelude> {
+        let host = self
+            .host_facts
+            .as_ref()
+            .filter(|_| !self.pure)
+            .map(HostFacts::prelude);
+        self.preludes.iter().cloned().chain(host).collect()
+    }
+
+    /// Fails with [`Error::Cancelled`] if the [`Loader::cancellation`] token was cancelled.
+    fn check_cancellation(&self) -> Result<()> {
+        if self.cancellation.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Tells whether evaluated configurations can be reused: restricted imports and limits
+    /// are only enforced by the evaluation.
+    fn reusable(&self) -> bool {
+        *self.import_policy() == ImportPolicy::Allow && self.limits == Limits::default()
+    }
+
+    /// Returns the directory of the [`Loader::disk_cache`], if it is used for the
+    /// configuration `path`.
+    fn cache_location(&self, path: &Path) -> Option<PathBuf> {
+        // The host facts are read at each load, which the cache can't tell.
+        if !self.reusable()
+            || self.host_facts.is_some()
+            || encrypted(path)
+            || !self.secrets.is_empty()
+        {
+            return None;
+        }
+        match &self.disk_cache {
+            CacheLocation::Off => None,
+            CacheLocation::Default => disk_cache::default_dir(&self.app),
+            CacheLocation::In(dir) => Some(dir.clone()),
+        }
+    }
+
+    /// Returns the key the configuration `path`, as evaluated by this loader, is kept
+    /// under in the [`Loader::disk_cache`].
+    fn cache_key(&self, path: &Path) -> String {
+        disk_cache::key(path, &[&self.cache_options()])
+    }
+
+    /// Identifies the options changing the evaluation of a configuration: loaders renaming
+    /// fields, merging defaults or applying preludes and contracts of their own don't share
+    /// their evaluations.
+    fn cache_options(&self) -> String {
+        // Evaluations are only reused by the same version of the crate, so the debug output
+        // of the options is stable enough.
+        let options = format!(
+            "{:?}",
+            (
+                &self.deprecations,
+                &self.preludes,
+                &self.contracts,
+                &self.defaults
+            )
+        );
+        disk_cache::hash(options.as_bytes())
+    }
+
+    /// Forgets the configurations memoized by this loader (and the ones with the same
+    /// options), so they are evaluated again the next time they are loaded.
+    pub(crate) fn forget_memoized(&self) {
+        memo::forget(&self.cache_options());
+    }
+
+    /// Returns the configuration `path`, evaluated to `rt`, as kept to be reused by later
+    /// loads, unless it isn't.
+    fn to_reuse(&self, path: &Path, rt: &RichTerm) -> Option<Value> {
+        let memoize = self.memoize && self.reusable();
+        (memoize || self.cache_location(path).is_some())
+            .then(|| to_json(rt).ok())
+            .flatten()
+    }
+
+    /// Keeps the configuration `path`, exported to `value`, to be reused by later loads. It
+    /// isn't cached on disk if `secrets` were deserialized from it.
+    fn reuse_later(&self, path: &Path, value: Value, report: &LoadReport, secrets: bool) {
+        if let Some(dir) = self.cache_location(path).filter(|_| !secrets) {
+            disk_cache::store(&dir, &self.cache_key(path), &report.files(), value.clone());
+        }
+        if self.memoize && self.reusable() {
+            memo::remember(&self.cache_options(), path, value, report.imports.clone());
+        }
+    }
+
+    /// Returns the configuration `path` as evaluated by an earlier load, memoized or cached
+    /// on disk, together with the files it imports.
+    ///
+    /// Only the references are kept, so they are resolved again, and secrets that changed
+    /// since are seen. A reference that can't be resolved evaluates the configuration again,
+    /// which reports it.
+    fn recall<T: DeserializeOwned>(&self, path: &Path) -> Option<(T, Vec<PathBuf>)> {
+        let (mut value, imports): (Value, _) = (self.memoize && self.reusable())
+            .then(|| memo::recall(&self.cache_options(), path))
+            .flatten()
+            .or_else(|| {
+                disk_cache::lookup(&self.cache_location(path)?, &self.cache_key(path), path)
+            })?;
+        resolve_exported_references(&mut value, &self.resolvers(), "").ok()?;
+        let config = resolving_against(Some(path), || serde_json::from_value(value).ok())?;
+        Some((config, imports))
+    }
+
+    /// Checks the configuration file at `path`, found among the `existing` ones, before
+    /// evaluating it, failing or adding warnings to `report` depending on the options of the
+    /// loader.
+    fn inspect(
+        &self,
+        path: &Path,
+        source: Source,
+        existing: &[PathBuf],
+        sink: &mut DiagnosticSink,
+        report: &mut LoadReport,
+    ) -> Result<()> {
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.len() > self.max_file_size) {
+            return Err(Error::ConfigTooLarge(
+                path.to_path_buf(),
+                self.max_file_size,
+            ));
+        }
+
+        if source != Source::Flag {
+            if self.strict_ambiguity {
+                if let Some(other) = ambiguous_sibling(path) {
+                    return Err(Error::AmbiguousConfig(path.to_path_buf(), other));
+                }
+            }
+            let shadowed = shadowed_configs(existing, path);
+            if !shadowed.is_empty() {
+                warn(self.shadowed_warning(path, &shadowed), sink, report);
+            }
+        }
+
+        if is_yaml(path) {
+            let warning = Diagnostic {
+                path: Some(path.to_path_buf()),
+                severity: Severity::Warning,
+                ..Diagnostic::error(self.messages.message(&Message::DeprecatedFormat("YAML")))
+            };
+            warn(warning, sink, report);
+            if let Some(callback) = &self.on_yaml_config {
+                callback(path);
+            }
+        }
+
+        if self.permission_check != PermissionCheck::Off {
+            if let Some(reason) = insecure(path, self.messages.as_ref()) {
+                if self.permission_check == PermissionCheck::Deny {
+                    return Err(Error::InsecurePermissions(path.to_path_buf(), reason));
+                }
+                let warning = Diagnostic {
+                    path: Some(path.to_path_buf()),
+                    severity: Severity::Warning,
+                    ..Diagnostic::error(reason)
+                };
+                warn(warning, sink, report);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the [`Loader::remote_config`]s in turn, if any, returning the path of the local
+    /// copy of the first one fetched (or the first one fetched earlier, with a warning for
+    /// each, if none can be fetched now).
+    #[cfg(feature = "remote")]
+    fn fetch_remote(&self, sink: &mut DiagnosticSink, report: &mut LoadReport) -> Option<PathBuf> {
+        let dir = self.remote_dir();
+        let mut prefetched = self.fetched_remotes.clone().unwrap_or_default().into_iter();
+        let mut fallback = None;
+        for remote in self.remotes() {
+            let fetched = prefetched.next().unwrap_or_else(|| remote.fetch(&dir));
+            let reason = match fetched {
+                Ok(copy) => return Some(copy),
+                Err(reason) => reason,
+            };
+            let earlier = remote.cached(&dir);
+            let copy = earlier.is_file().then_some(earlier);
+            let message = Message::RemoteUnavailable {
+                url: remote.url(),
+                reason: &reason,
+            };
+            let warning = Diagnostic {
+                path: copy.clone(),
+                severity: Severity::Warning,
+                ..Diagnostic::error(self.messages.message(&message))
+            };
+            warn(warning, sink, report);
+            fallback = fallback.or(copy);
+        }
+        fallback
+    }
+
+    /// Returns the [`Loader::remote_config`]s to fetch, in order, unless a configuration
+    /// file is given instead or the loader is [`Loader::pure`].
+    #[cfg(feature = "remote")]
+    fn remotes(&self) -> Vec<crate::remote::Remote> {
+        if self.config_path_from_flag.is_some() || self.pure {
+            return Vec::new();
+        }
+        self.remotes
+            .iter()
+            .map(|(url, timeout)| {
+                crate::remote::Remote::new(url, timeout.unwrap_or(self.remote_timeout))
+            })
+            .collect()
+    }
+
+    /// Returns the directory keeping the copies of the [`Loader::remote_config`].
+    #[cfg(feature = "remote")]
+    fn remote_dir(&self) -> PathBuf {
+        match &self.disk_cache {
+            CacheLocation::In(dir) => dir.clone(),
+            CacheLocation::Off | CacheLocation::Default => disk_cache::default_dir(&self.app)
+                .unwrap_or_else(|| std::env::temp_dir().join("nickelodeon").join(&self.app)),
+        }
+        .join("remote")
+    }
+
+    /// Without the `remote` feature, there is no [`Loader::remote_config`] to fetch.
+    #[cfg(not(feature = "remote"))]
+    #[allow(clippy::unused_self)]
+    const fn fetch_remote(
+        &self,
+        _sink: &mut DiagnosticSink,
+        _report: &mut LoadReport,
+    ) -> Option<PathBuf> {
+        None
+    }
+
+    /// Warns that the configuration files in `shadowed` are ignored, since `used` was found
+    /// first.
+    fn shadowed_warning(&self, used: &Path, shadowed: &[PathBuf]) -> Diagnostic {
+        Diagnostic {
+            path: Some(used.to_path_buf()),
+            severity: Severity::Warning,
+            notes: shadowed
+                .iter()
+                .map(|path| self.messages.message(&Message::Ignored(path)))
+                .collect(),
+            ..Diagnostic::error(self.messages.message(&Message::Shadowed))
+        }
+    }
+
+    /// Locates and evaluates the configuration (or only its nested `field`, when not empty),
+    /// turning the evaluated term into a `T` with `deserialize_term`, unless `recall` returns
+    /// it from an earlier evaluation.
+    fn load_with<T, R, D>(
+        &self,
+        field: &[String],
+        recall: R,
+        deserialize_term: D,
+    ) -> Result<(T, LoadReport)>
+    where
+        T: Default,
+        R: FnOnce(&Path) -> Option<(T, Vec<PathBuf>)>,
+        D: FnOnce(RichTerm, VirtualMachine<Cache, LimitedCache>, &mut DiagnosticSink) -> Result<T>,
+    {
+        let started = Instant::now();
+        let mut report = LoadReport::default();
+        let mut sink = self.diagnostics.clone();
+
+        self.check_cancellation()?;
+        let fetched = self.fetch_remote(&mut sink, &mut report);
+        // Listed once, as listing them queries the environment and the current directory.
+        let candidates = match (&self.config_path_from_flag, &self.mount) {
+            (Some(_), _) => Vec::new(),
+            (None, Some(dir)) => vec![dir.clone()],
+            (None, None) => all_location_candidates(&self.app),
+        };
+        // Probed once too, telling both which one is used and which ones it shadows.
+        let existing = match (&self.config_path_from_flag, &self.mount) {
+            (None, None) => traced(Stage::Discovery, None, || existing_configs(&candidates)),
+            _ => Vec::new(),
+        };
+        let found = match (&self.config_path_from_flag, &self.mount) {
+            (Some(path), _) => Some((path.clone(), Source::Flag)),
+            (None, _) if fetched.is_some() => fetched.map(|path| (path, Source::System)),
+            (None, Some(dir)) => dir.is_dir().then(|| (dir.clone(), Source::System)),
+            (None, None) => existing.first().cloned().map(|path| {
+                let source = source_of(&self.app, &path);
+                (path, source)
+            }),
+        };
+
+        let value = match found {
+            None if self.required => {
+                return Err(Error::ConfigNotFound(candidates));
+            }
+            None if self.defaults.is_some() => {
+                report.searched = candidates;
+                catching_panics(Path::new(DEFAULTS), || {
+                    let (mut rt, vm) = evaluate_defaults(self, field, &mut sink)?;
+                    resolve_references(&mut rt, &self.resolvers(), &field.join("."))?;
+                    traced(Stage::Deserialization, None, || {
+                        deserialize_term(rt, vm, &mut sink)
+                    })
+                })?
+            }
+            None => {
+                report.searched = candidates;
+                T::default()
+            }
+            Some((path, source)) => {
+                self.check_cancellation()?;
+                report.path = Some(path.clone());
+                report.layers.push(path.clone());
+                self.inspect(&path, source, &existing, &mut sink, &mut report)?;
+
+                let whole = field.is_empty();
+                if let Some((value, imports)) = recall(&path) {
+                    report.cache_hits = report.cache_hits.saturating_add(1);
+                    report.imports = imports;
+                    value
+                } else {
+                    catching_panics(&path, || {
+                        let evaluation_started = Instant::now();
+                        let (mut rt, vm) = evaluate(self, &path, field, &mut sink)?;
+                        report.evaluation_duration = evaluation_started.elapsed();
+
+                        if whole {
+                            let warnings =
+                                remap(&mut rt, &self.deprecations, self.messages.as_ref());
+                            report.warnings.extend(emit(
+                                vm.import_resolver(),
+                                &warnings,
+                                &mut sink,
+                            ));
+                            report.provenance =
+                                Provenance::track(&rt, source, vm.import_resolver().files());
+                        }
+                        report.imports = import_closure(vm.import_resolver(), &path);
+                        // Exported before the references are resolved, so the memo and the disk
+                        // cache never keep the secrets they point to.
+                        let reused = whole.then(|| self.to_reuse(&path, &rt)).flatten();
+                        let resolved =
+                            resolve_references(&mut rt, &self.resolvers(), &field.join("."));
+                        if let Err(unresolved) = resolved {
+                            // Whether secrets would be deserialized from it isn't known, so it
+                            // is only memoized.
+                            if let Some(value) = reused {
+                                self.reuse_later(&path, value, &report, true);
+                            }
+                            return Err(unresolved);
+                        }
+
+                        let (deserialized, secrets) =
+                            traced(Stage::Deserialization, Some(&path), || {
+                                resolving_against(Some(&path), || {
+                                    detecting_secrets(|| deserialize_term(rt, vm, &mut sink))
+                                })
+                            });
+                        let config = deserialized?;
+                        if let Some(value) = reused {
+                            self.reuse_later(&path, value, &report, secrets);
+                        }
+                        Ok(config)
+                    })?
+                }
+            }
+        };
+
+        report.total_duration = started.elapsed();
+        Ok((value, report))
+    }
+}
+
+/// Tells whether the configuration file at `path` is encrypted, so it is never written to
+/// the disk cache decrypted.
+#[cfg(any(feature = "age", feature = "sops"))]
+fn encrypted(path: &Path) -> bool {
+    #[cfg(feature = "age")]
+    if crate::age::is_encrypted(path) {
+        return true;
+    }
+    #[cfg(feature = "sops")]
+    if crate::sops::is_encrypted_file(path) {
+        return true;
+    }
+    false
+}
+
+/// Configuration files can't be encrypted without the `age` and `sops` features.
+#[cfg(not(any(feature = "age", feature = "sops")))]
+const fn encrypted(_path: &Path) -> bool {
+    false
+}
+
+/// Writes `warning` to `sink` and adds it to `report`.
+fn warn(warning: Diagnostic, sink: &mut DiagnosticSink, report: &mut LoadReport) {
+    let _ignored: io::Result<()> =
+        write!(sink, "{}", render(std::slice::from_ref(&warning), false));
+    report.warnings.push(warning);
+}
+
+/// Runs `load`, turning a panic into [`Error::EvaluationPanicked`] so a bug in Nickel can't
+/// take down the host application.
+///
+/// The panic hook still runs, so the panic is printed to `stderr` unless the application
+/// installed its own hook.
+fn catching_panics<T, L>(path: &Path, load: L) -> Result<T>
+where
+    L: FnOnce() -> Result<T>,
+{
+    panic::catch_unwind(AssertUnwindSafe(load)).unwrap_or_else(|payload| {
+        if let Some(limit) = payload.downcast_ref::<Limit>() {
+            return Err(Error::LimitExceeded(path.to_path_buf(), *limit));
+        }
+        if payload.is::<Cancelled>() {
+            return Err(Error::Cancelled);
+        }
+        if let Some(expired) = payload.downcast_ref::<Expired>() {
+            return Err(Error::EvaluationTimeout(
+                path.to_path_buf(),
+                expired.timeout(),
+            ));
+        }
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_owned())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(Error::EvaluationPanicked(path.to_path_buf(), message))
+    })
+}
+
+/// A cloneable handle over the [`Write`] where diagnostics are sent.
+#[derive(Clone)]
+pub(crate) struct DiagnosticSink(Arc<Mutex<dyn Write + Send>>);
+
+impl DiagnosticSink {
+    pub(crate) fn new<W>(sink: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(sink)))
+    }
+}
+
+impl Write for DiagnosticSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_poisoned| io::Error::other("diagnostic sink poisoned"))?
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0
+            .lock()
+            .map_err(|_poisoned| io::Error::other("diagnostic sink poisoned"))?
+            .flush()
+    }
+}
+
+/// Writes the human readable version of `error` to `sink`, and returns it as structured
+/// [`Diagnostic`]s.
+pub(crate) fn report<E>(cache: &mut Cache, error: E, sink: &mut DiagnosticSink) -> Vec<Diagnostic>
+where
+    E: IntoDiagnostics<codespan::FileId>,
+{
+    let stdlib_ids = cache.get_all_stdlib_modules_file_id();
+    let diagnostics = error.into_diagnostics(cache.files_mut(), stdlib_ids.as_ref());
+    emit(cache, &diagnostics, sink)
+}
+
+/// Writes the human readable version of `diagnostics` to `sink`, and returns them as
+/// structured [`Diagnostic`]s.
+///
+/// Failing to write a diagnostic must never hide the actual problem, so write errors are
+/// ignored.
+fn emit(
+    cache: &Cache,
+    diagnostics: &[codespan_reporting::diagnostic::Diagnostic<codespan::FileId>],
+    sink: &mut DiagnosticSink,
+) -> Vec<Diagnostic> {
+    let config = codespan_reporting::term::Config::default();
+    let mut writer = NoColor::new(sink);
+    for diagnostic in diagnostics {
+        let _ignored: std::result::Result<(), _> =
+            codespan_reporting::term::emit(&mut writer, &config, cache.files(), diagnostic);
+    }
+    diagnostics
+        .iter()
+        .map(|diagnostic| Diagnostic::from_codespan(diagnostic, cache.files()))
+        .collect()
+}
+
+/// Loads and evaluates the file located at [`path`] (or only its nested `field`, when not
+/// empty) with the options of `loader`, returning the fully evaluated term together with
+/// the virtual machine that holds the sources it refers to.
+///
+/// The standard library prepared by earlier loads on this thread is reused, which the terms
+/// returned then share.
+fn evaluate(
+    loader: &Loader,
+    path: &Path,
+    field: &[String],
+    sink: &mut DiagnosticSink,
+) -> Result<(RichTerm, VirtualMachine<Cache, LimitedCache>)> {
+    let mut cache = new_cache();
+    let read = traced(Stage::Read, Some(path), || -> io::Result<FileId> {
+        let main_id = add_config(&mut cache, path, loader)?;
+        search_imports(&mut cache, main_id, &loader.search_paths);
+        Ok(main_id)
+    });
+    let main_id = read.map_err(|err| {
+        let reason = err.to_string();
+        let message = loader.messages.message(&Message::ReadingFailed(&reason));
+        let _ignored: io::Result<()> = writeln!(sink, "{message}");
+        Error::ConfigFileReadingError(err.to_string())
+    })?;
+
+    let policy = loader.import_policy();
+    let search_paths = loader.importable_search_paths();
+    if let Some((file, import)) = forbidden_import(&mut cache, path, main_id, policy, search_paths)
+    {
+        return Err(Error::ForbiddenImport(file, import));
+    }
+
+    run(loader, cache, main_id, path, field, sink)
+}
+
+/// Adds the configuration file `path` to `cache`, decrypted when it's encrypted (and the
+/// feature decrypting it is enabled), returning its id. A directory is a
+/// [`Loader::kubernetes_mount`], whose keys are merged.
+#[cfg_attr(not(feature = "age"), allow(unused_variables))]
+fn add_config(cache: &mut Cache, path: &Path, loader: &Loader) -> io::Result<FileId> {
+    if path.is_dir() {
+        return Ok(add_read(cache, path, merged_keys(path)?));
+    }
+    #[cfg(feature = "age")]
+    if crate::age::is_encrypted(path) {
+        let decrypted = crate::age::decrypted(path, loader.age_identity.as_deref())?;
+        return Ok(add_read(cache, path, decrypted));
+    }
+    #[cfg(feature = "sops")]
+    if let Some(decrypted) = crate::sops::decrypted(path)? {
+        return Ok(add_read(cache, path, decrypted));
+    }
+    cache.add_file(path.to_path_buf())
+}
+
+/// Adds the `source` read (decrypted, or merged) from the configuration file `path` to
+/// `cache`, returning its id. It's named like the file, so its format is still told by its
+/// extension and its imports are still resolved next to it.
+fn add_read(cache: &mut Cache, path: &Path, source: String) -> FileId {
+    cache.add_string(nickel_lang_core::cache::normalize_path(path), source)
+}
+
+/// Evaluates the [`Loader::embedded_defaults`] of `loader` alone (or only their nested
+/// `field`, when not empty), for when no configuration file is found.
+fn evaluate_defaults(
+    loader: &Loader,
+    field: &[String],
+    sink: &mut DiagnosticSink,
+) -> Result<(RichTerm, VirtualMachine<Cache, LimitedCache>)> {
+    let mut cache = new_cache();
+    let main_id = cache.add_string("<no configuration>", String::from("{}"));
+    run(loader, cache, main_id, Path::new(DEFAULTS), field, sink)
+}
+
+/// Evaluates the source `main_id` of `cache`, read from `path`, with the options of
+/// `loader`.
+fn run(
+    loader: &Loader,
+    cache: Cache,
+    main_id: codespan::FileId,
+    path: &Path,
+    field: &[String],
+    sink: &mut DiagnosticSink,
+) -> Result<(RichTerm, VirtualMachine<Cache, LimitedCache>)> {
+    let deadline = loader.timeout.map(Deadline::start);
+    let mut limited = LimitedCache::with_limits(loader.limits, loader.cancellation.clone());
+    if let Some(expiring) = &deadline {
+        limited = limited.until(expiring);
+    }
+    let mut running = VirtualMachine::new_with_cache(cache, limited, sink.clone());
+    // Reaching one of the limits unwinds the evaluation, which is caught here to tear the
+    // machine down before unwinding further.
+    let interrupted = panic::catch_unwind(AssertUnwindSafe(|| {
+        traced(Stage::Evaluation, Some(path), || {
+            let (term, initial_env) = prepare_eval(
+                &mut running,
+                main_id,
+                &loader.preludes(),
+                loader.defaults.as_deref(),
+                &loader.contracts,
+            )?;
+            running.reset();
+            running.cache.restart();
+            running
+                .eval_full_for_export(access(term, field), &initial_env)
+                .map_err(nickel_lang_core::error::Error::from)
+        })
+    }));
+    drop(deadline);
+    let evaluation = match interrupted {
+        Ok(evaluation) => evaluation,
+        Err(payload) => {
+            tear_down(running);
+            panic::resume_unwind(payload);
+        }
+    };
+    let mut vm = running;
+
+    let rt: RichTerm = evaluation.map_err(|err| {
+        let blamed = blamed_field(vm.import_resolver(), main_id, &err);
+        let mut diagnostics = report(vm.import_resolver_mut(), err.clone(), sink);
+        if let (Some((culprit, custom)), Some(diagnostic)) = (blamed, diagnostics.first_mut()) {
+            let message = custom.unwrap_or_else(|| diagnostic.message.clone());
+            diagnostic.message = format!("`{culprit}`: {message}");
+            diagnostic.field = Some(culprit);
+        }
+        Error::NickelEvaluationError(err, diagnostics)
+    })?;
+
+    if let Some(limit) = oversized(&rt, loader.limits) {
+        return Err(Error::LimitExceeded(path.to_path_buf(), limit));
+    }
+
+    Ok((rt, vm))
+}
+
+/// The stack [`tear_down`] uses for each value allocated by the evaluation.
+const TEAR_DOWN_STACK_PER_ALLOCATION: usize = 4 * 1024;
+
+/// The stack [`tear_down`] uses at least.
+const TEAR_DOWN_STACK: usize = 1024 * 1024;
+
+/// The stack [`tear_down`] uses at most.
+const MAX_TEAR_DOWN_STACK: usize = 64 * 1024 * 1024;
+
+/// Drops `vm`, whose evaluation was interrupted.
+///
+/// Its environments can then be nested as deeply as the number of values it allocated (e.g.
+/// by an infinite recursion), and they are dropped recursively, which would overflow the
+/// stack of the thread: they are dropped on a stack large enough for them instead. Past
+/// [`MAX_TEAR_DOWN_STACK`], the machine is leaked: no stack could be reserved for it, and
+/// dropping it would take about as long as the evaluation did.
+#[allow(clippy::mem_forget)]
+fn tear_down(vm: VirtualMachine<Cache, LimitedCache>) {
+    let allocations = usize::try_from(vm.cache.allocations()).unwrap_or(usize::MAX);
+    let size = allocations
+        .saturating_mul(TEAR_DOWN_STACK_PER_ALLOCATION)
+        .max(TEAR_DOWN_STACK);
+    if size > MAX_TEAR_DOWN_STACK {
+        mem::forget(vm);
+    } else {
+        stacker::grow(size, move || drop(vm));
+    }
+}
+
+/// Wraps `term` into the accesses to its nested `field`, so only that field is evaluated.
+fn access(term: RichTerm, field: &[String]) -> RichTerm {
+    field.iter().fold(term, |record, name| {
+        Term::Op1(UnaryOp::StaticAccess(Ident::from(name.as_str())), record).into()
+    })
+}
+
+/// Loads, evaluates and deserializes the data in the file located at [`path`].
+#[cfg(test)]
+pub(crate) fn load<T: DeserializeOwned>(path: &Path, mut sink: DiagnosticSink) -> Result<T> {
+    let loader = Loader::new("nickelodeon_test");
+    let (rt, mut vm) = evaluate(&loader, path, &[], &mut sink)?;
+    deserialize(&rt, &mut vm, &mut sink, &English)
+}
+
+/// Serializes the evaluated configuration `rt` into JSON, like `nickel export` does.
+pub(crate) fn export(
+    rt: &RichTerm,
+    vm: &mut VirtualMachine<Cache, LimitedCache>,
+    sink: &mut DiagnosticSink,
+) -> Result<Value> {
+    serde_json::to_value(rt).map_err(|err| {
+        let message = err.to_string();
+        let diagnostics = report(
+            vm.import_resolver_mut(),
+            EvalError::DeserializationError(String::from("json"), message.clone(), rt.pos),
+            sink,
+        );
+        Error::RustDeserializationError(RustDeserializationError::Other(message), diagnostics)
+    })
+}
+
+/// Deserializes the evaluated term `rt`, reporting failures as Nickel diagnostics.
+///
+/// When a field is missing, the fields expected next to it are listed too.
+pub(crate) fn deserialize<T: DeserializeOwned>(
+    rt: &RichTerm,
+    vm: &mut VirtualMachine<Cache, LimitedCache>,
+    sink: &mut DiagnosticSink,
+    messages: &dyn Messages,
+) -> Result<T> {
+    let pos = rt.pos;
+
+    T::deserialize(rt.clone()).map_err(|err| {
+        let available = serde_json::to_value(rt)
+            .ok()
+            .and_then(|value| available_fields::<T>(&value));
+        let message = available.map_or_else(
+            || err.to_string(),
+            |fields| {
+                format!(
+                    "{err}; {}",
+                    messages.message(&Message::AvailableFields(fields))
+                )
+            },
+        );
+        let diagnostics = report(
+            vm.import_resolver_mut(),
+            EvalError::DeserializationError(String::from("nickel"), message, pos),
+            sink,
+        );
+        Error::RustDeserializationError(err, diagnostics)
+    })
+}
+
+/// Same as [`deserialize`], but reports every field that doesn't match `T` instead of the
+/// first one.
+fn deserialize_collecting_all_errors<T: DeserializeOwned>(
+    rt: &RichTerm,
+    sink: &mut DiagnosticSink,
+    messages: &dyn Messages,
+) -> Result<T> {
+    deserialize_collecting_errors(to_json(rt)?, messages).map_err(|errors| {
+        for error in &errors {
+            let _ignored: io::Result<()> = writeln!(sink, "error: {error}");
+        }
+        Error::InvalidFields(errors)
+    })
+}
+
+/// Same as [`deserialize`], but the fields that don't match `T` are replaced by their
+/// value in `T::default()` and returned instead of failing.
+fn deserialize_replacing_with_defaults<T>(
+    rt: &RichTerm,
+    sink: &mut DiagnosticSink,
+    messages: &dyn Messages,
+) -> Result<(T, Vec<FieldError>)>
+where
+    T: DeserializeOwned + Serialize + Default,
+{
+    let defaults =
+        exposing(Exposure::Revealed, || serde_json::to_value(T::default())).map_err(|err| {
+            Error::InvalidFields(vec![FieldError {
+                path: String::new(),
+                message: err.to_string(),
+            }])
+        })?;
+
+    match deserialize_with_defaults(to_json(rt)?, &defaults, messages) {
+        Ok((value, errors)) => {
+            for error in &errors {
+                let _ignored: io::Result<()> = writeln!(sink, "warning: {error}");
+            }
+            Ok((value, errors))
+        }
+        Err(errors) => {
+            for error in &errors {
+                let _ignored: io::Result<()> = writeln!(sink, "error: {error}");
+            }
+            Err(Error::InvalidFields(errors))
+        }
+    }
+}
+
+/// Converts the evaluated term `rt` into JSON, so it can be deserialized field by field.
+fn to_json(rt: &RichTerm) -> Result<Value> {
+    serde_json::to_value(rt).map_err(|err| {
+        Error::InvalidFields(vec![FieldError {
+            path: String::new(),
+            message: err.to_string(),
+        }])
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde::Deserialize;
+    use std::io;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    #[derive(Deserialize, Debug, Default, PartialEq)]
+    struct TestConfiguration {
+        pub test_value: String,
+    }
+
+    #[derive(Deserialize, Debug, Default)]
+    struct Paths {
+        data: crate::ResolvedPathBuf,
+    }
+
+    /// A `Write` that can be inspected after being handed over to a [`super::Loader`].
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .map_err(|_poisoned| io::Error::other("poisoned"))?
+                .write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod diagnostics {
+        use super::super::Loader;
+        use super::SharedBuffer;
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::Severity;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[test]
+        fn evaluation_errors_go_to_the_sink() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ test_value = 1 + "nick" }}"#).unwrap();
+            let captured = SharedBuffer::default();
+
+            let result: crate::Result<TestConfiguration> = Loader::new("some_app")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(captured.clone())
+                .load();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+            assert!(captured.contents().contains("error"));
+        }
+
+        #[test]
+        fn evaluation_errors_carry_structured_diagnostics() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{{\n  test_value = 1 + \"nick\",\n}}").unwrap();
+
+            let result: crate::Result<TestConfiguration> = Loader::new("some_app")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load();
+
+            let diagnostics = result.unwrap_err().diagnostics();
+            let first = diagnostics.first().unwrap();
+            assert_eq!(first.severity, Severity::Error);
+            assert_eq!(first.path.as_deref(), Some(ntf.path()));
+            assert_eq!(first.span.unwrap().start.line, 2);
+        }
+
+        #[test]
+        fn deserialization_errors_go_to_the_sink() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{{ test_value = 1 }}").unwrap();
+            let captured = SharedBuffer::default();
+
+            let result: crate::Result<TestConfiguration> = Loader::new("some_app")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(captured.clone())
+                .load();
+
+            assert!(matches!(result, Err(Error::RustDeserializationError(..))));
+            assert!(!captured.contents().is_empty());
+        }
+
+        #[test]
+        fn missing_file_is_reported_to_the_sink() {
+            let captured = SharedBuffer::default();
+
+            let result: crate::Result<TestConfiguration> = Loader::new("some_app")
+                .config_path_from_flag(Some("/this/file/does/not/exist.ncl".into()))
+                .diagnostics(captured.clone())
+                .load();
+
+            assert!(matches!(result, Err(Error::ConfigFileReadingError(_))));
+            assert!(captured.contents().starts_with("Error when reading input"));
+        }
+    }
+
+    #[cfg(test)]
+    mod collect_all_errors {
+        use super::super::Loader;
+        use crate::Error;
+        use crate::FieldError;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Deserialize, Debug, Default)]
+        struct Configuration {
+            #[allow(dead_code)]
+            name: String,
+            #[allow(dead_code)]
+            port: u16,
+        }
+
+        #[test]
+        fn every_mismatch_is_reported() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ name = 1, port = "80" }}"#).unwrap();
+
+            let result: crate::Result<Configuration> = Loader::new("some_app")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .collect_all_errors(true)
+                .load();
+
+            let Err(Error::InvalidFields(errors)) = result else {
+                panic!("expected invalid fields, got {result:?}");
+            };
+            let paths: Vec<&str> = errors
+                .iter()
+                .map(|error: &FieldError| error.path.as_str())
+                .collect();
+            assert_eq!(paths, vec!["name", "port"]);
+        }
+    }
+
+    #[cfg(test)]
+    mod load_with_report {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[test]
+        fn describes_the_file_used() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ test_value = "nick" }}"#).unwrap();
+
+            let (value, report) = Loader::new("some_app")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(value.test_value, "nick");
+            assert_eq!(report.path.as_deref(), Some(ntf.path()));
+            assert_eq!(report.layers, vec![ntf.path().to_path_buf()]);
+            assert!(report.warnings.is_empty());
+            assert!(report.evaluation_duration <= report.total_duration);
+        }
+
+        #[test]
+        fn defaults_have_no_path() {
+            let (value, report) = Loader::new("this_app_does_not_exist")
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(value, TestConfiguration::default());
+            assert_eq!(report.path, None);
+            assert!(report.layers.is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod required {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+
+        #[test]
+        fn lists_the_searched_locations() {
+            let result = Loader::new("this_app_does_not_exist")
+                .required(true)
+                .load::<TestConfiguration>();
+
+            let Err(Error::ConfigNotFound(searched)) = result else {
+                panic!("expected a missing configuration, got {result:?}");
+            };
+            assert!(!searched.is_empty());
+            assert!(searched
+                .iter()
+                .all(|path| path.to_string_lossy().contains("this_app_does_not_exist")));
+        }
+
+        #[test]
+        fn optional_configurations_report_the_searched_locations() {
+            let (_, report) = Loader::new("this_app_does_not_exist")
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert!(!report.searched.is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod deprecated_field {
+        use super::super::Loader;
+        use super::SharedBuffer;
+        use crate::Severity;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Deserialize, Debug, Default, PartialEq)]
+        struct Server {
+            address: String,
+        }
+
+        #[derive(Deserialize, Debug, Default, PartialEq)]
+        struct TestConfiguration {
+            name: String,
+            server: Server,
+        }
+
+        fn config(contents: &str) -> NamedTempFile {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{contents}").unwrap();
+            ntf
+        }
+
+        #[test]
+        fn remaps_the_old_names() {
+            let ntf = config("{\n  title = \"nick\",\n  server.addr = \"localhost\",\n}");
+            let buffer = SharedBuffer::default();
+
+            let (result, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(buffer.clone())
+                .deprecated_field("title", "name")
+                .deprecated_field("server.addr", "server.address")
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            let expected = TestConfiguration {
+                name: "nick".to_owned(),
+                server: Server {
+                    address: "localhost".to_owned(),
+                },
+            };
+            assert_eq!(result, expected);
+            assert_eq!(report.warnings.len(), 2);
+            let warning = report.warnings.first().unwrap();
+            assert_eq!(warning.severity, Severity::Warning);
+            assert_eq!(warning.message, "`title` is deprecated");
+            assert_eq!(warning.path.as_deref(), Some(ntf.path()));
+            assert_eq!(warning.span.unwrap().start.line, 2);
+            assert!(buffer.contents().contains("`server.addr` is deprecated"));
+        }
+
+        #[test]
+        fn the_new_name_wins() {
+            let ntf = config(r#"{ title = "old", name = "new", server.address = "localhost" }"#);
+
+            let (result, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .deprecated_field("title", "name")
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.name, "new");
+            assert_eq!(report.warnings.len(), 1);
+            assert_eq!(
+                report.warnings.first().unwrap().notes,
+                vec![
+                    "use `name` instead".to_owned(),
+                    "`name` is also set, so this value is ignored".to_owned(),
+                ]
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod provenance {
+        use super::super::Loader;
+        use crate::Source;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Deserialize, Debug, Default, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[derive(Deserialize, Debug, Default, PartialEq)]
+        struct TestConfiguration {
+            name: String,
+            server: Server,
+        }
+
+        #[test]
+        fn tracks_nested_fields() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(
+                ntf,
+                "{{\n  name = \"nick\",\n  server = {{\n    port = 80,\n  }},\n}}"
+            )
+            .unwrap();
+
+            let (_, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            let port = report.provenance.get("server.port").unwrap();
+            assert_eq!(port.source, Source::Flag);
+            assert_eq!(port.value, serde_json::json!(80));
+            assert_eq!(port.path.as_deref(), Some(ntf.path()));
+            assert_eq!(port.span.unwrap().start.line, 4);
+            assert!(report.provenance.get("server").is_some());
+            assert!(report.provenance.get("name").is_some());
+            assert_eq!(report.provenance.fields.len(), 3);
+        }
+
+        #[test]
+        fn defaults_have_no_provenance() {
+            let (_, report) = Loader::new("this_app_does_not_exist")
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert!(report.provenance.fields.is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod load_partial {
+        use super::super::Loader;
+        use serde::Deserialize;
+        use serde::Serialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Deserialize, Serialize, Debug, PartialEq)]
+        struct TestConfiguration {
+            name: String,
+            port: u16,
+        }
+
+        impl Default for TestConfiguration {
+            fn default() -> Self {
+                Self {
+                    name: "default".to_owned(),
+                    port: 8080,
+                }
+            }
+        }
+
+        #[test]
+        fn returns_the_best_effort_value() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ name = "nick", port = "80" }}"#).unwrap();
+
+            let (result, problems) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load_partial::<TestConfiguration>()
+                .unwrap();
+
+            let expected = TestConfiguration {
+                name: "nick".to_owned(),
+                port: 8080,
+            };
+            assert_eq!(result, expected);
+            let paths: Vec<&str> = problems.iter().map(|error| error.path.as_str()).collect();
+            assert_eq!(paths, vec!["port"]);
+        }
+    }
+
+    #[cfg(test)]
+    mod contracts {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        fn load(contents: &str) -> Error {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{contents}").unwrap();
+
+            Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load::<TestConfiguration>()
+                .unwrap_err()
+        }
+
+        #[test]
+        fn blame_points_to_the_field() {
+            let error = load(
+                r#"
+                let Port = std.contract.from_predicate (fun port => port > 0) in
+                {
+                  test_value = "nick",
+                  server = {
+                    port | Port = -1,
+                  },
+                }
+                "#,
+            );
+
+            let diagnostic = error.diagnostics().into_iter().next().unwrap();
+            assert_eq!(diagnostic.field.as_deref(), Some("server.port"));
+            assert!(diagnostic.message.starts_with("`server.port`: "));
+        }
+
+        #[test]
+        fn custom_contract_messages() {
+            let error = load(
+                r#"
+                let Port = fun label port =>
+                  if port > 0 then port
+                  else std.contract.blame_with_message "must be a positive number" label
+                in
+                {
+                  test_value = "nick",
+                  server.port | Port = -1,
+                }
+                "#,
+            );
+
+            let diagnostic = error.diagnostics().into_iter().next().unwrap();
+            assert_eq!(
+                diagnostic.message,
+                "`server.port`: must be a positive number"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod catching_panics {
+        use super::super::catching_panics;
+        use crate::Error;
+        use std::path::Path;
+
+        #[test]
+        fn panics_become_errors() {
+            let path = Path::new("/etc/app/config.ncl");
+
+            let result: crate::Result<()> = catching_panics(path, || panic!("boom"));
+
+            assert_eq!(
+                result,
+                Err(Error::EvaluationPanicked(
+                    path.to_path_buf(),
+                    "boom".to_owned()
+                ))
+            );
+        }
+
+        #[test]
+        fn results_go_through() {
+            let result = catching_panics(Path::new("config.ncl"), || Ok(1_u8));
+
+            assert_eq!(result, Ok(1_u8));
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod permission_check {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::PermissionCheck;
+        use std::fs::Permissions;
+        use std::io::Write as _;
+        use std::os::unix::fs::PermissionsExt as _;
+        use tempfile::NamedTempFile;
+
+        fn world_writable() -> NamedTempFile {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ test_value = "nick" }}"#).unwrap();
+            std::fs::set_permissions(ntf.path(), Permissions::from_mode(0o666)).unwrap();
+            ntf
+        }
+
+        #[test]
+        fn warn() {
+            let ntf = world_writable();
+
+            let (_, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .permission_check(PermissionCheck::Warn)
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(report.warnings.len(), 1);
+        }
+
+        #[test]
+        fn deny() {
+            let ntf = world_writable();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .permission_check(PermissionCheck::Deny)
+                .load::<TestConfiguration>();
+
+            assert!(matches!(result, Err(Error::InsecurePermissions(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod available_fields {
+        use super::super::Loader;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Deserialize, Debug, Default)]
+        struct Server {
+            #[allow(dead_code)]
+            host: String,
+            #[allow(dead_code)]
+            timeout: u64,
+        }
+
+        #[derive(Deserialize, Debug, Default)]
+        struct TestConfiguration {
+            #[allow(dead_code)]
+            server: Server,
+        }
+
+        #[test]
+        fn listed_when_a_field_is_missing() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ server = {{ host = "localhost" }} }}"#).unwrap();
+
+            let error = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load::<TestConfiguration>()
+                .unwrap_err();
+
+            let diagnostic = error.diagnostics().into_iter().next().unwrap();
+            let expected = "missing field `timeout`; available fields are `host`, `timeout`";
+            assert!(diagnostic.message.ends_with(expected), "{diagnostic:?}");
+        }
+    }
+
+    #[cfg(test)]
+    mod imports {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::ImportPolicy;
+        use std::path::PathBuf;
+        use tempfile::TempDir;
+
+        fn importing_config() -> (TempDir, PathBuf) {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(dir.path().join("name.ncl"), r#""nick""#).unwrap();
+            std::fs::write(&config, r#"{ test_value = import "name.ncl" }"#).unwrap();
+            (dir, config)
+        }
+
+        #[test]
+        fn allowed_by_default() {
+            let (_dir, config) = importing_config();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "nick");
+        }
+
+        #[test]
+        fn denied() {
+            let (_dir, config) = importing_config();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .imports(ImportPolicy::Deny)
+                .load::<TestConfiguration>();
+
+            assert_eq!(
+                result,
+                Err(Error::ForbiddenImport(config, PathBuf::from("name.ncl")))
+            );
+        }
+
+        #[test]
+        fn reported() {
+            let (dir, config) = importing_config();
+
+            let (_, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(report.imports, vec![dir.path().join("name.ncl")]);
+        }
+
+        #[test]
+        fn transitive_imports_are_files_to_watch() {
+            let (dir, config) = importing_config();
+            std::fs::write(dir.path().join("name.ncl"), r#"import "nick.ncl""#).unwrap();
+            std::fs::write(dir.path().join("nick.ncl"), r#""nick""#).unwrap();
+
+            let (_, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            let expected = vec![
+                config,
+                dir.path().join("name.ncl"),
+                dir.path().join("nick.ncl"),
+            ];
+            assert_eq!(report.files(), expected);
+        }
+
+        #[test]
+        fn config_directory() {
+            let (_dir, config) = importing_config();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .imports(ImportPolicy::ConfigDirectory)
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "nick");
+        }
+
+        #[test]
+        fn outside_of_the_config_directory() {
+            let dir = tempfile::tempdir().unwrap();
+            let app = dir.path().join("app");
+            std::fs::create_dir_all(&app).unwrap();
+            std::fs::write(dir.path().join("secret.ncl"), r#""nick""#).unwrap();
+            std::fs::write(app.join("name.ncl"), r#"import "../secret.ncl""#).unwrap();
+            let config = app.join("config.ncl");
+            std::fs::write(&config, r#"{ test_value = import "name.ncl" }"#).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .imports(ImportPolicy::ConfigDirectory)
+                .load::<TestConfiguration>();
+
+            let expected = Error::ForbiddenImport(
+                app.join("name.ncl").canonicalize().unwrap(),
+                PathBuf::from("../secret.ncl"),
+            );
+            assert_eq!(result, Err(expected));
+        }
+
+        #[test]
+        fn within() {
+            let (dir, config) = importing_config();
+            let other = tempfile::tempdir().unwrap();
+
+            let allowed = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .imports(ImportPolicy::Within(vec![dir.path().to_path_buf()]))
+                .load::<TestConfiguration>()
+                .unwrap();
+            let rejected = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .imports(ImportPolicy::Within(vec![other.path().to_path_buf()]))
+                .load::<TestConfiguration>();
+
+            assert_eq!(allowed.test_value, "nick");
+            assert!(matches!(rejected, Err(Error::ForbiddenImport(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod timeout {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use std::io::Write as _;
+        use std::time::Duration;
+        use std::time::Instant;
+        use tempfile::NamedTempFile;
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .timeout(Duration::from_millis(200));
+            (ntf, loader)
+        }
+
+        #[test]
+        fn in_time() {
+            let (_ntf, loader) = loader(r#"{ test_value = "nick" }"#);
+            let result = loader.load::<TestConfiguration>().unwrap();
+            assert_eq!(result.test_value, "nick");
+        }
+
+        #[test]
+        fn infinite_recursion() {
+            let (_ntf, loader) = loader(r#"let rec f = fun x => f x in { test_value = f "nick" }"#);
+            let result = loader.load::<TestConfiguration>();
+            assert!(matches!(result, Err(Error::EvaluationTimeout(..))));
+        }
+
+        #[test]
+        fn long_infinite_recursion() {
+            let (_ntf, loader) = loader("let rec f = fun x => f x in f 0");
+
+            let result = loader
+                .timeout(Duration::from_secs(3))
+                .load::<TestConfiguration>();
+
+            assert!(matches!(result, Err(Error::EvaluationTimeout(..))));
+        }
+
+        #[test]
+        fn respected() {
+            let (_ntf, loader) = loader("let rec f = fun x => f x in f 0");
+            let timeout = Duration::from_secs(1);
+
+            let started = Instant::now();
+            let result = loader.timeout(timeout).load::<TestConfiguration>();
+            let elapsed = started.elapsed();
+
+            assert!(matches!(result, Err(Error::EvaluationTimeout(..))));
+            assert!(elapsed >= timeout);
+            assert!(elapsed < timeout.saturating_add(Duration::from_millis(500)));
+        }
+    }
+
+    #[cfg(test)]
+    mod limits {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::Limit;
+        use crate::Limits;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        fn loader(source: &str, limits: Limits) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .limits(limits);
+            (ntf, loader)
+        }
+
+        #[test]
+        fn within_the_limits() {
+            let limits = Limits::default()
+                .max_steps(1_000)
+                .max_allocations(1_000)
+                .max_size(10);
+            let (_ntf, loader) = loader(r#"{ test_value = "nick" }"#, limits);
+
+            let result = loader.load::<TestConfiguration>().unwrap();
+
+            assert_eq!(result.test_value, "nick");
+        }
+
+        #[test]
+        fn steps() {
+            let source = r#"let rec f = fun x => f x in { test_value = f "nick" }"#;
+            let (_ntf, loader) = loader(source, Limits::default().max_steps(100_000));
+
+            let result = loader.load::<TestConfiguration>();
+
+            assert!(matches!(
+                result,
+                Err(Error::LimitExceeded(_, Limit::Steps(100_000)))
+            ));
+        }
+
+        #[test]
+        fn allocations() {
+            let source =
+                r#"{ test_value = "nick", numbers = std.array.generate (fun i => i) 10000 }"#;
+            let (_ntf, loader) = loader(source, Limits::default().max_allocations(1_000));
+
+            let result = loader.load::<TestConfiguration>();
+
+            assert!(matches!(
+                result,
+                Err(Error::LimitExceeded(_, Limit::Allocations(1_000)))
+            ));
+        }
+
+        #[test]
+        fn size() {
+            let source = r#"{ test_value = "nick", numbers = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10] }"#;
+            let (_ntf, loader) = loader(source, Limits::default().max_size(10));
+
+            let result = loader.load::<TestConfiguration>();
+
+            assert!(matches!(
+                result,
+                Err(Error::LimitExceeded(_, Limit::Size(10)))
+            ));
+        }
+    }
+
+    #[cfg(test)]
+    mod disk_cache {
+        use super::super::Loader;
+        use super::Paths;
+        use super::TestConfiguration;
+        use crate::LoadReport;
+        use std::path::Path;
+
+        fn load(config: &Path, cache: &Path) -> (TestConfiguration, LoadReport) {
+            Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.to_path_buf()))
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.to_path_buf())
+                .load_with_report::<TestConfiguration>()
+                .unwrap()
+        }
+
+        #[test]
+        fn reused_until_an_import_changes() {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            let name = dir.path().join("name.ncl");
+            std::fs::write(&config, r#"{ test_value = import "name.ncl" }"#).unwrap();
+            std::fs::write(&name, r#""nick""#).unwrap();
+
+            let (first, first_report) = load(&config, cache.path());
+            let (second, second_report) = load(&config, cache.path());
+            std::fs::write(&name, r#""nickel""#).unwrap();
+            let (third, third_report) = load(&config, cache.path());
+
+            assert_eq!(first.test_value, "nick");
+            assert_eq!(first_report.cache_hits, 0);
+            assert_eq!(second.test_value, "nick");
+            assert_eq!(second_report.cache_hits, 1);
+            assert_eq!(second_report.imports, vec![name]);
+            assert_eq!(third.test_value, "nickel");
+            assert_eq!(third_report.cache_hits, 0);
+        }
+
+        #[test]
+        fn kept_apart_for_other_options() {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ name = "nick" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf());
+            let renaming = loader.clone().deprecated_field("name", "test_value");
+            let checking = renaming.clone().contract("{ name | String, .. }");
+
+            let (plain, plain_report) = loader.load_with_report::<serde_json::Value>().unwrap();
+            let (renamed, renamed_report) =
+                renaming.load_with_report::<TestConfiguration>().unwrap();
+            let (checked, checked_report) =
+                checking.load_with_report::<TestConfiguration>().unwrap();
+            let (_, checked_again) = checking.load_with_report::<TestConfiguration>().unwrap();
+
+            assert_eq!(plain, serde_json::json!({ "name": "nick" }));
+            assert_eq!(plain_report.cache_hits, 0);
+            assert_eq!(renamed.test_value, "nick");
+            assert_eq!(renamed_report.cache_hits, 0);
+            assert_eq!(checked.test_value, "nick");
+            assert_eq!(checked_report.cache_hits, 0);
+            assert_eq!(checked_again.cache_hits, 1);
+        }
+
+        #[test]
+        fn not_with_secrets() {
+            #[derive(serde::Deserialize, Debug, Default)]
+            struct Database {
+                password: crate::Secret<String>,
+            }
+
+            let dir = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ password = "hunter2" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf());
+
+            let database: Database = loader.load().unwrap();
+            let (_, secret_report) = loader.load_with_report::<Database>().unwrap();
+            let with_secret_field = loader.secret_field("password");
+
+            assert_eq!(database.password.expose(), "hunter2");
+            assert_eq!(secret_report.cache_hits, 0);
+            assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 0);
+            assert_eq!(with_secret_field.cache_location(&config), None);
+        }
+
+        #[test]
+        fn paths_resolved_when_reused() {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ data = "data" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf());
+
+            let (first, _) = loader.load_with_report::<Paths>().unwrap();
+            let (second, second_report) = loader.load_with_report::<Paths>().unwrap();
+
+            assert_eq!(first.data.to_path_buf(), dir.path().join("data"));
+            assert_eq!(second_report.cache_hits, 1);
+            assert_eq!(second.data.to_path_buf(), dir.path().join("data"));
+        }
+    }
+
+    #[cfg(feature = "age")]
+    mod age {
+        use super::super::Loader;
+
+        #[test]
+        fn never_cached_on_disk() {
+            let dir = tempfile::tempdir().unwrap();
+            let loader = Loader::new("nickelodeon_test").cache_dir(dir.path().to_path_buf());
+
+            assert_eq!(
+                loader.cache_location(&dir.path().join("config.ncl.age")),
+                None
+            );
+            assert_eq!(
+                loader.cache_location(&dir.path().join("config.ncl")),
+                Some(dir.path().to_path_buf())
+            );
+        }
+    }
+
+    #[cfg(feature = "sops")]
+    mod sops {
+        use super::super::Loader;
+
+        #[test]
+        fn never_cached_on_disk() {
+            let dir = tempfile::tempdir().unwrap();
+            let encrypted = dir.path().join("config.json");
+            let plain = dir.path().join("plain.json");
+            std::fs::write(
+                &encrypted,
+                r#"{ "port": "ENC[AES256_GCM,data:OUI=,type:int]", "sops": { "mac": "x" } }"#,
+            )
+            .unwrap();
+            std::fs::write(&plain, r#"{ "port": 80 }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test").cache_dir(dir.path().join("cache"));
+
+            assert_eq!(loader.cache_location(&encrypted), None);
+            assert_eq!(
+                loader.cache_location(&plain),
+                Some(dir.path().join("cache"))
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod memoize {
+        use super::super::Loader;
+        use super::Paths;
+        use super::TestConfiguration;
+        use crate::forget_memoized;
+        use crate::LoadReport;
+        use std::path::Path;
+
+        fn load(config: &Path) -> (TestConfiguration, LoadReport) {
+            Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.to_path_buf()))
+                .diagnostics(std::io::sink())
+                .memoize(true)
+                .load_with_report::<TestConfiguration>()
+                .unwrap()
+        }
+
+        #[test]
+        fn reused_until_forgotten() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ test_value = "nick" }"#).unwrap();
+
+            let (first, first_report) = load(&config);
+            std::fs::write(&config, r#"{ test_value = "nickel" }"#).unwrap();
+            let (second, second_report) = load(&config);
+            forget_memoized();
+            let (third, third_report) = load(&config);
+
+            assert_eq!(first.test_value, "nick");
+            assert_eq!(first_report.cache_hits, 0);
+            assert_eq!(second.test_value, "nick");
+            assert_eq!(second_report.cache_hits, 1);
+            assert_eq!(third.test_value, "nickel");
+            assert_eq!(third_report.cache_hits, 0);
+        }
+
+        #[test]
+        fn paths_resolved_when_reused() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ data = "data" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .memoize(true);
+
+            let (first, _) = loader.load_with_report::<Paths>().unwrap();
+            let (second, second_report) = loader.load_with_report::<Paths>().unwrap();
+
+            assert_eq!(first.data.to_path_buf(), dir.path().join("data"));
+            assert_eq!(second_report.cache_hits, 1);
+            assert_eq!(second.data.to_path_buf(), dir.path().join("data"));
+        }
+
+        #[test]
+        fn forgotten_by_loader() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ test_value = "nick" }"#).unwrap();
+            let forgetting = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .memoize(true);
+            let remembering = forgetting.clone().deprecated_field("name", "test_value");
+
+            let _first: TestConfiguration = forgetting.load().unwrap();
+            let _other: TestConfiguration = remembering.load().unwrap();
+            std::fs::write(&config, r#"{ test_value = "nickel" }"#).unwrap();
+            forgetting.forget_memoized();
+            let (forgotten, forgotten_report) =
+                forgetting.load_with_report::<TestConfiguration>().unwrap();
+            let (remembered, remembered_report) =
+                remembering.load_with_report::<TestConfiguration>().unwrap();
+
+            assert_eq!(forgotten.test_value, "nickel");
+            assert_eq!(forgotten_report.cache_hits, 0);
+            assert_eq!(remembered.test_value, "nick");
+            assert_eq!(remembered_report.cache_hits, 1);
+        }
+    }
+
+    #[cfg(test)]
+    mod load_field {
+        use super::super::Loader;
+        use crate::Error;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink());
+            (ntf, loader)
+        }
+
+        #[test]
+        fn only_the_field_is_evaluated() {
+            let (_ntf, loader) = loader(
+                r#"
+                {
+                  server = { tls = { cert_path = "/etc/cert.pem" } },
+                  broken = std.fail_with "never evaluated",
+                }
+                "#,
+            );
+
+            let result = loader.load_field::<String>("server.tls.cert_path").unwrap();
+
+            assert_eq!(result.as_deref(), Some("/etc/cert.pem"));
+        }
+
+        #[test]
+        fn missing_field() {
+            let (_ntf, loader) = loader(r#"{ server = { host = "localhost" } }"#);
+
+            let result = loader.load_field::<u16>("server.port");
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod program {
+        use super::super::Loader;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Deserialize, PartialEq, Eq)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Eq)]
+        struct Logging {
+            verbose: bool,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Eq)]
+        struct Whole {
+            server: Server,
+            logging: Logging,
+        }
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink());
+            (ntf, loader)
+        }
+
+        #[test]
+        fn queried_several_times() {
+            let (ntf, loader) = loader(
+                r#"{ server = { host = "localhost", port = 80 }, logging = { verbose = true } }"#,
+            );
+
+            let mut program = loader.program().unwrap().unwrap();
+            let server: Option<Server> = program.get("server").unwrap();
+            let verbose: Option<bool> = program.get("logging.verbose").unwrap();
+            let whole: Whole = program.deserialize().unwrap();
+
+            assert_eq!(
+                server,
+                Some(Server {
+                    host: "localhost".to_owned(),
+                    port: 80
+                })
+            );
+            assert_eq!(verbose, Some(true));
+            assert_eq!(whole.logging, Logging { verbose: true });
+            assert_eq!(program.report().path.as_deref(), Some(ntf.path()));
+        }
+
+        #[test]
+        fn missing_field() {
+            let (_ntf, loader) = loader(r#"{ server = { host = "localhost" } }"#);
+
+            let mut program = loader.program().unwrap().unwrap();
+
+            assert_eq!(program.get::<u16>("server.port").unwrap(), None);
+            assert_eq!(program.get::<u16>("server.host.port").unwrap(), None);
+        }
+
+        #[test]
+        fn no_configuration() {
+            let loader = Loader::new("nickelodeon_test_without_configuration");
+
+            assert!(loader.program().unwrap().is_none());
+        }
+
+        #[test]
+        fn single_section() {
+            let (_ntf, loader) = loader(
+                r#"
+                {
+                  server = { host = "localhost", port = 80 },
+                  broken = std.fail_with "never evaluated",
+                }
+                "#,
+            );
+
+            let mut program = loader.program_field("server").unwrap().unwrap();
+            let server: Server = program.deserialize().unwrap();
+            let port: Option<u16> = program.get("port").unwrap();
+
+            assert_eq!(
+                server,
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 80
+                }
+            );
+            assert_eq!(port, Some(80));
+        }
+    }
+
+    #[cfg(test)]
+    mod max_file_size {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[test]
+        fn larger_files_are_refused() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ test_value = "{}" }}"#, "x".repeat(64)).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .max_file_size(32)
+                .load::<TestConfiguration>();
+
+            assert_eq!(
+                result,
+                Err(Error::ConfigTooLarge(ntf.path().to_path_buf(), 32))
+            );
+        }
+
+        #[test]
+        fn smaller_files_are_read() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ test_value = "small" }}"#).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .max_file_size(32)
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "small");
+        }
+    }
+
+    #[cfg(test)]
+    mod cancellation {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::CancellationToken;
+        use crate::Error;
+        use std::io::Write as _;
+        use std::sync::atomic::AtomicBool;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tempfile::NamedTempFile;
+
+        fn loader(source: &str, token: CancellationToken) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .cancellation(token);
+            (ntf, loader)
+        }
+
+        #[test]
+        fn not_cancelled() {
+            let (_ntf, loader) = loader(r#"{ test_value = "nick" }"#, CancellationToken::default());
+
+            let result = loader.load::<TestConfiguration>().unwrap();
+
+            assert_eq!(result.test_value, "nick");
+        }
+
+        #[test]
+        fn cancelled_before_loading() {
+            let flag = Arc::new(AtomicBool::new(false));
+            let (_ntf, loader) = loader(r#"{ test_value = "nick" }"#, Arc::clone(&flag).into());
+            flag.store(true, Ordering::Relaxed);
+
+            let result = loader.load::<TestConfiguration>();
+
+            assert_eq!(result, Err(Error::Cancelled));
+        }
+
+        #[test]
+        fn cancelled_while_evaluating() {
+            let token = CancellationToken::default();
+            let source = r#"let rec f = fun x => f x in { test_value = f "nick" }"#;
+            let (_ntf, loader) = loader(source, token.clone());
+
+            let canceller = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                token.cancel();
+            });
+            let result = loader.load::<TestConfiguration>();
+            canceller.join().unwrap();
+
+            assert_eq!(result, Err(Error::Cancelled));
+        }
+    }
+
+    #[cfg(test)]
+    mod pure {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use std::path::PathBuf;
+
+        fn loader() -> (tempfile::TempDir, tempfile::TempDir, Loader) {
+            let config_dir = tempfile::tempdir().unwrap();
+            let other_dir = tempfile::tempdir().unwrap();
+            let config = config_dir.path().join("config.ncl");
+            std::fs::write(other_dir.path().join("outside.ncl"), r#""outside""#).unwrap();
+            std::fs::write(config_dir.path().join("inside.ncl"), r#""inside""#).unwrap();
+            std::fs::write(
+                &config,
+                format!(
+                    r#"{{ test_value = (import "inside.ncl") ++ (import "{}") }}"#,
+                    other_dir.path().join("outside.ncl").display()
+                ),
+            )
+            .unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink());
+            (config_dir, other_dir, loader)
+        }
+
+        #[test]
+        fn imports_outside_the_config_directory_are_forbidden() {
+            let (config_dir, other_dir, loader) = loader();
+
+            let result = loader.pure(true).load::<TestConfiguration>();
+
+            assert_eq!(
+                result,
+                Err(Error::ForbiddenImport(
+                    config_dir.path().join("config.ncl"),
+                    other_dir.path().join("outside.ncl")
+                ))
+            );
+        }
+
+        #[test]
+        fn impure_by_default() {
+            let (_config_dir, _other_dir, loader) = loader();
+
+            let result = loader.load::<TestConfiguration>().unwrap();
+
+            assert_eq!(result.test_value, "insideoutside");
+        }
+
+        #[test]
+        fn imports_inside_the_config_directory_are_allowed() {
+            let dir = tempfile::tempdir().unwrap();
+            let config: PathBuf = dir.path().join("config.ncl");
+            std::fs::write(dir.path().join("inside.ncl"), r#""inside""#).unwrap();
+            std::fs::write(&config, r#"{ test_value = import "inside.ncl" }"#).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .pure(true)
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "inside");
+        }
+
+        #[test]
+        fn search_paths_are_forbidden() {
+            let config_dir = tempfile::tempdir().unwrap();
+            let library_dir = tempfile::tempdir().unwrap();
+            let config = config_dir.path().join("config.ncl");
+            std::fs::write(library_dir.path().join("lib.ncl"), r#""shipped""#).unwrap();
+            std::fs::write(&config, r#"{ test_value = import "lib.ncl" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .import_search_path(library_dir.path().to_path_buf());
+
+            let impure = loader.load::<TestConfiguration>().unwrap();
+            let pure = loader.pure(true).load::<TestConfiguration>();
+
+            assert_eq!(impure.test_value, "shipped");
+            assert!(matches!(pure, Err(Error::ForbiddenImport(..))));
+        }
+
+        #[test]
+        fn references_are_not_resolved() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ test_value = "env://HOME" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .resolve_references("env", |_name| Ok("resolved".to_owned()));
+
+            let impure = loader.load::<TestConfiguration>().unwrap();
+            let pure = loader.pure(true).load::<TestConfiguration>();
+
+            assert_eq!(impure.test_value, "resolved");
+            assert_eq!(
+                pure,
+                Err(Error::UnresolvedReference(
+                    "test_value".to_owned(),
+                    "env://HOME".to_owned(),
+                    "references aren't resolved by pure loaders".to_owned()
+                ))
+            );
+        }
+
+        #[cfg(feature = "remote")]
+        #[test]
+        fn remote_configs_are_not_fetched() {
+            let loader = Loader::new("nickelodeon_test")
+                .remote_config("https://config.example.com/app.ncl")
+                .pure(true);
+
+            let (config, report) = loader.load_with_report::<TestConfiguration>().unwrap();
+
+            assert_eq!(config, TestConfiguration::default());
+            assert!(report.warnings.is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod import_search_path {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::ImportPolicy;
+        use std::path::Path;
+
+        fn loader(config_dir: &Path, library_dir: &Path, config: &str) -> Loader {
+            std::fs::write(config_dir.join("config.ncl"), config).unwrap();
+            std::fs::write(
+                library_dir.join("my-app-lib.ncl"),
+                r#"{ greeting = import "greeting.ncl" }"#,
+            )
+            .unwrap();
+            std::fs::write(library_dir.join("greeting.ncl"), r#""shipped""#).unwrap();
+
+            Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config_dir.join("config.ncl")))
+                .diagnostics(std::io::sink())
+                .import_search_path(library_dir.to_path_buf())
+        }
+
+        #[test]
+        fn found_in_the_search_path() {
+            let config_dir = tempfile::tempdir().unwrap();
+            let library_dir = tempfile::tempdir().unwrap();
+            let loader = loader(
+                config_dir.path(),
+                library_dir.path(),
+                r#"{ test_value = (import "my-app-lib.ncl").greeting }"#,
+            );
+
+            let (result, report) = loader.load_with_report::<TestConfiguration>().unwrap();
+
+            assert_eq!(result.test_value, "shipped");
+            assert_eq!(
+                report.imports,
+                vec![
+                    library_dir.path().join("greeting.ncl"),
+                    library_dir.path().join("my-app-lib.ncl"),
+                ]
+            );
+        }
+
+        #[test]
+        fn files_next_to_the_config_win() {
+            let config_dir = tempfile::tempdir().unwrap();
+            let library_dir = tempfile::tempdir().unwrap();
+            std::fs::write(config_dir.path().join("greeting.ncl"), r#""local""#).unwrap();
+            let loader = loader(
+                config_dir.path(),
+                library_dir.path(),
+                r#"{ test_value = import "greeting.ncl" }"#,
+            );
+
+            let result = loader.load::<TestConfiguration>().unwrap();
+
+            assert_eq!(result.test_value, "local");
+        }
+
+        #[test]
+        fn allowed_by_restricted_imports() {
+            let config_dir = tempfile::tempdir().unwrap();
+            let library_dir = tempfile::tempdir().unwrap();
+            let loader = loader(
+                config_dir.path(),
+                library_dir.path(),
+                r#"{ test_value = (import "my-app-lib.ncl").greeting }"#,
+            );
+
+            let result = loader
+                .imports(ImportPolicy::ConfigDirectory)
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "shipped");
+        }
+    }
+
+    #[cfg(test)]
+    mod prelude {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink());
+            (ntf, loader)
+        }
+
+        #[test]
+        fn in_scope() {
+            let (_ntf, loader) = loader(r#"{ test_value = my_app.helpers.greet "nick" }"#);
+
+            let result = loader
+                .prelude(
+                    "my_app",
+                    r#"{ helpers = { greet = fun name => "hello " ++ name } }"#,
+                )
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "hello nick");
+        }
+
+        #[test]
+        fn later_preludes_use_earlier_ones() {
+            let (_ntf, loader) = loader("{ test_value = full }");
+
+            let (result, report) = loader
+                .prelude("name", r#""nick""#)
+                .prelude("full", "std.string.uppercase name")
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "NICK");
+            assert!(report.imports.is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod host_facts {
+        use super::super::Loader;
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::HostFacts;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink());
+            (ntf, loader)
+        }
+
+        #[test]
+        fn exposed() {
+            let (_ntf, loader) =
+                loader(r#"{ test_value = "%{host.os}/%{host.arch}/%{host.env.PATH}" }"#);
+
+            let result = loader
+                .host_facts(HostFacts::default().env_var("PATH"))
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            let expected = format!(
+                "{}/{}/{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                std::env::var("PATH").unwrap()
+            );
+            assert_eq!(result.test_value, expected);
+        }
+
+        #[test]
+        fn dotenv() {
+            let (_ntf, loader) = loader(
+                r#"{ test_value = "%{host.env.NICKELODEON_TEST_DOTENV}/%{host.env.PATH}" }"#,
+            );
+            let mut dotenv = NamedTempFile::new().unwrap();
+            write!(dotenv, "NICKELODEON_TEST_DOTENV=local\nPATH=/overridden").unwrap();
+
+            let result = loader
+                .host_facts(HostFacts::default().dotenv(dotenv.path()))
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            let expected = format!("local/{}", std::env::var("PATH").unwrap());
+            assert_eq!(result.test_value, expected);
+        }
+
+        #[test]
+        fn only_allowed_environment_variables() {
+            let (_ntf, loader) = loader(
+                r#"{ test_value = if std.record.has_field "PATH" host.env then "leaked" else "hidden" }"#,
+            );
+
+            let result = loader
+                .host_facts(HostFacts::default())
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "hidden");
+        }
+
+        #[test]
+        fn not_exposed_by_default() {
+            let (_ntf, loader) = loader("{ test_value = host.os }");
+
+            let result = loader.load::<TestConfiguration>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+
+        #[test]
+        fn not_exposed_to_pure_loaders() {
+            let (_ntf, loader) = loader("{ test_value = host.os }");
+
+            let result = loader
+                .host_facts(HostFacts::default())
+                .pure(true)
+                .load::<TestConfiguration>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod contract {
+        use super::super::Loader;
+        use crate::Error;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Deserialize, PartialEq, Eq)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        const CONTRACT: &str = "
+            {
+              host | String,
+              port | std.number.PosNat | default = 8080,
+            }
+        ";
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .contract(CONTRACT);
+            (ntf, loader)
+        }
+
+        #[test]
+        fn satisfied() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost" }"#);
+
+            let result = loader.load::<Option<Server>>().unwrap();
+
+            assert_eq!(
+                result,
+                Some(Server {
+                    host: "localhost".to_owned(),
+                    port: 8080
+                })
+            );
+        }
+
+        #[test]
+        fn violated() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost", port = -1 }"#);
+
+            let result = loader.load::<Option<Server>>();
+
+            let Err(Error::NickelEvaluationError(_, diagnostics)) = result else {
+                panic!("expected a contract violation, got {result:?}");
+            };
+            assert_eq!(diagnostics.first().unwrap().field.as_deref(), Some("port"));
+        }
+    }
+
+    #[cfg(test)]
+    mod derived_contract {
+        use super::super::Loader;
+        use crate::Error;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+        struct Server {
+            host: String,
+            #[serde(default)]
+            port: u16,
+            mirrors: Vec<String>,
+            tls: Option<Tls>,
+        }
+
+        #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+        struct Tls {
+            cert_path: String,
+        }
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .derived_contract::<Server>();
+            (ntf, loader)
+        }
+
+        #[test]
+        fn satisfied() {
+            let (_ntf, loader) =
+                loader(r#"{ host = "localhost", mirrors = ["a"], tls = null, extra = 1 }"#);
+
+            let result = loader.load::<Server>().unwrap();
+
+            assert_eq!(
+                result,
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 0,
+                    mirrors: vec!["a".to_owned()],
+                    tls: None,
+                }
+            );
+        }
+
+        #[test]
+        fn wrong_type() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost", port = -1, mirrors = [] }"#);
+
+            let result = loader.load::<Server>();
+
+            let Err(Error::NickelEvaluationError(_, diagnostics)) = result else {
+                panic!("expected a contract violation, got {result:?}");
+            };
+            assert_eq!(diagnostics.first().unwrap().field.as_deref(), Some("port"));
+        }
+
+        #[test]
+        fn nested_field() {
+            let (_ntf, loader) =
+                loader(r#"{ host = "localhost", mirrors = [], tls = { cert_path = 1 } }"#);
+
+            let result = loader.load::<Server>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+
+        #[test]
+        fn missing_field() {
+            let (_ntf, loader) = loader("{ mirrors = [] }");
+
+            let result = loader.load::<Server>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod json_schema_contract {
+        use super::super::Loader;
+        use crate::Error;
+        use serde::Deserialize;
+        use serde_json::json;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+        struct Server {
+            host: String,
+            port: u16,
+            tags: Vec<String>,
+        }
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let schema = json!({
+                "type": "object",
+                "properties": {
+                    "host": { "type": "string", "pattern": "^[a-z.]+$" },
+                    "port": { "$ref": "#/$defs/port" },
+                    "tags": { "type": "array", "items": { "enum": ["web", "db"] }, "maxItems": 2 },
+                },
+                "required": ["host", "port"],
+                "$defs": { "port": { "type": "integer", "minimum": 1, "maximum": 0xFFFF } },
+            });
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .json_schema_contract(&schema);
+            (ntf, loader)
+        }
+
+        fn violated_field(source: &str) -> String {
+            let (_ntf, loader) = loader(source);
+
+            let result = loader.load::<Server>();
+
+            let Err(Error::NickelEvaluationError(_, diagnostics)) = result else {
+                panic!("expected a contract violation, got {result:?}");
+            };
+            diagnostics.first().unwrap().field.clone().unwrap()
+        }
+
+        #[test]
+        fn satisfied() {
+            let (_ntf, loader) =
+                loader(r#"{ host = "localhost", port = 8080, tags = ["web", "db"] }"#);
+
+            let result = loader.load::<Server>().unwrap();
+
+            assert_eq!(
+                result,
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 8080,
+                    tags: vec!["web".to_owned(), "db".to_owned()],
+                }
+            );
+        }
+
+        #[test]
+        fn violated() {
+            assert_eq!(
+                violated_field(r#"{ host = "local host", port = 80, tags = [] }"#),
+                "host"
+            );
+            assert_eq!(
+                violated_field(r#"{ host = "localhost", port = 0, tags = [] }"#),
+                "port"
+            );
+            assert_eq!(
+                violated_field(r#"{ host = "localhost", port = 80, tags = ["cache"] }"#),
+                "tags"
+            );
+        }
+
+        #[test]
+        fn missing_required_fields() {
+            let (_ntf, loader) = loader("{ port = 80, tags = [] }");
+
+            let result = loader.load::<Server>();
+
+            assert!(
+                matches!(result, Err(Error::NickelEvaluationError(..))),
+                "{result:?}"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod embedded_defaults {
+        use super::super::Loader;
+        use crate::Error;
+        use serde::Deserialize;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        const DEFAULTS: &str = "
+            {
+              host | String | default = \"localhost\",
+              port | Number | default = 8080,
+            }
+        ";
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .embedded_defaults(DEFAULTS);
+            (ntf, loader)
+        }
+
+        #[test]
+        fn overridden() {
+            let (_ntf, loader) = loader("{ port = 9000 }");
+
+            let result = loader.load::<Server>().unwrap();
+
+            assert_eq!(
+                result,
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 9000
+                }
+            );
+        }
+
+        #[test]
+        fn contracts_of_the_defaults() {
+            let (_ntf, loader) = loader(r#"{ port = "80" }"#);
+
+            let result = loader.load::<Server>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+
+        #[test]
+        fn single_field() {
+            let (_ntf, loader) = loader(r#"{ host = "example.com" }"#);
+
+            let result = loader.load_field::<u16>("port").unwrap();
+
+            assert_eq!(result, Some(8080));
+        }
+
+        #[test]
+        fn without_configuration() {
+            let loader = Loader::new("this_app_does_not_exist")
+                .diagnostics(std::io::sink())
+                .embedded_defaults(DEFAULTS);
+
+            let result = loader.load::<Server>().unwrap();
+
+            assert_eq!(
+                result,
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 8080
+                }
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod default_config_source {
+        use super::super::Loader;
+
+        #[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        fn embedded_defaults() {
+            let defaults = "{ port | Number | default = 8080 }";
+
+            let source = Loader::new("nickelodeon_test")
+                .embedded_defaults(defaults)
+                .default_config_source::<Server>()
+                .unwrap();
+
+            assert_eq!(source, defaults);
+        }
+
+        #[test]
+        fn default_value() {
+            let source = Loader::new("nickelodeon_test")
+                .default_config_source::<Server>()
+                .unwrap();
+
+            assert_eq!(source, "{\n  port | std.number.Nat = 0,\n}\n");
+        }
+    }
+
+    #[cfg(test)]
+    mod load_async {
+        use super::super::Loader;
+        use crate::offload::tests::block_on;
+        use crate::Error;
+        use crate::Offload;
+        use std::io::Write as _;
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+
+        #[derive(serde::Deserialize, Debug, Default, PartialEq, Eq)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        /// Runs the jobs right away, counting them.
+        #[derive(Clone, Default)]
+        struct Inline(Arc<AtomicUsize>);
+
+        impl Offload for Inline {
+            fn offload(&self, job: Box<dyn FnOnce() + Send>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                job();
+            }
+        }
+
+        fn loader(source: &str) -> (tempfile::NamedTempFile, Loader) {
+            let mut ntf = tempfile::Builder::new().suffix(".ncl").tempfile().unwrap();
+            write!(ntf, "{source}").unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink());
+            (ntf, loader)
+        }
+
+        #[test]
+        fn load() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost", port = 80 }"#);
+
+            let server: Server = block_on(loader.load_async()).unwrap();
+
+            assert_eq!(
+                server,
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 80
+                }
+            );
+        }
+
+        #[test]
+        fn load_field() {
+            let (_ntf, loader) = loader("{ server = { port = 80 } }");
+
+            let port: Option<u16> = block_on(loader.load_field_async("server.port")).unwrap();
+
+            assert_eq!(port, Some(80));
+        }
+
+        #[test]
+        fn error() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost", port = "80" }"#);
+
+            let result = block_on(loader.load_async::<Server>());
+
+            assert!(matches!(result, Err(Error::RustDeserializationError(..))));
+        }
+
+        #[test]
+        fn evaluation_error() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost", port = 1 + "1" }"#);
+            let expected = loader.load::<Server>().unwrap_err().diagnostics();
+
+            let result = block_on(loader.load_async::<Server>());
+
+            let Err(error @ Error::NickelEvaluationError(..)) = result else {
+                panic!("expected an evaluation error, got {result:?}");
+            };
+            assert_eq!(error.diagnostics(), expected);
+        }
+
+        #[test]
+        fn custom_offload() {
+            let (_ntf, loader) = loader(r#"{ host = "localhost", port = 80 }"#);
+            let offload = Inline::default();
+
+            let server: Server = block_on(loader.offload(offload.clone()).load_async()).unwrap();
+
+            assert_eq!(server.port, 80);
+            assert_eq!(offload.0.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    mod toml {
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::Loader;
+
+        #[test]
+        fn loaded() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.toml");
+            std::fs::write(&config, "test_value = \"nick\"\n").unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "nick");
+        }
+
+        #[test]
+        fn invalid() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.toml");
+            std::fs::write(&config, "test_value = ").unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load::<TestConfiguration>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    mod yaml {
+        use super::TestConfiguration;
+        use crate::Loader;
+        use std::path::PathBuf;
+        use std::sync::Arc;
+        use std::sync::Mutex;
+
+        #[test]
+        fn deprecated() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.yml");
+            std::fs::write(&config, "test_value: nick\n").unwrap();
+            let reported: Arc<Mutex<Vec<PathBuf>>> = Arc::default();
+            let on_yaml = Arc::clone(&reported);
+
+            let (result, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .on_yaml_config(move |path| on_yaml.lock().unwrap().push(path.to_path_buf()))
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "nick");
+            assert_eq!(*reported.lock().unwrap(), [config]);
+            assert_eq!(
+                report.warnings.first().unwrap().message,
+                "YAML configurations are deprecated, consider moving this one to Nickel"
+            );
+        }
+    }
+
+    #[cfg(feature = "json")]
+    mod json {
+        use super::TestConfiguration;
+        use crate::Error;
+        use crate::Loader;
+
+        #[test]
+        fn loaded() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.json");
+            std::fs::write(&config, r#"{ "test_value": "nick" }"#).unwrap();
+
+            let (result, report) = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load_with_report::<TestConfiguration>()
+                .unwrap();
+
+            assert_eq!(result.test_value, "nick");
+            assert!(report.warnings.is_empty());
+        }
+
+        #[test]
+        fn not_nickel() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.json");
+            std::fs::write(&config, r#"{ test_value = "nick" }"#).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load::<TestConfiguration>();
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod load_dynamic {
+        use crate::Loader;
+        use serde_json::json;
+
+        #[test]
+        fn any_shape() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(
+                &config,
+                "{ plugins = [{ name = \"a\", weight = 1.5 }], retries = 3 }",
+            )
+            .unwrap();
+
+            let loaded = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .load_dynamic()
+                .unwrap();
+
+            assert_eq!(
+                loaded,
+                json!({ "plugins": [{ "name": "a", "weight": 1.5 }], "retries": 3 })
+            );
+        }
+
+        #[test]
+        fn no_configuration() {
+            let loaded = Loader::new("this_app_does_not_exist")
+                .load_dynamic()
+                .unwrap();
+
+            assert_eq!(loaded, json!({}));
+        }
+    }
+
+    #[cfg(test)]
+    mod export_json {
+        use crate::Loader;
+        use serde_json::json;
+
+        #[test]
+        fn merged() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, "{ server.port = 8080, name = \"nick\" }").unwrap();
+
+            let exported = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .embedded_defaults("{ server.host | default = \"localhost\" }")
+                .export_json()
+                .unwrap();
+
+            assert_eq!(
+                exported,
+                json!({ "name": "nick", "server": { "host": "localhost", "port": 8080 } })
+            );
+        }
+
+        #[test]
+        fn no_configuration() {
+            let exported = Loader::new("this_app_does_not_exist")
+                .export_json()
+                .unwrap();
+
+            assert_eq!(exported, json!({}));
+        }
+    }
+
+    #[cfg(any(feature = "yaml", feature = "toml"))]
+    mod export_as {
+        use crate::Loader;
+        use std::path::PathBuf;
+        use tempfile::TempDir;
+
+        fn loader(source: &str) -> (TempDir, Loader) {
+            let dir = tempfile::tempdir().unwrap();
+            let config: PathBuf = dir.path().join("config.ncl");
+            std::fs::write(&config, source).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink());
+            (dir, loader)
+        }
+
+        #[test]
+        #[cfg(feature = "yaml")]
+        fn yaml() {
+            let (_dir, loader) = loader("{ name = \"nick\", server.port = 8080 }");
+
+            let exported = loader.export_yaml().unwrap();
+
+            assert_eq!(exported, "name: nick\nserver:\n  port: 8080\n");
+        }
+
+        #[test]
+        #[cfg(feature = "toml")]
+        fn toml() {
+            let (_dir, loader) = loader("{ name = \"nick\", server.port = 8080 }");
+
+            let exported = loader.export_toml().unwrap();
+
+            assert_eq!(exported, "name = \"nick\"\n\n[server]\nport = 8080\n");
+        }
+
+        #[test]
+        #[cfg(feature = "toml")]
+        fn not_representable() {
+            let (_dir, loader) = loader("{ name = null }");
+
+            let result = loader.export_toml();
+
+            assert!(matches!(
+                result,
+                Err(crate::Error::NickelEvaluationError(..))
+            ));
+        }
+
+        #[test]
+        #[cfg(feature = "yaml")]
+        fn no_configuration() {
+            let exported = Loader::new("this_app_does_not_exist")
+                .export_yaml()
+                .unwrap();
+
+            assert_eq!(exported, "{}\n");
+        }
+    }
+
+    #[cfg(test)]
+    mod systemd_credentials {
+        use super::super::Loader;
+        use crate::Error;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Config {
+            password: String,
+        }
+
+        fn loader(credentials: Option<std::path::PathBuf>) -> (tempfile::TempDir, Loader) {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ password = "credential://db_password" }"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .memoize(true)
+                .cache_dir(dir.path().join("cache"))
+                .credentials_in(credentials);
+            (dir, loader)
+        }
+
+        #[test]
+        fn rotated() {
+            let credentials = tempfile::tempdir().unwrap();
+            std::fs::write(credentials.path().join("db_password"), "hunter2\n").unwrap();
+            let (dir, loader) = loader(Some(credentials.path().to_path_buf()));
+
+            let first: Config = loader.load().unwrap();
+            std::fs::write(credentials.path().join("db_password"), "correct horse").unwrap();
+            let (second, report) = loader.load_with_report::<Config>().unwrap();
+
+            assert_eq!(first.password, "hunter2");
+            assert_eq!(second.password, "correct horse");
+            assert_eq!(report.cache_hits, 1);
+            let cached: Vec<String> = std::fs::read_dir(dir.path().join("cache"))
+                .unwrap()
+                .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+                .collect();
+            assert_eq!(cached.len(), 1);
+            assert!(cached.iter().all(|entry| !entry.contains("hunter2")));
+        }
+
+        #[test]
+        fn no_credentials() {
+            let (_dir, loader) = loader(None);
+
+            let error = loader.load::<Config>().unwrap_err();
+
+            assert!(matches!(
+                error,
+                Error::UnresolvedReference(path, _reference, reason)
+                    if path == "password" && reason.contains("CREDENTIALS_DIRECTORY")
+            ));
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod kubernetes_mount {
+        use crate::ImportPolicy;
+        use crate::Loader;
+        use std::fs;
+        use std::os::unix::fs::symlink;
+        use std::path::Path;
+
+        #[derive(Debug, Default, serde::Deserialize, PartialEq, Eq)]
+        struct Config {
+            port: u16,
+            database: String,
+        }
+
+        /// Mounts the `keys` in `dir` like Kubernetes does, in the timestamped directory
+        /// `version` that `..data` points to, swapping the link atomically.
+        fn mount(dir: &Path, version: &str, keys: &[(&str, &str)]) {
+            let timestamped = dir.join(version);
+            fs::create_dir_all(&timestamped).unwrap();
+            for (key, content) in keys {
+                fs::write(timestamped.join(key), content).unwrap();
+                let link = dir.join(key);
+                if !link.exists() {
+                    symlink(format!("..data/{key}"), link).unwrap();
+                }
+            }
+            symlink(version, dir.join("..data_tmp")).unwrap();
+            fs::rename(dir.join("..data_tmp"), dir.join("..data")).unwrap();
+        }
+
+        #[test]
+        fn merges_the_keys() {
+            let dir = tempfile::tempdir().unwrap();
+            mount(
+                dir.path(),
+                "..1",
+                &[
+                    ("server.ncl", "{ port = 80 }"),
+                    ("database.json", r#"{ "database": "db" }"#),
+                ],
+            );
+
+            let (config, report) = Loader::new("nickelodeon_test")
+                .diagnostics(std::io::sink())
+                .kubernetes_mount_at(dir.path().to_path_buf())
+                .imports(ImportPolicy::Deny)
+                .load_with_report::<Config>()
+                .unwrap();
+
+            assert_eq!(
+                config,
+                Config {
+                    port: 80,
+                    database: "db".to_owned(),
+                }
+            );
+            assert_eq!(report.path.as_deref(), Some(dir.path()));
+            assert_eq!(
+                report.imports,
+                vec![
+                    dir.path().join("database.json"),
+                    dir.path().join("server.ncl")
+                ]
+            );
+        }
+
+        #[test]
+        fn follows_the_swaps() {
+            let dir = tempfile::tempdir().unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .diagnostics(std::io::sink())
+                .kubernetes_mount_at(dir.path().to_path_buf());
+            mount(
+                dir.path(),
+                "..1",
+                &[("config.ncl", "{ port = 80, database = \"a\" }")],
+            );
+            let first = loader.load::<Config>().unwrap();
+
+            mount(
+                dir.path(),
+                "..2",
+                &[("config.ncl", "{ port = 81, database = \"b\" }")],
+            );
+            let second = loader.load::<Config>().unwrap();
+
+            assert_eq!(first.port, 80);
+            assert_eq!(second.port, 81);
+            assert_eq!(
+                loader.locations(),
+                vec![dir.path().to_path_buf(), dir.path().join("..data")]
+            );
+        }
+
+        #[test]
+        fn not_mounted() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .diagnostics(std::io::sink())
+                .kubernetes_mount_at(dir.path().join("missing"))
+                .load::<Config>()
+                .unwrap();
+
+            assert_eq!(config, Config::default());
+        }
+    }
+
+    #[cfg(feature = "remote")]
+    mod remote_config {
+        use crate::remote::Remote;
+        use crate::Loader;
+        use crate::Severity;
+        use std::fs;
+        use std::time::Duration;
+
+        #[derive(Debug, Default, serde::Deserialize, PartialEq, Eq)]
+        struct Config {
+            port: u16,
+        }
+
+        #[test]
+        fn falls_back_to_the_copy() {
+            let cache = tempfile::tempdir().unwrap();
+            // Plain HTTP is refused before running `curl`, so the fetch always fails.
+            let url = "http://config.example.com/app.ncl";
+            let copy = Remote::new(url, Duration::ZERO).cached(&cache.path().join("remote"));
+            fs::create_dir_all(copy.parent().unwrap()).unwrap();
+            fs::write(&copy, "{ port = 80 }").unwrap();
+
+            let (config, report) = Loader::new("nickelodeon_test")
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf())
+                .remote_config(url)
+                .load_with_report::<Config>()
+                .unwrap();
+
+            assert_eq!(config, Config { port: 80 });
+            assert_eq!(report.path, Some(copy.clone()));
+            let warning = report.warnings.first().unwrap();
+            assert_eq!(warning.severity, Severity::Warning);
+            assert_eq!(warning.path, Some(copy));
+            assert_eq!(
+                warning.message,
+                "can't fetch http://config.example.com/app.ncl: only HTTPS URLs are supported"
+            );
+        }
+
+        #[test]
+        fn falls_back_to_the_local_files() {
+            let cache = tempfile::tempdir().unwrap();
+
+            let (config, report) = Loader::new("nickelodeon_test_without_config")
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf())
+                .remote_config("http://config.example.com/app.ncl")
+                .load_with_report::<Config>()
+                .unwrap();
+
+            assert_eq!(config, Config::default());
+            assert_eq!(report.path, None);
+            assert_eq!(
+                report.warnings.first().map(|warning| warning.path.clone()),
+                Some(None)
+            );
+        }
+
+        #[test]
+        fn in_order() {
+            let cache = tempfile::tempdir().unwrap();
+            let mirror = "http://mirror.example.com/app.ncl";
+            let copy = Remote::new(mirror, Duration::ZERO).cached(&cache.path().join("remote"));
+            fs::create_dir_all(copy.parent().unwrap()).unwrap();
+            fs::write(&copy, "{ port = 80 }").unwrap();
+
+            let (config, report) = Loader::new("nickelodeon_test")
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf())
+                .remote_config("http://config.example.com/app.ncl")
+                .remote_config(mirror)
+                .load_with_report::<Config>()
+                .unwrap();
+
+            assert_eq!(config, Config { port: 80 });
+            assert_eq!(report.path, Some(copy.clone()));
+            let paths: Vec<_> = report
+                .warnings
+                .iter()
+                .map(|warning| warning.path.clone())
+                .collect();
+            assert_eq!(paths, [None, Some(copy)]);
+        }
+
+        #[test]
+        fn timeouts() {
+            let loader = Loader::new("nickelodeon_test")
+                .remote_config("https://config.example.com/app.ncl")
+                .remote_config_with_timeout("https://mirror.example.com/app.ncl", Duration::ZERO)
+                .remote_timeout(Duration::from_secs(1));
+
+            assert_eq!(
+                loader.remotes(),
+                [
+                    Remote::new("https://config.example.com/app.ncl", Duration::from_secs(1)),
+                    Remote::new("https://mirror.example.com/app.ncl", Duration::ZERO),
+                ]
+            );
+            assert_eq!(
+                loader
+                    .config_path_from_flag(Some("config.ncl".into()))
+                    .remotes(),
+                []
+            );
+        }
+
+        #[cfg(feature = "tokio")]
+        #[test]
+        fn awaited() {
+            let cache = tempfile::tempdir().unwrap();
+            let url = "http://config.example.com/app.ncl";
+            let copy = Remote::new(url, Duration::ZERO).cached(&cache.path().join("remote"));
+            fs::create_dir_all(copy.parent().unwrap()).unwrap();
+            fs::write(&copy, "{ port = 80 }").unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .diagnostics(std::io::sink())
+                .cache_dir(cache.path().to_path_buf())
+                .remote_config(url)
+                .remote_config("http://mirror.example.com/app.ncl");
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let config = runtime.block_on(loader.load_async::<Config>()).unwrap();
+            let port = runtime
+                .block_on(loader.load_field_async::<u16>("port"))
+                .unwrap();
+
+            assert_eq!(config, Config { port: 80 });
+            assert_eq!(port, Some(80));
+        }
+    }
+}
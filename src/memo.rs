@@ -0,0 +1,57 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+/// A configuration evaluated earlier in the process, with the files it imports.
+struct Memo {
+    value: Value,
+    imports: Vec<PathBuf>,
+}
+
+/// The configurations evaluated by [`crate::Loader::memoize`] loaders, by the (hashed)
+/// options of the loader and path.
+static MEMOS: Mutex<BTreeMap<(String, PathBuf), Memo>> = Mutex::new(BTreeMap::new());
+
+/// Returns the configuration `config` evaluated earlier in the process by a loader with the
+/// same `options` as a `T`, together with the files it imports, unless it isn't a valid `T`.
+pub(crate) fn recall<T: DeserializeOwned>(
+    options: &str,
+    config: &Path,
+) -> Option<(T, Vec<PathBuf>)> {
+    let (json, imports) = MEMOS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&(options.to_owned(), config.to_path_buf()))
+        .map(|memo| (memo.value.clone(), memo.imports.clone()))?;
+    let value = serde_json::from_value(json).ok()?;
+    Some((value, imports))
+}
+
+/// Remembers the configuration `config`, which imports `imports`, as evaluated by a loader
+/// with the given `options`.
+pub(crate) fn remember(options: &str, config: &Path, value: Value, imports: Vec<PathBuf>) {
+    MEMOS.lock().unwrap_or_else(PoisonError::into_inner).insert(
+        (options.to_owned(), config.to_path_buf()),
+        Memo { value, imports },
+    );
+}
+
+/// Forgets the configurations memoized by the loaders with the given `options`, leaving the
+/// other ones alone.
+pub(crate) fn forget(options: &str) {
+    MEMOS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|(memoized_by, _config), _memo| memoized_by != options);
+}
+
+/// Forgets every configuration memoized by [`crate::Loader::memoize`] loaders, so they are
+/// evaluated again the next time they are loaded. Meant to be called when the configuration
+/// files change.
+pub fn forget_memoized() {
+    MEMOS.lock().unwrap_or_else(PoisonError::into_inner).clear();
+}
@@ -0,0 +1,203 @@
+use crate::schema::list;
+use crate::Limit;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// A message produced by nickelodeon itself, as opposed to the ones coming from Nickel or
+/// serde.
+///
+/// The [`fmt::Display`] implementation renders the English version. New messages may be
+/// added in the future, so translations should fall back to it for unknown variants.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<'text> {
+    /// No configuration file was found.
+    ConfigNotFound,
+
+    /// A location where the configuration file was looked for.
+    Searched(&'text Path),
+
+    /// Both `used` and `other` exist in the same directory.
+    Ambiguous {
+        used: &'text Path,
+        other: &'text Path,
+    },
+
+    /// Other existing configuration files were ignored in favour of the one used.
+    Shadowed,
+
+    /// A configuration file that was ignored.
+    Ignored(&'text Path),
+
+    /// The configuration `file` imports `import`, which isn't allowed.
+    ForbiddenImport {
+        file: &'text Path,
+        import: &'text Path,
+    },
+
+    /// The configuration file can be written by any user.
+    WorldWritable,
+
+    /// The configuration file is owned by the user with the given id, other than the
+    /// current one.
+    OwnedByOtherUser(u32),
+
+    /// The configuration file couldn't be read, because of `reason`.
+    ReadingFailed(&'text str),
+
+    /// The configuration file couldn't be written, because of `reason`.
+    WritingFailed(&'text str),
+
+    /// The configuration file is larger than the given number of bytes.
+    TooLarge(u64),
+
+    /// The fields expected by the struct a field is missing from.
+    AvailableFields(&'text [&'text str]),
+
+    /// Evaluating the configuration panicked, with the panic message `reason`.
+    Panicked(&'text str),
+
+    /// Evaluating the configuration took longer than the given timeout.
+    TimedOut(Duration),
+
+    /// Evaluating the configuration reached the given limit.
+    LimitExceeded(Limit),
+
+    /// The load was cancelled.
+    Cancelled,
+
+    /// A field required by the Rust type is not set.
+    MissingField,
+
+    /// The configuration sets the deprecated field `old`.
+    Deprecated { old: &'text str },
+
+    /// Label pointing at a deprecated field.
+    DeprecatedField,
+
+    /// The deprecated field was renamed to `new`.
+    UseInstead { new: &'text str },
+
+    /// The deprecated field was ignored, since its replacement `new` is also set.
+    DeprecatedIgnored { new: &'text str },
+
+    /// The configuration file is in the given format, whose support is deprecated.
+    DeprecatedFormat(&'text str),
+
+    /// The file isn't in any of the formats that can be migrated to Nickel.
+    UnsupportedFormat(&'text Path),
+
+    /// The field at the given dotted path can't be set, since the configuration file
+    /// doesn't write it, or one of its parents, as a record.
+    NotEditable(&'text str),
+
+    /// The `reference` held by the configuration couldn't be resolved, because of `reason`.
+    UnresolvedReference {
+        reference: &'text str,
+        reason: &'text str,
+    },
+
+    /// The remote configuration at `url` couldn't be fetched, because of `reason`.
+    RemoteUnavailable { url: &'text str, reason: &'text str },
+}
+
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfigNotFound => write!(f, "no configuration file found"),
+            Self::Searched(path) => write!(f, "searched {}", path.display()),
+            Self::Ambiguous { used, other } => write!(
+                f,
+                "ambiguous configuration: both {} and {} exist",
+                used.display(),
+                other.display()
+            ),
+            Self::Shadowed => write!(f, "other configuration files were found and ignored"),
+            Self::Ignored(path) => write!(f, "ignored {}", path.display()),
+            Self::ForbiddenImport { file, import } => write!(
+                f,
+                "{} imports {}, which is not allowed",
+                file.display(),
+                import.display()
+            ),
+            Self::WorldWritable => write!(f, "the file can be written by any user"),
+            Self::OwnedByOtherUser(uid) => write!(f, "the file is owned by another user ({uid})"),
+            Self::ReadingFailed(reason) => write!(f, "Error when reading input: {reason}"),
+            Self::WritingFailed(reason) => {
+                write!(f, "the configuration couldn't be written: {reason}")
+            }
+            Self::TooLarge(max) => write!(f, "the file is larger than the limit of {max} bytes"),
+            Self::AvailableFields(fields) => {
+                write!(f, "available fields are {}", list(fields))
+            }
+            Self::Panicked(reason) => {
+                write!(f, "the evaluation of the configuration panicked: {reason}")
+            }
+            Self::TimedOut(timeout) => write!(
+                f,
+                "the evaluation of the configuration didn't finish within {}ms",
+                timeout.as_millis()
+            ),
+            Self::LimitExceeded(limit) => write!(
+                f,
+                "the evaluation of the configuration exceeded the limit of {limit}"
+            ),
+            Self::Cancelled => write!(f, "the load of the configuration was cancelled"),
+            Self::MissingField => write!(f, "missing field"),
+            Self::Deprecated { old } => write!(f, "`{old}` is deprecated"),
+            Self::DeprecatedField => write!(f, "deprecated field"),
+            Self::UseInstead { new } => write!(f, "use `{new}` instead"),
+            Self::DeprecatedIgnored { new } => {
+                write!(f, "`{new}` is also set, so this value is ignored")
+            }
+            Self::DeprecatedFormat(format) => write!(
+                f,
+                "{format} configurations are deprecated, consider moving this one to Nickel"
+            ),
+            Self::UnsupportedFormat(path) => {
+                write!(f, "{} is not a TOML, YAML or JSON file", path.display())
+            }
+            Self::NotEditable(path) => write!(
+                f,
+                "`{path}` can't be set, as the configuration file doesn't write it in a record"
+            ),
+            Self::UnresolvedReference { reference, reason } => {
+                write!(f, "can't resolve `{reference}`: {reason}")
+            }
+            Self::RemoteUnavailable { url, reason } => write!(f, "can't fetch {url}: {reason}"),
+        }
+    }
+}
+
+/// Builds the text of the [`Message`]s produced by nickelodeon, so applications shipping
+/// in other languages can translate them. The structured data (paths, spans, severities)
+/// is never affected.
+///
+/// ```
+/// use nickelodeon::{Message, Messages};
+///
+/// struct Catalan;
+///
+/// impl Messages for Catalan {
+///     fn message(&self, message: &Message<'_>) -> String {
+///         match message {
+///             Message::ConfigNotFound => "no s'ha trobat cap fitxer de configuració".to_owned(),
+///             other => other.to_string(),
+///         }
+///     }
+/// }
+/// ```
+pub trait Messages: Send + Sync {
+    /// Returns the text of `message`. Defaults to English.
+    fn message(&self, message: &Message<'_>) -> String {
+        message.to_string()
+    }
+}
+
+/// The default, English, [`Messages`].
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct English;
+
+impl Messages for English {}
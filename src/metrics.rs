@@ -0,0 +1,72 @@
+use crate::Failure;
+use std::time::SystemTime;
+
+/// Receives the counters of the watches of a [`crate::Loader`] (see
+/// [`crate::Loader::metrics`]) after each load, to publish them through the metrics system
+/// of the application.
+///
+/// ```
+/// use std::sync::atomic::AtomicU64;
+/// use std::sync::atomic::Ordering;
+///
+/// #[derive(Default)]
+/// struct Gauges {
+///     generation: AtomicU64,
+///     failures: AtomicU64,
+/// }
+///
+/// impl nickelodeon::ReloadMetrics for Gauges {
+///     fn reloaded(&self, counters: &nickelodeon::ReloadCounters) {
+///         self.generation.store(counters.generation, Ordering::Relaxed);
+///     }
+///
+///     fn failed(&self, counters: &nickelodeon::ReloadCounters, _: &nickelodeon::Failure) {
+///         self.failures.store(counters.failures, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let loader = nickelodeon::Loader::new("my-app").metrics(Gauges::default());
+/// ```
+pub trait ReloadMetrics: Send + Sync {
+    /// Called once the configuration loaded successfully, with the updated counters.
+    fn reloaded(&self, _counters: &ReloadCounters) {}
+
+    /// Called once the configuration failed to load, with the updated counters and the
+    /// reason it failed.
+    fn failed(&self, _counters: &ReloadCounters, _failure: &Failure) {}
+}
+
+/// The counters of a watch, as given to the [`ReloadMetrics`].
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReloadCounters {
+    /// How many times the configuration was loaded successfully after the first attempt,
+    /// i.e. following a change.
+    pub reloads: u64,
+
+    /// How many times the configuration failed to load, including the first attempt.
+    pub failures: u64,
+
+    /// When the configuration last loaded successfully, if ever.
+    pub last_reload: Option<SystemTime>,
+
+    /// The generation of the current configuration: its [`crate::SharedConfig::generation`]
+    /// for a shared one, or how many valid configurations a [`crate::Watcher`] received,
+    /// 0 if there is none yet.
+    pub generation: u64,
+}
+
+impl ReloadCounters {
+    /// Counts a successful load, which is a reload unless it's the `first` attempt.
+    pub(crate) fn loaded(&mut self, first: bool) {
+        if !first {
+            self.reloads = self.reloads.saturating_add(1);
+        }
+        self.last_reload = Some(SystemTime::now());
+    }
+
+    /// Counts a failed load.
+    pub(crate) const fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::SharedConfig;
+use std::task::Context;
+use std::task::Poll;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A `tower` layer attaching the current version of a [`SharedConfig`] to each request, as a
+/// [`crate::Snapshot`] in the request extensions.
+///
+/// Every handler of a request then sees the same version, even if a reload happens while it
+/// is being handled, e.g. taking an axum `Extension<nickelodeon::Snapshot<MyConfig>>`.
+#[derive(Debug)]
+pub struct SnapshotLayer<T> {
+    config: SharedConfig<T>,
+}
+
+impl<T> SnapshotLayer<T> {
+    /// Creates a layer attaching the versions of `config`.
+    #[must_use]
+    pub const fn new(config: SharedConfig<T>) -> Self {
+        Self { config }
+    }
+}
+
+impl<T> Clone for SnapshotLayer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl<S, T> Layer<S> for SnapshotLayer<T> {
+    type Service = SnapshotService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SnapshotService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// The service wrapped by a [`SnapshotLayer`].
+#[derive(Debug)]
+pub struct SnapshotService<S, T> {
+    inner: S,
+    config: SharedConfig<T>,
+}
+
+impl<S: Clone, T> Clone for SnapshotService<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl<S, T, B> Service<http::Request<B>> for SnapshotService<S, T>
+where
+    S: Service<http::Request<B>>,
+    T: Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        req.extensions_mut().insert(self.config.snapshot());
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod snapshot_layer {
+        use super::super::SnapshotLayer;
+        use crate::offload::tests::block_on;
+        use crate::SharedConfig;
+        use crate::Snapshot;
+        use std::convert::Infallible;
+        use std::future::ready;
+        use std::future::Ready;
+        use std::task::Context;
+        use std::task::Poll;
+        use tower_layer::Layer as _;
+        use tower_service::Service;
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        /// Answers the port and generation of the snapshot attached to the request.
+        struct Handler;
+
+        impl Service<http::Request<()>> for Handler {
+            type Response = Option<(u16, u64)>;
+            type Error = Infallible;
+            type Future = Ready<Result<Self::Response, Infallible>>;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: http::Request<()>) -> Self::Future {
+                let attached = req.extensions().get::<Snapshot<Server>>();
+                ready(Ok(
+                    attached.map(|snapshot| (snapshot.port, snapshot.generation()))
+                ))
+            }
+        }
+
+        #[test]
+        fn attached() {
+            let config = SharedConfig::new(Server { port: 80 });
+            let mut service = SnapshotLayer::new(config).layer(Handler);
+
+            let answer = block_on(service.call(http::Request::new(())));
+
+            assert_eq!(answer, Ok(Some((80, 0))));
+        }
+
+        #[test]
+        fn unwrapped() {
+            let answer = block_on(Handler.call(http::Request::new(())));
+
+            assert_eq!(answer, Ok(None));
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use crate::loader::report;
+use crate::loader::DiagnosticSink;
+use crate::syntax::string;
+use crate::syntax::Comments;
+use crate::syntax::Nickel;
+use crate::Error;
+use crate::Message;
+use crate::Result;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::cache::ErrorTolerance;
+use nickel_lang_core::cache::InputFormat;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use std::io;
+use std::path::Path;
+
+/// Converts the configuration file at `path` into the equivalent Nickel source.
+///
+/// The file can be in TOML, YAML or JSON, told apart by its extension, so applications can
+/// offer a one-shot "migrate my configuration" command:
+///
+/// ```no_run
+/// # fn main() -> nickelodeon::Result<()> {
+/// let nickel = nickelodeon::migrate_to_nickel(std::path::Path::new("config.toml"))?;
+/// std::fs::write("config.ncl", nickel).expect("can't write the configuration");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The settings keep their order. The comments of a TOML file written on their own lines,
+/// before a setting or a table, are kept too; the other ones are lost.
+///
+/// # Errors
+///
+/// Will return `Err` if the file can't be read, isn't in one of the supported formats or
+/// can't be parsed.
+pub fn migrate_to_nickel(path: &Path) -> Result<String> {
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => InputFormat::Toml,
+        Some("yaml" | "yml") => InputFormat::Yaml,
+        Some("json") => InputFormat::Json,
+        _ => {
+            return Err(Error::ConfigFileReadingError(
+                Message::UnsupportedFormat(path).to_string(),
+            ))
+        }
+    };
+
+    let mut cache = Cache::new(ErrorTolerance::Strict);
+    let id = cache
+        .add_file(path)
+        .map_err(|err| Error::ConfigFileReadingError(err.to_string()))?;
+    if let Err(errors) = cache.parse_multi(id, format) {
+        let error = nickel_lang_core::error::Error::from(errors);
+        let mut sink = DiagnosticSink::new(io::sink());
+        let diagnostics = report(&mut cache, error.clone(), &mut sink);
+        return Err(Error::NickelEvaluationError(error, diagnostics));
+    }
+
+    let comments = if format == InputFormat::Toml {
+        toml_comments(cache.files().source(id))
+    } else {
+        Comments::new()
+    };
+    let term = cache.get_owned(id).unwrap_or_else(|| Term::Null.into());
+    Ok(nickel(&term)?.to_source(&comments))
+}
+
+/// Turns `term`, parsed from a data file, into a [`Nickel`] value.
+#[allow(clippy::wildcard_enum_match_arm)]
+fn nickel(term: &RichTerm) -> Result<Nickel> {
+    Ok(match term.as_ref() {
+        Term::Record(record) => Nickel::Record(
+            record
+                .fields
+                .iter()
+                .filter_map(|(name, field)| {
+                    let value = field.value.as_ref()?;
+                    Some(nickel(value).map(|converted| (name.label().to_owned(), converted)))
+                })
+                .collect::<Result<_>>()?,
+        ),
+        Term::Array(array, _) => Nickel::Array(array.iter().map(nickel).collect::<Result<_>>()?),
+        Term::Str(text) => Nickel::Literal(string(text)),
+        _ => {
+            let value = serde_json::to_value(term)
+                .map_err(|err| Error::ConfigFileReadingError(err.to_string()))?;
+            Nickel::Literal(value.to_string())
+        }
+    })
+}
+
+/// Collects the comments written on their own lines before each setting, or table, of the
+/// TOML `source`.
+///
+/// The TOML parser drops the comments, so they are found line by line: the settings in
+/// arrays of tables, and the lines of multi-line strings and arrays, are skipped.
+fn toml_comments(source: &str) -> Comments {
+    let mut comments = Comments::new();
+    let mut pending = Vec::new();
+    let mut table = Some(Vec::new());
+    let mut in_string = false;
+    let mut brackets = 0_usize;
+    for line in source.lines().map(str::trim) {
+        let quotes = line.matches("\"\"\"").chain(line.matches("'''")).count();
+        if in_string || brackets > 0 {
+            in_string ^= !quotes.is_multiple_of(2);
+            brackets = unclosed(brackets, line);
+        } else if line.starts_with('#') {
+            pending.push(line.to_owned());
+        } else if line.starts_with("[[") {
+            table = None;
+            pending.clear();
+        } else if let Some(header) = line.strip_prefix('[') {
+            let name = keys(header.split(']').next().unwrap_or_default());
+            comments.insert(name.clone(), std::mem::take(&mut pending));
+            table = Some(name);
+        } else {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if let Some(current) = &table {
+                let mut name = current.clone();
+                name.extend(keys(key));
+                comments.insert(name, std::mem::take(&mut pending));
+            }
+            pending.clear();
+            in_string = !quotes.is_multiple_of(2);
+            brackets = unclosed(0, value);
+        }
+    }
+    comments.retain(|_, lines| !lines.is_empty());
+    comments
+}
+
+/// Returns how many brackets are still open after `line`, given that `open` were before it.
+fn unclosed(open: usize, line: &str) -> usize {
+    line.chars().fold(open, |count, next| match next {
+        '[' => count.saturating_add(1),
+        ']' => count.saturating_sub(1),
+        _ => count,
+    })
+}
+
+/// Splits the dotted TOML `key` (e.g. `server."host name"`) into the names of its parts.
+fn keys(key: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut quote = None;
+    for next in key.trim().chars() {
+        match (quote, next) {
+            (None, '"' | '\'') => quote = Some(next),
+            (Some(open), _) if open == next => quote = None,
+            (None, '.') => parts.push(String::new()),
+            (None, blank) if blank.is_whitespace() => {}
+            (_, other) => {
+                if let Some(part) = parts.last_mut() {
+                    part.push(other);
+                }
+            }
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod migrate_to_nickel {
+        use super::super::migrate_to_nickel;
+        use crate::Error;
+        use crate::Loader;
+        use std::path::PathBuf;
+        use tempfile::TempDir;
+
+        #[derive(serde::Deserialize, Debug, Default, PartialEq)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(serde::Deserialize, Debug, Default, PartialEq)]
+        struct Server {
+            port: u16,
+            hosts: Vec<String>,
+        }
+
+        fn file(name: &str, contents: &str) -> (TempDir, PathBuf) {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            (dir, path)
+        }
+
+        fn load(nickel: &str) -> Config {
+            let (_dir, path) = file("config.ncl", nickel);
+            Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path))
+                .diagnostics(std::io::sink())
+                .load()
+                .unwrap()
+        }
+
+        #[test]
+        fn toml() {
+            let (_dir, path) = file(
+                "config.toml",
+                r#"
+                # The name shown in the title bar.
+                name = "my \"app\" %{version}"
+
+                # Where to listen.
+                [server]
+                port = 8080
+                # Every host served.
+                hosts = [
+                  "example.com", # the main one
+                  "www.example.com",
+                ]
+                "#,
+            );
+
+            let nickel = migrate_to_nickel(&path).unwrap();
+
+            assert_eq!(
+                nickel,
+                r#"{
+  # The name shown in the title bar.
+  name = "my \"app\" \%{version}",
+  # Where to listen.
+  server = {
+    port = 8080,
+    # Every host served.
+    hosts = ["example.com", "www.example.com"],
+  },
+}
+"#
+            );
+            assert_eq!(load(&nickel).name, r#"my "app" %{version}"#);
+        }
+
+        #[test]
+        fn yaml() {
+            let (_dir, path) = file(
+                "config.yml",
+                "name: app\nserver:\n  port: 8080\n  hosts: [example.com]\n",
+            );
+
+            let nickel = migrate_to_nickel(&path).unwrap();
+
+            assert_eq!(
+                load(&nickel),
+                Config {
+                    name: "app".to_owned(),
+                    server: Server {
+                        port: 8080,
+                        hosts: vec!["example.com".to_owned()],
+                    },
+                }
+            );
+        }
+
+        #[test]
+        fn json() {
+            let (_dir, path) = file(
+                "config.json",
+                r#"{ "name": "app", "server": { "port": 8080, "hosts": [] }, "tiers": [{ "max-size": 1.5 }] }"#,
+            );
+
+            let nickel = migrate_to_nickel(&path).unwrap();
+
+            assert_eq!(
+                nickel,
+                r#"{
+  name = "app",
+  server = {
+    port = 8080,
+    hosts = [],
+  },
+  tiers = [
+    {
+      max-size = 1.5,
+    },
+  ],
+}
+"#
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            let (_dir, path) = file("config.toml", "name = ");
+
+            let result = migrate_to_nickel(&path);
+
+            assert!(matches!(result, Err(Error::NickelEvaluationError(..))));
+        }
+
+        #[test]
+        fn unsupported() {
+            let (_dir, path) = file("config.ini", "name = app");
+
+            let result = migrate_to_nickel(&path);
+
+            assert!(matches!(result, Err(Error::ConfigFileReadingError(..))));
+        }
+    }
+}
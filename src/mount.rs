@@ -0,0 +1,87 @@
+use crate::prelude::quoted;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The symbolic link Kubernetes swaps atomically to update the keys of a mounted `ConfigMap`
+/// or `Secret`, all at once: the keys are links into the directory it points to.
+pub(crate) const DATA: &str = "..data";
+
+/// The extensions of the mounted keys making up the configuration, the ones Nickel can
+/// import.
+const EXTENSIONS: &[&str] = &["ncl", "nickel", "json", "yaml", "yml", "toml"];
+
+/// Returns the Nickel source of the configuration mounted in `dir`: the merge of its keys,
+/// imported in the order of their names.
+pub(crate) fn merged_keys(dir: &Path) -> io::Result<String> {
+    let keys = keys_in(dir)?;
+    if keys.is_empty() {
+        return Ok("{}".to_owned());
+    }
+    let imports: Vec<String> = keys
+        .iter()
+        .map(|key| format!("(import {})", quoted(&key.to_string_lossy())))
+        .collect();
+    Ok(imports.join(" & "))
+}
+
+/// Returns the files mounted in `dir` with one of the [`EXTENSIONS`], sorted. The hidden
+/// entries, like [`DATA`] and the directories it points to, are skipped.
+fn keys_in(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_none_or(|name| name.to_string_lossy().starts_with('.'));
+        let imported = path
+            .extension()
+            .is_some_and(|extension| EXTENSIONS.iter().any(|known| extension == *known));
+        // Keys are usually symbolic links, so they are followed.
+        if !hidden && imported && path.is_file() {
+            keys.push(path);
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(test, unix))]
+    mod merged_keys {
+        use super::super::merged_keys;
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        #[test]
+        fn imports_the_keys() {
+            let dir = tempfile::tempdir().unwrap();
+            let timestamped = dir.path().join("..2024_01_01_00_00_00.1");
+            fs::create_dir_all(&timestamped).unwrap();
+            for key in ["server.ncl", "database.json", "notes.txt"] {
+                fs::write(timestamped.join(key), "{}").unwrap();
+            }
+            symlink("..2024_01_01_00_00_00.1", dir.path().join("..data")).unwrap();
+            for key in ["server.ncl", "database.json", "notes.txt"] {
+                symlink(format!("..data/{key}"), dir.path().join(key)).unwrap();
+            }
+
+            let source = merged_keys(dir.path()).unwrap();
+
+            let root = dir.path().display();
+            assert_eq!(
+                source,
+                format!(r#"(import "{root}/database.json") & (import "{root}/server.ncl")"#)
+            );
+        }
+
+        #[test]
+        fn empty() {
+            let dir = tempfile::tempdir().unwrap();
+
+            assert_eq!(merged_keys(dir.path()).unwrap(), "{}");
+        }
+    }
+}
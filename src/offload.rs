@@ -0,0 +1,349 @@
+use crate::Diagnostic;
+use crate::Error;
+use crate::FieldError;
+use crate::Limit;
+use crate::Result;
+use nickel_lang_core::deserialize::RustDeserializationError;
+use nickel_lang_core::error::EvalError;
+use nickel_lang_core::position::TermPos;
+use std::future::Future;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::thread;
+use std::time::Duration;
+
+/// Runs the blocking work of the async loads (like [`crate::Loader::load_async`]): reading
+/// the configuration files and evaluating them, off the tasks awaiting it.
+///
+/// Nickelodeon doesn't depend on any async runtime: by default, each load runs on its own
+/// thread ([`SpawnThread`]). Implement this trait to use the blocking pool of your runtime
+/// instead, e.g. with `tokio::task::spawn_blocking`, `async_std::task::spawn_blocking` or
+/// `blocking::unblock`:
+///
+/// ```
+/// struct Tokio;
+///
+/// impl nickelodeon::Offload for Tokio {
+///     fn offload(&self, job: Box<dyn FnOnce() + Send>) {
+///         # let spawn_blocking = |job: Box<dyn FnOnce() + Send>| std::thread::spawn(job);
+///         // tokio::task::spawn_blocking(job);
+///         spawn_blocking(job);
+///     }
+/// }
+///
+/// let loader = nickelodeon::Loader::new("my-app").offload(Tokio);
+/// ```
+pub trait Offload: Send + Sync {
+    /// Runs `job`, which blocks, on another thread. A `job` dropped without being run (e.g.
+    /// by a runtime shutting down) fails its load with [`Error::Cancelled`].
+    fn offload(&self, job: Box<dyn FnOnce() + Send>);
+}
+
+/// The default [`Offload`], running each job on a new thread.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpawnThread;
+
+impl Offload for SpawnThread {
+    fn offload(&self, job: Box<dyn FnOnce() + Send>) {
+        thread::spawn(job);
+    }
+}
+
+/// The future of an async load, resolving to the result of the offloaded work. It doesn't
+/// need to be polled for the work to progress.
+#[must_use = "futures do nothing unless awaited, although the load still runs"]
+pub struct Loading<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> std::fmt::Debug for Loading<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Loading").finish_non_exhaustive()
+    }
+}
+
+/// Where the offloaded work leaves its outcome for the [`Loading`] future.
+struct State<T> {
+    outcome: Option<Outcome<T>>,
+    waker: Option<Waker>,
+}
+
+/// The result of the offloaded work, or the panic it unwound with.
+struct Outcome<T>(thread::Result<std::result::Result<T, SentError>>);
+
+/// An [`Error`] sent by the offloaded work to the [`Loading`] future.
+///
+/// The Nickel errors hold `Rc`s on the terms of the evaluation, which can't be sent to
+/// another thread, so an [`Error::NickelEvaluationError`] is sent as its message and its
+/// diagnostics, and built again from them.
+enum SentError {
+    ConfigFileReadingError(String),
+    ConfigTooLarge(PathBuf, u64),
+    ConfigNotFound(Vec<PathBuf>),
+    InsecurePermissions(PathBuf, String),
+    AmbiguousConfig(PathBuf, PathBuf),
+    ForbiddenImport(PathBuf, PathBuf),
+    NickelEvaluationError(String, Vec<Diagnostic>),
+    EvaluationPanicked(PathBuf, String),
+    EvaluationTimeout(PathBuf, Duration),
+    LimitExceeded(PathBuf, Limit),
+    Cancelled,
+    RustDeserializationError(RustDeserializationError, Vec<Diagnostic>),
+    InvalidFields(Vec<FieldError>),
+    SerializationError(String),
+    ConfigFileWritingError(String),
+    UnresolvedReference(String, String, String),
+}
+
+impl From<Error> for SentError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::ConfigFileReadingError(message) => Self::ConfigFileReadingError(message),
+            Error::ConfigTooLarge(path, max) => Self::ConfigTooLarge(path, max),
+            Error::ConfigNotFound(searched) => Self::ConfigNotFound(searched),
+            Error::InsecurePermissions(path, reason) => Self::InsecurePermissions(path, reason),
+            Error::AmbiguousConfig(used, other) => Self::AmbiguousConfig(used, other),
+            Error::ForbiddenImport(file, import) => Self::ForbiddenImport(file, import),
+            Error::NickelEvaluationError(nickel, diagnostics) => {
+                let message = diagnostics.first().map_or_else(
+                    || format!("{nickel:?}"),
+                    |diagnostic| diagnostic.message.clone(),
+                );
+                Self::NickelEvaluationError(message, diagnostics)
+            }
+            Error::EvaluationPanicked(path, message) => Self::EvaluationPanicked(path, message),
+            Error::EvaluationTimeout(path, timeout) => Self::EvaluationTimeout(path, timeout),
+            Error::LimitExceeded(path, limit) => Self::LimitExceeded(path, limit),
+            Error::Cancelled => Self::Cancelled,
+            Error::RustDeserializationError(mismatch, diagnostics) => {
+                Self::RustDeserializationError(mismatch, diagnostics)
+            }
+            Error::InvalidFields(fields) => Self::InvalidFields(fields),
+            Error::SerializationError(message) => Self::SerializationError(message),
+            Error::ConfigFileWritingError(message) => Self::ConfigFileWritingError(message),
+            Error::UnresolvedReference(path, reference, reason) => {
+                Self::UnresolvedReference(path, reference, reason)
+            }
+        }
+    }
+}
+
+impl From<SentError> for Error {
+    fn from(error: SentError) -> Self {
+        match error {
+            SentError::ConfigFileReadingError(message) => Self::ConfigFileReadingError(message),
+            SentError::ConfigTooLarge(path, max) => Self::ConfigTooLarge(path, max),
+            SentError::ConfigNotFound(searched) => Self::ConfigNotFound(searched),
+            SentError::InsecurePermissions(path, reason) => Self::InsecurePermissions(path, reason),
+            SentError::AmbiguousConfig(used, other) => Self::AmbiguousConfig(used, other),
+            SentError::ForbiddenImport(file, import) => Self::ForbiddenImport(file, import),
+            SentError::NickelEvaluationError(message, diagnostics) => {
+                let nickel = EvalError::Other(message, TermPos::None);
+                Self::NickelEvaluationError(nickel.into(), diagnostics)
+            }
+            SentError::EvaluationPanicked(path, message) => Self::EvaluationPanicked(path, message),
+            SentError::EvaluationTimeout(path, timeout) => Self::EvaluationTimeout(path, timeout),
+            SentError::LimitExceeded(path, limit) => Self::LimitExceeded(path, limit),
+            SentError::Cancelled => Self::Cancelled,
+            SentError::RustDeserializationError(mismatch, diagnostics) => {
+                Self::RustDeserializationError(mismatch, diagnostics)
+            }
+            SentError::InvalidFields(fields) => Self::InvalidFields(fields),
+            SentError::SerializationError(message) => Self::SerializationError(message),
+            SentError::ConfigFileWritingError(message) => Self::ConfigFileWritingError(message),
+            SentError::UnresolvedReference(path, reference, reason) => {
+                Self::UnresolvedReference(path, reference, reason)
+            }
+        }
+    }
+}
+
+impl<T> Future for Loading<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match state.outcome.take() {
+            Some(Outcome(Ok(loaded))) => Poll::Ready(loaded.map_err(Error::from)),
+            Some(Outcome(Err(payload))) => panic::resume_unwind(payload),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Completes a [`Loading`] future when dropped, with [`Error::Cancelled`] if the work
+/// didn't run.
+struct Completion<T> {
+    state: Arc<Mutex<State<T>>>,
+    outcome: Option<Outcome<T>>,
+}
+
+impl<T> Completion<T> {
+    fn complete(mut self, outcome: Outcome<T>) {
+        self.outcome = Some(outcome);
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        state.outcome = Some(
+            self.outcome
+                .take()
+                .unwrap_or(Outcome(Ok(Err(SentError::Cancelled)))),
+        );
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Runs `work` through `offload`, returning the future of its result. Panics in `work` are
+/// resumed when the future is polled.
+pub(crate) fn offloaded<T, F>(offload: &dyn Offload, work: F) -> Loading<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let (loading, completion) = pending();
+    offload.offload(job(work, completion));
+    loading
+}
+
+/// Awaits `awaited` on the tokio runtime `runtime`, then runs `work` with its output through
+/// `offload`, returning the future of the result. Like [`offloaded`], the future doesn't need
+/// to be polled for the work to progress.
+#[cfg(all(feature = "remote", feature = "tokio"))]
+pub(crate) fn offloaded_after<T, A, F>(
+    runtime: &tokio::runtime::Handle,
+    offload: Arc<dyn Offload>,
+    awaited: A,
+    work: F,
+) -> Loading<T>
+where
+    T: Send + 'static,
+    A: Future + Send + 'static,
+    A::Output: Send + 'static,
+    F: FnOnce(A::Output) -> Result<T> + Send + 'static,
+{
+    let (loading, completion) = pending();
+    let _detached = runtime.spawn(async move {
+        let output = awaited.await;
+        offload.offload(job(move || work(output), completion));
+    });
+    loading
+}
+
+/// Returns a [`Loading`] future, with the [`Completion`] completing it.
+fn pending<T>() -> (Loading<T>, Completion<T>) {
+    let state = Arc::new(Mutex::new(State {
+        outcome: None,
+        waker: None,
+    }));
+    let completion = Completion {
+        state: Arc::clone(&state),
+        outcome: None,
+    };
+    (Loading { state }, completion)
+}
+
+/// Returns the job running `work`, then completing `completion` with its outcome.
+fn job<T, F>(work: F, completion: Completion<T>) -> Box<dyn FnOnce() + Send>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    Box::new(move || {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| work().map_err(SentError::from)));
+        completion.complete(Outcome(outcome));
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::Context;
+    use std::task::Poll;
+    use std::task::Wake;
+    use std::thread;
+    use std::thread::Thread;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Runs `future` to completion on the current thread, like a minimal runtime.
+    pub(crate) fn block_on<F: Future>(unpinned: F) -> F::Output {
+        let mut future = pin!(unpinned);
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[cfg(test)]
+    mod offloaded {
+        use super::super::offloaded;
+        use super::super::Offload;
+        use super::super::SpawnThread;
+        use super::block_on;
+        use crate::Error;
+
+        struct Dropping;
+
+        impl Offload for Dropping {
+            fn offload(&self, job: Box<dyn FnOnce() + Send>) {
+                drop(job);
+            }
+        }
+
+        #[test]
+        fn result() {
+            let loading = offloaded(&SpawnThread, || Ok(42));
+
+            assert_eq!(block_on(loading).unwrap(), 42);
+        }
+
+        #[test]
+        fn dropped_job() {
+            let loading = offloaded(&Dropping, || Ok(42));
+
+            assert!(matches!(block_on(loading), Err(Error::Cancelled)));
+        }
+
+        #[test]
+        #[should_panic(expected = "boom")]
+        fn panicking_job() {
+            let loading = offloaded::<(), _>(&SpawnThread, || std::panic::panic_any("boom"));
+
+            let _result = block_on(loading);
+        }
+    }
+}
@@ -0,0 +1,599 @@
+//! Deserialization helpers for the values configurations commonly hold as strings, to use
+//! with `#[serde(deserialize_with = "...")]`:
+//!
+//! ```
+//! use std::net::SocketAddr;
+//! use std::time::Duration;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Server {
+//!     #[serde(deserialize_with = "nickelodeon::parse::duration")]
+//!     timeout: Duration,
+//!     #[serde(deserialize_with = "nickelodeon::parse::byte_size")]
+//!     max_body: u64,
+//!     #[serde(deserialize_with = "nickelodeon::parse::url")]
+//!     upstream: String,
+//!     #[serde(deserialize_with = "nickelodeon::parse::socket_addr")]
+//!     listen: SocketAddr,
+//! }
+//! ```
+//!
+//! The mistakes are reported like the other deserialization errors (with the path of the
+//! field when [`crate::Loader::collect_all_errors`] is enabled), saying what was expected,
+//! e.g. ``invalid duration `30x`: unknown unit `x` ``.
+//!
+//! The `chrono`, `time` and `cron` modules, behind the features of the same names,
+//! parse timestamps, dates and schedules into the types of those crates.
+
+use serde::de;
+use serde::de::Visitor;
+use serde::Deserializer;
+use std::fmt;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs as _;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(feature = "chrono")]
+pub mod chrono;
+#[cfg(feature = "cron")]
+pub mod cron;
+#[cfg(feature = "time")]
+pub mod time;
+
+/// The units of durations, by their names, with their length in nanoseconds.
+const DURATION_UNITS: &[(&[&str], u64)] = &[
+    (&["ns", "nsec", "nanos"], 1),
+    (&["us", "\u{b5}s", "usec", "micros"], 1_000),
+    (&["ms", "msec", "millis"], 1_000_000),
+    (&["s", "sec", "secs", "second", "seconds"], 1_000_000_000),
+    (&["m", "min", "mins", "minute", "minutes"], 60_000_000_000),
+    (&["h", "hr", "hrs", "hour", "hours"], 3_600_000_000_000),
+    (&["d", "day", "days"], 86_400_000_000_000),
+    (&["w", "week", "weeks"], 604_800_000_000_000),
+];
+
+/// The units of byte sizes, lowercased, with their size in bytes.
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("b", 1),
+    ("k", 1_000),
+    ("kb", 1_000),
+    ("kib", 1 << 10),
+    ("m", 1_000_000),
+    ("mb", 1_000_000),
+    ("mib", 1 << 20),
+    ("g", 1_000_000_000),
+    ("gb", 1_000_000_000),
+    ("gib", 1 << 30),
+    ("t", 1_000_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("tib", 1 << 40),
+    ("p", 1_000_000_000_000_000),
+    ("pb", 1_000_000_000_000_000),
+    ("pib", 1 << 50),
+];
+
+/// Deserializes a [`Duration`] written like `"30s"`, `"1h 30m"` or `"250ms"`, or as a
+/// number of seconds.
+///
+/// The units are `ns`, `us`, `ms`, `s`, `m`, `h`, `d` and `w`, also accepted spelled out
+/// (e.g. `"2 hours"`).
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string or a positive number, or if the string
+/// isn't a valid duration.
+pub fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    match deserializer.deserialize_any(Raw("a duration, like \"30s\""))? {
+        Written::Integer(seconds) => Ok(Duration::from_secs(seconds)),
+        Written::Number(seconds) => {
+            Duration::try_from_secs_f64(seconds).map_err(|err| de::Error::custom(err.to_string()))
+        }
+        Written::Text(text) => parse_duration(&text)
+            .map_err(|reason| de::Error::custom(format!("invalid duration `{text}`: {reason}"))),
+    }
+}
+
+/// Deserializes a size in bytes written like `"512MiB"`, `"1.5 GB"` or `"64k"`, or as a
+/// number of bytes.
+///
+/// The units are case insensitive: `k`, `M`, `G`, `T` and `P`, with an optional `B`, are
+/// powers of 1000, and `KiB`, `MiB`, `GiB`, `TiB` and `PiB` powers of 1024.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string or a positive number, or if the string
+/// isn't a valid size.
+pub fn byte_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match deserializer.deserialize_any(Raw("a size, like \"512MiB\""))? {
+        Written::Integer(bytes) => Ok(bytes),
+        Written::Number(bytes) => parse_byte_size(&bytes.to_string())
+            .map_err(|reason| de::Error::custom(format!("invalid size `{bytes}`: {reason}"))),
+        Written::Text(text) => parse_byte_size(&text)
+            .map_err(|reason| de::Error::custom(format!("invalid size `{text}`: {reason}"))),
+    }
+}
+
+/// Deserializes an absolute URL, like `"https://example.com/api"`, checking it has a
+/// scheme and a host.
+///
+/// The URL is kept as a string. To get a parsed one, e.g. a `url::Url`, use [`from_str`].
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't an absolute URL.
+pub fn url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let text = text(deserializer, "a URL")?;
+    check_url(&text)
+        .map(|()| text.clone())
+        .map_err(|reason| de::Error::custom(format!("invalid URL `{text}`: {reason}")))
+}
+
+/// Deserializes a [`SocketAddr`] written as an address and a port.
+///
+/// The address is an IP address (e.g. `"127.0.0.1:8080"` or `"[::1]:8080"`) or a host name
+/// (e.g. `"localhost:8080"`), which is resolved to its first address.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't an address with a port
+/// or can't be resolved.
+pub fn socket_addr<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SocketAddr, D::Error> {
+    let text = text(deserializer, "a socket address, like \"127.0.0.1:8080\"")?;
+    if let Ok(address) = text.parse() {
+        return Ok(address);
+    }
+    text.to_socket_addrs()
+        .map_err(|err| err.to_string())
+        .and_then(|mut addresses| {
+            addresses
+                .next()
+                .ok_or_else(|| "the host has no address".to_owned())
+        })
+        .map_err(|reason| de::Error::custom(format!("invalid socket address `{text}`: {reason}")))
+}
+
+/// Deserializes a log level, like `"debug"`, `"WARN"` or the enum tag `'Info`.
+///
+/// It can be any type parsing the lowercase names of the levels (`trace`, `debug`, `info`,
+/// `warn` and `error`), like `log::LevelFilter` or `tracing::Level`. The names are case
+/// insensitive, and `warning`, `err`, `fatal` and `critical` are
+/// accepted too.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if `L` doesn't parse it.
+pub fn log_level<'de, D, L>(deserializer: D) -> Result<L, D::Error>
+where
+    D: Deserializer<'de>,
+    L: FromStr,
+{
+    let text = text(deserializer, "a log level, like \"info\"")?;
+    let name = text.trim().to_lowercase();
+    let level = match name.as_str() {
+        "warning" => "warn",
+        "err" | "fatal" | "critical" => "error",
+        other => other,
+    };
+    level.parse().map_err(|_err| {
+        de::Error::custom(format!(
+            "invalid log level `{text}`, expected one of trace, debug, info, warn, error"
+        ))
+    })
+}
+
+/// Deserializes a string into any type implementing [`FromStr`] (e.g. an `IpAddr` or a
+/// `url::Url`), reporting its parsing errors.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if `T` doesn't parse it.
+pub fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let text = text(deserializer, "a string")?;
+    text.parse()
+        .map_err(|err| de::Error::custom(format!("invalid value `{text}`: {err}")))
+}
+
+/// Parses a duration made of numbers followed by their units, e.g. `1h 30m`.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err("empty duration".to_owned());
+    }
+    let mut nanoseconds: u64 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|next: char| !next.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, after_number) = rest.split_at(digits);
+        let amount: u64 = number
+            .parse()
+            .map_err(|_err| format!("expected a number at `{rest}`"))?;
+        let after_spaces = after_number.trim_start();
+        let letters = after_spaces
+            .find(|next: char| next.is_ascii_digit() || next.is_whitespace())
+            .unwrap_or(after_spaces.len());
+        let (unit, after_unit) = after_spaces.split_at(letters);
+        if unit.is_empty() {
+            return Err(format!("missing the unit of `{number}`"));
+        }
+        let length = DURATION_UNITS
+            .iter()
+            .find(|(names, _)| names.contains(&unit))
+            .map(|&(_, length)| length)
+            .ok_or_else(|| format!("unknown unit `{unit}`"))?;
+        nanoseconds = amount
+            .checked_mul(length)
+            .and_then(|part| nanoseconds.checked_add(part))
+            .ok_or_else(|| "too long".to_owned())?;
+        rest = after_unit.trim_start();
+    }
+    Ok(Duration::from_nanos(nanoseconds))
+}
+
+/// Parses a size made of a number followed by an optional unit, e.g. `1.5 GiB`.
+fn parse_byte_size(text: &str) -> Result<u64, String> {
+    let trimmed = text.trim();
+    let end = trimmed
+        .find(|next: char| !next.is_ascii_digit() && next != '.')
+        .unwrap_or(trimmed.len());
+    let (number, after_number) = trimmed.split_at(end);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let mantissa: u128 = format!("{whole}{fraction}")
+        .parse()
+        .map_err(|_err| "expected a number followed by a unit".to_owned())?;
+    let unit = after_number.trim().to_lowercase();
+    let size = if unit.is_empty() {
+        1
+    } else {
+        SIZE_UNITS
+            .iter()
+            .find(|&&(name, _)| name == unit)
+            .map(|&(_, size)| size)
+            .ok_or_else(|| format!("unknown unit `{}`", after_number.trim()))?
+    };
+
+    let too_large = || "too large".to_owned();
+    let scale = u32::try_from(fraction.len())
+        .ok()
+        .and_then(|digits| 10_u128.checked_pow(digits))
+        .ok_or_else(too_large)?;
+    let scaled = mantissa
+        .checked_mul(u128::from(size))
+        .ok_or_else(too_large)?;
+    if scaled.checked_rem(scale) != Some(0) {
+        return Err("not a whole number of bytes".to_owned());
+    }
+    scaled
+        .checked_div(scale)
+        .and_then(|bytes| u64::try_from(bytes).ok())
+        .ok_or_else(too_large)
+}
+
+/// Checks that `text` is an absolute URL: a scheme, followed by `://` and a host.
+fn check_url(text: &str) -> Result<(), String> {
+    let (scheme, rest) = text
+        .split_once("://")
+        .ok_or_else(|| "expected a scheme followed by `://`".to_owned())?;
+    let mut scheme_chars = scheme.chars();
+    let valid_scheme = scheme_chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic())
+        && scheme_chars.all(|next| next.is_ascii_alphanumeric() || matches!(next, '+' | '-' | '.'));
+    if !valid_scheme {
+        return Err(format!("invalid scheme `{scheme}`"));
+    }
+    if text.contains(char::is_whitespace) {
+        return Err("contains whitespace".to_owned());
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_user, host)| host);
+    if host.is_empty() || host.starts_with(':') {
+        return Err("missing the host".to_owned());
+    }
+    Ok(())
+}
+
+/// Deserializes a string, expected to be `expecting`.
+fn text<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    expecting: &'static str,
+) -> Result<String, D::Error> {
+    match deserializer.deserialize_any(Raw(expecting))? {
+        Written::Text(text) => Ok(text),
+        Written::Integer(number) => Err(de::Error::invalid_type(
+            de::Unexpected::Unsigned(number),
+            &expecting,
+        )),
+        Written::Number(number) => Err(de::Error::invalid_type(
+            de::Unexpected::Float(number),
+            &expecting,
+        )),
+    }
+}
+
+/// A value written either as a string or as a number.
+enum Written {
+    Text(String),
+    Integer(u64),
+    Number(f64),
+}
+
+/// Deserializes a [`Written`] value, expected to be the value it holds.
+struct Raw(&'static str);
+
+impl Visitor<'_> for Raw {
+    type Value = Written;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Written, E> {
+        Ok(Written::Text(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Written, E> {
+        Ok(Written::Text(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Written, E> {
+        Ok(Written::Integer(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Written, E> {
+        let positive =
+            u64::try_from(v).map_err(|_err| E::invalid_value(de::Unexpected::Signed(v), &self))?;
+        self.visit_u64(positive)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Written, E> {
+        if v >= 0.0 {
+            Ok(Written::Number(v))
+        } else {
+            Err(E::invalid_value(de::Unexpected::Float(v), &self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod duration {
+        use super::super::duration;
+        use serde_json::json;
+        use std::time::Duration;
+
+        #[test]
+        fn written_with_units() {
+            assert_eq!(duration(json!("30s")).unwrap(), Duration::from_secs(30));
+            assert_eq!(duration(json!("1h 30m")).unwrap(), Duration::from_mins(90));
+            assert_eq!(duration(json!("1h30m")).unwrap(), Duration::from_mins(90));
+            assert_eq!(
+                duration(json!("250ms")).unwrap(),
+                Duration::from_millis(250)
+            );
+            assert_eq!(duration(json!("2 days")).unwrap(), Duration::from_hours(48));
+        }
+
+        #[test]
+        fn written_as_seconds() {
+            assert_eq!(duration(json!(90)).unwrap(), Duration::from_secs(90));
+            assert_eq!(duration(json!(0.5)).unwrap(), Duration::from_millis(500));
+        }
+
+        #[test]
+        fn invalid() {
+            let errors = ["30x", "30", "s", "", "-1s"]
+                .map(|text| duration(json!(text)).unwrap_err().to_string());
+
+            assert_eq!(
+                errors,
+                [
+                    "invalid duration `30x`: unknown unit `x`",
+                    "invalid duration `30`: missing the unit of `30`",
+                    "invalid duration `s`: expected a number at `s`",
+                    "invalid duration ``: empty duration",
+                    "invalid duration `-1s`: expected a number at `-1s`",
+                ]
+            );
+            duration(json!(-1)).unwrap_err();
+            duration(json!(true)).unwrap_err();
+        }
+    }
+
+    #[cfg(test)]
+    mod byte_size {
+        use super::super::byte_size;
+        use serde_json::json;
+
+        #[test]
+        fn written_with_units() {
+            assert_eq!(byte_size(json!("512MiB")).unwrap(), 512 << 20);
+            assert_eq!(byte_size(json!("1.5 GB")).unwrap(), 1_500_000_000);
+            assert_eq!(byte_size(json!("64k")).unwrap(), 64_000);
+            assert_eq!(byte_size(json!("10")).unwrap(), 10);
+            assert_eq!(byte_size(json!(1024)).unwrap(), 1024);
+        }
+
+        #[test]
+        fn invalid() {
+            let errors = ["12 parsecs", "MiB", "0.5B"]
+                .map(|text| byte_size(json!(text)).unwrap_err().to_string());
+
+            assert_eq!(
+                errors,
+                [
+                    "invalid size `12 parsecs`: unknown unit `parsecs`",
+                    "invalid size `MiB`: expected a number followed by a unit",
+                    "invalid size `0.5B`: not a whole number of bytes",
+                ]
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod url {
+        use super::super::url;
+        use serde_json::json;
+
+        #[test]
+        fn absolute() {
+            assert_eq!(
+                url(json!("https://user@example.com:8443/api?q=1")).unwrap(),
+                "https://user@example.com:8443/api?q=1"
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            let errors = [
+                "example.com",
+                "1http://example.com",
+                "https:///api",
+                "https://a b",
+            ]
+            .map(|text| url(json!(text)).unwrap_err().to_string());
+
+            assert_eq!(
+                errors,
+                [
+                    "invalid URL `example.com`: expected a scheme followed by `://`",
+                    "invalid URL `1http://example.com`: invalid scheme `1http`",
+                    "invalid URL `https:///api`: missing the host",
+                    "invalid URL `https://a b`: contains whitespace",
+                ]
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod socket_addr {
+        use super::super::socket_addr;
+        use serde_json::json;
+        use std::net::SocketAddr;
+
+        #[test]
+        fn addresses() {
+            assert_eq!(
+                socket_addr(json!("127.0.0.1:8080")).unwrap(),
+                SocketAddr::from(([127, 0, 0, 1], 8080))
+            );
+            assert_eq!(
+                socket_addr(json!("[::1]:8080")).unwrap(),
+                SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 8080))
+            );
+            assert_eq!(socket_addr(json!("localhost:8080")).unwrap().port(), 8080);
+            socket_addr(json!("localhost")).unwrap_err();
+        }
+    }
+
+    #[cfg(test)]
+    mod log_level {
+        use super::super::log_level;
+        use serde_json::json;
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Level(&'static str);
+
+        impl std::str::FromStr for Level {
+            type Err = ();
+
+            fn from_str(s: &str) -> Result<Self, ()> {
+                ["trace", "debug", "info", "warn", "error"]
+                    .into_iter()
+                    .find(|&name| name == s)
+                    .map(Self)
+                    .ok_or(())
+            }
+        }
+
+        #[test]
+        fn names() {
+            let levels: Vec<Level> = ["Debug", "WARNING", " info ", "fatal"]
+                .into_iter()
+                .map(|name| log_level(json!(name)).unwrap())
+                .collect();
+
+            assert_eq!(
+                levels,
+                [Level("debug"), Level("warn"), Level("info"), Level("error")]
+            );
+            assert_eq!(
+                log_level::<_, Level>(json!("loud"))
+                    .unwrap_err()
+                    .to_string(),
+                "invalid log level `loud`, expected one of trace, debug, info, warn, error"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod loading {
+        use crate::Error;
+        use crate::Loader;
+        use std::io::Write as _;
+        use std::time::Duration;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, serde::Deserialize, PartialEq, Eq)]
+        struct Server {
+            #[serde(deserialize_with = "crate::parse::duration")]
+            timeout: Duration,
+            #[serde(deserialize_with = "crate::parse::byte_size")]
+            max_body: u64,
+            #[serde(deserialize_with = "crate::parse::url")]
+            upstream: String,
+        }
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink());
+            (ntf, loader)
+        }
+
+        #[test]
+        fn parsed() {
+            let (_ntf, loader) =
+                loader(r#"{ timeout = "1m", max_body = "1 KiB", upstream = "http://10.0.0.1" }"#);
+
+            let server = loader.load::<Server>().unwrap();
+
+            assert_eq!(
+                server,
+                Server {
+                    timeout: Duration::from_mins(1),
+                    max_body: 1024,
+                    upstream: "http://10.0.0.1".to_owned(),
+                }
+            );
+        }
+
+        #[test]
+        fn invalid_fields() {
+            let (_ntf, loader) =
+                loader(r#"{ timeout = "1 fortnight", max_body = 1, upstream = "http://b" }"#);
+
+            let result = loader.collect_all_errors(true).load::<Server>();
+
+            let Err(Error::InvalidFields(errors)) = result else {
+                panic!("expected invalid fields, got {result:?}");
+            };
+            assert_eq!(
+                errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                ["`timeout`: invalid duration `1 fortnight`: unknown unit `fortnight`"]
+            );
+        }
+    }
+}
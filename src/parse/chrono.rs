@@ -0,0 +1,130 @@
+//! Deserialization helpers for the [`chrono`](::chrono) types, behind the `chrono` feature.
+
+use super::text;
+use ::chrono::DateTime;
+use ::chrono::FixedOffset;
+use ::chrono::NaiveDate;
+use serde::de;
+use serde::Deserializer;
+
+/// Deserializes an RFC 3339 timestamp, like `"2024-05-01T12:00:00Z"` or
+/// `"2024-05-01T14:00:00+02:00"`.
+///
+/// It can be a `DateTime<FixedOffset>`, keeping the offset it was written with, or a
+/// `DateTime<Utc>`.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't an RFC 3339 timestamp.
+pub fn timestamp<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<DateTime<FixedOffset>>,
+{
+    let text = text(
+        deserializer,
+        "an RFC 3339 timestamp, like \"2024-05-01T12:00:00Z\"",
+    )?;
+    DateTime::parse_from_rfc3339(text.trim())
+        .map(T::from)
+        .map_err(|err| de::Error::custom(format!("invalid timestamp `{text}`: {err}")))
+}
+
+/// Deserializes a [`NaiveDate`] written like `"2024-05-01"`.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't a valid date.
+pub fn date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+    let text = text(deserializer, "a date, like \"2024-05-01\"")?;
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|err| de::Error::custom(format!("invalid date `{text}`: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod timestamp {
+        use super::super::timestamp;
+        use ::chrono::DateTime;
+        use ::chrono::FixedOffset;
+        use ::chrono::TimeZone as _;
+        use ::chrono::Utc;
+        use serde_json::json;
+
+        #[test]
+        fn rfc3339() {
+            let utc: DateTime<Utc> = timestamp(json!("2024-05-01T14:00:00+02:00")).unwrap();
+            assert_eq!(utc, Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap());
+
+            let offset: DateTime<FixedOffset> =
+                timestamp(json!("2024-05-01T14:00:00+02:00")).unwrap();
+            assert_eq!(offset.offset().local_minus_utc(), 7200);
+        }
+
+        #[test]
+        fn invalid() {
+            let result: Result<DateTime<Utc>, _> = timestamp(json!("2024-05-01"));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "invalid timestamp `2024-05-01`: premature end of input"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod date {
+        use super::super::date;
+        use ::chrono::NaiveDate;
+        use serde_json::json;
+
+        #[test]
+        fn parsed() {
+            assert_eq!(
+                date(json!("2024-05-01")).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+            );
+            assert_eq!(
+                date(json!("2024-02-30")).unwrap_err().to_string(),
+                "invalid date `2024-02-30`: input is out of range"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod loading {
+        use crate::Error;
+        use crate::Loader;
+        use ::chrono::DateTime;
+        use ::chrono::Utc;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Release {
+            #[serde(deserialize_with = "crate::parse::chrono::timestamp")]
+            #[allow(dead_code)]
+            published: DateTime<Utc>,
+        }
+
+        #[test]
+        fn invalid_field() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ published = "2024-13-01T00:00:00Z" }}"#).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .collect_all_errors(true)
+                .load::<Release>();
+
+            let Err(Error::InvalidFields(errors)) = result else {
+                panic!("expected invalid fields, got {result:?}");
+            };
+            assert_eq!(
+                errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                ["`published`: invalid timestamp `2024-13-01T00:00:00Z`: input is out of range"]
+            );
+        }
+    }
+}
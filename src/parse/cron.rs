@@ -0,0 +1,57 @@
+//! Deserialization helpers for the [`cron`](::cron) types, behind the `cron` feature.
+
+use super::text;
+use ::cron::Schedule;
+use serde::de;
+use serde::Deserializer;
+use std::str::FromStr as _;
+
+/// Deserializes a cron [`Schedule`], like `"0 */5 * * * *"`.
+///
+/// Besides the fields of the `cron` crate (seconds, minutes, hours, days of the month,
+/// months, days of the week and an optional year), the five fields of a crontab line are
+/// accepted, like `"*/5 * * * *"`, running at the start of the minute.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't a valid schedule.
+pub fn schedule<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Schedule, D::Error> {
+    let text = text(deserializer, "a cron schedule, like \"0 */5 * * * *\"")?;
+    let expression = if text.split_whitespace().count() == 5 {
+        format!("0 {}", text.trim())
+    } else {
+        text.trim().to_owned()
+    };
+    Schedule::from_str(&expression)
+        .map_err(|err| de::Error::custom(format!("invalid cron schedule `{text}`: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod schedule {
+        use super::super::schedule;
+        use serde_json::json;
+
+        #[test]
+        fn with_seconds() {
+            let parsed = schedule(json!("0 30 9 * * Mon-Fri")).unwrap();
+            assert_eq!(parsed.to_string(), "0 30 9 * * Mon-Fri");
+        }
+
+        #[test]
+        fn crontab() {
+            let parsed = schedule(json!("*/5 * * * *")).unwrap();
+            assert_eq!(parsed.to_string(), "0 */5 * * * *");
+        }
+
+        #[test]
+        fn invalid() {
+            let message = schedule(json!("every day")).unwrap_err().to_string();
+            assert!(
+                message.starts_with("invalid cron schedule `every day`: "),
+                "{message}"
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Deserialization helpers for the [`time`](::time) types, behind the `time` feature.
+
+use super::text;
+use ::time::format_description::well_known::Iso8601;
+use ::time::format_description::well_known::Rfc3339;
+use ::time::Date;
+use ::time::OffsetDateTime;
+use serde::de;
+use serde::Deserializer;
+
+/// Deserializes an [`OffsetDateTime`] written as an RFC 3339 timestamp, like
+/// `"2024-05-01T12:00:00Z"` or `"2024-05-01T14:00:00+02:00"`.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't an RFC 3339 timestamp.
+pub fn timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+    let text = text(
+        deserializer,
+        "an RFC 3339 timestamp, like \"2024-05-01T12:00:00Z\"",
+    )?;
+    OffsetDateTime::parse(text.trim(), &Rfc3339)
+        .map_err(|err| de::Error::custom(format!("invalid timestamp `{text}`: {err}")))
+}
+
+/// Deserializes a [`Date`] written like `"2024-05-01"`.
+///
+/// # Errors
+///
+/// Will return `Err` if the value isn't a string, or if it isn't a valid date.
+pub fn date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Date, D::Error> {
+    let text = text(deserializer, "a date, like \"2024-05-01\"")?;
+    Date::parse(text.trim(), &Iso8601::DATE)
+        .map_err(|err| de::Error::custom(format!("invalid date `{text}`: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod timestamp {
+        use super::super::timestamp;
+        use ::time::Date;
+        use ::time::Month;
+        use ::time::UtcOffset;
+        use serde_json::json;
+
+        #[test]
+        fn rfc3339() {
+            let parsed = timestamp(json!("2024-05-01T14:00:00+02:00")).unwrap();
+
+            assert_eq!(
+                parsed.date(),
+                Date::from_calendar_date(2024, Month::May, 1).unwrap()
+            );
+            assert_eq!(parsed.offset(), UtcOffset::from_hms(2, 0, 0).unwrap());
+        }
+
+        #[test]
+        fn invalid() {
+            let message = timestamp(json!("yesterday")).unwrap_err().to_string();
+            assert!(
+                message.starts_with("invalid timestamp `yesterday`: "),
+                "{message}"
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod date {
+        use super::super::date;
+        use ::time::Date;
+        use ::time::Month;
+        use serde_json::json;
+
+        #[test]
+        fn parsed() {
+            assert_eq!(
+                date(json!("2024-05-01")).unwrap(),
+                Date::from_calendar_date(2024, Month::May, 1).unwrap()
+            );
+            let message = date(json!("2024-02-30")).unwrap_err().to_string();
+            assert!(
+                message.starts_with("invalid date `2024-02-30`: "),
+                "{message}"
+            );
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::fmt;
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A path read from the configuration, with a leading `~` and the `${VAR}` environment
+/// variables it contains expanded.
+///
+/// Users can so write paths the way they would in their shell:
+///
+/// ```no_run
+/// #[derive(Default, serde::Deserialize)]
+/// struct Config {
+///     data_dir: nickelodeon::ExpandedPathBuf,
+/// }
+///
+/// // With `data_dir = "~/data/${APP_ENV}"` and `APP_ENV=prod`, this is
+/// // `$HOME/data/prod`.
+/// # fn main() -> nickelodeon::Result<()> {
+/// let config: Config = nickelodeon::Loader::new("my-app").load()?;
+/// let data_dir: &std::path::Path = &config.data_dir;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The `~` is only expanded at the start of the path, when followed by a `/` or nothing,
+/// into `$HOME` (or `%USERPROFILE%` on Windows). Deserializing fails if the home directory
+/// or a variable isn't set, or if a `${` isn't closed. It serializes back as the expanded
+/// path.
+///
+/// Relative paths are kept relative to the current directory. Use a [`ResolvedPathBuf`]
+/// for them to be relative to the configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExpandedPathBuf(PathBuf);
+
+impl ExpandedPathBuf {
+    /// Returns the expanded path.
+    #[must_use]
+    pub fn into_path_buf(self) -> PathBuf {
+        self.0
+    }
+}
+
+impl Deref for ExpandedPathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ExpandedPathBuf {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<ExpandedPathBuf> for PathBuf {
+    fn from(path: ExpandedPathBuf) -> Self {
+        path.0
+    }
+}
+
+impl<'de> Deserialize<'de> for ExpandedPathBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        expand(&text, |name| std::env::var_os(name))
+            .map(Self)
+            .map_err(|reason| de::Error::custom(format!("invalid path `{text}`: {reason}")))
+    }
+}
+
+impl Serialize for ExpandedPathBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl fmt::Display for ExpandedPathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.display().fmt(f)
+    }
+}
+
+thread_local! {
+    /// The directory of the configuration file being deserialized on this thread, against
+    /// which the [`ResolvedPathBuf`]s are resolved.
+    static CONFIG_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// A path read from the configuration, expanded like an [`ExpandedPathBuf`] and then, when
+/// relative, resolved against the directory of the configuration file rather than the
+/// current directory.
+///
+/// With `/etc/my-app/config.ncl` holding `certificate = "tls/cert.pem"`, a `certificate`
+/// field of this type is `/etc/my-app/tls/cert.pem`, wherever the application is started
+/// from. Relative paths given by the defaults (see [`crate::Loader::defaults`]), or
+/// deserialized outside of a [`crate::Loader`], are kept relative.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResolvedPathBuf(PathBuf);
+
+impl ResolvedPathBuf {
+    /// Returns the resolved path.
+    #[must_use]
+    pub fn into_path_buf(self) -> PathBuf {
+        self.0
+    }
+}
+
+impl Deref for ResolvedPathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ResolvedPathBuf {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<ResolvedPathBuf> for PathBuf {
+    fn from(path: ResolvedPathBuf) -> Self {
+        path.0
+    }
+}
+
+impl<'de> Deserialize<'de> for ResolvedPathBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ExpandedPathBuf(path) = ExpandedPathBuf::deserialize(deserializer)?;
+        if path.is_absolute() {
+            return Ok(Self(path));
+        }
+        let resolved = CONFIG_DIR.with_borrow(|dir| dir.as_ref().map(|parent| parent.join(&path)));
+        Ok(Self(resolved.unwrap_or(path)))
+    }
+}
+
+impl Serialize for ResolvedPathBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl fmt::Display for ResolvedPathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.display().fmt(f)
+    }
+}
+
+/// Runs `deserialize` with the [`ResolvedPathBuf`]s resolved against the directory of the
+/// configuration file at `config`, or kept relative without one.
+pub(crate) fn resolving_against<T, D>(config: Option<&Path>, deserialize: D) -> T
+where
+    D: FnOnce() -> T,
+{
+    let dir = config.and_then(Path::parent).map(Path::to_path_buf);
+    let _restore = Restore(CONFIG_DIR.replace(dir));
+    deserialize()
+}
+
+/// Restores the directory [`ResolvedPathBuf`]s were resolved against when dropped, even if
+/// deserializing panicked.
+struct Restore(Option<PathBuf>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CONFIG_DIR.set(self.0.take());
+    }
+}
+
+/// Expands the leading `~` and the `${VAR}` variables of `text`, looking the variables up
+/// with `var`.
+fn expand(text: &str, var: impl Fn(&str) -> Option<OsString>) -> Result<PathBuf, String> {
+    let home_relative = text
+        .strip_prefix('~')
+        .filter(|rest| rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\'));
+    let (mut expanded, mut rest) = match home_relative {
+        Some(rest) => {
+            let home_var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+            let home = var(home_var).ok_or_else(|| format!("`{home_var}` isn't set"))?;
+            (home, rest)
+        }
+        None => (OsString::new(), text),
+    };
+
+    while let Some((before, after_dollar)) = rest.split_once("${") {
+        let (name, after_name) = after_dollar
+            .split_once('}')
+            .ok_or_else(|| "a `${` isn't closed".to_owned())?;
+        expanded.push(before);
+        expanded.push(var(name).ok_or_else(|| format!("`{name}` isn't set"))?);
+        rest = after_name;
+    }
+    expanded.push(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod expand {
+        use super::super::expand;
+        use std::ffi::OsString;
+        use std::path::PathBuf;
+
+        fn var(name: &str) -> Option<OsString> {
+            match name {
+                "HOME" | "USERPROFILE" => Some("/home/nick".into()),
+                "APP_ENV" => Some("prod".into()),
+                _ => None,
+            }
+        }
+
+        #[test]
+        fn home() {
+            assert_eq!(expand("~", var), Ok(PathBuf::from("/home/nick")));
+            assert_eq!(expand("~/data", var), Ok(PathBuf::from("/home/nick/data")));
+            assert_eq!(expand("~nick/data", var), Ok(PathBuf::from("~nick/data")));
+            assert_eq!(expand("data/~", var), Ok(PathBuf::from("data/~")));
+            assert_eq!(
+                expand("~/data", |_name| None),
+                Err(format!(
+                    "`{}` isn't set",
+                    if cfg!(windows) { "USERPROFILE" } else { "HOME" }
+                ))
+            );
+        }
+
+        #[test]
+        fn variables() {
+            assert_eq!(
+                expand("~/data/${APP_ENV}/${APP_ENV}.db", var),
+                Ok(PathBuf::from("/home/nick/data/prod/prod.db"))
+            );
+            assert_eq!(
+                expand("/var/$APP_ENV", var),
+                Ok(PathBuf::from("/var/$APP_ENV"))
+            );
+            assert_eq!(
+                expand("/var/${MISSING}", var),
+                Err("`MISSING` isn't set".to_owned())
+            );
+            assert_eq!(
+                expand("/var/${APP_ENV", var),
+                Err("a `${` isn't closed".to_owned())
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod expanded_path_buf {
+        use super::super::ExpandedPathBuf;
+        use crate::Loader;
+        use std::io::Write as _;
+        use std::path::Path;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Config {
+            data_dir: ExpandedPathBuf,
+        }
+
+        #[test]
+        fn loads() {
+            std::env::set_var("NICKELODEON_TEST_EXPANDED_DIR", "/srv/app");
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(
+                ntf,
+                r#"{{ data_dir = "${{NICKELODEON_TEST_EXPANDED_DIR}}/data" }}"#
+            )
+            .unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load::<Config>()
+                .unwrap();
+
+            assert_eq!(&*config.data_dir, Path::new("/srv/app/data"));
+            assert_eq!(config.data_dir.to_string(), "/srv/app/data");
+        }
+    }
+
+    #[cfg(test)]
+    mod resolved_path_buf {
+        use super::super::resolving_against;
+        use super::super::ResolvedPathBuf;
+        use crate::Loader;
+        use std::path::Path;
+        use std::path::PathBuf;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Config {
+            certificate: ResolvedPathBuf,
+            log: ResolvedPathBuf,
+        }
+
+        #[test]
+        fn relative_to_the_config_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            std::fs::write(
+                &path,
+                r#"{ certificate = "tls/cert.pem", log = "/var/log/app.log" }"#,
+            )
+            .unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path))
+                .diagnostics(std::io::sink())
+                .load::<Config>()
+                .unwrap();
+
+            assert_eq!(&*config.certificate, dir.path().join("tls/cert.pem"));
+            assert_eq!(&*config.log, Path::new("/var/log/app.log"));
+        }
+
+        #[test]
+        fn relative_without_a_config_file() {
+            let path: ResolvedPathBuf = serde_json::from_str(r#""tls/cert.pem""#).unwrap();
+            assert_eq!(&*path, Path::new("tls/cert.pem"));
+
+            let restored: ResolvedPathBuf =
+                resolving_against(Some(Path::new("/etc/app/config.ncl")), || {
+                    let _inner: ResolvedPathBuf =
+                        resolving_against(None, || serde_json::from_str(r#""a""#).unwrap());
+                    serde_json::from_str(r#""b""#).unwrap()
+                });
+            assert_eq!(restored.into_path_buf(), PathBuf::from("/etc/app/b"));
+        }
+    }
+}
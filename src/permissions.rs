@@ -0,0 +1,84 @@
+use crate::Message;
+use crate::Messages;
+use std::path::Path;
+
+/// What to do when the configuration file could have been tampered with by someone other
+/// than the user running the application, similarly to what OpenSSH does.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PermissionCheck {
+    /// Don't check the permissions of the configuration file.
+    #[default]
+    Off,
+
+    /// Add a warning to the [`crate::LoadReport`] when the configuration file is insecure.
+    Warn,
+
+    /// Fail with [`crate::Error::InsecurePermissions`] when the configuration file is
+    /// insecure.
+    Deny,
+}
+
+/// Returns why the file at `path` is insecure, if it is: either anyone can write it, or it
+/// is owned by a user other than the current one (or `root`).
+///
+/// Always `None` outside of Unix.
+#[cfg(unix)]
+pub(crate) fn insecure(path: &Path, messages: &dyn Messages) -> Option<String> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let metadata = std::fs::metadata(path).ok()?;
+    if metadata.mode() & 0o002 != 0 {
+        return Some(messages.message(&Message::WorldWritable));
+    }
+
+    let owner = metadata.uid();
+    // SAFETY: `geteuid` always succeeds and has no side effects.
+    let user = unsafe { libc::geteuid() };
+    (owner != user && owner != 0).then(|| messages.message(&Message::OwnedByOtherUser(owner)))
+}
+
+/// Returns why the file at `path` is insecure, if it is: either anyone can write it, or it
+/// is owned by a user other than the current one (or `root`).
+///
+/// Always `None` outside of Unix.
+#[cfg(not(unix))]
+pub(crate) fn insecure(_path: &Path, _messages: &dyn Messages) -> Option<String> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+
+    #[cfg(test)]
+    mod insecure {
+        use super::super::insecure;
+        use crate::English;
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt as _;
+        use tempfile::NamedTempFile;
+
+        #[test]
+        fn world_writable() {
+            let ntf = NamedTempFile::new().unwrap();
+            std::fs::set_permissions(ntf.path(), Permissions::from_mode(0o666)).unwrap();
+
+            let result = insecure(ntf.path(), &English);
+
+            assert_eq!(
+                result.as_deref(),
+                Some("the file can be written by any user")
+            );
+        }
+
+        #[test]
+        fn private() {
+            let ntf = NamedTempFile::new().unwrap();
+            std::fs::set_permissions(ntf.path(), Permissions::from_mode(0o600)).unwrap();
+
+            let result = insecure(ntf.path(), &English);
+
+            assert_eq!(result, None);
+        }
+    }
+}
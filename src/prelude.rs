@@ -0,0 +1,177 @@
+use crate::limits::LimitedCache;
+use codespan::FileId;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::cache::Envs;
+use nickel_lang_core::cache::ErrorTolerance;
+use nickel_lang_core::cache::InputFormat;
+use nickel_lang_core::error::Error;
+use nickel_lang_core::error::EvalError;
+use nickel_lang_core::eval::cache::Cache as _;
+use nickel_lang_core::eval::cache::CacheImpl;
+use nickel_lang_core::eval::Environment;
+use nickel_lang_core::eval::VirtualMachine;
+use nickel_lang_core::identifier::Ident;
+use nickel_lang_core::label::Label;
+use nickel_lang_core::label::MergeLabel;
+use nickel_lang_core::position::TermPos;
+use nickel_lang_core::term::make;
+use nickel_lang_core::term::BinaryOp;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use nickel_lang_core::types::TypeF;
+use nickel_lang_core::types::Types;
+use std::path::Path;
+use std::rc::Rc;
+
+thread_local! {
+    /// A cache holding only the standard library, already parsed, typechecked and
+    /// transformed, which otherwise takes most of the time of loading small configurations.
+    static STDLIB: Option<Cache> = {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        cache
+            .prepare_stdlib(&mut CacheImpl::new())
+            .ok()
+            .map(|_envs| cache)
+    };
+}
+
+/// Returns a cache holding only the standard library, prepared once per thread.
+///
+/// The terms of the standard library are then shared, through `Rc`s, with the cache of the
+/// thread: like any other Nickel term, they can't be sent to another thread.
+pub(crate) fn new_cache() -> Cache {
+    STDLIB
+        .with(Clone::clone)
+        .unwrap_or_else(|| Cache::new(ErrorTolerance::Strict))
+}
+
+/// A Nickel library provided by the application, bound to `name` in the environment of the
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Prelude {
+    name: String,
+    source: String,
+}
+
+impl Prelude {
+    pub(crate) fn new(name: &str, source: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            source: source.to_owned(),
+        }
+    }
+}
+
+/// Returns `text` as a Nickel string literal.
+pub(crate) fn quoted(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "\\%")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+/// Prepares the configuration file `main_id` for its evaluation, like
+/// [`VirtualMachine::prepare_eval`] does, but with the `preludes` in scope, after the
+/// standard library, merged over the Nickel `defaults`, if any, and checked against the
+/// Nickel `contracts`, in order.
+///
+/// Each prelude can use the ones before it, and the defaults and the contracts can use all of
+/// them.
+pub(crate) fn prepare_eval(
+    vm: &mut VirtualMachine<Cache, LimitedCache>,
+    main_id: FileId,
+    preludes: &[Prelude],
+    defaults: Option<&str>,
+    contracts: &[String],
+) -> Result<(RichTerm, Environment), Error> {
+    let Envs {
+        mut eval_env,
+        mut type_ctxt,
+    } = vm.prepare_stdlib()?;
+
+    for prelude in preludes {
+        let id = vm
+            .import_resolver_mut()
+            .add_string(format!("<{}>", prelude.name), prelude.source.clone());
+        let term = prepared(vm, id, &type_ctxt)?;
+        let ident = Ident::from(prelude.name.as_str());
+        nickel_lang_core::typecheck::env_add(
+            &mut type_ctxt.type_env,
+            ident,
+            &term,
+            &type_ctxt.term_env,
+            vm.import_resolver(),
+        );
+        let local_env = eval_env.clone();
+        nickel_lang_core::eval::env_add(&mut vm.cache, &mut eval_env, ident, term, local_env);
+    }
+
+    if let Some(format) = fallback_format(Path::new(vm.import_resolver().name(main_id))) {
+        vm.import_resolver_mut().parse_multi(main_id, format)?;
+    }
+    let mut main = prepared(vm, main_id, &type_ctxt)?;
+    if let Some(source) = defaults {
+        let id = vm
+            .import_resolver_mut()
+            .add_string("<defaults>", source.to_owned());
+        let term = prepared(vm, id, &type_ctxt)?;
+        let mut label = Label::default();
+        if let Some(span) = main.pos.into_opt() {
+            label.span = span;
+        }
+        main = make::op2(BinaryOp::Merge(MergeLabel::from(label)), term, main);
+    }
+    for (position, source) in contracts.iter().enumerate() {
+        let id = vm
+            .import_resolver_mut()
+            .add_string(format!("<contract {position}>"), source.clone());
+        let term = prepared(vm, id, &type_ctxt)?;
+        // Not a valid Nickel identifier, so configurations can't refer to the contract.
+        let ident = Ident::from(format!("%contract{position}"));
+        let types = Types::from(TypeF::Flat(RichTerm::from(Term::Var(ident))));
+        let mut label = Label {
+            types: Rc::new(types.clone()),
+            ..Label::default()
+        };
+        if let Some(span) = term.pos.into_opt() {
+            label.span = span;
+        }
+        let local_env = eval_env.clone();
+        nickel_lang_core::eval::env_add(&mut vm.cache, &mut eval_env, ident, term, local_env);
+        main = make::assume(types, label, main).map_err(|err| Error::EvalError(err.into()))?;
+    }
+    Ok((main, eval_env))
+}
+
+/// Returns the format of the configuration file at `path`, if it's one of the fallback
+/// formats enabled by the features of the crate, instead of Nickel.
+fn fallback_format(path: &Path) -> Option<InputFormat> {
+    match path.extension()?.to_str()? {
+        #[cfg(feature = "toml")]
+        "toml" => Some(InputFormat::Toml),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => Some(InputFormat::Yaml),
+        #[cfg(feature = "json")]
+        "json" => Some(InputFormat::Json),
+        _ => None,
+    }
+}
+
+/// Parses, typechecks and transforms the source `id`, returning the resulting term.
+fn prepared(
+    vm: &mut VirtualMachine<Cache, LimitedCache>,
+    id: FileId,
+    type_ctxt: &nickel_lang_core::typecheck::Context,
+) -> Result<RichTerm, Error> {
+    vm.import_resolver_mut().prepare(id, type_ctxt)?;
+    vm.import_resolver().get_owned(id).ok_or_else(|| {
+        Error::EvalError(EvalError::InternalError(
+            String::from("prepared source not found"),
+            TermPos::None,
+        ))
+    })
+}
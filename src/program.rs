@@ -0,0 +1,130 @@
+use crate::limits::LimitedCache;
+use crate::loader::deserialize;
+use crate::loader::export;
+use crate::loader::DiagnosticSink;
+use crate::path::resolving_against;
+use crate::LoadReport;
+use crate::Messages;
+use crate::Result;
+use crate::Value;
+use nickel_lang_core::cache::Cache;
+use nickel_lang_core::eval::VirtualMachine;
+use nickel_lang_core::identifier::Ident;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+/// An evaluated configuration, as returned by [`crate::Loader::program`].
+///
+/// It can be deserialized into several types, or queried at several paths, without reading
+/// and evaluating the configuration file again.
+///
+/// ```no_run
+/// # fn main() -> nickelodeon::Result<()> {
+/// #[derive(serde::Deserialize)]
+/// struct Server {
+///     port: u16,
+/// }
+///
+/// if let Some(mut program) = nickelodeon::Loader::new("my-app").program()? {
+///     let server: Option<Server> = program.get("server")?;
+///     let verbose: Option<bool> = program.get("logging.verbose")?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProgramHandle {
+    rt: RichTerm,
+    vm: VirtualMachine<Cache, LimitedCache>,
+    sink: DiagnosticSink,
+    messages: Arc<dyn Messages>,
+    report: LoadReport,
+}
+
+impl ProgramHandle {
+    pub(crate) fn new(
+        rt: RichTerm,
+        vm: VirtualMachine<Cache, LimitedCache>,
+        sink: DiagnosticSink,
+        messages: Arc<dyn Messages>,
+    ) -> Self {
+        Self {
+            rt,
+            vm,
+            sink,
+            messages,
+            report: LoadReport::default(),
+        }
+    }
+
+    pub(crate) fn set_report(&mut self, report: LoadReport) {
+        self.report = report;
+    }
+
+    /// Describes how the configuration was loaded.
+    #[must_use]
+    pub const fn report(&self) -> &LoadReport {
+        &self.report
+    }
+
+    /// Deserializes the whole configuration into a `T`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the configuration doesn't match the deserialization contract
+    /// for `T`.
+    pub fn deserialize<T: DeserializeOwned>(&mut self) -> Result<T> {
+        resolving_against(self.report.path.as_deref(), || {
+            deserialize(
+                &self.rt,
+                &mut self.vm,
+                &mut self.sink,
+                self.messages.as_ref(),
+            )
+        })
+    }
+
+    /// Returns the whole configuration as a [`Value`], to inspect it without knowing its
+    /// shape.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the configuration holds values that aren't data, like
+    /// functions.
+    pub fn value(&mut self) -> Result<Value> {
+        export(&self.rt, &mut self.vm, &mut self.sink).map(Value::from)
+    }
+
+    /// Deserializes the field of the configuration at `path` (a dotted path, like
+    /// `server.tls.cert_path`) into a `T`, returning `None` if there is no such field.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the field doesn't match the deserialization contract for `T`.
+    pub fn get<T: DeserializeOwned>(&mut self, path: &str) -> Result<Option<T>> {
+        let mut current = &self.rt;
+        for name in path.split('.') {
+            let Term::Record(record) = current.as_ref() else {
+                return Ok(None);
+            };
+            let Some(value) = record
+                .fields
+                .get(&Ident::from(name))
+                .and_then(|field| field.value.as_ref())
+            else {
+                return Ok(None);
+            };
+            current = value;
+        }
+        resolving_against(self.report.path.as_deref(), || {
+            deserialize(
+                current,
+                &mut self.vm,
+                &mut self.sink,
+                self.messages.as_ref(),
+            )
+        })
+        .map(Some)
+    }
+}
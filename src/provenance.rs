@@ -0,0 +1,215 @@
+use crate::diagnostic::location;
+use crate::Span;
+use codespan::Files;
+use nickel_lang_core::position::TermPos;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::Term;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+/// The kind of source a configuration value comes from.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    /// The `Default` implementation of the configuration type.
+    Default,
+
+    /// A system wide configuration file (e.g. `/etc/app/config.ncl`).
+    System,
+
+    /// A configuration file in the user's configuration directory.
+    User,
+
+    /// A configuration file in the current project (e.g. `./.app/config.ncl`).
+    Project,
+
+    /// An environment variable override.
+    Environment,
+
+    /// The configuration file passed explicitly, usually through a `--config` flag.
+    Flag,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::System => "system configuration",
+            Self::User => "user configuration",
+            Self::Project => "project configuration",
+            Self::Environment => "environment",
+            Self::Flag => "configuration flag",
+        })
+    }
+}
+
+/// Where a single field of the configuration was set.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The kind of source that set the field.
+    pub source: Source,
+
+    /// The value the source gave to the field.
+    pub value: Value,
+
+    /// The file the field was set in. Might be a file imported by the configuration file.
+    pub path: Option<PathBuf>,
+
+    /// The region of [`Origin::path`] where the field was set.
+    pub span: Option<Span>,
+
+    /// The values set by lower priority sources and replaced by this one, from the highest
+    /// to the lowest priority.
+    pub overridden: Vec<Self>,
+}
+
+impl fmt::Display for Origin {
+    /// Renders the value and where it was set, e.g. `80 (/etc/app/config.ncl:3:5, system
+    /// configuration)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (", self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "{}", path.display())?;
+            if let Some(span) = self.span {
+                write!(f, ":{}:{}", span.start.line, span.start.column)?;
+            }
+            write!(f, ", ")?;
+        }
+        write!(f, "{})", self.source)
+    }
+}
+
+/// Tells, for every field of the configuration, which source set it.
+///
+/// Fields are identified by their dotted path (e.g. `server.port`). Nested records are
+/// tracked too, so both `server` and `server.port` have an [`Origin`].
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    pub fields: BTreeMap<String, Origin>,
+}
+
+impl Provenance {
+    /// Returns where the field at the dotted `path` was set, if it was set by any source.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&Origin> {
+        self.fields.get(path)
+    }
+
+    /// Explains, in a human readable way, how the value of the field at the dotted `path`
+    /// was computed: the winning value, where it was set and the values it overrode.
+    #[must_use]
+    pub fn explain(&self, path: &str) -> String {
+        let Some(origin) = self.get(path) else {
+            return format!("`{path}` is not set by any source");
+        };
+
+        let mut explanation = format!("`{path}` is {origin}");
+        for overridden in &origin.overridden {
+            let _infallible: fmt::Result = write!(explanation, "\n  overriding {overridden}");
+        }
+        explanation
+    }
+
+    /// Records the origin of every field of the evaluated term `rt`, which was loaded from
+    /// `source`.
+    pub(crate) fn track(rt: &RichTerm, source: Source, files: &Files<String>) -> Self {
+        let mut provenance = Self::default();
+        provenance.track_record(rt, "", source, files);
+        provenance
+    }
+
+    fn track_record(&mut self, rt: &RichTerm, prefix: &str, source: Source, files: &Files<String>) {
+        let Term::Record(record) = rt.as_ref() else {
+            return;
+        };
+
+        for (ident, field) in &record.fields {
+            let path = if prefix.is_empty() {
+                ident.label().to_owned()
+            } else {
+                format!("{prefix}.{}", ident.label())
+            };
+            let value = field
+                .value
+                .as_ref()
+                .and_then(|value| serde_json::to_value(value).ok())
+                .unwrap_or(Value::Null);
+            self.fields
+                .insert(path.clone(), origin(ident.pos, value, source, files));
+            if let Some(nested) = &field.value {
+                self.track_record(nested, &path, source, files);
+            }
+        }
+    }
+}
+
+fn origin(pos: TermPos, value: Value, source: Source, files: &Files<String>) -> Origin {
+    let span = pos.into_opt();
+    Origin {
+        source,
+        value,
+        path: span.map(|raw| PathBuf::from(files.name(raw.src_id))),
+        span: span.and_then(|raw| {
+            Some(Span {
+                start: location(files, raw.src_id, raw.start.to_usize())?,
+                end: location(files, raw.src_id, raw.end.to_usize())?,
+            })
+        }),
+        overridden: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[cfg(test)]
+    mod explain {
+        use super::super::Origin;
+        use super::super::Provenance;
+        use super::super::Source;
+        use crate::Location;
+        use crate::Span;
+        use serde_json::json;
+        use std::path::PathBuf;
+
+        fn origin(source: Source, path: &str, line: usize, value: u16) -> Origin {
+            let location = Location { line, column: 3 };
+            Origin {
+                source,
+                value: json!(value),
+                path: Some(PathBuf::from(path)),
+                span: Some(Span {
+                    start: location,
+                    end: location,
+                }),
+                overridden: Vec::new(),
+            }
+        }
+
+        #[test]
+        fn describes_the_value_and_what_it_overrode() {
+            let mut port = origin(Source::Project, "/work/.app/config.ncl", 4, 8080);
+            port.overridden
+                .push(origin(Source::System, "/etc/app/config.ncl", 2, 80));
+            let mut provenance = Provenance::default();
+            provenance.fields.insert("server.port".to_owned(), port);
+
+            let result = provenance.explain("server.port");
+
+            let expected = "`server.port` is 8080 (/work/.app/config.ncl:4:3, project configuration)\n  overriding 80 (/etc/app/config.ncl:2:3, system configuration)";
+            assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn unset_fields() {
+            let result = Provenance::default().explain("server.port");
+
+            assert_eq!(result, "`server.port` is not set by any source");
+        }
+    }
+}
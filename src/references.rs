@@ -0,0 +1,344 @@
+use crate::Error;
+use nickel_lang_core::term::RichTerm;
+use nickel_lang_core::term::SharedTerm;
+use nickel_lang_core::term::Term;
+use serde_json::Value;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Resolves the references of a scheme, given what follows `scheme://`, into the values
+/// they stand for, or says why it can't.
+pub(crate) type Resolver = Arc<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+/// Replaces the strings of the evaluated `rt`, at `path`, that are references (like
+/// `vault://secret/data/app#password`) of one of the schemes of `resolvers` by the values
+/// they stand for.
+///
+/// # Errors
+///
+/// Returns [`Error::UnresolvedReference`] for the first reference that can't be resolved.
+#[allow(clippy::wildcard_enum_match_arm)]
+pub(crate) fn resolve_references(
+    rt: &mut RichTerm,
+    resolvers: &[(String, Resolver)],
+    path: &str,
+) -> Result<(), Error> {
+    if resolvers.is_empty() {
+        return Ok(());
+    }
+    match SharedTerm::make_mut(&mut rt.term) {
+        Term::Record(record) => {
+            for (ident, field) in &mut record.fields {
+                if let Some(value) = field.value.as_mut() {
+                    let nested = if path.is_empty() {
+                        ident.label().to_owned()
+                    } else {
+                        format!("{path}.{}", ident.label())
+                    };
+                    resolve_references(value, resolvers, &nested)?;
+                }
+            }
+        }
+        Term::Array(elements, _attrs) => {
+            for (index, element) in elements.make_mut().iter_mut().enumerate() {
+                resolve_references(element, resolvers, &format!("{path}[{index}]"))?;
+            }
+        }
+        Term::Str(text) => {
+            if let Some(value) = resolved(text.as_str(), resolvers, path) {
+                *text = value?.into();
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Same as [`resolve_references`], for the configuration exported to `value` (as kept by the
+/// memo and the disk cache, which only keep the references, never what they stand for).
+///
+/// # Errors
+///
+/// Returns [`Error::UnresolvedReference`] for the first reference that can't be resolved.
+pub(crate) fn resolve_exported_references(
+    value: &mut Value,
+    resolvers: &[(String, Resolver)],
+    path: &str,
+) -> Result<(), Error> {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                let nested = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path}.{name}")
+                };
+                resolve_exported_references(field, resolvers, &nested)?;
+            }
+        }
+        Value::Array(elements) => {
+            for (index, element) in elements.iter_mut().enumerate() {
+                resolve_exported_references(element, resolvers, &format!("{path}[{index}]"))?;
+            }
+        }
+        Value::String(text) => {
+            if let Some(resolved) = resolved(text, resolvers, path) {
+                *text = resolved?;
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+/// Resolves `text`, at `path`, if it is a reference of one of the schemes of `resolvers`.
+fn resolved(
+    text: &str,
+    resolvers: &[(String, Resolver)],
+    path: &str,
+) -> Option<Result<String, Error>> {
+    resolvers.iter().find_map(|(scheme, resolver)| {
+        let reference = text.strip_prefix(scheme.as_str())?.strip_prefix("://")?;
+        Some(
+            resolver(reference).map_err(|reason| {
+                Error::UnresolvedReference(path.to_owned(), text.to_owned(), reason)
+            }),
+        )
+    })
+}
+
+/// Returns a [`Resolver`] reading the secrets of the references, the name of a file, from
+/// the files of the directory `dir` (like `/run/secrets`), without their final newline.
+///
+/// Names can't leave `dir`, so they can't be used to read other files.
+pub(crate) fn files_in(dir: PathBuf) -> Resolver {
+    Arc::new(move |name| {
+        let mut components = Path::new(name).components();
+        let plain = matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(_)), None)
+        );
+        if !plain {
+            return Err(format!("`{name}` isn't the name of a file"));
+        }
+        let path = dir.join(name);
+        let secret = std::fs::read_to_string(&path)
+            .map_err(|err| format!("can't read {}: {err}", path.display()))?;
+        let trimmed = secret.strip_suffix('\n').map_or(secret.as_str(), |line| {
+            line.strip_suffix('\r').unwrap_or(line)
+        });
+        Ok(trimmed.to_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod resolve_references {
+        use crate::Error;
+        use crate::Loader;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, serde::Deserialize, PartialEq, Eq)]
+        struct Config {
+            password: String,
+            tokens: Vec<String>,
+            url: String,
+        }
+
+        fn loader(source: &str) -> (NamedTempFile, Loader) {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{source}").unwrap();
+
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .resolve_references("test", |reference| match reference {
+                    "missing" => Err("not found".to_owned()),
+                    other => Ok(other.to_uppercase()),
+                });
+            (ntf, loader)
+        }
+
+        #[test]
+        fn resolved() {
+            let (_ntf, loader) = loader(
+                r#"{ password = "test://db", tokens = ["a", "test://b"], url = "https://c" }"#,
+            );
+
+            let config = loader.load::<Config>().unwrap();
+
+            assert_eq!(
+                config,
+                Config {
+                    password: "DB".to_owned(),
+                    tokens: vec!["a".to_owned(), "B".to_owned()],
+                    url: "https://c".to_owned(),
+                }
+            );
+        }
+
+        #[test]
+        fn unresolved() {
+            let (_ntf, loader) =
+                loader(r#"{ password = "", tokens = ["test://missing"], url = "" }"#);
+
+            let result = loader.load::<Config>();
+
+            assert_eq!(
+                result,
+                Err(Error::UnresolvedReference(
+                    "tokens[0]".to_owned(),
+                    "test://missing".to_owned(),
+                    "not found".to_owned()
+                ))
+            );
+            assert_eq!(
+                result
+                    .unwrap_err()
+                    .diagnostics()
+                    .first()
+                    .map(|diagnostic| diagnostic.message.as_str()),
+                Some("can't resolve `test://missing`: not found")
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod reused {
+        use crate::Loader;
+        use std::sync::Arc;
+        use std::sync::Mutex;
+        use std::sync::PoisonError;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Config {
+            password: String,
+        }
+
+        #[test]
+        fn resolved_again_and_never_kept() {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            std::fs::write(&config, r#"{ password = "test://db" }"#).unwrap();
+            let secret = Arc::new(Mutex::new("hunter2".to_owned()));
+            let current = Arc::clone(&secret);
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config))
+                .diagnostics(std::io::sink())
+                .memoize(true)
+                .cache_dir(cache.path().to_path_buf())
+                .resolve_references("test", move |_reference| {
+                    Ok(current
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .clone())
+                });
+
+            let (first, _first_report) = loader.load_with_report::<Config>().unwrap();
+            *secret.lock().unwrap() = "correct horse".to_owned();
+            let (second, second_report) = loader.load_with_report::<Config>().unwrap();
+
+            assert_eq!(first.password, "hunter2");
+            assert_eq!(second.password, "correct horse");
+            assert_eq!(second_report.cache_hits, 1);
+            for entry in std::fs::read_dir(cache.path()).unwrap() {
+                let cached = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+                assert!(cached.contains("test://db"), "{cached}");
+                assert!(!cached.contains("hunter2"), "{cached}");
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod docker_secrets {
+        use crate::Loader;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        struct Config {
+            password: String,
+        }
+
+        #[test]
+        fn resolved() {
+            let secrets = tempfile::tempdir().unwrap();
+            std::fs::write(secrets.path().join("db_password"), "hunter2\n").unwrap();
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ password = "docker-secret://db_password" }}"#).unwrap();
+
+            let config: Config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .docker_secrets_in(secrets.path().to_path_buf())
+                .load()
+                .unwrap();
+
+            assert_eq!(config.password, "hunter2");
+        }
+
+        #[test]
+        fn rotated() {
+            let secrets = tempfile::tempdir().unwrap();
+            let cache = tempfile::tempdir().unwrap();
+            std::fs::write(secrets.path().join("db_password"), "hunter2\n").unwrap();
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, r#"{{ password = "docker-secret://db_password" }}"#).unwrap();
+            let loader = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .memoize(true)
+                .cache_dir(cache.path().to_path_buf())
+                .docker_secrets_in(secrets.path().to_path_buf());
+
+            let first: Config = loader.load().unwrap();
+            std::fs::write(secrets.path().join("db_password"), "correct horse\n").unwrap();
+            let (second, report) = loader.load_with_report::<Config>().unwrap();
+
+            assert_eq!(first.password, "hunter2");
+            assert_eq!(second.password, "correct horse");
+            assert_eq!(report.cache_hits, 1);
+            let cached: Vec<String> = std::fs::read_dir(cache.path())
+                .unwrap()
+                .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+                .collect();
+            assert_eq!(cached.len(), 1);
+            assert!(cached.iter().all(|entry| !entry.contains("hunter2")));
+        }
+    }
+
+    #[cfg(test)]
+    mod files_in {
+        use super::super::files_in;
+
+        #[test]
+        fn reads_the_files() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("db_password"), "hunter2\n").unwrap();
+            std::fs::write(dir.path().join("token"), "a\nb").unwrap();
+            let resolve = files_in(dir.path().to_path_buf());
+
+            assert_eq!(resolve("db_password"), Ok("hunter2".to_owned()));
+            assert_eq!(resolve("token"), Ok("a\nb".to_owned()));
+            assert!(resolve("missing").unwrap_err().starts_with("can't read "));
+        }
+
+        #[test]
+        fn stays_in_the_directory() {
+            let dir = tempfile::tempdir().unwrap();
+            let resolve = files_in(dir.path().join("secrets"));
+
+            for name in ["../db_password", "/etc/passwd", "nested/token", "", "."] {
+                assert_eq!(
+                    resolve(name),
+                    Err(format!("`{name}` isn't the name of a file")),
+                    "{name}"
+                );
+            }
+        }
+    }
+}
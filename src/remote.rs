@@ -0,0 +1,292 @@
+use crate::disk_cache::hash;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+use std::time::Duration;
+
+/// The default [`crate::Loader::remote_timeout`].
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The formats the fetched configuration can be in, told by the extension of the URL. It's
+/// Nickel otherwise.
+const EXTENSIONS: &[&str] = &["ncl", "nickel", "json", "yaml", "yml", "toml"];
+
+/// A configuration fetched from an HTTPS URL (see [`crate::Loader::remote_config`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Remote {
+    /// Where the configuration is fetched from.
+    url: String,
+
+    /// How long fetching it can take, at most.
+    timeout: Duration,
+}
+
+impl Remote {
+    /// Fetches the configuration at `url`, giving up after `timeout`.
+    pub(crate) fn new(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.to_owned(),
+            timeout,
+        }
+    }
+
+    /// Returns the URL the configuration is fetched from.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Fetches the configuration into the cache directory `dir` with the `curl` command
+    /// line, returning the path of the copy. Only what changed since the copy was fetched
+    /// is downloaded (using its `ETag` and modification time).
+    pub(crate) fn fetch(&self, dir: &Path) -> Result<PathBuf, String> {
+        self.fetch_with(Command::new("curl"), dir)
+    }
+
+    /// Returns where the copy of the configuration is kept, in the cache directory `dir`.
+    pub(crate) fn cached(&self, dir: &Path) -> PathBuf {
+        let extension = self
+            .url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit_once('.'))
+            .map(|(_rest, extension)| extension)
+            .filter(|extension| EXTENSIONS.contains(extension))
+            .unwrap_or("ncl");
+        dir.join(format!("{}.{extension}", hash(self.url.as_bytes())))
+    }
+
+    /// Same as [`Remote::fetch`], fetching with `curl`, the command running the `curl`
+    /// command line.
+    fn fetch_with(&self, curl: Command, dir: &Path) -> Result<PathBuf, String> {
+        let output = self
+            .command(curl, dir)?
+            .output()
+            .map_err(|err| format!("can't run `curl`: {err}"))?;
+        self.fetched(&output, dir)
+    }
+
+    /// Async version of [`Remote::fetch`], awaiting `curl` on the current tokio runtime. It
+    /// is killed if it's still running after the timeout.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn fetch_async(&self, dir: &Path) -> Result<PathBuf, String> {
+        self.fetch_async_with(Command::new("curl"), dir).await
+    }
+
+    /// Same as [`Remote::fetch_async`], fetching with `curl`, the command running the `curl`
+    /// command line.
+    #[cfg(feature = "tokio")]
+    async fn fetch_async_with(&self, curl: Command, dir: &Path) -> Result<PathBuf, String> {
+        let mut command = tokio::process::Command::from(self.command(curl, dir)?);
+        let running = command.kill_on_drop(true).output();
+        let output = tokio::time::timeout(self.timeout, running)
+            .await
+            .map_err(|_elapsed| format!("no answer within {:?}", self.timeout))?
+            .map_err(|err| format!("can't run `curl`: {err}"))?;
+        self.fetched(&output, dir)
+    }
+
+    /// Adds to `curl` the arguments fetching the configuration into the cache directory
+    /// `dir`, creating it if needed.
+    fn command(&self, mut curl: Command, dir: &Path) -> Result<Command, String> {
+        if !self.url.starts_with("https://") {
+            return Err("only HTTPS URLs are supported".to_owned());
+        }
+        fs::create_dir_all(dir).map_err(|err| format!("can't create {}: {err}", dir.display()))?;
+        let cached = self.cached(dir);
+        let etag = cached.with_extension("etag");
+
+        curl.args(["--silent", "--show-error", "--fail", "--location"])
+            .args(["--proto", "=https", "--proto-redir", "=https"])
+            .arg("--max-time")
+            .arg(format!(
+                "{}.{:03}",
+                self.timeout.as_secs(),
+                self.timeout.subsec_millis()
+            ))
+            .arg("--remote-time")
+            .arg("--etag-save")
+            .arg(&etag);
+        if cached.is_file() {
+            curl.arg("--time-cond").arg(&cached);
+            if etag.is_file() {
+                curl.arg("--etag-compare").arg(&etag);
+            }
+        }
+        curl.arg("--output")
+            .arg(cached.with_extension("part"))
+            .args(["--write-out", "%{http_code}"])
+            .arg(&self.url);
+        Ok(curl)
+    }
+
+    /// Keeps what the [`Remote::command`] in the cache directory `dir` downloaded, given its
+    /// `output`, returning the path of the copy.
+    fn fetched(&self, output: &Output, dir: &Path) -> Result<PathBuf, String> {
+        let cached = self.cached(dir);
+        let partial = cached.with_extension("part");
+        if !output.status.success() {
+            let _ignored = fs::remove_file(&partial);
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+        }
+
+        if String::from_utf8_lossy(&output.stdout).trim() == "304" && cached.is_file() {
+            let _ignored = fs::remove_file(&partial);
+        } else {
+            fs::rename(&partial, &cached)
+                .map_err(|err| format!("can't write {}: {err}", cached.display()))?;
+        }
+        Ok(cached)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod cached {
+        use super::super::Remote;
+        use super::super::DEFAULT_TIMEOUT;
+        use std::path::Path;
+
+        fn cached(url: &str) -> String {
+            let remote = Remote::new(url, DEFAULT_TIMEOUT);
+            let path = remote.cached(Path::new("/cache"));
+            path.extension().unwrap().to_string_lossy().into_owned()
+        }
+
+        #[test]
+        fn keeps_the_format() {
+            assert_eq!(cached("https://example.com/app/config.json"), "json");
+            assert_eq!(cached("https://example.com/app/config.yaml?v=2"), "yaml");
+            assert_eq!(cached("https://example.com/app/config"), "ncl");
+            assert_eq!(cached("https://example.com/app.config/"), "ncl");
+        }
+
+        #[test]
+        fn stable_name() {
+            let remote = Remote::new("https://example.com/app/config.json", DEFAULT_TIMEOUT);
+
+            assert_eq!(
+                remote.cached(Path::new("/cache")),
+                Path::new(
+                    "/cache/4351f92ae7d994433920f65b8a1ed95f6a8366efc02b93e48884058860a8a714.json"
+                )
+            );
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod fetch_with {
+        use super::super::Remote;
+        use crate::fake_command::fake_command;
+        use std::fs;
+        use std::process::Command;
+        use std::time::Duration;
+
+        fn remote(url: &str) -> Remote {
+            Remote::new(url, Duration::from_millis(1500))
+        }
+
+        /// Returns a fake `curl` command line running `script`, with `$out` set to the path
+        /// given to `--output` and `$args` to all the arguments.
+        fn curl(script: &str) -> Command {
+            let parse =
+                r#"args="$*"; while [ $# -gt 0 ]; do [ "$1" = --output ] && out="$2"; shift; done"#;
+            fake_command("curl", &format!("{parse}; {script}"))
+        }
+
+        #[test]
+        fn fetched() {
+            let dir = tempfile::tempdir().unwrap();
+            let remote = remote("https://example.com/config.ncl");
+            let server = r#"case "$args" in
+                *"--max-time 1.500"*) echo '{ port = 80 }' > "$out"; printf 200;;
+                *) exit 2;;
+            esac"#;
+
+            let cached = remote.fetch_with(curl(server), dir.path()).unwrap();
+
+            assert_eq!(cached, remote.cached(dir.path()));
+            assert_eq!(fs::read_to_string(&cached).unwrap(), "{ port = 80 }\n");
+        }
+
+        #[test]
+        fn not_modified() {
+            let dir = tempfile::tempdir().unwrap();
+            let remote = remote("https://example.com/config.ncl");
+            fs::write(remote.cached(dir.path()), "{ port = 80 }").unwrap();
+            fs::write(remote.cached(dir.path()).with_extension("etag"), "\"v1\"").unwrap();
+
+            let server = r#"case "$args" in
+                *--time-cond*--etag-compare*) printf 304;;
+                *) exit 2;;
+            esac"#;
+
+            let cached = remote.fetch_with(curl(server), dir.path()).unwrap();
+
+            assert_eq!(fs::read_to_string(cached).unwrap(), "{ port = 80 }");
+        }
+
+        #[test]
+        fn failed() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let result = remote("https://example.com/config.ncl").fetch_with(
+                curl("echo 'curl: (28) Operation timed out' >&2; exit 28"),
+                dir.path(),
+            );
+            let insecure =
+                remote("http://example.com/config.ncl").fetch_with(curl("exit 0"), dir.path());
+
+            assert_eq!(result, Err("curl: (28) Operation timed out".to_owned()));
+            assert_eq!(insecure, Err("only HTTPS URLs are supported".to_owned()));
+        }
+    }
+
+    #[cfg(all(test, unix, feature = "tokio"))]
+    mod fetch_async_with {
+        use super::super::Remote;
+        use crate::fake_command::fake_command;
+        use std::fs;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        fn run<F: std::future::Future>(future: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(future)
+        }
+
+        #[test]
+        fn fetched() {
+            let dir = tempfile::tempdir().unwrap();
+            let remote = Remote::new("https://example.com/config.ncl", Duration::from_secs(10));
+            let curl = fake_command(
+                "curl",
+                r#"while [ $# -gt 0 ]; do [ "$1" = --output ] && out="$2"; shift; done
+                echo '{ port = 80 }' > "$out"; printf 200"#,
+            );
+
+            let cached = run(remote.fetch_async_with(curl, dir.path())).unwrap();
+
+            assert_eq!(fs::read_to_string(cached).unwrap(), "{ port = 80 }\n");
+        }
+
+        #[test]
+        fn timed_out() {
+            let dir = tempfile::tempdir().unwrap();
+            let remote = Remote::new("https://example.com/config.ncl", Duration::from_millis(50));
+            // Ignores `--max-time`, like a `curl` stuck resolving the host.
+            let curl = fake_command("curl", "sleep 10");
+
+            let started = Instant::now();
+            let result = run(remote.fetch_async_with(curl, dir.path()));
+
+            assert_eq!(result, Err("no answer within 50ms".to_owned()));
+            assert!(started.elapsed() < Duration::from_secs(5));
+        }
+    }
+}
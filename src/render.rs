@@ -0,0 +1,116 @@
+use crate::Diagnostic;
+use crate::Severity;
+use codespan_reporting::term::termcolor::Buffer;
+use codespan_reporting::term::termcolor::Color;
+use codespan_reporting::term::termcolor::ColorSpec;
+use codespan_reporting::term::termcolor::WriteColor as _;
+use std::io;
+use std::io::IsTerminal as _;
+use std::io::Write as _;
+
+/// Whether output meant for `stderr` should be colored: only when it is a terminal and
+/// `NO_COLOR` (see <https://no-color.org>) is unset or empty.
+pub(crate) fn use_color() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && io::stderr().is_terminal()
+}
+
+/// Renders `diagnostics` in the style of the Nickel reports, with ANSI colors if `color`
+/// is set.
+pub(crate) fn render(diagnostics: &[Diagnostic], color: bool) -> String {
+    let mut buffer = if color {
+        Buffer::ansi()
+    } else {
+        Buffer::no_color()
+    };
+    for diagnostic in diagnostics {
+        // Writing to an in-memory buffer can't fail.
+        let _infallible: io::Result<()> = write_diagnostic(&mut buffer, diagnostic);
+    }
+    String::from_utf8_lossy(buffer.as_slice()).into_owned()
+}
+
+fn write_diagnostic(buffer: &mut Buffer, diagnostic: &Diagnostic) -> io::Result<()> {
+    buffer.set_color(
+        ColorSpec::new()
+            .set_bold(true)
+            .set_fg(Some(color_of(diagnostic.severity))),
+    )?;
+    write!(buffer, "{}", diagnostic.severity)?;
+    buffer.set_color(ColorSpec::new().set_bold(true))?;
+    writeln!(buffer, ": {}", diagnostic.message)?;
+    buffer.reset()?;
+
+    if let Some(path) = &diagnostic.path {
+        buffer.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
+        write!(buffer, "  --> ")?;
+        buffer.reset()?;
+        write!(buffer, "{}", path.display())?;
+        if let Some(span) = diagnostic.span {
+            write!(buffer, ":{}:{}", span.start.line, span.start.column)?;
+        }
+        writeln!(buffer)?;
+    }
+
+    for note in &diagnostic.notes {
+        buffer.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
+        write!(buffer, "  = ")?;
+        buffer.reset()?;
+        writeln!(buffer, "{note}")?;
+    }
+
+    Ok(())
+}
+
+const fn color_of(severity: Severity) -> Color {
+    match severity {
+        Severity::Help => Color::Cyan,
+        Severity::Note => Color::Green,
+        Severity::Warning => Color::Yellow,
+        Severity::Error | Severity::Bug => Color::Red,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[cfg(test)]
+    mod render {
+        use super::super::render;
+        use crate::Diagnostic;
+        use crate::Location;
+        use crate::Severity;
+        use crate::Span;
+        use std::path::PathBuf;
+
+        fn diagnostic() -> Diagnostic {
+            Diagnostic {
+                path: Some(PathBuf::from("/etc/app/config.ncl")),
+                span: Some(Span {
+                    start: Location { line: 2, column: 3 },
+                    end: Location { line: 2, column: 7 },
+                }),
+                field: Some("port".to_owned()),
+                severity: Severity::Warning,
+                message: "`port` is deprecated".to_owned(),
+                notes: vec!["use `server.port` instead".to_owned()],
+            }
+        }
+
+        #[test]
+        fn plain() {
+            let result = render(&[diagnostic()], false);
+
+            let expected = "warning: `port` is deprecated\n  --> /etc/app/config.ncl:2:3\n  = use `server.port` instead\n";
+            assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn colored() {
+            let result = render(&[diagnostic()], true);
+
+            assert!(result.contains("\u{1b}[1m\u{1b}[33mwarning"));
+            assert!(result.contains("`port` is deprecated"));
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::Diagnostic;
+use crate::Provenance;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Describes how a configuration was loaded, as returned by
+/// [`crate::Loader::load_with_report`].
+///
+/// Meant to be logged by applications at startup, so it's easy to tell which file was used
+/// and how long it took to load it.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// The configuration file that was used, or `None` if none was found and the default
+    /// configuration was used instead.
+    pub path: Option<PathBuf>,
+
+    /// Every source merged into the final configuration, from the lowest to the highest
+    /// priority.
+    pub layers: Vec<PathBuf>,
+
+    /// Every file imported, directly or transitively, by the configuration file. Changing
+    /// any of them changes the configuration.
+    pub imports: Vec<PathBuf>,
+
+    /// Every location where the configuration file was looked for, when none was found.
+    pub searched: Vec<PathBuf>,
+
+    /// Which source set each field of the configuration.
+    pub provenance: Provenance,
+
+    /// Problems that didn't prevent the configuration from loading.
+    pub warnings: Vec<Diagnostic>,
+
+    /// Time spent evaluating the Nickel program.
+    pub evaluation_duration: Duration,
+
+    /// Time spent in the whole load, including locating, evaluating and deserializing the
+    /// configuration.
+    pub total_duration: Duration,
+
+    /// How many previously evaluated configurations were reused instead of evaluated again.
+    pub cache_hits: usize,
+}
+
+impl LoadReport {
+    /// Returns every file the configuration depends on: the configuration files used and
+    /// everything they import. These are the files to watch to reload the configuration
+    /// when it changes.
+    #[must_use]
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self.layers.clone();
+        for import in &self.imports {
+            if !files.contains(import) {
+                files.push(import.clone());
+            }
+        }
+        files
+    }
+}
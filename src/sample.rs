@@ -0,0 +1,280 @@
+use crate::json_schema_for;
+use crate::serializer::to_nickel;
+use crate::syntax::enum_tag;
+use crate::syntax::string;
+use crate::syntax::Annotations;
+use crate::syntax::Comments;
+use crate::syntax::Nickel;
+use crate::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Returns a sample Nickel configuration for `T`, with the default value of each field,
+/// its type and its documentation.
+///
+/// Applications can so scaffold a real starting file for their users (e.g. in a
+/// `config init` command):
+///
+/// ```
+/// #[derive(Default, serde::Serialize, schemars::JsonSchema)]
+/// struct Config {
+///     /// The port to listen on.
+///     port: u16,
+/// }
+///
+/// let sample = nickelodeon::sample_config::<Config>().unwrap();
+///
+/// assert_eq!(
+///     sample,
+///     "{\n  port | std.number.Nat | doc \"The port to listen on.\" = 0,\n}\n"
+/// );
+/// ```
+///
+/// The types and doc comments come from the JSON Schema of `T` (see
+/// [`crate::json_schema_for`]): fields get the contract of their type when it is a simple
+/// one (a number, a string, a boolean, an enum or an array of those) and aren't `null` by
+/// default, and a `doc` with their description when they have one. The description of `T`
+/// itself is written as a comment at the top of the file.
+///
+/// # Errors
+///
+/// Will return `Err` if the default value of `T` can't be written in Nickel (see
+/// [`crate::to_nickel_string`]).
+pub fn sample_config<T>() -> Result<String>
+where
+    T: JsonSchema + Serialize + Default,
+{
+    let value = to_nickel(&T::default())?;
+    let schema = json_schema_for::<T>();
+    let mut annotations = Annotations::new();
+    annotate(&schema, &schema, &value, &mut Vec::new(), &mut annotations);
+
+    let mut sample = String::new();
+    if let Some(description) = schema.get("description").and_then(Value::as_str) {
+        for line in description.lines() {
+            sample.push_str(format!("# {line}").trim_end());
+            sample.push('\n');
+        }
+        sample.push('\n');
+    }
+    sample.push_str(&value.to_annotated_source(&Comments::new(), &annotations));
+    Ok(sample)
+}
+
+/// Adds the annotations of the fields of `value`, the value at `path` described by
+/// `schema`, to `annotations`. References are resolved against `root`.
+fn annotate(
+    root: &Value,
+    schema: &Value,
+    value: &Nickel,
+    path: &mut Vec<String>,
+    annotations: &mut Annotations,
+) {
+    let Nickel::Record(fields) = value else {
+        return;
+    };
+    let (described, _nullable) = resolved(root, schema);
+    for (name, field) in fields {
+        let Some(property) = described
+            .get("properties")
+            .and_then(|properties| properties.get(name))
+        else {
+            continue;
+        };
+        path.push(name.clone());
+        let (property_schema, nullable) = resolved(root, property);
+        let mut parts = Vec::new();
+        if !nullable && !matches!(field, Nickel::Record(_)) {
+            parts.extend(contract(root, property_schema));
+        }
+        let description = property
+            .get("description")
+            .or_else(|| property_schema.get("description"))
+            .and_then(Value::as_str);
+        parts.extend(description.map(|text| format!("doc {}", string(text))));
+        if !parts.is_empty() {
+            annotations.insert(path.clone(), format!(" | {}", parts.join(" | ")));
+        }
+        annotate(root, property_schema, field, path, annotations);
+        path.pop();
+    }
+}
+
+/// Returns the schema `schema` stands for, following its `$ref` and the alternatives
+/// making it nullable, and whether `null` is one of its values.
+fn resolved<'schema>(root: &'schema Value, schema: &'schema Value) -> (&'schema Value, bool) {
+    if let Some(target) = schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| root.pointer(reference.strip_prefix('#')?))
+    {
+        return resolved(root, target);
+    }
+    let alternatives = schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(Value::as_array);
+    if let Some([first, second]) = alternatives.map(Vec::as_slice) {
+        let is_null = |alternative: &Value| alternative.get("type") == Some(&"null".into());
+        if is_null(first) {
+            return (resolved(root, second).0, true);
+        }
+        if is_null(second) {
+            return (resolved(root, first).0, true);
+        }
+    }
+    let nullable = schema
+        .get("type")
+        .and_then(Value::as_array)
+        .is_some_and(|types| types.contains(&"null".into()));
+    (schema, nullable)
+}
+
+/// Returns the contract of the values `schema` describes, if it's a simple one.
+fn contract(root: &Value, schema: &Value) -> Option<String> {
+    let (described, nullable) = resolved(root, schema);
+    if nullable {
+        return None;
+    }
+    if let Some(tags) = enum_tags(described) {
+        return Some(format!("[| {} |]", tags.join(", ")));
+    }
+    match described.get("type").and_then(Value::as_str)? {
+        "string" => Some("String".to_owned()),
+        "number" => Some("Number".to_owned()),
+        "integer" => {
+            let natural = described
+                .get("minimum")
+                .and_then(Value::as_f64)
+                .is_some_and(|minimum| minimum >= 0.0);
+            Some(
+                if natural {
+                    "std.number.Nat"
+                } else {
+                    "std.number.Integer"
+                }
+                .to_owned(),
+            )
+        }
+        "boolean" => Some("Bool".to_owned()),
+        "array" => {
+            let element = described
+                .get("items")
+                .and_then(|items| contract(root, items))
+                .unwrap_or_else(|| "Dyn".to_owned());
+            Some(format!("Array {element}"))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the enum tags of the unit variants of the enum `schema` describes, written
+/// either as an `enum` or as `oneOf` constants (when the variants are documented).
+fn enum_tags(schema: &Value) -> Option<Vec<String>> {
+    if let Some(variants) = schema.get("enum").and_then(Value::as_array) {
+        return variants
+            .iter()
+            .map(|variant| variant.as_str().map(enum_tag))
+            .collect();
+    }
+    schema
+        .get("oneOf")
+        .and_then(Value::as_array)?
+        .iter()
+        .map(|variant| variant.get("const")?.as_str().map(enum_tag))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod sample_config {
+        use super::super::sample_config;
+        use crate::Loader;
+        use std::collections::HashMap;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        /// The configuration of the test application.
+        #[derive(Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema, PartialEq)]
+        struct Config {
+            /// The name of the service.
+            name: String,
+            /// How much to log.
+            level: Level,
+            /// Where to listen.
+            server: Server,
+            proxy: Option<String>,
+            ratio: f64,
+            tags: Vec<String>,
+            labels: HashMap<String, String>,
+        }
+
+        #[derive(
+            Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema, PartialEq,
+        )]
+        struct Server {
+            /// The port,
+            /// between 1 and 65535.
+            port: u16,
+            offset: i32,
+        }
+
+        #[derive(Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema, PartialEq)]
+        enum Level {
+            Debug,
+            Info,
+        }
+
+        impl Default for Config {
+            fn default() -> Self {
+                Self {
+                    name: "app".to_owned(),
+                    level: Level::Info,
+                    server: Server::default(),
+                    proxy: None,
+                    ratio: 0.5,
+                    tags: vec!["web".to_owned()],
+                    labels: HashMap::new(),
+                }
+            }
+        }
+
+        #[test]
+        fn documents_the_fields() {
+            let sample = sample_config::<Config>().unwrap();
+
+            let expected = r#"# The configuration of the test application.
+
+{
+  name | String | doc "The name of the service." = "app",
+  level | [| 'Debug, 'Info |] | doc "How much to log." = 'Info,
+  server | doc "Where to listen." = {
+    port | std.number.Nat | doc "The port,\nbetween 1 and 65535." = 0,
+    offset | std.number.Integer = 0,
+  },
+  proxy = null,
+  ratio | Number = 0.5,
+  tags | Array String = ["web"],
+  labels = {},
+}
+"#;
+            assert_eq!(sample, expected);
+        }
+
+        #[test]
+        fn loads_back() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{}", sample_config::<Config>().unwrap()).unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load::<Config>()
+                .unwrap();
+
+            assert_eq!(config, Config::default());
+        }
+    }
+}
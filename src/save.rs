@@ -0,0 +1,404 @@
+use crate::edit::nested;
+use crate::edit::with_field;
+use crate::serializer::to_nickel;
+use crate::serializer::to_nickel_string;
+use crate::syntax::Comments;
+use crate::Error;
+use crate::Message;
+use crate::Result;
+use config_finder::ConfigDirs;
+use serde::Serialize;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Writes `value` as the configuration of the application with the codename `app`.
+///
+/// The file is `$XDG_CONFIG_HOME/<app>/config.ncl` (or the platform equivalent), whose
+/// directories are created as needed. Returns the path of the written file.
+///
+/// Meant for applications with a preferences screen, which need to persist what the user
+/// changed:
+///
+/// ```no_run
+/// #[derive(serde::Serialize)]
+/// struct Preferences {
+///     theme: String,
+///     font_size: u8,
+/// }
+///
+/// # fn main() -> nickelodeon::Result<()> {
+/// let preferences = Preferences { theme: "dark".to_owned(), font_size: 12 };
+/// nickelodeon::save_configuration("my_app", &preferences)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The file is written with [`to_nickel_string`], replacing the previous one.
+///
+/// # Errors
+///
+/// Will return `Err` if `value` can't be written as Nickel, if there is no configuration
+/// directory for the current user, or if the file or its directories can't be written.
+pub fn save_configuration<T>(app: &str, value: &T) -> Result<PathBuf>
+where
+    T: Serialize + ?Sized,
+{
+    Saver::new(app).save(value)
+}
+
+/// Sets the field at `path` (a dotted path, like `editor.font_size`) of the configuration
+/// of the application with the codename `app` to `value`. See [`Saver::set_field`].
+///
+/// # Errors
+///
+/// Will return `Err` if the configuration file can't be read, parsed or written, if
+/// `value` can't be written as Nickel, or if the file doesn't write the field in a record.
+pub fn set_field<T>(app: &str, path: &str, value: &T) -> Result<PathBuf>
+where
+    T: Serialize + ?Sized,
+{
+    Saver::new(app).set_field(path, value)
+}
+
+/// Writes the Nickel configuration of an application.
+///
+/// [`save_configuration`] is a shortcut for the most common setup. Build a [`Saver`] when
+/// you need to tweak how the configuration is written, for example to keep backups of the
+/// previous versions:
+///
+/// ```no_run
+/// # fn main() -> nickelodeon::Result<()> {
+/// # let preferences = std::collections::BTreeMap::from([("theme", "dark")]);
+/// nickelodeon::Saver::new("my_app").backups(3).save(&preferences)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The file is always written atomically: the new contents go to a temporary file in the
+/// same directory, which then replaces the configuration file, so a crash or a failed write
+/// never leaves a truncated configuration behind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Saver {
+    app: String,
+    config_path_from_flag: Option<PathBuf>,
+    backups: usize,
+}
+
+impl Saver {
+    /// Creates a saver for the application with the codename `app`. By default, it writes
+    /// `$XDG_CONFIG_HOME/<app>/config.ncl` (or the platform equivalent) and keeps no
+    /// backups.
+    #[must_use]
+    pub fn new(app: &str) -> Self {
+        Self {
+            app: app.to_owned(),
+            config_path_from_flag: None,
+            backups: 0,
+        }
+    }
+
+    /// Writes to the given path (usually coming from a `--config` flag) instead of the
+    /// configuration directory of the user.
+    #[must_use]
+    pub fn config_path_from_flag(mut self, path: Option<PathBuf>) -> Self {
+        self.config_path_from_flag = path;
+        self
+    }
+
+    /// Keeps the `count` most recent previous versions of the configuration file, next to
+    /// it, as `config.ncl.bak.<timestamp>` (in milliseconds since the Unix epoch). Older
+    /// backups are deleted on every save.
+    #[must_use]
+    pub const fn backups(mut self, count: usize) -> Self {
+        self.backups = count;
+        self
+    }
+
+    /// Returns the path of the file this saver writes.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if no path was given and there is no configuration directory for
+    /// the current user.
+    pub fn path(&self) -> Result<PathBuf> {
+        self.config_path_from_flag
+            .clone()
+            .or_else(|| user_config_path(&self.app))
+            .ok_or_else(|| {
+                Error::ConfigFileWritingError(
+                    Message::WritingFailed("no configuration directory for the current user")
+                        .to_string(),
+                )
+            })
+    }
+
+    /// Writes `value`, as Nickel, replacing the configuration file. Returns the path of the
+    /// written file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` can't be written as Nickel, if there is nowhere to
+    /// write it, or if the file, its directories or its backups can't be written.
+    pub fn save<T>(&self, value: &T) -> Result<PathBuf>
+    where
+        T: Serialize + ?Sized,
+    {
+        let source = to_nickel_string(value)?;
+        let path = self.path()?;
+        self.write(&path, &source)?;
+        Ok(path)
+    }
+
+    /// Sets the field at `path` (a dotted path, like `editor.font_size`) of the configuration
+    /// file to `value`, rewriting only the text of its value: the comments, order and
+    /// formatting of the rest of the file are kept. Returns the path of the written file.
+    ///
+    /// Meant for settings screens writing the changes of the user back to a configuration
+    /// file they also edit by hand:
+    ///
+    /// ```no_run
+    /// # fn main() -> nickelodeon::Result<()> {
+    /// nickelodeon::Saver::new("my_app").set_field("editor.font_size", &14)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// A missing field is added at the end of its record, and a missing file is created.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the configuration file can't be read, parsed or written, if
+    /// `value` can't be written as Nickel, or if the file doesn't write the field in a
+    /// record (e.g. it's the result of a function).
+    pub fn set_field<T>(&self, path: &str, value: &T) -> Result<PathBuf>
+    where
+        T: Serialize + ?Sized,
+    {
+        let field: Vec<String> = path.split('.').map(str::to_owned).collect();
+        let nickel = to_nickel(value)?;
+        let file = self.path()?;
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => with_field(&source, &field, nickel)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                nested(&field, nickel).to_source(&Comments::new())
+            }
+            Err(err) => {
+                return Err(Error::ConfigFileReadingError(format!(
+                    "{}: {err}",
+                    file.display()
+                )))
+            }
+        };
+        self.write(&file, &source)?;
+        Ok(file)
+    }
+
+    /// Atomically replaces the file at `path` with `source`, creating its directories and
+    /// backing up its previous version as needed.
+    fn write(&self, path: &Path, source: &str) -> Result<()> {
+        let failed = |err: io::Error| writing_failed(path, &err);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(failed)?;
+        }
+        let previous = fs::metadata(path).ok();
+        if previous.is_some() && self.backups > 0 {
+            back_up(path, self.backups).map_err(failed)?;
+        }
+
+        let temporary = sibling(path, &format!(".tmp.{}", std::process::id()));
+        let written = write_synced(&temporary, source, previous.as_ref())
+            .and_then(|()| fs::rename(&temporary, path));
+        if written.is_err() {
+            drop(fs::remove_file(&temporary));
+        }
+        written.map_err(failed)
+    }
+}
+
+/// Returns where the configuration of the application with the codename `app` is saved:
+/// the `config.ncl` in its directory of the platform configuration directory.
+fn user_config_path(app: &str) -> Option<PathBuf> {
+    ConfigDirs::empty()
+        .add_platform_config_dir()
+        .paths()
+        .first()
+        .map(|dir| dir.join(app).join("config.ncl"))
+}
+
+/// Returns the path of the file named like the one at `path`, followed by `suffix`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Writes `source` to the file at `path`, with the permissions of the `previous` version,
+/// and waits until it reaches the disk.
+fn write_synced(path: &Path, source: &str, previous: Option<&fs::Metadata>) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    if let Some(metadata) = previous {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.write_all(source.as_bytes())?;
+    file.sync_all()
+}
+
+/// Copies the file at `path` to a new timestamped backup, deleting the oldest ones so only
+/// `keep` remain.
+fn back_up(path: &Path, keep: usize) -> io::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let backup = (now..now.saturating_add(1000))
+        .map(|timestamp| sibling(path, &format!(".bak.{timestamp}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| sibling(path, ".bak"));
+    fs::copy(path, backup)?;
+
+    let mut backups = backups_of(path)?;
+    backups.sort_unstable();
+    let excess = backups.len().saturating_sub(keep);
+    for (_, old) in backups.into_iter().take(excess) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Lists the backups of the file at `path`, with their timestamps.
+fn backups_of(path: &Path) -> io::Result<Vec<(u128, PathBuf)>> {
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{file}.bak.");
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    Ok(fs::read_dir(dir)?
+        .filter_map(io::Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let timestamp = name.to_str()?.strip_prefix(&prefix)?.parse().ok()?;
+            Some((timestamp, entry.path()))
+        })
+        .collect())
+}
+
+/// Builds the error returned when the file at `path` can't be written, because of `err`.
+fn writing_failed(path: &Path, err: &io::Error) -> Error {
+    let reason = format!("{}: {err}", path.display());
+    Error::ConfigFileWritingError(Message::WritingFailed(&reason).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod saver {
+        use super::super::Saver;
+        use crate::Error;
+        use crate::Loader;
+        use std::collections::BTreeMap;
+        use std::path::Path;
+        use std::path::PathBuf;
+
+        fn saver(path: &Path) -> Saver {
+            Saver::new("nickelodeon_test").config_path_from_flag(Some(path.to_owned()))
+        }
+
+        fn files(dir: &Path) -> Vec<String> {
+            let mut names: Vec<String> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+
+        #[test]
+        fn creates_the_directories() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("some_app").join("config.ncl");
+
+            let saved = saver(&path)
+                .save(&BTreeMap::from([("theme", "dark")]))
+                .unwrap();
+
+            let loaded: BTreeMap<String, String> = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(saved))
+                .load()
+                .unwrap();
+            assert_eq!(loaded.get("theme").map(String::as_str), Some("dark"));
+        }
+
+        #[test]
+        fn replaces_the_previous_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            std::fs::write(&path, "{ theme = \"light\", font_size = 10 }").unwrap();
+
+            saver(&path)
+                .save(&BTreeMap::from([("theme", "dark")]))
+                .unwrap();
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(source, "{\n  theme = \"dark\",\n}\n");
+            assert_eq!(files(dir.path()), ["config.ncl"]);
+        }
+
+        #[test]
+        fn keeps_the_most_recent_backups() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            let saver = saver(&path).backups(2);
+
+            for version in 1..=4 {
+                saver.save(&BTreeMap::from([("version", version)])).unwrap();
+            }
+
+            let names = files(dir.path());
+            assert_eq!(names.len(), 3);
+            assert!(names
+                .iter()
+                .all(|name| name == "config.ncl" || name.starts_with("config.ncl.bak.")));
+            let newest_backup = names.last().map(PathBuf::from).unwrap();
+            let source = std::fs::read_to_string(dir.path().join(newest_backup)).unwrap();
+            assert_eq!(source, "{\n  version = 3,\n}\n");
+        }
+
+        #[test]
+        fn sets_a_field() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            std::fs::write(&path, "{\n  # Points.\n  font_size = 10,\n}\n").unwrap();
+
+            saver(&path).set_field("font_size", &14).unwrap();
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(source, "{\n  # Points.\n  font_size = 14,\n}\n");
+        }
+
+        #[test]
+        fn sets_a_field_of_a_missing_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+
+            saver(&path).set_field("editor.theme", "dark").unwrap();
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(source, "{\n  editor = {\n    theme = \"dark\",\n  },\n}\n");
+        }
+
+        #[test]
+        fn unwritable() {
+            let dir = tempfile::tempdir().unwrap();
+            let file = dir.path().join("file");
+            std::fs::write(&file, "").unwrap();
+
+            let result = saver(&file.join("config.ncl")).save(&BTreeMap::from([("a", 1)]));
+
+            assert!(matches!(result, Err(Error::ConfigFileWritingError(_))));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use serde::de;
+use serde::de::value::Error;
+use serde::de::IntoDeserializer as _;
+use serde::de::Visitor;
+use serde::forward_to_deserialize_any;
+use serde::Deserialize;
+use std::cell::Cell;
+
+/// A step of the path leading to a nested struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PathSegment<'path> {
+    /// A field of a struct or map.
+    Key(&'path str),
+
+    /// An element of a sequence.
+    Element,
+}
+
+/// Returns the fields expected by the struct found at `path` inside `T`, if there is a
+/// struct there.
+///
+/// Found by feeding `T` with a deserializer that follows `path` and records the fields the
+/// `Deserialize` implementation of the struct at its end asks for, so it works with any
+/// `#[derive(Deserialize)]` type without a schema.
+pub(crate) fn fields_of<'de, T: Deserialize<'de>>(
+    path: &[PathSegment<'_>],
+) -> Option<&'static [&'static str]> {
+    let found = Cell::new(None);
+    let _expected_to_fail: Result<T, Error> = T::deserialize(Probe {
+        path,
+        found: &found,
+    });
+    found.get()
+}
+
+/// Renders `fields` as a human readable list: `` `a`, `b`, `c` ``.
+pub(crate) fn list(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|field| format!("`{field}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct Probe<'probe> {
+    path: &'probe [PathSegment<'probe>],
+    found: &'probe Cell<Option<&'static [&'static str]>>,
+}
+
+impl<'de> de::Deserializer<'de> for Probe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.path.split_first() {
+            None => {
+                self.found.set(Some(fields));
+                Err(de::Error::custom("found"))
+            }
+            Some((segment, rest)) => visitor.visit_map(ProbeAccess {
+                segment: Some(*segment),
+                rest,
+                found: self.found,
+            }),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let Some((segment, rest)) = self.path.split_first() else {
+            return Err(de::Error::custom("not a struct"));
+        };
+        visitor.visit_map(ProbeAccess {
+            segment: Some(*segment),
+            rest,
+            found: self.found,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let Some((segment, rest)) = self.path.split_first() else {
+            return Err(de::Error::custom("not a struct"));
+        };
+        visitor.visit_seq(ProbeAccess {
+            segment: Some(*segment),
+            rest,
+            found: self.found,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+/// Gives access to the single entry (or element) that leads to the rest of the path.
+struct ProbeAccess<'probe> {
+    segment: Option<PathSegment<'probe>>,
+    rest: &'probe [PathSegment<'probe>],
+    found: &'probe Cell<Option<&'static [&'static str]>>,
+}
+
+impl ProbeAccess<'_> {
+    const fn probe(&self) -> Probe<'_> {
+        Probe {
+            path: self.rest,
+            found: self.found,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for ProbeAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.segment.take() {
+            Some(PathSegment::Key(key)) => seed.deserialize(key.into_deserializer()).map(Some),
+            Some(PathSegment::Element) | None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.probe())
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for ProbeAccess<'_> {
+    type Error = Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        match self.segment.take() {
+            Some(PathSegment::Element) => seed.deserialize(self.probe()).map(Some),
+            Some(PathSegment::Key(_)) | None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Server {
+        host: String,
+        timeout: u64,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct TestConfiguration {
+        name: String,
+        server: Option<Server>,
+        mirrors: Vec<Server>,
+    }
+
+    #[cfg(test)]
+    mod fields_of {
+        use super::super::fields_of;
+        use super::super::PathSegment;
+        use super::TestConfiguration;
+
+        #[test]
+        fn top_level() {
+            let result = fields_of::<TestConfiguration>(&[]);
+            assert_eq!(result, Some(&["name", "server", "mirrors"][..]));
+        }
+
+        #[test]
+        fn nested() {
+            let result = fields_of::<TestConfiguration>(&[PathSegment::Key("server")]);
+            assert_eq!(result, Some(&["host", "timeout"][..]));
+        }
+
+        #[test]
+        fn inside_a_sequence() {
+            let path = [PathSegment::Key("mirrors"), PathSegment::Element];
+            let result = fields_of::<TestConfiguration>(&path);
+            assert_eq!(result, Some(&["host", "timeout"][..]));
+        }
+
+        #[test]
+        fn not_a_struct() {
+            let result = fields_of::<TestConfiguration>(&[PathSegment::Key("name")]);
+            assert_eq!(result, None);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use serde::de;
+use serde::ser::SerializeMap as _;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use serde_json::Value;
+use std::cell::Cell;
+use std::fmt;
+
+/// What the secrets are shown as.
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// The name of the single field of the records [`Secret`]s are serialized as while
+/// [`Exposure::Marked`], to be found in the serialized configuration.
+const MARKER: &str = "$nickelodeon::secret";
+
+/// How the [`Secret`]s are serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Exposure {
+    /// As [`REDACTED`].
+    Redacted,
+
+    /// As their value, within a record marking them (see [`unmarked`]).
+    Marked,
+
+    /// As their value.
+    Revealed,
+}
+
+thread_local! {
+    /// How the [`Secret`]s are serialized on this thread.
+    static EXPOSURE: Cell<Exposure> = const { Cell::new(Exposure::Redacted) };
+
+    /// Whether a [`Secret`] was deserialized on this thread (see [`detecting_secrets`]).
+    static DESERIALIZED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A value, like a password or a token, that is never shown by nickelodeon or by the logs
+/// of the application.
+///
+/// Its [`fmt::Debug`] and [`fmt::Display`] implementations, as well as its serialization,
+/// write `<redacted>` instead of the value, which is only reachable with
+/// [`Secret::expose`]:
+///
+/// ```
+/// #[derive(Debug, Default, serde::Deserialize)]
+/// struct Database {
+///     user: String,
+///     password: nickelodeon::Secret<String>,
+/// }
+///
+/// let database: Database =
+///     serde_json::from_str(r#"{ "user": "nick", "password": "hunter2" }"#).unwrap();
+///
+/// assert_eq!(
+///     format!("{database:?}"),
+///     r#"Database { user: "nick", password: <redacted> }"#
+/// );
+/// assert_eq!(database.password.expose(), "hunter2");
+/// ```
+///
+/// The errors of a secret that doesn't match `T` don't show its value either, and the
+/// [`crate::ChangeSet`]s redact it, as they do for the [`crate::Loader::secret_field`]s.
+/// The value is only written where it is needed: by [`crate::to_nickel_string`] and
+/// [`crate::Saver`], to keep it in the configuration file, and when nickelodeon itself
+/// serializes the configuration to read it back (e.g. to merge the command line arguments
+/// into it).
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    /// Wraps `value`.
+    #[must_use]
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the value, to use it (never to log it).
+    #[must_use]
+    pub const fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns the value, unwrapped.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DESERIALIZED.set(true);
+        T::deserialize(deserializer)
+            .map(Self)
+            .map_err(|_err| de::Error::custom("invalid secret (its value isn't shown)"))
+    }
+}
+
+impl<T: Serialize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match EXPOSURE.get() {
+            Exposure::Redacted => serializer.serialize_str(REDACTED),
+            Exposure::Marked => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(MARKER, &self.0)?;
+                map.end()
+            }
+            Exposure::Revealed => self.0.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl<T: schemars::JsonSchema> schemars::JsonSchema for Secret<T> {
+    fn inline_schema() -> bool {
+        T::inline_schema()
+    }
+
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        T::schema_name()
+    }
+
+    fn schema_id() -> std::borrow::Cow<'static, str> {
+        T::schema_id()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        T::json_schema(generator)
+    }
+}
+
+/// Runs `serialize` with the [`Secret`]s serialized as `exposure` says.
+pub(crate) fn exposing<T, S>(exposure: Exposure, serialize: S) -> T
+where
+    S: FnOnce() -> T,
+{
+    let _restore = Restore(EXPOSURE.replace(exposure));
+    serialize()
+}
+
+/// Restores how the [`Secret`]s were serialized when dropped, even if serializing
+/// panicked.
+struct Restore(Exposure);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        EXPOSURE.set(self.0);
+    }
+}
+
+/// Runs `deserialize`, also returning whether it deserialized any [`Secret`].
+pub(crate) fn detecting_secrets<T, D>(deserialize: D) -> (T, bool)
+where
+    D: FnOnce() -> T,
+{
+    let outer = DESERIALIZED.replace(false);
+    let deserialized = deserialize();
+    let secrets = DESERIALIZED.replace(outer);
+    (deserialized, secrets)
+}
+
+/// Returns `value`, serialized with [`Exposure::Marked`], with the [`Secret`]s replaced by
+/// their values, adding to `secrets` the dotted paths they are at (the path of the whole
+/// array, for the ones in an array).
+pub(crate) fn unmarked(value: &Value, path: &str, secrets: &mut Vec<String>) -> Value {
+    match value {
+        Value::Object(fields) => {
+            if let (1, Some(secret)) = (fields.len(), fields.get(MARKER)) {
+                secrets.push(path.to_owned());
+                return unmarked(secret, path, &mut Vec::new());
+            }
+            let unmarked_fields = fields
+                .iter()
+                .map(|(name, field)| {
+                    let nested = if path.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{path}.{name}")
+                    };
+                    (name.clone(), unmarked(field, &nested, secrets))
+                })
+                .collect();
+            Value::Object(unmarked_fields)
+        }
+        Value::Array(elements) => {
+            let mut nested = Vec::new();
+            let unmarked_elements = elements
+                .iter()
+                .map(|element| unmarked(element, path, &mut nested))
+                .collect();
+            if !nested.is_empty() {
+                secrets.push(path.to_owned());
+            }
+            Value::Array(unmarked_elements)
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod secret {
+        use super::super::detecting_secrets;
+        use super::super::exposing;
+        use super::super::Exposure;
+        use super::super::Secret;
+        use serde_json::json;
+        use serde_json::Value;
+
+        #[derive(Debug, serde::Deserialize, serde::Serialize)]
+        struct Database {
+            user: String,
+            password: Secret<String>,
+        }
+
+        fn database() -> Database {
+            Database {
+                user: "nick".to_owned(),
+                password: Secret::new("hunter2".to_owned()),
+            }
+        }
+
+        #[test]
+        fn redacted() {
+            let shown = format!("{:?} {}", database(), database().password);
+            assert!(!shown.contains("hunter2"), "{shown}");
+
+            assert_eq!(
+                serde_json::to_value(database()).unwrap(),
+                json!({ "user": "nick", "password": "<redacted>" })
+            );
+        }
+
+        #[test]
+        fn exposed() {
+            let revealed = exposing(Exposure::Revealed, || serde_json::to_value(database()));
+            assert_eq!(
+                revealed.unwrap(),
+                json!({ "user": "nick", "password": "hunter2" })
+            );
+            assert_eq!(
+                serde_json::to_value(database()).unwrap(),
+                json!({ "user": "nick", "password": "<redacted>" })
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            let result: Result<Secret<u16>, _> = serde_json::from_value(json!("hunter2"));
+            let message = result.unwrap_err().to_string();
+
+            assert_eq!(message, "invalid secret (its value isn't shown)");
+        }
+
+        #[test]
+        fn deserialized() {
+            let config = json!({ "user": "nick", "password": "hunter2" });
+
+            let (database, secrets) =
+                detecting_secrets(|| serde_json::from_value::<Database>(config.clone()));
+            let (_, plain) = detecting_secrets(|| serde_json::from_value::<Value>(config));
+
+            assert_eq!(database.unwrap().password.expose(), "hunter2");
+            assert!(secrets);
+            assert!(!plain);
+        }
+    }
+
+    #[cfg(test)]
+    mod unmarked {
+        use super::super::exposing;
+        use super::super::unmarked;
+        use super::super::Exposure;
+        use super::super::Secret;
+        use serde_json::json;
+
+        #[derive(serde::Serialize)]
+        struct Config {
+            server: Server,
+            tokens: Vec<Secret<String>>,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Server {
+            port: u16,
+            key: Secret<String>,
+        }
+
+        #[test]
+        fn finds_the_secrets() {
+            let config = Config {
+                server: Server {
+                    port: 80,
+                    key: Secret::new("k".to_owned()),
+                },
+                tokens: vec![Secret::new("t".to_owned())],
+            };
+            let marked = exposing(Exposure::Marked, || serde_json::to_value(config)).unwrap();
+
+            let mut secrets = Vec::new();
+            let value = unmarked(&marked, "", &mut secrets);
+
+            assert_eq!(
+                value,
+                json!({ "server": { "port": 80, "key": "k" }, "tokens": ["t"] })
+            );
+            assert_eq!(secrets, ["server.key", "tokens"]);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::field_error::deserialize_collecting_errors;
+use crate::FieldError;
+use crate::Messages;
+use serde::de::DeserializeOwned;
+use serde_json::Number;
+use serde_json::Value;
+
+/// Configuration structs deserialized from sections of the same configuration, as a tuple
+/// of up to 6 of them, by [`crate::Loader::watch_sections`].
+pub trait Sections: Sized {
+    /// The dotted paths of the sections (like `logging` or `server.tls`), one per struct.
+    type Paths;
+
+    /// Deserializes each section of `config` at `paths` into its struct, reporting every
+    /// mismatched field of every section, so a version is only used if all of them match.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with the mismatched fields, with their full dotted path, if any
+    /// section doesn't match its struct.
+    fn decode(
+        config: &Value,
+        paths: &Self::Paths,
+        messages: &dyn Messages,
+    ) -> Result<Self, Vec<FieldError>>;
+}
+
+/// Deserializes the section of `config` at `path` into an `S`, adding the errors to
+/// `errors`. A missing section is deserialized from `null`, so it can be optional.
+fn section<S: DeserializeOwned>(
+    config: &Value,
+    path: &str,
+    messages: &dyn Messages,
+    errors: &mut Vec<FieldError>,
+) -> Option<S> {
+    let value = path
+        .split('.')
+        .try_fold(config, |parent, field| parent.get(field))
+        .cloned()
+        .map_or(Value::Null, integral);
+    match deserialize_collecting_errors(value, messages) {
+        Ok(decoded) => Some(decoded),
+        Err(mismatches) => {
+            errors.extend(mismatches.into_iter().map(|mismatch| FieldError {
+                path: if mismatch.path.is_empty() {
+                    path.to_owned()
+                } else {
+                    format!("{path}.{}", mismatch.path)
+                },
+                message: mismatch.message,
+            }));
+            None
+        }
+    }
+}
+
+/// Returns `value` with its integral numbers as integers: Nickel numbers are deserialized as
+/// floating-point numbers, which integer fields don't accept.
+fn integral(value: Value) -> Value {
+    match value {
+        Value::Number(number) => {
+            // Unlike `serde_json`, Rust writes the integral floating-point numbers without
+            // a fractional part.
+            let text = number.as_f64().map(|float| float.to_string());
+            let integer = text.and_then(|digits| {
+                digits
+                    .parse::<i64>()
+                    .map(Number::from)
+                    .or_else(|_| digits.parse::<u64>().map(Number::from))
+                    .ok()
+            });
+            Value::Number(integer.unwrap_or(number))
+        }
+        Value::Array(elements) => Value::Array(elements.into_iter().map(integral).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, field)| (name, integral(field)))
+                .collect(),
+        ),
+        Value::Null | Value::Bool(_) | Value::String(_) => value,
+    }
+}
+
+/// Implements [`Sections`] for the tuple of the given struct types, with the number of
+/// sections.
+macro_rules! sections {
+    ($count:literal; $($section:ident $decoded:ident $index:tt),+) => {
+        impl<$($section: DeserializeOwned),+> Sections for ($($section,)+) {
+            type Paths = [&'static str; $count];
+
+            fn decode(
+                config: &Value,
+                paths: &Self::Paths,
+                messages: &dyn Messages,
+            ) -> Result<Self, Vec<FieldError>> {
+                let mut errors = Vec::new();
+                $(
+                    let $decoded = section::<$section>(config, paths[$index], messages, &mut errors);
+                )+
+                match ($($decoded,)+) {
+                    ($(Some($decoded),)+) if errors.is_empty() => Ok(($($decoded,)+)),
+                    _ => Err(errors),
+                }
+            }
+        }
+    };
+}
+
+sections!(1; First first 0);
+sections!(2; First first 0, Second second 1);
+sections!(3; First first 0, Second second 1, Third third 2);
+sections!(4; First first 0, Second second 1, Third third 2, Fourth fourth 3);
+sections!(5; First first 0, Second second 1, Third third 2, Fourth fourth 3, Fifth fifth 4);
+sections!(6; First first 0, Second second 1, Third third 2, Fourth fourth 3, Fifth fifth 4, Sixth sixth 5);
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod decode {
+        use super::super::Sections as _;
+        use crate::English;
+        use crate::FieldError;
+
+        #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+        struct Logging {
+            level: String,
+        }
+
+        #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+        struct Tls {
+            cert: String,
+        }
+
+        #[test]
+        fn every_section() {
+            let config = serde_json::json!({
+                "logging": { "level": "info" },
+                "server": { "tls": { "cert": "cert.pem" } },
+            });
+
+            let decoded =
+                <(Logging, Option<Tls>)>::decode(&config, &["logging", "server.tls"], &English);
+            let missing = <(Option<Tls>,)>::decode(&config, &["client.tls"], &English);
+
+            assert_eq!(
+                decoded,
+                Ok((
+                    Logging {
+                        level: "info".to_owned()
+                    },
+                    Some(Tls {
+                        cert: "cert.pem".to_owned()
+                    })
+                ))
+            );
+            assert_eq!(missing, Ok((None,)));
+        }
+
+        #[test]
+        fn integers() {
+            let config = serde_json::json!({ "server": { "port": 8080.0, "ratio": 0.5 } });
+
+            let decoded = <(u16, f64)>::decode(&config, &["server.port", "server.ratio"], &English);
+
+            assert_eq!(decoded, Ok((8080, 0.5)));
+        }
+
+        #[test]
+        fn all_mismatches() {
+            let config = serde_json::json!({
+                "logging": { "level": 1 },
+                "server": { "tls": { "cert": false } },
+            });
+
+            let decoded = <(Logging, Tls)>::decode(&config, &["logging", "server.tls"], &English);
+
+            let paths: Vec<String> = decoded
+                .unwrap_err()
+                .into_iter()
+                .map(|error: FieldError| error.path)
+                .collect();
+            assert_eq!(paths, ["logging.level", "server.tls.cert"]);
+        }
+    }
+}
@@ -0,0 +1,704 @@
+use crate::contract::field_contracts;
+use crate::secret::exposing;
+use crate::secret::Exposure;
+use crate::syntax::enum_tag;
+use crate::syntax::string;
+use crate::syntax::Comments;
+use crate::syntax::Nickel;
+use crate::Error;
+use crate::Result;
+use serde::de::DeserializeOwned;
+use serde::ser;
+use serde::Serialize;
+use std::fmt;
+
+/// Writes `value` as the source of a Nickel file, e.g. to write a configuration back or to
+/// generate a sample one:
+///
+/// ```
+/// #[derive(serde::Serialize)]
+/// struct Server {
+///     host: String,
+///     ports: Vec<u16>,
+/// }
+///
+/// let server = Server { host: "localhost".to_owned(), ports: vec![80, 443] };
+/// let source = nickelodeon::to_nickel_string(&server).unwrap();
+///
+/// assert_eq!(source, "{\n  host = \"localhost\",\n  ports = [80, 443],\n}\n");
+/// ```
+///
+/// Fields keep the order they are serialized in, and strings are escaped, so loading the
+/// source back gives the same value. Enum variants without data become enum tags (e.g.
+/// `'Debug`), and the other ones records with a single field, named after the variant, as
+/// nickelodeon deserializes them.
+///
+/// # Errors
+///
+/// Will return `Err` if `value` can't be written in Nickel, e.g. if it holds a map whose
+/// keys aren't strings or numbers, or a floating point number that isn't finite.
+pub fn to_nickel_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    to_nickel(value).map(|nickel| nickel.to_source(&Comments::new()))
+}
+
+/// Returns the default configuration, `T::default()`, as the source of a Nickel file with
+/// the types of its fields, so applications can print it (e.g. for a
+/// `--print-default-config` flag) in one call:
+///
+/// ```
+/// #[derive(Default, serde::Deserialize, serde::Serialize)]
+/// struct Config {
+///     name: String,
+///     ports: Vec<u16>,
+/// }
+///
+/// let source = nickelodeon::default_config_source::<Config>().unwrap();
+///
+/// assert_eq!(
+///     source,
+///     "{\n  name | String = \"\",\n  ports | Array (std.number.Nat) = [],\n}\n"
+/// );
+/// ```
+///
+/// The fields whose type is a simple one (a number, a string, a boolean or an array of
+/// those), as found by [`crate::contract_for`], are annotated with its contract, so
+/// mistakes made while editing the printed configuration are caught. See
+/// [`crate::Loader::default_config_source`] to print the embedded defaults instead, and
+/// `sample_config` (with the `schemars` feature) to also write the doc comments of `T`.
+///
+/// # Errors
+///
+/// Will return `Err` if the default value of `T` can't be written in Nickel (see
+/// [`crate::to_nickel_string`]).
+pub fn default_config_source<T>() -> Result<String>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    let value = to_nickel(&T::default())?;
+    Ok(value.to_annotated_source(&Comments::new(), &field_contracts::<T>(&value)))
+}
+
+/// Turns `value` into a [`Nickel`] value, ready to be written as source.
+pub(crate) fn to_nickel<T>(value: &T) -> Result<Nickel>
+where
+    T: Serialize + ?Sized,
+{
+    exposing(Exposure::Revealed, || value.serialize(Serializer))
+        .map_err(|SerializeError(reason)| Error::SerializationError(reason))
+}
+
+/// Why a value can't be written in Nickel.
+#[derive(Debug)]
+struct SerializeError(String);
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+impl ser::Error for SerializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Builds the [`Nickel`] value of whatever it serializes.
+struct Serializer;
+
+/// Writes `value`, a number or a boolean, as is.
+fn literal<T: fmt::Display>(value: T) -> Nickel {
+    Nickel::Literal(value.to_string())
+}
+
+/// Writes `value`, a floating point number, as a literal, as long as it's finite.
+fn float<T>(value: T, finite: bool) -> std::result::Result<Nickel, SerializeError>
+where
+    T: fmt::Display,
+{
+    if finite {
+        Ok(literal(value))
+    } else {
+        Err(SerializeError(format!(
+            "{value} can't be written in Nickel"
+        )))
+    }
+}
+
+/// Returns the name of the field a map `key` is written as.
+fn field_name_of<T>(key: &T) -> std::result::Result<String, SerializeError>
+where
+    T: Serialize + ?Sized,
+{
+    match serde_json::to_value(key) {
+        Ok(serde_json::Value::String(name)) => Ok(name),
+        Ok(serde_json::Value::Number(name)) => Ok(name.to_string()),
+        Ok(serde_json::Value::Bool(name)) => Ok(name.to_string()),
+        _ => Err(SerializeError(String::from(
+            "map keys must be strings, numbers or booleans",
+        ))),
+    }
+}
+
+/// Wraps `value`, the data of the enum `variant`, into a record with a single field named
+/// after it.
+fn wrapped(variant: Option<&'static str>, value: Nickel) -> Nickel {
+    match variant {
+        Some(name) => Nickel::Record(vec![(name.to_owned(), value)]),
+        None => value,
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = ArraySerializer;
+    type SerializeTupleStruct = ArraySerializer;
+    type SerializeTupleVariant = ArraySerializer;
+    type SerializeMap = RecordSerializer;
+    type SerializeStruct = RecordSerializer;
+    type SerializeStructVariant = RecordSerializer;
+
+    fn serialize_bool(self, v: bool) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> std::result::Result<Nickel, SerializeError> {
+        Ok(literal(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> std::result::Result<Nickel, SerializeError> {
+        float(v, v.is_finite())
+    }
+
+    fn serialize_f64(self, v: f64) -> std::result::Result<Nickel, SerializeError> {
+        float(v, v.is_finite())
+    }
+
+    fn serialize_char(self, v: char) -> std::result::Result<Nickel, SerializeError> {
+        Ok(Nickel::Literal(string(v.encode_utf8(&mut [0; 4]))))
+    }
+
+    fn serialize_str(self, v: &str) -> std::result::Result<Nickel, SerializeError> {
+        Ok(Nickel::Literal(string(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> std::result::Result<Nickel, SerializeError> {
+        Ok(Nickel::Array(
+            v.iter()
+                .map(|byte| Nickel::Literal(byte.to_string()))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> std::result::Result<Nickel, SerializeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> std::result::Result<Nickel, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(Nickel::Literal(String::from("null")))
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> std::result::Result<Nickel, SerializeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> std::result::Result<Nickel, SerializeError> {
+        Ok(Nickel::Literal(enum_tag(variant)))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> std::result::Result<Nickel, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> std::result::Result<Nickel, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(wrapped(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(
+        self,
+        len: Option<usize>,
+    ) -> std::result::Result<ArraySerializer, SerializeError> {
+        Ok(ArraySerializer::new(None, len.unwrap_or_default()))
+    }
+
+    fn serialize_tuple(self, len: usize) -> std::result::Result<ArraySerializer, SerializeError> {
+        Ok(ArraySerializer::new(None, len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> std::result::Result<ArraySerializer, SerializeError> {
+        Ok(ArraySerializer::new(None, len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> std::result::Result<ArraySerializer, SerializeError> {
+        Ok(ArraySerializer::new(Some(variant), len))
+    }
+
+    fn serialize_map(
+        self,
+        len: Option<usize>,
+    ) -> std::result::Result<RecordSerializer, SerializeError> {
+        Ok(RecordSerializer::new(None, len.unwrap_or_default()))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> std::result::Result<RecordSerializer, SerializeError> {
+        Ok(RecordSerializer::new(None, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> std::result::Result<RecordSerializer, SerializeError> {
+        Ok(RecordSerializer::new(Some(variant), len))
+    }
+}
+
+/// The elements of an array being serialized, the data of the enum `variant` if set.
+struct ArraySerializer {
+    variant: Option<&'static str>,
+    elements: Vec<Nickel>,
+}
+
+impl ArraySerializer {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        Self {
+            variant,
+            elements: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T>(&mut self, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.elements.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Nickel {
+        wrapped(self.variant, Nickel::Array(self.elements))
+    }
+}
+
+impl ser::SerializeSeq for ArraySerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for ArraySerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for ArraySerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for ArraySerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+/// The fields of a record being serialized, the data of the enum `variant` if set.
+struct RecordSerializer {
+    variant: Option<&'static str>,
+    fields: Vec<(String, Nickel)>,
+    next_key: Option<String>,
+}
+
+impl RecordSerializer {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        Self {
+            variant,
+            fields: Vec::with_capacity(len),
+            next_key: None,
+        }
+    }
+
+    fn push<T>(&mut self, name: String, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.fields.push((name, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Nickel {
+        wrapped(self.variant, Nickel::Record(self.fields))
+    }
+}
+
+impl ser::SerializeMap for RecordSerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.next_key = Some(field_name_of(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let name = self
+            .next_key
+            .take()
+            .ok_or_else(|| SerializeError(String::from("map value serialized before its key")))?;
+        self.push(name, value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for RecordSerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(key.to_owned(), value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for RecordSerializer {
+    type Ok = Nickel;
+    type Error = SerializeError;
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(key.to_owned(), value)
+    }
+
+    fn end(self) -> std::result::Result<Nickel, SerializeError> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod to_nickel_string {
+        use super::super::to_nickel_string;
+        use crate::Error;
+        use crate::Loader;
+        use serde::Deserialize;
+        use serde::Serialize;
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+        struct Config {
+            name: String,
+            level: Level,
+            retries: Option<u8>,
+            ratio: f64,
+            limits: BTreeMap<String, u32>,
+            outputs: Vec<Output>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+        enum Level {
+            #[default]
+            Info,
+            Debug,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        enum Output {
+            File(String),
+            Syslog { facility: u8 },
+        }
+
+        #[test]
+        fn round_trip() {
+            let config = Config {
+                name: "my \"app\" %{name}\n".to_owned(),
+                level: Level::Debug,
+                retries: None,
+                ratio: 0.5,
+                limits: BTreeMap::from([("max connections".to_owned(), 10)]),
+                outputs: vec![
+                    Output::File("/var/log/app".to_owned()),
+                    Output::Syslog { facility: 3 },
+                ],
+            };
+
+            let source = to_nickel_string(&config).unwrap();
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            std::fs::write(&path, &source).unwrap();
+            let loaded: Config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path))
+                .diagnostics(std::io::sink())
+                .load()
+                .unwrap();
+
+            assert_eq!(
+                source,
+                r#"{
+  name = "my \"app\" \%{name}\n",
+  level = 'Debug,
+  retries = null,
+  ratio = 0.5,
+  limits = {
+    "max connections" = 10,
+  },
+  outputs = [
+    {
+      File = "/var/log/app",
+    },
+    {
+      Syslog = {
+        facility = 3,
+      },
+    },
+  ],
+}
+"#
+            );
+            assert_eq!(loaded, config);
+        }
+
+        #[test]
+        fn not_finite() {
+            let result = to_nickel_string(&[f64::NAN]);
+
+            assert!(matches!(result, Err(Error::SerializationError(..))));
+        }
+
+        #[test]
+        fn unsupported_keys() {
+            let map = BTreeMap::from([((1, 2), "pair")]);
+
+            let result = to_nickel_string(&map);
+
+            assert!(matches!(result, Err(Error::SerializationError(..))));
+        }
+    }
+
+    #[cfg(test)]
+    mod default_config_source {
+        use super::super::default_config_source;
+        use crate::Loader;
+        use std::io::Write as _;
+        use tempfile::NamedTempFile;
+
+        #[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq)]
+        struct Config {
+            name: String,
+            server: Server,
+            proxy: Option<String>,
+            retries: i8,
+            ratio: f64,
+        }
+
+        #[derive(Debug, Default, serde::Deserialize, serde::Serialize, PartialEq)]
+        struct Server {
+            port: u16,
+            hosts: Vec<String>,
+        }
+
+        impl Default for Config {
+            fn default() -> Self {
+                Self {
+                    name: "app".to_owned(),
+                    server: Server {
+                        port: 8080,
+                        hosts: vec!["localhost".to_owned()],
+                    },
+                    proxy: None,
+                    retries: -1,
+                    ratio: 0.5,
+                }
+            }
+        }
+
+        #[test]
+        fn annotates_the_fields() {
+            let source = default_config_source::<Config>().unwrap();
+
+            let expected = r#"{
+  name | String = "app",
+  server = {
+    port | std.number.Nat = 8080,
+    hosts | Array (String) = ["localhost"],
+  },
+  proxy = null,
+  retries | std.number.Integer = -1,
+  ratio | Number = 0.5,
+}
+"#;
+            assert_eq!(source, expected);
+        }
+
+        #[test]
+        fn loads_back() {
+            let mut ntf = NamedTempFile::new().unwrap();
+            write!(ntf, "{}", default_config_source::<Config>().unwrap()).unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(ntf.path().to_path_buf()))
+                .diagnostics(std::io::sink())
+                .load::<Config>()
+                .unwrap();
+
+            assert_eq!(config, Config::default());
+        }
+    }
+}
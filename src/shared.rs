@@ -0,0 +1,943 @@
+use crate::audit::remember;
+use crate::secret::exposing;
+use crate::secret::Exposure;
+use crate::subscription::subscription;
+use crate::subscription::Publisher;
+use crate::subscription::Subscription;
+use crate::CancellationToken;
+use crate::ChangeSet;
+use crate::Event;
+use crate::Failure;
+use crate::ReloadRecord;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+#[cfg(not(feature = "arc-swap"))]
+use std::sync::RwLock;
+use std::sync::Weak;
+
+/// A configuration shared by the whole application, kept up to date by a watch (see
+/// [`crate::Loader::watch_shared`]).
+///
+/// Readers get the current version with [`SharedConfig::load`], which they keep for as long
+/// as they need a consistent view of the configuration: new versions replace the current one
+/// as a whole, so a reader never sees some settings from one version and some from the next.
+///
+/// Clones share the same configuration. The watch stops once every clone is dropped.
+///
+/// With the `arc-swap` feature, reading the current version is lock-free, so readers on hot
+/// paths never wait, not even for each other. Otherwise, it takes a read lock, only held to
+/// clone an [`Arc`]: readers never wait for each other, only, briefly, for a new version to
+/// be stored, which avoids a dependency for applications reading it now and then.
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig { workers: usize }
+/// # fn main() -> nickelodeon::Result<()> {
+/// let config = nickelodeon::Loader::new("my_app")
+///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+///
+/// // In a hot path, possibly on another thread:
+/// let workers = config.load().workers;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Applications made of subsystems with their own configuration structs should gather them
+/// in a single one, loaded from the same file and shared by one [`SharedConfig`], rather
+/// than watching each of them on its own. Each version is then validated as a whole before
+/// replacing the current one, so the subsystems never observe a mix of versions, and none
+/// of them is updated when the configuration of any of them is invalid (structs that can't
+/// be gathered, e.g. from different crates, can be watched together with
+/// [`crate::Loader::watch_sections`] instead):
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct LoggingConfig { level: String }
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct ServerConfig { port: u16 }
+/// #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// struct MyConfig {
+///     logging: LoggingConfig,
+///     server: ServerConfig,
+/// }
+///
+/// # fn main() -> nickelodeon::Result<()> {
+/// let config = nickelodeon::Loader::new("my_app")
+///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+///
+/// let current = config.load();
+/// let (logging, server) = (&current.logging, &current.server);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SharedConfig<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// A version of a [`SharedConfig`], as returned by [`SharedConfig::snapshot`]: the
+/// configuration together with its generation, which tells the versions apart.
+///
+/// A snapshot dereferences to the configuration, and is cheap to clone. Services handling
+/// requests can attach one to each request, so every handler involved sees the same version
+/// even if a reload happens in the middle, and logs can tell which version served it:
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig { greeting: String }
+/// # fn main() -> nickelodeon::Result<()> {
+/// let config = nickelodeon::Loader::new("my_app")
+///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+///
+/// // Attached to each request by `nickelodeon::SnapshotLayer::new(config.clone())`, with
+/// // the `tower` feature, or by hand:
+/// let snapshot = config.snapshot();
+///
+/// // In the handlers, e.g. taking an axum `Extension<nickelodeon::Snapshot<MyConfig>>`:
+/// eprintln!("generation {}: {}", snapshot.generation(), snapshot.greeting);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Snapshot<T> {
+    generation: u64,
+    config: Arc<T>,
+}
+
+impl<T> Snapshot<T> {
+    /// Returns the generation of this version: 0 for the configuration the [`SharedConfig`]
+    /// was created with, increased by one each time a new version changing some settings
+    /// replaces the current one.
+    #[must_use]
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the configuration, to keep it beyond the snapshot.
+    #[must_use]
+    pub fn config(&self) -> Arc<T> {
+        Arc::clone(&self.config)
+    }
+}
+
+impl<T> Clone for Snapshot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            generation: self.generation,
+            config: Arc::clone(&self.config),
+        }
+    }
+}
+
+impl<T> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.config
+    }
+}
+
+/// The current version of a [`SharedConfig`], swapped atomically with the `arc-swap`
+/// feature.
+#[cfg(feature = "arc-swap")]
+#[derive(Debug)]
+struct Current<T>(arc_swap::ArcSwap<Snapshot<T>>);
+
+#[cfg(feature = "arc-swap")]
+impl<T> Current<T> {
+    fn new(snapshot: Snapshot<T>) -> Self {
+        Self(arc_swap::ArcSwap::from_pointee(snapshot))
+    }
+
+    fn get(&self) -> Snapshot<T> {
+        Snapshot::clone(&self.0.load())
+    }
+
+    /// Makes `config` the current version, returning it.
+    fn replace(&self, config: Arc<T>) -> Snapshot<T> {
+        let previous = self.0.rcu(|current| Snapshot {
+            generation: current.generation.saturating_add(1),
+            config: Arc::clone(&config),
+        });
+        Snapshot {
+            generation: previous.generation.saturating_add(1),
+            config,
+        }
+    }
+}
+
+/// The current version of a [`SharedConfig`], behind a lock without the `arc-swap` feature.
+#[cfg(not(feature = "arc-swap"))]
+#[derive(Debug)]
+struct Current<T>(RwLock<Snapshot<T>>);
+
+#[cfg(not(feature = "arc-swap"))]
+impl<T> Current<T> {
+    const fn new(snapshot: Snapshot<T>) -> Self {
+        Self(RwLock::new(snapshot))
+    }
+
+    fn get(&self) -> Snapshot<T> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Makes `config` the current version, returning it.
+    fn replace(&self, config: Arc<T>) -> Snapshot<T> {
+        let mut current = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        *current = Snapshot {
+            generation: current.generation.saturating_add(1),
+            config,
+        };
+        current.clone()
+    }
+}
+
+/// A function called with each new version of a configuration and what changed in it.
+type Callback<T> = Box<dyn FnMut(&T, &ChangeSet) + Send>;
+
+/// A function called with the reason each invalid version of a configuration was skipped.
+type FailureCallback = Box<dyn FnMut(&Failure) + Send>;
+
+struct Shared<T> {
+    current: Current<T>,
+    watching: CancellationToken,
+    callbacks: Mutex<Vec<Callback<T>>>,
+    failure_callbacks: Mutex<Vec<FailureCallback>>,
+    subscribers: Mutex<Vec<Publisher<Arc<T>>>>,
+    #[cfg(feature = "tokio")]
+    updates: tokio::sync::watch::Sender<Snapshot<T>>,
+    history: Mutex<VecDeque<ReloadRecord>>,
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("current", &self.current)
+            .field("watching", &self.watching)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        self.watching.cancel();
+    }
+}
+
+impl<T> Clone for SharedConfig<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> SharedConfig<T> {
+    /// Shares `config`, which is never updated.
+    pub fn new(config: T) -> Self {
+        Self::watched(config, CancellationToken::default())
+    }
+
+    /// Shares `config`, stopping the watch using `watching` once dropped.
+    pub(crate) fn watched(config: T, watching: CancellationToken) -> Self {
+        let initial = Snapshot {
+            generation: 0,
+            config: Arc::new(config),
+        };
+        Self {
+            shared: Arc::new(Shared {
+                #[cfg(feature = "tokio")]
+                updates: tokio::sync::watch::Sender::new(initial.clone()),
+                current: Current::new(initial),
+                watching,
+                callbacks: Mutex::new(Vec::new()),
+                failure_callbacks: Mutex::new(Vec::new()),
+                subscribers: Mutex::new(Vec::new()),
+                history: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+
+    /// Returns the current version of the configuration.
+    #[must_use]
+    pub fn load(&self) -> Arc<T> {
+        self.snapshot().config
+    }
+
+    /// Returns the generation of the current version of the configuration (see
+    /// [`Snapshot::generation`]): comparing it with one seen before tells whether some
+    /// settings changed since, without diffing the configuration.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.snapshot().generation
+    }
+
+    /// Returns the current version of the configuration, together with its generation.
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot<T> {
+        self.shared.current.get()
+    }
+
+    /// Registers `callback`, to be called with each new version of the configuration that
+    /// changes some settings, and the [`ChangeSet`] listing them, so subsystems can react to
+    /// the settings they care about:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct MyConfig { log: String }
+    /// # fn main() -> nickelodeon::Result<()> {
+    /// let config = nickelodeon::Loader::new("my_app")
+    ///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+    ///
+    /// config.on_change(|config, changes| {
+    ///     if changes.contains("log") {
+    ///         eprintln!("logging at the {} level", config.log);
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Callbacks run on the thread of the watch, in the order they were registered, once
+    /// the new version is the current one. They must not register further callbacks.
+    pub fn on_change<F>(&self, callback: F)
+    where
+        F: FnMut(&T, &ChangeSet) + Send + 'static,
+    {
+        self.shared
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(callback));
+    }
+
+    /// Registers `callback`, to be called with the diagnostics of each new version of the
+    /// configuration that fails to load. The current version stays the last valid one until
+    /// the configuration is fixed:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct MyConfig {}
+    /// # fn main() -> nickelodeon::Result<()> {
+    /// let config = nickelodeon::Loader::new("my_app")
+    ///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+    ///
+    /// config.on_failure(|failure| {
+    ///     eprint!("invalid configuration, keeping the previous one:\n{failure}");
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Callbacks run like the [`SharedConfig::on_change`] ones.
+    pub fn on_failure<F>(&self, callback: F)
+    where
+        F: FnMut(&Failure) + Send + 'static,
+    {
+        self.shared
+            .failure_callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(callback));
+    }
+
+    /// Returns a [`Subscription`] receiving the new versions of the configuration that change
+    /// some settings, for a subsystem to apply them at its own pace, from its own thread:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct MyConfig { workers: usize }
+    /// # fn resize(workers: usize) {}
+    /// # fn main() -> nickelodeon::Result<()> {
+    /// let config = nickelodeon::Loader::new("my_app")
+    ///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+    ///
+    /// let updates = config.subscribe();
+    /// std::thread::spawn(move || {
+    ///     for update in updates {
+    ///         resize(update.workers);
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Each subscriber receives the latest version it didn't receive yet, skipping the ones
+    /// replaced while it was busy, so slow subscribers don't pile versions up. The
+    /// subscription closes once every clone of this [`SharedConfig`] is dropped.
+    #[must_use]
+    pub fn subscribe(&self) -> Subscription<Arc<T>> {
+        let (publisher, subscription) = subscription();
+        self.shared
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(publisher);
+        subscription
+    }
+
+    /// Returns a [`tokio::sync::watch::Receiver`] of the new versions of the configuration
+    /// that change some settings, for async subsystems to wait for them:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct MyConfig { workers: usize }
+    /// # fn resize(workers: usize) {}
+    /// # async fn run() -> nickelodeon::Result<()> {
+    /// let config = nickelodeon::Loader::new("my_app")
+    ///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+    ///
+    /// let mut updates = config.subscribe_async();
+    /// while updates.changed().await.is_ok() {
+    ///     resize(updates.borrow_and_update().workers);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Like a [`Subscription`], the receiver only keeps the latest version. It closes once
+    /// every clone of this [`SharedConfig`] is dropped.
+    #[cfg(feature = "tokio")]
+    #[must_use]
+    pub fn subscribe_async(&self) -> tokio::sync::watch::Receiver<Snapshot<T>> {
+        self.shared.updates.subscribe()
+    }
+
+    /// Returns the most recent attempts of the watch to load the configuration, from the
+    /// oldest to the newest, including the failed ones. Only the last 32 are kept.
+    #[must_use]
+    pub fn history(&self) -> Vec<ReloadRecord> {
+        self.shared
+            .history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns a handle to replace the configuration, which doesn't keep it alive.
+    pub(crate) fn updater(&self) -> Updater<T> {
+        Updater(Arc::downgrade(&self.shared))
+    }
+}
+
+impl<T: Serialize> SharedConfig<T> {
+    /// Returns a [`Subscription`] receiving the section at `path` (a dotted path, like
+    /// `logging` or `server.tls`) of the new versions of the configuration, as an `S`, only
+    /// when some setting of that section changed. Subsystems can then subscribe to their own section
+    /// without being bothered by the changes of the others:
+    ///
+    /// ```no_run
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct LoggingConfig { level: String }
+    /// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+    /// # struct MyConfig { logging: LoggingConfig }
+    /// # fn main() -> nickelodeon::Result<()> {
+    /// let config = nickelodeon::Loader::new("my_app")
+    ///     .watch_shared::<MyConfig>(std::time::Duration::from_secs(1))?;
+    ///
+    /// let logging = config.subscribe_section::<LoggingConfig>("logging");
+    /// std::thread::spawn(move || {
+    ///     for update in logging {
+    ///         eprintln!("logging at the {} level", update.level);
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Versions whose section isn't a valid `S` are skipped. The subscription closes once
+    /// every clone of this [`SharedConfig`] is dropped.
+    #[must_use]
+    pub fn subscribe_section<S>(&self, path: &str) -> Subscription<S>
+    where
+        S: DeserializeOwned + Send + 'static,
+    {
+        let (publisher, subscription) = subscription();
+        let section = path.to_owned();
+        self.on_change(move |config, changes| {
+            if !changes.contains(&section) {
+                return;
+            }
+            let serialized = exposing(Exposure::Revealed, || serde_json::to_value(config).ok());
+            let value = serialized
+                .as_ref()
+                .and_then(|whole| nested(whole, &section))
+                .cloned()
+                .and_then(|part| serde_json::from_value(part).ok());
+            if let Some(update) = value {
+                let _unsubscribed = publisher.publish(update);
+            }
+        });
+        subscription
+    }
+}
+
+/// Returns the value at the dotted `path` of `value`.
+fn nested<'value>(value: &'value Value, path: &str) -> Option<&'value Value> {
+    path.split('.')
+        .try_fold(value, |parent, field| parent.get(field))
+}
+
+/// Replaces the configuration of a [`SharedConfig`] with the new versions of a watch.
+pub(crate) struct Updater<T>(Weak<Shared<T>>);
+
+impl<T> Updater<T> {
+    /// Adds `record` to the [`SharedConfig::history`]. Returns `false` if every
+    /// [`SharedConfig`] is gone.
+    pub(crate) fn remember(&self, record: ReloadRecord) -> bool {
+        let Some(shared) = self.0.upgrade() else {
+            return false;
+        };
+        remember(
+            &mut shared
+                .history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            record,
+        );
+        true
+    }
+
+    /// Remembers the `record` of an attempt, then stores the configuration of an
+    /// [`Event::Updated`], or reports an [`Event::Failed`] to the
+    /// [`SharedConfig::on_failure`] callbacks. Returns the generation of the current
+    /// configuration, or `None` if every [`SharedConfig`] is gone.
+    pub(crate) fn deliver(&self, event: Event<T>, record: ReloadRecord) -> Option<u64> {
+        if !self.remember(record) {
+            return None;
+        }
+        match event {
+            Event::Updated(update) => self.store(update.config, &update.changes),
+            Event::Failed(failure) => {
+                let shared = self.0.upgrade()?;
+                let mut callbacks = shared
+                    .failure_callbacks
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                for callback in callbacks.iter_mut() {
+                    callback(&failure);
+                }
+                drop(callbacks);
+                Some(shared.current.get().generation)
+            }
+        }
+    }
+
+    /// Replaces the configuration with `config`, then calls the [`SharedConfig::on_change`]
+    /// callbacks and notifies the [`SharedConfig::subscribe`]rs, if some settings changed.
+    /// Returns the generation of the current configuration, or `None` if every
+    /// [`SharedConfig`] is gone.
+    fn store(&self, config: T, changes: &ChangeSet) -> Option<u64> {
+        let shared = self.0.upgrade()?;
+        if changes.is_empty() {
+            return Some(shared.current.get().generation);
+        }
+        let new = shared.current.replace(Arc::new(config));
+        let generation = new.generation;
+        let mut callbacks = shared
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for callback in callbacks.iter_mut() {
+            callback(&new, changes);
+        }
+        drop(callbacks);
+        shared
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|subscriber| subscriber.publish(new.config()));
+        #[cfg(feature = "tokio")]
+        shared.updates.send_replace(new);
+        Some(generation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod shared_config {
+        use super::super::SharedConfig;
+        #[cfg(feature = "tokio")]
+        use crate::offload::tests::block_on;
+        use crate::Error;
+        use crate::Loader;
+        use std::fs;
+        use std::sync::mpsc;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        fn fixed() {
+            let config = SharedConfig::new(Server { port: 80 });
+            let clone = config.clone();
+            drop(config);
+
+            assert_eq!(clone.load().port, 80);
+            assert_eq!(clone.snapshot().port, 80);
+            assert_eq!(clone.snapshot().generation(), 0);
+        }
+
+        #[test]
+        fn watched() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            let initial = config.load();
+            let first_generation = config.generation();
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            let started = Instant::now();
+            while config.load().port != 8080 && started.elapsed() < Duration::from_secs(10) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            assert_eq!(initial.port, 80);
+            assert_eq!(first_generation, 0);
+            assert_eq!(config.load().port, 8080);
+            assert_eq!(config.snapshot().generation(), 1);
+            assert_eq!(config.generation(), 1);
+        }
+
+        #[test]
+        fn unchanged() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            let started = Instant::now();
+            while config.load().port != 8080 && started.elapsed() < Duration::from_secs(10) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let changed = config.load();
+            fs::write(&path, "{ port = 8080 } # touched").unwrap();
+            while config.history().len() < 3 && started.elapsed() < Duration::from_secs(10) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            assert_eq!(config.history().len(), 3);
+            assert_eq!(config.generation(), 1);
+            assert!(Arc::ptr_eq(&config.load(), &changed));
+        }
+
+        #[test]
+        fn invalid_at_first() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, r#"{ port = "80" }"#).unwrap();
+
+            let result = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path))
+                .diagnostics(std::io::sink())
+                .watch_shared::<Server>(Duration::from_millis(10));
+
+            assert!(matches!(result, Err(Error::RustDeserializationError(..))));
+        }
+
+        #[test]
+        fn sections_are_published_together() {
+            #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+            struct Logging {
+                level: String,
+            }
+
+            #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+            struct Sections {
+                logging: Logging,
+                server: Server,
+            }
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, r#"{ logging.level = "info", server.port = 80 }"#).unwrap();
+            let (sender, receiver) = mpsc::channel();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Sections>(Duration::from_millis(10))
+                .unwrap();
+            config.on_failure(move |_| {
+                let _ignored = sender.send(());
+            });
+            fs::write(
+                &path,
+                r#"{ logging.level = "debug", server.port = "8080" }"#,
+            )
+            .unwrap();
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+
+            assert_eq!(config.load().logging.level, "info");
+            assert_eq!(config.load().server.port, 80);
+        }
+
+        #[test]
+        fn watch_sections() {
+            #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+            struct Logging {
+                level: String,
+            }
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, r#"{ logging.level = "info", server.port = 80 }"#).unwrap();
+            let (sender, receiver) = mpsc::channel();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_sections::<(Logging, Server)>(
+                    ["logging", "server"],
+                    Duration::from_millis(10),
+                )
+                .unwrap();
+            let versions = config.subscribe();
+            config.on_failure(move |failure| {
+                let _ignored = sender.send(failure.diagnostics.len());
+            });
+            fs::write(
+                &path,
+                r#"{ logging.level = "debug", server.port = "8080" }"#,
+            )
+            .unwrap();
+            let failed = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            let kept = config.snapshot();
+            fs::write(&path, r#"{ logging.level = "debug", server.port = 8080 }"#).unwrap();
+            let fixed = versions.recv_timeout(Duration::from_secs(10)).unwrap();
+            let [_, rejected, _] = config.history().try_into().unwrap();
+
+            assert_eq!(failed, 1);
+            assert_eq!(kept.generation(), 0);
+            assert_eq!(kept.0.level, "info");
+            assert_eq!(kept.1, Server { port: 80 });
+            assert_eq!(fixed.0.level, "debug");
+            assert_eq!(fixed.1, Server { port: 8080 });
+            assert_eq!(config.generation(), 1);
+            assert!(!rejected.succeeded());
+        }
+
+        #[test]
+        fn subscribe_section() {
+            #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+            struct Sections {
+                logging: String,
+                server: Server,
+            }
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, r#"{ logging = "info", server.port = 80 }"#).unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Sections>(Duration::from_millis(10))
+                .unwrap();
+            let server = config.subscribe_section::<Server>("server");
+            let all = config.subscribe();
+            fs::write(&path, r#"{ logging = "debug", server.port = 80 }"#).unwrap();
+            let _logging_changed = all.recv_timeout(Duration::from_secs(10)).unwrap();
+            fs::write(&path, r#"{ logging = "debug", server.port = 8080 }"#).unwrap();
+            let changed = server.recv_timeout(Duration::from_secs(10)).unwrap();
+
+            assert_eq!(changed, Server { port: 8080 });
+            assert_eq!(server.try_recv(), None);
+        }
+
+        #[test]
+        fn history() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            let updates = config.subscribe();
+            fs::write(&path, r#"{ port = "8080" }"#).unwrap();
+            while config.history().len() < 2 {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            let _updated = updates.recv_timeout(Duration::from_secs(10)).unwrap();
+            let [loaded, failed, fixed] = config.history().try_into().unwrap();
+
+            assert!(loaded.succeeded());
+            assert!(!failed.succeeded());
+            assert!(fixed.succeeded());
+            assert_eq!(loaded.path, Some(path.clone()));
+            assert_eq!(failed.path, Some(path));
+            assert_ne!(loaded.hash, fixed.hash);
+            assert!(!failed.failure.unwrap().diagnostics.is_empty());
+        }
+
+        #[test]
+        fn on_change() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+            let (sender, receiver) = mpsc::channel();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            config.on_change(move |server, change_set| {
+                let _ignored = sender.send((server.port, change_set.to_string()));
+            });
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            let changed = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+
+            assert_eq!(changed, (8080, "~ port: 80 -> 8080\n".to_owned()));
+        }
+
+        #[test]
+        fn subscribe() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            let first = config.subscribe();
+            let second = config.subscribe();
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            let received = (
+                first.recv_timeout(Duration::from_secs(10)).unwrap().port,
+                second.recv_timeout(Duration::from_secs(10)).unwrap().port,
+            );
+            drop(config);
+
+            assert_eq!(received, (8080, 8080));
+            assert_eq!(first.recv(), None);
+        }
+
+        #[cfg(feature = "tokio")]
+        #[test]
+        fn subscribe_async() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            let mut updates = config.subscribe_async();
+            fs::write(&path, "{ port = 8080 }").unwrap();
+            block_on(updates.changed()).unwrap();
+            let received = updates.borrow_and_update().clone();
+            drop(config);
+
+            assert_eq!(received.port, 8080);
+            assert_eq!(received.generation(), 1);
+            assert!(block_on(updates.changed()).is_err());
+        }
+
+        #[test]
+        fn on_failure() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 80 }").unwrap();
+            let (sender, receiver) = mpsc::channel();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            config.on_failure(move |failure| {
+                let _ignored = sender.send(failure.diagnostics.len());
+            });
+            fs::write(&path, r#"{ port = "8080" }"#).unwrap();
+            let failed = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+
+            assert!(failed > 0);
+            assert_eq!(config.load().port, 80);
+        }
+
+        #[test]
+        fn load_in_background() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, "{ port = 8080 }").unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path.clone()))
+                .diagnostics(std::io::sink())
+                .load_in_background::<Server>();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while config.history().is_empty() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            assert_eq!(config.load().port, 8080);
+            assert_eq!(config.generation(), 1);
+            assert_eq!(config.history().first().unwrap().path, Some(path));
+        }
+
+        #[test]
+        fn load_in_background_failed() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            fs::write(&path, r#"{ port = "8080" }"#).unwrap();
+
+            let config = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path))
+                .diagnostics(std::io::sink())
+                .load_in_background::<Server>();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while config.history().is_empty() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            assert_eq!(*config.load(), Server::default());
+            assert!(!config.history().first().unwrap().succeeded());
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::is_yaml;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Returns the configuration file at `path` decrypted with the `sops` command line, when it
+/// was encrypted by SOPS.
+pub(crate) fn decrypted(path: &Path) -> io::Result<Option<String>> {
+    let source = fs::read_to_string(path)?;
+    if !is_encrypted(path, &source) {
+        return Ok(None);
+    }
+    decrypt(Command::new("sops"), path).map(Some)
+}
+
+/// Tells whether the file at `path` was encrypted by SOPS.
+pub(crate) fn is_encrypted_file(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|source| is_encrypted(path, &source))
+}
+
+/// Tells whether the `source` of the file at `path` was encrypted by SOPS: it then has a
+/// `sops` field with the metadata needed to decrypt it (the other formats, like Nickel,
+/// are encrypted as a JSON document).
+fn is_encrypted(path: &Path, source: &str) -> bool {
+    if is_yaml(path) {
+        return source.lines().any(|line| line.trim_end() == "sops:");
+    }
+    serde_json::from_str::<Value>(source)
+        .ok()
+        .and_then(|document| document.get("sops")?.get("mac").cloned())
+        .is_some()
+}
+
+/// Decrypts the file at `path` with `sops`, the command running the `sops` command line.
+fn decrypt(mut sops: Command, path: &Path) -> io::Result<String> {
+    let output = sops
+        .arg("--decrypt")
+        .arg(path)
+        .output()
+        .map_err(|err| io::Error::other(format!("can't run `sops`: {err}")))?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!(
+            "can't decrypt {} with `sops`: {}",
+            path.display(),
+            reason.trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod is_encrypted {
+        use super::super::is_encrypted;
+        use std::path::Path;
+
+        #[test]
+        fn encrypted() {
+            let json =
+                r#"{ "port": "ENC[AES256_GCM,data:OUI=,type:int]", "sops": { "mac": "x" } }"#;
+            let binary =
+                r#"{ "data": "ENC[AES256_GCM,data:e30=,type:str]", "sops": { "mac": "x" } }"#;
+            let yaml = "port: ENC[AES256_GCM,data:OUI=,type:int]\nsops:\n    mac: x\n";
+
+            assert!(is_encrypted(Path::new("config.json"), json));
+            assert!(is_encrypted(Path::new("config.ncl"), binary));
+            assert!(is_encrypted(Path::new("config.yaml"), yaml));
+        }
+
+        #[test]
+        fn plain() {
+            assert!(!is_encrypted(Path::new("config.ncl"), "{ sops = 1 }"));
+            assert!(!is_encrypted(Path::new("config.json"), r#"{ "sops": 1 }"#));
+            assert!(!is_encrypted(Path::new("config.yaml"), "sops: 1\n"));
+        }
+    }
+
+    #[cfg(all(test, unix))]
+    mod decrypt {
+        use super::super::decrypt;
+        use crate::fake_command::fake_command;
+        use std::path::Path;
+        use std::process::Command;
+
+        /// Returns a fake `sops` command line running `script`.
+        fn sops(script: &str) -> Command {
+            fake_command("sops", script)
+        }
+
+        #[test]
+        fn decrypted() {
+            let decrypted = decrypt(
+                sops(r#"test "$1" = --decrypt && echo "{ port = 80 }""#),
+                Path::new("config.ncl"),
+            );
+
+            assert_eq!(decrypted.unwrap(), "{ port = 80 }\n");
+        }
+
+        #[test]
+        fn failed() {
+            let result = decrypt(
+                sops("echo 'no key could decrypt the data' >&2; exit 128"),
+                Path::new("config.ncl"),
+            );
+
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "can't decrypt config.ncl with `sops`: no key could decrypt the data"
+            );
+        }
+    }
+}
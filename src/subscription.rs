@@ -0,0 +1,184 @@
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Receives the new versions of a configuration, as returned by
+/// [`crate::SharedConfig::subscribe`].
+///
+/// Only the latest version not received yet is kept: a subscriber slower than the reloads
+/// skips the versions replaced in the meantime, instead of piling them up, and always
+/// receives the current one next.
+#[derive(Debug)]
+pub struct Subscription<V> {
+    slot: Arc<Slot<V>>,
+}
+
+/// The latest version not received yet by a [`Subscription`], if any.
+#[derive(Debug)]
+struct Slot<V> {
+    state: Mutex<State<V>>,
+    published: Condvar,
+}
+
+#[derive(Debug)]
+struct State<V> {
+    latest: Option<V>,
+
+    /// Whether no version will be published anymore.
+    closed: bool,
+}
+
+/// Publishes the versions received by a [`Subscription`], which it doesn't keep alive. The
+/// subscription is closed once the publisher is dropped.
+pub(crate) struct Publisher<V>(Weak<Slot<V>>);
+
+/// Creates a [`Subscription`], with the [`Publisher`] of its versions.
+pub(crate) fn subscription<V>() -> (Publisher<V>, Subscription<V>) {
+    let slot = Arc::new(Slot {
+        state: Mutex::new(State {
+            latest: None,
+            closed: false,
+        }),
+        published: Condvar::new(),
+    });
+    (Publisher(Arc::downgrade(&slot)), Subscription { slot })
+}
+
+impl<V> Slot<V> {
+    fn lock(&self) -> MutexGuard<'_, State<V>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<V> Publisher<V> {
+    /// Publishes `version`, replacing the one not received yet, if any. Returns `false` if
+    /// the [`Subscription`] was dropped.
+    pub(crate) fn publish(&self, version: V) -> bool {
+        let Some(slot) = self.0.upgrade() else {
+            return false;
+        };
+        slot.lock().latest = Some(version);
+        slot.published.notify_all();
+        true
+    }
+}
+
+impl<V> Drop for Publisher<V> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0.upgrade() {
+            slot.lock().closed = true;
+            slot.published.notify_all();
+        }
+    }
+}
+
+impl<V> Subscription<V> {
+    /// Waits for the next version. Returns `None` once no version will be published
+    /// anymore, when every clone of the [`crate::SharedConfig`] is dropped.
+    #[must_use]
+    pub fn recv(&self) -> Option<V> {
+        let mut state = self.slot.lock();
+        while state.latest.is_none() && !state.closed {
+            state = self
+                .slot
+                .published
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        state.latest.take()
+    }
+
+    /// Waits at most `timeout` for the next version.
+    #[must_use]
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<V> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.slot.lock();
+        while state.latest.is_none() && !state.closed {
+            let left = deadline.map_or(timeout, |instant| {
+                instant.saturating_duration_since(Instant::now())
+            });
+            if left.is_zero() {
+                break;
+            }
+            state = self
+                .slot
+                .published
+                .wait_timeout(state, left)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        state.latest.take()
+    }
+
+    /// Returns the next version if there is one already, without waiting.
+    #[must_use]
+    pub fn try_recv(&self) -> Option<V> {
+        self.slot.lock().latest.take()
+    }
+}
+
+impl<V> Iterator for Subscription<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod subscription {
+        use super::super::subscription;
+        use std::time::Duration;
+
+        #[test]
+        fn latest_only() {
+            let (publisher, subscription) = subscription();
+
+            assert!(publisher.publish(1));
+            assert!(publisher.publish(2));
+
+            assert_eq!(subscription.try_recv(), Some(2));
+            assert_eq!(subscription.try_recv(), None);
+        }
+
+        #[test]
+        fn waits() {
+            let (publisher, subscription) = subscription();
+
+            let later = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                publisher.publish(1)
+            });
+
+            assert_eq!(subscription.recv(), Some(1));
+            assert!(later.join().unwrap());
+        }
+
+        #[test]
+        fn closed() {
+            let (publisher, subscription) = subscription::<u8>();
+
+            assert!(publisher.publish(1));
+            drop(publisher);
+
+            assert_eq!(subscription.recv(), Some(1));
+            assert_eq!(subscription.recv(), None);
+            assert_eq!(subscription.recv_timeout(Duration::from_mins(1)), None);
+        }
+
+        #[test]
+        fn unsubscribed() {
+            let (publisher, subscription) = subscription();
+            drop(subscription);
+
+            assert!(!publisher.publish(1));
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use nickel_lang_core::parser::lexer::KEYWORDS;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// The comments to write on their own lines before each field, by path.
+pub(crate) type Comments = HashMap<Vec<String>, Vec<String>>;
+
+/// The annotations to write between the name of each field and its value (e.g.
+/// ` | Number | doc "The port."`), by path.
+pub(crate) type Annotations = HashMap<Vec<String>, String>;
+
+/// A value ready to be written as Nickel source, keeping the order of its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Nickel {
+    /// A value written as is: a number, a boolean, `null`, a string or an enum tag.
+    Literal(String),
+
+    /// An array, written on a single line unless it holds records or arrays.
+    Array(Vec<Self>),
+
+    /// A record, with the names of its fields.
+    Record(Vec<(String, Self)>),
+}
+
+impl Nickel {
+    /// Writes this value as the source of a Nickel file, with the `comments` of its fields.
+    pub(crate) fn to_source(&self, comments: &Comments) -> String {
+        self.to_annotated_source(comments, &Annotations::new())
+    }
+
+    /// Writes this value as the source of a Nickel file, with the `comments` and the
+    /// `annotations` of its fields.
+    pub(crate) fn to_annotated_source(
+        &self,
+        comments: &Comments,
+        annotations: &Annotations,
+    ) -> String {
+        let mut source = String::new();
+        self.write(&mut source, &mut Vec::new(), comments, annotations, 0);
+        source.push('\n');
+        source
+    }
+
+    /// Writes this value, the one at `path`, indented `depth` levels, to `source`.
+    fn write(
+        &self,
+        source: &mut String,
+        path: &mut Vec<String>,
+        comments: &Comments,
+        annotations: &Annotations,
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth.saturating_add(1));
+        match self {
+            Self::Literal(literal) => source.push_str(literal),
+            Self::Record(fields) if fields.is_empty() => source.push_str("{}"),
+            Self::Record(fields) => {
+                source.push_str("{\n");
+                for (name, value) in fields {
+                    path.push(name.clone());
+                    for comment in comments.get(path.as_slice()).into_iter().flatten() {
+                        let _infallible = writeln!(source, "{indent}{comment}");
+                    }
+                    let annotation = annotations.get(path.as_slice()).map_or("", String::as_str);
+                    let _infallible = write!(source, "{indent}{}{annotation} = ", field_name(name));
+                    value.write(source, path, comments, annotations, depth.saturating_add(1));
+                    source.push_str(",\n");
+                    path.pop();
+                }
+                source.push_str(&"  ".repeat(depth));
+                source.push('}');
+            }
+            Self::Array(elements) if elements.iter().any(Self::is_nested) => {
+                source.push_str("[\n");
+                for element in elements {
+                    source.push_str(&indent);
+                    element.write(source, path, comments, annotations, depth.saturating_add(1));
+                    source.push_str(",\n");
+                }
+                source.push_str(&"  ".repeat(depth));
+                source.push(']');
+            }
+            Self::Array(elements) => {
+                source.push('[');
+                for (position, element) in elements.iter().enumerate() {
+                    if position > 0 {
+                        source.push_str(", ");
+                    }
+                    element.write(source, path, comments, annotations, depth);
+                }
+                source.push(']');
+            }
+        }
+    }
+
+    /// Tells whether this is a non-empty record or array, written on several lines.
+    const fn is_nested(&self) -> bool {
+        match self {
+            Self::Literal(_) => false,
+            Self::Array(elements) => !elements.is_empty(),
+            Self::Record(fields) => !fields.is_empty(),
+        }
+    }
+}
+
+/// Writes `name` as the name of a Nickel field: as is when it's a valid identifier, quoted
+/// otherwise.
+pub(crate) fn field_name(name: &str) -> String {
+    if is_identifier(name) && !KEYWORDS.contains(&name) {
+        name.to_owned()
+    } else {
+        string(name)
+    }
+}
+
+/// Tells whether `name` is a valid Nickel identifier, i.e. matches
+/// `_?[a-zA-Z][_a-zA-Z0-9-']*`.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.strip_prefix('_').unwrap_or(name).chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic())
+        && chars.all(|next| next.is_ascii_alphanumeric() || matches!(next, '_' | '-' | '\''))
+}
+
+/// Writes `name` as a Nickel enum tag, like `'Debug`.
+pub(crate) fn enum_tag(name: &str) -> String {
+    if is_identifier(name) {
+        format!("'{name}")
+    } else {
+        format!("'{}", string(name))
+    }
+}
+
+/// Writes `text` as a double quoted Nickel string, escaping what Nickel would otherwise
+/// interpret, including the `%{` of interpolations.
+pub(crate) fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len().saturating_add(2));
+    quoted.push('"');
+    let mut chars = text.chars().peekable();
+    while let Some(next) = chars.next() {
+        match next {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '%' if chars.peek() == Some(&'{') => quoted.push_str("\\%"),
+            control if control.is_ascii_control() => {
+                let _infallible = write!(quoted, "\\x{:02x}", u32::from(control));
+            }
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod field_name {
+        use super::super::field_name;
+
+        #[test]
+        fn identifiers() {
+            assert_eq!(field_name("port"), "port");
+            assert_eq!(field_name("_max-size'"), "_max-size'");
+        }
+
+        #[test]
+        fn quoted() {
+            assert_eq!(field_name("1st"), r#""1st""#);
+            assert_eq!(field_name("a.b"), r#""a.b""#);
+            assert_eq!(field_name("let"), r#""let""#);
+            assert_eq!(field_name(""), r#""""#);
+        }
+    }
+
+    #[cfg(test)]
+    mod nickel {
+        use super::super::Comments;
+        use super::super::Nickel;
+
+        fn literal(literal: &str) -> Nickel {
+            Nickel::Literal(literal.to_owned())
+        }
+
+        #[test]
+        fn indented() {
+            let value = Nickel::Record(vec![
+                ("empty".to_owned(), Nickel::Record(Vec::new())),
+                (
+                    "ports".to_owned(),
+                    Nickel::Array(vec![literal("80"), literal("443")]),
+                ),
+                (
+                    "tiers".to_owned(),
+                    Nickel::Array(vec![Nickel::Record(vec![(
+                        "max size".to_owned(),
+                        literal("1"),
+                    )])]),
+                ),
+            ]);
+            let mut comments = Comments::new();
+            comments.insert(vec!["ports".to_owned()], vec!["# Open ports.".to_owned()]);
+
+            let source = value.to_source(&comments);
+
+            assert_eq!(
+                source,
+                r#"{
+  empty = {},
+  # Open ports.
+  ports = [80, 443],
+  tiers = [
+    {
+      "max size" = 1,
+    },
+  ],
+}
+"#
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod enum_tag {
+        use super::super::enum_tag;
+
+        #[test]
+        fn quoted() {
+            assert_eq!(enum_tag("Debug"), "'Debug");
+            assert_eq!(enum_tag("not found"), r#"'"not found""#);
+        }
+    }
+
+    #[cfg(test)]
+    mod string {
+        use super::super::string;
+
+        #[test]
+        fn escaped() {
+            assert_eq!(
+                string("say \"%{hi}\"\n\\ 100% \u{1b}"),
+                r#""say \"\%{hi}\"\n\\ 100% \x1b""#
+            );
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+/// A stage of the load pipeline, reported as a `tracing` span when the `tracing` feature is
+/// enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// Looking for the configuration file in the standard locations.
+    Discovery,
+
+    /// Reading the configuration file.
+    Read,
+
+    /// Evaluating the Nickel program.
+    Evaluation,
+
+    /// Converting the evaluated configuration into the Rust type.
+    Deserialization,
+}
+
+/// Runs `run` inside a span named after `stage`, recording the `path` of the configuration
+/// file (when known) and how long the stage took, in milliseconds.
+///
+/// Without the `tracing` feature, just runs `run`.
+#[cfg(feature = "tracing")]
+pub(crate) fn traced<T, R>(stage: Stage, path: Option<&Path>, run: R) -> T
+where
+    R: FnOnce() -> T,
+{
+    use tracing::field::Empty;
+
+    let shown = path.map(|value| value.display().to_string());
+    let span = match stage {
+        Stage::Discovery => {
+            tracing::debug_span!("discovery", path = shown.as_deref(), duration_ms = Empty)
+        }
+        Stage::Read => tracing::debug_span!("read", path = shown.as_deref(), duration_ms = Empty),
+        Stage::Evaluation => {
+            tracing::debug_span!("evaluation", path = shown.as_deref(), duration_ms = Empty)
+        }
+        Stage::Deserialization => {
+            tracing::debug_span!(
+                "deserialization",
+                path = shown.as_deref(),
+                duration_ms = Empty
+            )
+        }
+    };
+
+    let _entered = span.enter();
+    let started = std::time::Instant::now();
+    let result = run();
+    let duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+    span.record("duration_ms", duration_ms);
+    result
+}
+
+/// Runs `run` inside a span named after `stage`, recording the `path` of the configuration
+/// file (when known) and how long the stage took, in milliseconds.
+///
+/// Without the `tracing` feature, just runs `run`.
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn traced<T, R>(_stage: Stage, _path: Option<&Path>, run: R) -> T
+where
+    R: FnOnce() -> T,
+{
+    run()
+}
@@ -0,0 +1,301 @@
+use serde::ser::SerializeMap as _;
+use serde::Serialize;
+use serde::Serializer;
+use serde_json::Number;
+use std::collections::BTreeMap;
+use std::ops::Index;
+
+/// An evaluated configuration, or one of its values, to inspect configurations without
+/// depending on the types of `nickel-lang-core`. Returned by
+/// [`crate::ProgramHandle::value`].
+///
+/// Values can be indexed, missing fields and elements giving [`Value::Null`], or looked up
+/// by path:
+///
+/// ```no_run
+/// # fn main() -> nickelodeon::Result<()> {
+/// if let Some(mut program) = nickelodeon::Loader::new("my-app").program()? {
+///     let config = program.value()?;
+///     let port = config["server"]["port"].as_u64();
+///     let first_host = config.lookup("server.hosts[0]").and_then(|host| host.as_str());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enum tags (e.g. `'Debug`) are strings, as they are exported by Nickel.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Value {
+    /// `null`.
+    #[default]
+    Null,
+
+    /// `true` or `false`.
+    Bool(bool),
+
+    /// A number.
+    Number(Number),
+
+    /// A string, or an enum tag.
+    String(String),
+
+    /// An array.
+    Array(Vec<Self>),
+
+    /// A record, by the names of its fields.
+    Record(BTreeMap<String, Self>),
+}
+
+/// The value of the missing fields and elements.
+static NULL: Value = Value::Null;
+
+impl Value {
+    /// Returns the field `name` of this record, or `None` if it has no such field or isn't
+    /// a record.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Self> {
+        match self {
+            Self::Record(fields) => fields.get(name),
+            Self::Null | Self::Bool(_) | Self::Number(_) | Self::String(_) | Self::Array(_) => None,
+        }
+    }
+
+    /// Returns the element at `index` of this array, or `None` if it has no such element or
+    /// isn't an array.
+    #[must_use]
+    pub fn get_index(&self, index: usize) -> Option<&Self> {
+        match self {
+            Self::Array(elements) => elements.get(index),
+            Self::Null | Self::Bool(_) | Self::Number(_) | Self::String(_) | Self::Record(_) => {
+                None
+            }
+        }
+    }
+
+    /// Returns the value at `path`, a dotted path with the indexes of array elements in
+    /// brackets (e.g. `server.hosts[0]`), as in [`crate::FieldError::path`]. The empty path
+    /// is this value.
+    #[must_use]
+    pub fn lookup(&self, path: &str) -> Option<&Self> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.').try_fold(self, |value, segment| {
+            let mut parts = segment.split('[');
+            let name = parts.next()?;
+            let field = if name.is_empty() {
+                value
+            } else {
+                value.get(name)?
+            };
+            parts.try_fold(field, |array, index| {
+                array.get_index(index.strip_suffix(']')?.parse().ok()?)
+            })
+        })
+    }
+
+    /// Tells whether this is [`Value::Null`].
+    #[must_use]
+    pub const fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// Returns this boolean, or `None` if it isn't one.
+    #[must_use]
+    pub const fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            Self::Null | Self::Number(_) | Self::String(_) | Self::Array(_) | Self::Record(_) => {
+                None
+            }
+        }
+    }
+
+    /// Returns this number as an `i64`, or `None` if it isn't an integer fitting in one.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(Number::as_i64)
+    }
+
+    /// Returns this number as a `u64`, or `None` if it isn't a positive integer fitting in
+    /// one.
+    #[must_use]
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(Number::as_u64)
+    }
+
+    /// Returns this number as an `f64`, or `None` if it isn't a number.
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().and_then(Number::as_f64)
+    }
+
+    /// Returns this string, or `None` if it isn't one.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            Self::Null | Self::Bool(_) | Self::Number(_) | Self::Array(_) | Self::Record(_) => None,
+        }
+    }
+
+    /// Returns the elements of this array, or `None` if it isn't one.
+    #[must_use]
+    pub fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Self::Array(elements) => Some(elements),
+            Self::Null | Self::Bool(_) | Self::Number(_) | Self::String(_) | Self::Record(_) => {
+                None
+            }
+        }
+    }
+
+    /// Returns the fields of this record, or `None` if it isn't one.
+    #[must_use]
+    pub const fn as_record(&self) -> Option<&BTreeMap<String, Self>> {
+        match self {
+            Self::Record(fields) => Some(fields),
+            Self::Null | Self::Bool(_) | Self::Number(_) | Self::String(_) | Self::Array(_) => None,
+        }
+    }
+
+    /// Returns this number, or `None` if it isn't one.
+    const fn as_number(&self) -> Option<&Number> {
+        match self {
+            Self::Number(number) => Some(number),
+            Self::Null | Self::Bool(_) | Self::String(_) | Self::Array(_) | Self::Record(_) => None,
+        }
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(value) => Self::Bool(value),
+            serde_json::Value::Number(number) => Self::Number(number),
+            serde_json::Value::String(text) => Self::String(text),
+            serde_json::Value::Array(elements) => {
+                Self::Array(elements.into_iter().map(Self::from).collect())
+            }
+            serde_json::Value::Object(fields) => Self::Record(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (name, Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Index<&str> for Value {
+    type Output = Self;
+
+    fn index(&self, index: &str) -> &Self {
+        self.get(index).unwrap_or(&NULL)
+    }
+}
+
+impl Index<usize> for Value {
+    type Output = Self;
+
+    fn index(&self, index: usize) -> &Self {
+        self.get_index(index).unwrap_or(&NULL)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Number(number) => number.serialize(serializer),
+            Self::String(text) => serializer.serialize_str(text),
+            Self::Array(elements) => serializer.collect_seq(elements),
+            Self::Record(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, value) in fields {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod value {
+        use super::super::Value;
+        use crate::Loader;
+        use serde_json::json;
+
+        fn config() -> Value {
+            Value::from(json!({
+                "name": "nick",
+                "server": { "port": 8080, "hosts": ["a.example.com", "b.example.com"] },
+                "ratio": 0.5,
+            }))
+        }
+
+        #[test]
+        #[allow(clippy::indexing_slicing)]
+        fn indexing() {
+            let value = config();
+
+            assert_eq!(value["name"].as_str(), Some("nick"));
+            assert_eq!(value["server"]["port"].as_u64(), Some(8080));
+            assert_eq!(value["server"]["hosts"][1].as_str(), Some("b.example.com"));
+            assert_eq!(value["ratio"].as_f64(), Some(0.5));
+            assert!(value["missing"][3].is_null());
+        }
+
+        #[test]
+        fn lookup() {
+            let value = config();
+
+            assert_eq!(
+                value.lookup("server.hosts[0]").and_then(Value::as_str),
+                Some("a.example.com")
+            );
+            assert_eq!(value.lookup(""), Some(&value));
+            assert_eq!(value.lookup("server.hosts[2]"), None);
+            assert_eq!(value.lookup("name.first"), None);
+        }
+
+        #[test]
+        fn serialize() {
+            assert_eq!(
+                serde_json::to_value(config()).unwrap(),
+                json!({
+                    "name": "nick",
+                    "server": { "port": 8080, "hosts": ["a.example.com", "b.example.com"] },
+                    "ratio": 0.5,
+                })
+            );
+        }
+
+        #[test]
+        fn from_a_program() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.ncl");
+            std::fs::write(&path, "{ level = 'Debug, ports = [80, 443] }").unwrap();
+
+            let value = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(path))
+                .diagnostics(std::io::sink())
+                .program()
+                .unwrap()
+                .unwrap()
+                .value()
+                .unwrap();
+
+            assert_eq!(
+                value,
+                Value::from(json!({ "level": "Debug", "ports": [80, 443] }))
+            );
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use serde_json::Value;
+use std::io::Read;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long reading a secret can take, at most, before `vault` is killed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running `vault` is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Resolves a `vault://` reference, `<path>#<field>`, into the value of the `field` of the
+/// secret at `path`, read with the `vault` command line.
+pub(crate) fn resolve(reference: &str) -> Result<String, String> {
+    resolve_with(Command::new("vault"), reference, TIMEOUT)
+}
+
+/// Same as [`resolve`], reading the secret with `vault`, the command running the `vault`
+/// command line, and giving up after `timeout`.
+fn resolve_with(mut vault: Command, reference: &str, timeout: Duration) -> Result<String, String> {
+    let (path, field) = reference
+        .split_once('#')
+        .filter(|(path, field)| !path.is_empty() && !field.is_empty())
+        .ok_or_else(|| "expected `vault://<path>#<field>`".to_owned())?;
+    // Paths never start with a dash, which would be taken for an option by older versions.
+    if path.starts_with('-') {
+        return Err(format!("`{path}` isn't a valid path"));
+    }
+    vault.args(["read", "-format=json", "--", path]);
+    let output = output_within(vault, timeout)?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+    let response: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|err| format!("unexpected answer from `vault`: {err}"))?;
+
+    let data = response.get("data").unwrap_or(&Value::Null);
+    // Version 2 of the key/value engine nests the secret, next to its metadata.
+    let secret = match (data.get("data"), data.get("metadata")) {
+        (Some(nested), Some(_metadata)) => nested,
+        _ => data,
+    };
+    match secret.get(field) {
+        Some(Value::String(text)) => Ok(text.clone()),
+        Some(other) => Ok(other.to_string()),
+        None => Err(format!("the secret at `{path}` has no field `{field}`")),
+    }
+}
+
+/// Runs `vault` to completion, killing it if it's still running after `timeout`.
+fn output_within(mut vault: Command, timeout: Duration) -> Result<Output, String> {
+    let mut child = vault
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("can't run `vault`: {err}"))?;
+    // Both pipes are drained while waiting, so that `vault` never blocks writing to them.
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() < timeout => thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                let _ignored = child.kill();
+                let _reaped = child.wait();
+                return Err(format!("no answer within {timeout:?}"));
+            }
+            Err(err) => return Err(format!("can't run `vault`: {err}")),
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.map(collect).unwrap_or_default(),
+        stderr: stderr.map(collect).unwrap_or_default(),
+    })
+}
+
+/// Reads everything written to `pipe` on another thread.
+fn drain<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut read = Vec::new();
+        let _ignored = pipe.read_to_end(&mut read);
+        read
+    })
+}
+
+/// Returns what was read by a [`drain`]ing thread.
+fn collect(reader: thread::JoinHandle<Vec<u8>>) -> Vec<u8> {
+    reader.join().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(test, unix))]
+    mod resolve_with {
+        use super::super::resolve_with;
+        use super::super::TIMEOUT;
+        use crate::fake_command::fake_command;
+        use std::process::Command;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        /// Returns a fake `vault` command line writing `answer`, to `stdout` when it succeeds
+        /// and to `stderr` otherwise, and exiting with `status`.
+        fn vault(answer: &str, status: u8) -> Command {
+            let redirect = if status == 0 { "" } else { " >&2" };
+            fake_command(
+                "vault",
+                &format!("cat{redirect} <<'EOF'\n{answer}\nEOF\nexit {status}"),
+            )
+        }
+
+        #[test]
+        fn key_value_version_2() {
+            let answer = r#"{ "data": { "data": { "password": "hunter2" }, "metadata": {} } }"#;
+
+            let value = resolve_with(vault(answer, 0), "secret/data/app#password", TIMEOUT);
+
+            assert_eq!(value, Ok("hunter2".to_owned()));
+        }
+
+        #[test]
+        fn key_value_version_1() {
+            let answer = r#"{ "data": { "port": 5432 } }"#;
+
+            assert_eq!(
+                resolve_with(vault(answer, 0), "secret/app#port", TIMEOUT),
+                Ok("5432".to_owned())
+            );
+            assert_eq!(
+                resolve_with(vault(answer, 0), "secret/app#user", TIMEOUT),
+                Err("the secret at `secret/app` has no field `user`".to_owned())
+            );
+        }
+
+        #[test]
+        fn failures() {
+            assert_eq!(
+                resolve_with(vault("", 0), "secret/app", TIMEOUT),
+                Err("expected `vault://<path>#<field>`".to_owned())
+            );
+            assert_eq!(
+                resolve_with(
+                    vault("permission denied", 2),
+                    "secret/app#password",
+                    TIMEOUT
+                ),
+                Err("permission denied".to_owned())
+            );
+        }
+
+        #[test]
+        fn options_not_injected() {
+            let echo = fake_command("vault", r#"printf '{ "data": { "args": "%s" } }' "$*""#);
+
+            assert_eq!(
+                resolve_with(echo, "secret/app#args", TIMEOUT),
+                Ok("read -format=json -- secret/app".to_owned())
+            );
+            assert_eq!(
+                resolve_with(vault("", 0), "-address=https://attacker#x", TIMEOUT),
+                Err("`-address=https://attacker` isn't a valid path".to_owned())
+            );
+        }
+
+        #[test]
+        fn timeout() {
+            let hung = fake_command("vault", "sleep 10");
+            let timeout = Duration::from_millis(200);
+            let start = Instant::now();
+
+            let value = resolve_with(hung, "secret/app#password", timeout);
+
+            assert_eq!(value, Err("no answer within 200ms".to_owned()));
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+    }
+}
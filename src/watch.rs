@@ -0,0 +1,895 @@
+use crate::changes::serialized;
+use crate::disk_cache::hash;
+use crate::first_existing_config;
+use crate::hangup;
+use crate::render::render;
+use crate::CancellationToken;
+use crate::ChangeSet;
+use crate::Diagnostic;
+use crate::Loader;
+use crate::ReloadCounters;
+use crate::ReloadMetrics;
+use crate::ReloadRecord;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher as _;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+#[cfg(feature = "stream")]
+use std::sync::Arc;
+#[cfg(feature = "stream")]
+use std::sync::Mutex;
+#[cfg(feature = "stream")]
+use std::sync::PoisonError;
+#[cfg(feature = "stream")]
+use std::task::Waker;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Delivers the configuration of a long-running application each time it changes, as
+/// returned by [`crate::Loader::watch`].
+///
+/// The first [`Event`] is about the configuration as loaded when the watch starts. A new one
+/// follows every time the configuration file, or any file it imports, changes (or the
+/// process receives a `SIGHUP`, see [`crate::Loader::reload_on_hangup`]): an
+/// [`Event::Updated`] if the configuration loads successfully, or an [`Event::Failed`] if it
+/// doesn't, in which case the application should keep running with the last valid one. The
+/// watch goes on either way, so fixing the configuration updates it again.
+///
+/// An application started without a configuration file, and so with its default
+/// configuration, switches to the configuration file as soon as one is created in any of
+/// the standard locations, and back to the default configuration if it's removed. The same
+/// goes for a [`crate::Loader::config_path_from_flag`] created after the watch starts.
+///
+/// The files are watched from a background thread, which stops when the watcher is dropped.
+/// Changes are notified by the operating system, or noticed by checking the files
+/// regularly where notifications aren't available.
+///
+/// With the `stream` feature, a watcher is also a [`futures_core::Stream`] of the same
+/// events, for async applications.
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig {}
+/// # fn apply(config: MyConfig) {}
+/// for event in nickelodeon::watch_configuration::<MyConfig>("my_app") {
+///     match event {
+///         nickelodeon::Event::Updated(update) => {
+///             eprint!("configuration changed:\n{}", update.changes);
+///             apply(update.config);
+///         }
+///         nickelodeon::Event::Failed(failure) => {
+///             eprint!("invalid configuration, keeping the previous one:\n{failure}");
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Watcher<T> {
+    events: Receiver<Event<T>>,
+    stop: CancellationToken,
+    #[cfg(feature = "stream")]
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+/// Something that happened to a watched configuration, as delivered by a [`Watcher`].
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<T> {
+    /// The configuration was loaded.
+    Updated(Update<T>),
+
+    /// The configuration failed to load.
+    Failed(Failure),
+}
+
+/// A new version of a watched configuration.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update<T> {
+    /// The new configuration.
+    pub config: T,
+
+    /// What changed since the previous version. Empty for the first update.
+    pub changes: ChangeSet,
+}
+
+/// Why a watched configuration failed to load.
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    /// The problems found, as [`crate::Error::diagnostics`] lists them.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for Failure {
+    /// Renders the diagnostics like [`crate::Error::render_colored`] does, without colors.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render(&self.diagnostics, false))
+    }
+}
+
+impl<T> Watcher<T> {
+    /// Waits for the next event. Returns `None` once the watch has stopped, which only
+    /// happens if its thread panicked.
+    #[must_use]
+    pub fn recv(&self) -> Option<Event<T>> {
+        self.events.recv().ok()
+    }
+
+    /// Waits at most `timeout` for the next event.
+    #[must_use]
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event<T>> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the next event if there is one already, without waiting. Applications
+    /// with a main loop can call it on each iteration.
+    #[must_use]
+    pub fn try_recv(&self) -> Option<Event<T>> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for Watcher<T> {
+    type Item = Event<T>;
+
+    fn next(&mut self) -> Option<Event<T>> {
+        self.recv()
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<T> futures_core::Stream for Watcher<T> {
+    type Item = Event<T>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Event<T>>> {
+        use std::sync::mpsc::TryRecvError;
+        use std::task::Poll;
+
+        let mut waker = self.waker.lock().unwrap_or_else(PoisonError::into_inner);
+        // Checked with the waker locked, so an event sent in between wakes the new one.
+        let polled = match self.events.try_recv() {
+            Ok(event) => Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        };
+        drop(waker);
+        polled
+    }
+}
+
+impl<T> Drop for Watcher<T> {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+/// What is known of a watched file, to tell when it changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Fingerprint {
+    /// The file doesn't exist, or can't be read.
+    Missing,
+
+    /// The modification time and size of the file.
+    Metadata(SystemTime, u64),
+
+    /// The hash of the contents of the file.
+    Contents(String),
+}
+
+/// How a watch notices that the configuration changed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Polling {
+    /// How often the files are checked, without notifications, and how often the watch
+    /// checks whether it was stopped or hung up on.
+    interval: Duration,
+
+    /// How long the files must stay unchanged before reloading the configuration.
+    debounce: Duration,
+
+    /// Whether `SIGHUP`s reload the configuration right away.
+    on_hangup: bool,
+
+    /// Whether the contents of the files are compared, instead of their metadata.
+    by_contents: bool,
+}
+
+impl Polling {
+    pub(crate) const fn new(
+        interval: Duration,
+        debounce: Duration,
+        on_hangup: bool,
+        by_contents: bool,
+    ) -> Self {
+        Self {
+            interval,
+            debounce,
+            on_hangup,
+            by_contents,
+        }
+    }
+
+    /// Returns the fingerprint of each of the `files`.
+    fn fingerprint(&self, files: &[PathBuf]) -> Vec<Fingerprint> {
+        files
+            .iter()
+            .map(|file| {
+                let fingerprint = if self.by_contents {
+                    std::fs::read(file)
+                        .ok()
+                        .map(|contents| Fingerprint::Contents(hash(&contents)))
+                } else {
+                    std::fs::metadata(file).ok().and_then(|metadata| {
+                        Some(Fingerprint::Metadata(
+                            metadata.modified().ok()?,
+                            metadata.len(),
+                        ))
+                    })
+                };
+                fingerprint.unwrap_or(Fingerprint::Missing)
+            })
+            .collect()
+    }
+
+    /// Starts receiving the notifications of the operating system about changes, unless
+    /// they aren't available, or can't be trusted: they don't work on network file
+    /// systems, which comparing the contents is meant for.
+    fn notifications(&self) -> Option<Notifications> {
+        if self.by_contents {
+            return None;
+        }
+        Notifications::start()
+    }
+
+    /// Waits for the `files` to stay unchanged for the debounce window, or for the watch to
+    /// stop.
+    fn settle(&self, files: &[PathBuf], watching: &CancellationToken) {
+        let mut last = self.fingerprint(files);
+        while !watching.is_cancelled() {
+            thread::sleep(self.debounce);
+            let current = self.fingerprint(files);
+            if current == last {
+                return;
+            }
+            last = current;
+        }
+    }
+}
+
+/// The notifications of the operating system about the directories holding the watched
+/// files, telling when the files may have changed.
+struct Notifications {
+    watcher: RecommendedWatcher,
+    received: Receiver<notify::Result<notify::Event>>,
+    dirs: BTreeSet<PathBuf>,
+
+    /// Whether every watched file is covered, or some must still be checked regularly.
+    complete: bool,
+}
+
+impl Notifications {
+    fn start() -> Option<Self> {
+        let (sender, received) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender).ok()?;
+        Some(Self {
+            watcher,
+            received,
+            dirs: BTreeSet::new(),
+            complete: false,
+        })
+    }
+
+    /// Watches the directories holding the `files` (or their closest existing ancestor, so
+    /// creating them is noticed too), and only those. Directories are watched rather than
+    /// the files themselves, as editors and Kubernetes replace files instead of writing
+    /// them.
+    ///
+    /// Tells whether new directories are watched, in which case the files may have changed
+    /// before they were.
+    fn follow(&mut self, files: &[PathBuf]) -> bool {
+        let found: Vec<Option<PathBuf>> = files.iter().map(|file| existing_dir(file)).collect();
+        self.complete = found.iter().all(Option::is_some);
+        let wanted: BTreeSet<PathBuf> = found.into_iter().flatten().collect();
+
+        for dir in self.dirs.difference(&wanted) {
+            let _ignored = self.watcher.unwatch(dir);
+        }
+        let new: Vec<PathBuf> = wanted.difference(&self.dirs).cloned().collect();
+        self.dirs = wanted;
+        let mut added = false;
+        for dir in new {
+            if self
+                .watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .is_ok()
+            {
+                added = true;
+            } else {
+                // Tried again on the next change.
+                self.dirs.remove(&dir);
+                self.complete = false;
+            }
+        }
+        added
+    }
+
+    /// Waits at most `timeout` for notifications, telling whether the files may have
+    /// changed (always, when some of them aren't covered by notifications). The
+    /// notifications already received are all taken.
+    fn wait(&self, timeout: Duration) -> bool {
+        let Ok(first) = self.received.recv_timeout(timeout) else {
+            return !self.complete;
+        };
+        // Reading the files, like loading the configuration does, is no change.
+        let changed = std::iter::once(first).chain(self.received.try_iter()).fold(
+            false,
+            |changed, notification| {
+                changed | !notification.is_ok_and(|change| change.kind.is_access())
+            },
+        );
+        changed || !self.complete
+    }
+}
+
+/// Returns the directory holding `file`, or its closest existing ancestor.
+fn existing_dir(file: &Path) -> Option<PathBuf> {
+    file.ancestors()
+        .skip(1)
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .find(|dir| dir.is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Starts watching the configuration loaded by `loader`, delivering its events to a
+/// [`Watcher`].
+pub(crate) fn watch<T>(loader: Loader, polling: Polling) -> Watcher<T>
+where
+    T: DeserializeOwned + Serialize + Default + Send + 'static,
+{
+    let (sender, events) = mpsc::channel();
+    let stop = CancellationToken::default();
+    #[cfg(feature = "stream")]
+    let waker = Arc::new(Mutex::new(None::<Waker>));
+    #[cfg(feature = "stream")]
+    let waiting = Arc::clone(&waker);
+    let mut generation = 0_u64;
+    spawn(loader, polling, stop.clone(), None, move |event, _| {
+        if matches!(event, Event::Updated(_)) {
+            generation = generation.saturating_add(1);
+        }
+        let sent = sender.send(event).is_ok();
+        #[cfg(feature = "stream")]
+        wake(&waiting);
+        sent.then_some(generation)
+    });
+    Watcher {
+        events,
+        stop,
+        #[cfg(feature = "stream")]
+        waker,
+    }
+}
+
+/// Wakes the task waiting for the next event of a [`Watcher`], if any.
+#[cfg(feature = "stream")]
+fn wake(waiting: &Mutex<Option<Waker>>) {
+    let task = waiting
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(waker) = task {
+        waker.wake();
+    }
+}
+
+/// Starts watching the configuration loaded by `loader` from a background thread, until
+/// `stop` is cancelled or `deliver` returns `false`.
+///
+/// The configuration is loaded right away, and then each time its files stay unchanged for
+/// the debounce window after changing (as notified, or checked every interval), or on
+/// `SIGHUP`s if enabled. Each load is reported to
+/// `deliver`, the configurations loaded successfully with their changes since the previous
+/// one (or since the `initial` one, serialized, if any), together with a record of the
+/// attempt. A first load that changed nothing since the `initial` one isn't reported.
+/// `deliver` returns the generation of the current configuration once the event is
+/// delivered, which the [`ReloadCounters`] report, or `None` to stop.
+pub(crate) fn spawn<T, F>(
+    loader: Loader,
+    polling: Polling,
+    stop: CancellationToken,
+    initial: Option<Value>,
+    mut deliver: F,
+) where
+    T: DeserializeOwned + Serialize + Default + 'static,
+    F: FnMut(Event<T>, ReloadRecord) -> Option<u64> + Send + 'static,
+{
+    let watching = stop;
+    thread::spawn(move || {
+        let mut files = loader.locations();
+        let mut seen: Option<Vec<Fingerprint>> = None;
+        let mut hangups = hangup::hangups();
+        let preloaded = initial.is_some();
+        let mut previous = initial;
+        let mut counters = ReloadCounters::default();
+        let mut notifications = polling.notifications();
+        let mut woken = true;
+        while !watching.is_cancelled() {
+            let hung_up = polling.on_hangup && hangup::hangups() != hangups;
+            let changed = seen
+                .as_ref()
+                .is_none_or(|fingerprints| woken && *fingerprints != polling.fingerprint(&files));
+            if changed || hung_up {
+                hangups = hangup::hangups();
+                if seen.is_some() {
+                    if !hung_up {
+                        polling.settle(&files, &watching);
+                    }
+                    loader.forget_memoized();
+                }
+                // Taken before loading, so the changes made while loading aren't missed.
+                let mut current = polling.fingerprint(&files);
+                let (event, path) = match loader.load_with_report::<T>() {
+                    Ok((config, report)) => {
+                        let mut dependencies = loader.locations();
+                        for file in report.files() {
+                            if !dependencies.contains(&file) {
+                                dependencies.push(file);
+                            }
+                        }
+                        if dependencies != files {
+                            files = dependencies;
+                            current = polling.fingerprint(&files);
+                        }
+                        let serialized = serialized(&config);
+                        let change_set = match (&previous, &serialized) {
+                            (Some(old), Some(new)) => {
+                                ChangeSet::between(old, new, loader.secret_fields())
+                            }
+                            _ => ChangeSet::default(),
+                        };
+                        previous = serialized;
+                        let update = Update {
+                            config,
+                            changes: change_set,
+                        };
+                        (Event::Updated(update), report.path)
+                    }
+                    Err(error) => {
+                        let failure = Failure {
+                            diagnostics: loader.diagnostics_of(&error),
+                        };
+                        let path = first_existing_config(&loader.locations());
+                        (Event::Failed(failure), path)
+                    }
+                };
+                let redundant = preloaded
+                    && seen.is_none()
+                    && matches!(&event, Event::Updated(update) if update.changes.is_empty());
+                let failed = match &event {
+                    Event::Updated(_) => None,
+                    Event::Failed(failure) => Some(failure.clone()),
+                };
+                if !redundant {
+                    let Some(generation) = deliver(event, ReloadRecord::now(path, failed.clone()))
+                    else {
+                        return;
+                    };
+                    counters.generation = generation;
+                }
+                count(
+                    &mut counters,
+                    seen.is_none(),
+                    failed.as_ref(),
+                    loader.reload_metrics(),
+                );
+                seen = Some(current);
+            }
+            woken = notifications.as_mut().map_or_else(
+                || {
+                    thread::sleep(polling.interval);
+                    true
+                },
+                |notified| {
+                    let missed = woken && notified.follow(&files);
+                    missed || notified.wait(polling.interval)
+                },
+            );
+        }
+    });
+}
+
+/// Counts a load, the `first` attempt or not, which failed if there is a `failure`, in the
+/// `counters` reported to the `metrics`.
+fn count(
+    counters: &mut ReloadCounters,
+    first: bool,
+    failure: Option<&Failure>,
+    metrics: Option<&dyn ReloadMetrics>,
+) {
+    if let Some(failed) = failure {
+        counters.failed();
+        if let Some(hook) = metrics {
+            hook.failed(counters, failed);
+        }
+    } else {
+        counters.loaded(first);
+        if let Some(hook) = metrics {
+            hook.reloaded(counters);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod watch {
+        use super::super::Event;
+        use super::super::Failure;
+        use crate::Loader;
+        use crate::ReloadCounters;
+        use crate::ReloadMetrics;
+        use crate::Watcher;
+        use std::fs;
+        use std::path::Path;
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        const PATIENCE: Duration = Duration::from_secs(10);
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        /// Returns the configuration of the `event`, if it's an update.
+        fn updated(event: Event<Server>) -> Option<Server> {
+            match event {
+                Event::Updated(update) => Some(update.config),
+                Event::Failed(_) => None,
+            }
+        }
+
+        fn watcher(config: &Path) -> Watcher<Server> {
+            Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.to_path_buf()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch(Duration::from_millis(10))
+        }
+
+        #[test]
+        fn changes() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+
+            let watcher = watcher(&config);
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let updated = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(updated, Some(Server { port: 8080 }));
+        }
+
+        #[test]
+        fn notified() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+
+            // Never checked regularly within the test.
+            let watcher = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .watch(Duration::from_mins(10));
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let updated = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(updated, Some(Server { port: 8080 }));
+        }
+
+        #[test]
+        fn imports() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            let port = dir.path().join("port.ncl");
+            fs::write(&config, r#"{ port = import "port.ncl" }"#).unwrap();
+            fs::write(&port, "80").unwrap();
+
+            let watcher = watcher(&config);
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            fs::write(&port, "8080").unwrap();
+            let updated = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(updated, Some(Server { port: 8080 }));
+        }
+
+        #[test]
+        fn invalid_changes_are_skipped() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+
+            let watcher = watcher(&config);
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            fs::write(&config, r#"{ port = "80" }"#).unwrap();
+            let invalid = watcher.recv_timeout(PATIENCE);
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let updated = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            let Some(Event::Failed(failure)) = invalid else {
+                panic!("expected a failure, got {invalid:?}");
+            };
+            assert!(!failure.diagnostics.is_empty());
+            assert_eq!(updated, Some(Server { port: 8080 }));
+        }
+
+        #[test]
+        fn created_configuration() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+
+            let watcher = watcher(&config);
+            let missing = watcher.recv_timeout(PATIENCE);
+            fs::write(&config, "{ port = 80 }").unwrap();
+            let created = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert!(matches!(missing, Some(Event::Failed(_))));
+            assert_eq!(created, Some(Server { port: 80 }));
+        }
+
+        #[test]
+        fn standard_locations() {
+            let loader = Loader::new("nickelodeon_test");
+
+            assert_eq!(
+                loader.locations(),
+                crate::all_location_candidates("nickelodeon_test")
+            );
+        }
+
+        #[test]
+        fn bursts_are_coalesced() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+
+            let watcher = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(500))
+                .watch(Duration::from_millis(10));
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            fs::write(&config, "").unwrap();
+            fs::write(&config, "{ port =").unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let updated = watcher.recv_timeout(PATIENCE).and_then(updated);
+            let extra = watcher.recv_timeout(Duration::from_secs(1));
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(updated, Some(Server { port: 8080 }));
+            assert_eq!(extra, None);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn hangups() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+            let modified = fs::metadata(&config).unwrap().modified().unwrap();
+
+            let watcher = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .reload_on_hangup(true)
+                .watch(Duration::from_millis(10));
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            // Unnoticeable by the watch: same size and modification time.
+            fs::write(&config, "{ port = 81 }").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&config)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+            let unnoticed = watcher.recv_timeout(Duration::from_millis(200));
+            // SAFETY: the handler installed by the watch replaced the default one.
+            let raised = unsafe { libc::raise(libc::SIGHUP) };
+            let reloaded = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(unnoticed, None);
+            assert_eq!(raised, 0);
+            assert_eq!(reloaded, Some(Server { port: 81 }));
+        }
+
+        #[test]
+        fn compared_contents() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+            let modified = fs::metadata(&config).unwrap().modified().unwrap();
+            let file = || fs::File::options().write(true).open(&config).unwrap();
+
+            let watcher = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .compare_contents(true)
+                .watch(Duration::from_millis(10));
+            let initial = watcher.recv_timeout(PATIENCE).and_then(updated);
+            file()
+                .set_modified(modified + Duration::from_secs(5))
+                .unwrap();
+            let touched = watcher.recv_timeout(Duration::from_millis(200));
+            // Same size and modification time, only noticeable by the contents.
+            fs::write(&config, "{ port = 81 }").unwrap();
+            file().set_modified(modified).unwrap();
+            let changed = watcher.recv_timeout(PATIENCE).and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(touched, None);
+            assert_eq!(changed, Some(Server { port: 81 }));
+        }
+
+        #[cfg(feature = "stream")]
+        #[test]
+        fn stream() {
+            use futures_core::Stream as _;
+            use std::pin::Pin;
+
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+
+            let mut watcher = watcher(&config);
+            let mut next = || {
+                crate::offload::tests::block_on(std::future::poll_fn(|cx| {
+                    Pin::new(&mut watcher).poll_next(cx)
+                }))
+            };
+            let initial = next().and_then(updated);
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let changed = next().and_then(updated);
+
+            assert_eq!(initial, Some(Server { port: 80 }));
+            assert_eq!(changed, Some(Server { port: 8080 }));
+        }
+
+        /// Sends the counters it receives, telling whether they follow a successful load.
+        struct Recorder(mpsc::Sender<(bool, ReloadCounters)>);
+
+        impl ReloadMetrics for Recorder {
+            fn reloaded(&self, counters: &ReloadCounters) {
+                let _ignored = self.0.send((true, *counters));
+            }
+
+            fn failed(&self, counters: &ReloadCounters, _failure: &Failure) {
+                let _ignored = self.0.send((false, *counters));
+            }
+        }
+
+        #[test]
+        fn metrics() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+            let (sender, receiver) = mpsc::channel();
+
+            let watcher = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .metrics(Recorder(sender))
+                .watch::<Server>(Duration::from_millis(10));
+            let _initial = watcher.recv_timeout(PATIENCE);
+            fs::write(&config, r#"{ port = "80" }"#).unwrap();
+            let _invalid = watcher.recv_timeout(PATIENCE);
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let _updated = watcher.recv_timeout(PATIENCE);
+            let recorded: Vec<_> = (0..3)
+                .filter_map(|_| receiver.recv_timeout(PATIENCE).ok())
+                .map(|(loaded, counters)| {
+                    (
+                        loaded,
+                        counters.reloads,
+                        counters.failures,
+                        counters.generation,
+                        counters.last_reload.is_some(),
+                    )
+                })
+                .collect();
+
+            assert_eq!(
+                recorded,
+                [
+                    (true, 0, 0, 1, true),
+                    (false, 0, 1, 1, true),
+                    (true, 1, 1, 2, true),
+                ]
+            );
+        }
+
+        #[test]
+        fn shared_metrics() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+            let (sender, receiver) = mpsc::channel();
+
+            let shared = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .metrics(Recorder(sender))
+                .watch_shared::<Server>(Duration::from_millis(10))
+                .unwrap();
+            let (_, first) = receiver.recv_timeout(PATIENCE).unwrap();
+            fs::write(&config, "{ port = 80 } # touched").unwrap();
+            let (_, touched) = receiver.recv_timeout(PATIENCE).unwrap();
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let (_, changed) = receiver.recv_timeout(PATIENCE).unwrap();
+
+            assert_eq!((first.reloads, first.generation), (0, 0));
+            assert_eq!((touched.reloads, touched.generation), (1, 0));
+            assert_eq!((changed.reloads, changed.generation), (2, 1));
+            assert_eq!(shared.generation(), 1);
+        }
+
+        #[test]
+        fn change_sets() {
+            let dir = tempfile::tempdir().unwrap();
+            let config = dir.path().join("config.ncl");
+            fs::write(&config, "{ port = 80 }").unwrap();
+
+            let watcher = Loader::new("nickelodeon_test")
+                .config_path_from_flag(Some(config.clone()))
+                .diagnostics(std::io::sink())
+                .debounce(Duration::from_millis(20))
+                .secret_field("port")
+                .watch::<Server>(Duration::from_millis(10));
+            let first = watcher.recv_timeout(PATIENCE);
+            fs::write(&config, "{ port = 8080 }").unwrap();
+            let second = watcher.recv_timeout(PATIENCE);
+
+            let (Some(Event::Updated(initial)), Some(Event::Updated(updated))) = (first, second)
+            else {
+                panic!("expected two updates");
+            };
+            assert!(initial.changes.is_empty());
+            assert_eq!(
+                updated.changes.to_string(),
+                "~ port: \"<redacted>\" -> \"<redacted>\"\n"
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::Loader;
+use crate::Result;
+use crate::SharedConfig;
+#[cfg(any(feature = "actix-web", feature = "axum"))]
+use crate::Snapshot;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+#[cfg(any(feature = "actix-web", feature = "axum"))]
+use std::future::ready;
+#[cfg(feature = "axum")]
+use std::future::Future;
+#[cfg(feature = "actix-web")]
+use std::future::Ready;
+use std::time::Duration;
+
+/// Loads the configuration of the application with the codename `app` into a
+/// [`SharedConfig`], ready to be added to the state of a web service.
+///
+/// The configuration is kept up to date by checking its files every `reload_every`, if
+/// set, like [`Loader::watch_shared`] does. [`SharedConfig`] is cheap to clone, so it can
+/// be the state itself or one of its fields:
+///
+/// ```no_run
+/// # #[derive(serde::Deserialize, serde::Serialize, Default)]
+/// # struct MyConfig {}
+/// # fn main() -> nickelodeon::Result<()> {
+/// let config =
+///     nickelodeon::app_state::<MyConfig>("my_app", Some(std::time::Duration::from_secs(1)))?;
+///
+/// // axum:      Router::new().route("/", get(handler)).with_state(config)
+/// // actix-web: App::new().app_data(config.clone())
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Handlers then read the current version with [`SharedConfig::load`], or take a
+/// [`Snapshot`](crate::Snapshot) of it as an extractor with the `axum` or `actix-web`
+/// feature.
+///
+/// # Errors
+///
+/// Will return `Err` if the configuration can't be loaded at startup.
+pub fn app_state<T>(app: &str, reload_every: Option<Duration>) -> Result<SharedConfig<T>>
+where
+    T: DeserializeOwned + Serialize + Default + Send + Sync + 'static,
+{
+    let loader = Loader::new(app);
+    reload_every.map_or_else(
+        || loader.load().map(SharedConfig::new),
+        |interval| loader.watch_shared(interval),
+    )
+}
+
+/// Extracts the current version of the [`SharedConfig`] in the state, so the whole request is
+/// handled with the same version.
+#[cfg(feature = "axum")]
+impl<S, T> axum::extract::FromRequestParts<S> for Snapshot<T>
+where
+    SharedConfig<T>: axum::extract::FromRef<S>,
+    S: Send + Sync,
+    T: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    fn from_request_parts(
+        _parts: &mut axum::http::request::Parts,
+        state: &S,
+    ) -> impl Future<Output = std::result::Result<Self, Self::Rejection>> + Send {
+        let config: SharedConfig<T> = axum::extract::FromRef::from_ref(state);
+        ready(Ok(config.snapshot()))
+    }
+}
+
+/// Extracts the current version of the [`SharedConfig`] in the application data, added
+/// either as is or wrapped in `web::Data`, so the whole request is handled with the same
+/// version.
+#[cfg(feature = "actix-web")]
+impl<T: 'static> actix_web::FromRequest for Snapshot<T> {
+    type Error = actix_web::Error;
+    type Future = Ready<std::result::Result<Self, actix_web::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let config = req.app_data::<SharedConfig<T>>().or_else(|| {
+            req.app_data::<actix_web::web::Data<SharedConfig<T>>>()
+                .map(|data| &***data)
+        });
+        ready(config.map(SharedConfig::snapshot).ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError(
+                "the configuration is missing from the application data",
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(test)]
+    mod app_state {
+        use super::super::app_state;
+        use std::time::Duration;
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        fn no_configuration() {
+            let fixed = app_state::<Server>("this_app_does_not_exist", None).unwrap();
+            let watched =
+                app_state::<Server>("this_app_does_not_exist", Some(Duration::from_millis(10)))
+                    .unwrap();
+
+            assert_eq!(*fixed.load(), Server::default());
+            assert_eq!(*watched.load(), Server::default());
+        }
+    }
+
+    #[cfg(all(test, feature = "axum"))]
+    mod axum {
+        use crate::offload::tests::block_on;
+        use crate::SharedConfig;
+        use crate::Snapshot;
+        use axum::extract::FromRef;
+        use axum::extract::FromRequestParts as _;
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[derive(Clone)]
+        struct State {
+            config: SharedConfig<Server>,
+        }
+
+        impl FromRef<State> for SharedConfig<Server> {
+            fn from_ref(input: &State) -> Self {
+                input.config.clone()
+            }
+        }
+
+        #[test]
+        fn extracted() {
+            let config = SharedConfig::new(Server { port: 80 });
+            let state = State {
+                config: config.clone(),
+            };
+            let (mut parts, ()) = axum::http::Request::new(()).into_parts();
+
+            let whole = block_on(Snapshot::<Server>::from_request_parts(&mut parts, &config));
+            let field = block_on(Snapshot::<Server>::from_request_parts(&mut parts, &state));
+
+            assert_eq!(whole.unwrap().port, 80);
+            assert_eq!(field.unwrap().generation(), 0);
+        }
+    }
+
+    #[cfg(all(test, feature = "actix-web"))]
+    mod actix_web {
+        use crate::offload::tests::block_on;
+        use crate::SharedConfig;
+        use crate::Snapshot;
+        use actix_web::http::StatusCode;
+        use actix_web::test::TestRequest;
+        use actix_web::web::Data;
+        use actix_web::FromRequest as _;
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        fn extracted() {
+            let config = SharedConfig::new(Server { port: 80 });
+            let plain = TestRequest::default()
+                .app_data(config.clone())
+                .to_http_request();
+            let wrapped = TestRequest::default()
+                .app_data(Data::new(config))
+                .to_http_request();
+
+            let from_plain = block_on(Snapshot::<Server>::extract(&plain));
+            let from_wrapped = block_on(Snapshot::<Server>::extract(&wrapped));
+
+            assert_eq!(from_plain.unwrap().port, 80);
+            assert_eq!(from_wrapped.unwrap().port, 80);
+        }
+
+        #[test]
+        fn missing() {
+            let request = TestRequest::default().to_http_request();
+
+            let extracted = block_on(Snapshot::<Server>::extract(&request));
+
+            assert_eq!(
+                extracted.unwrap_err().error_response().status(),
+                StatusCode::INTERNAL_SERVER_ERROR
+            );
+        }
+    }
+}